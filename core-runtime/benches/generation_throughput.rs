@@ -4,7 +4,7 @@
 
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 
-use gg_core::engine::{FinishReason, GenerationResult, StreamingOutput};
+use gg_core::engine::{FinishReason, GenerationResult, GenerationTimings, StreamingOutput};
 
 fn create_generation_result(token_count: usize) -> GenerationResult {
     let text = "generated ".repeat(token_count);
@@ -13,6 +13,11 @@ fn create_generation_result(token_count: usize) -> GenerationResult {
         text,
         tokens_generated: token_count as u32,
         finish_reason: FinishReason::MaxTokens,
+        prompt_tokens: 0,
+        timings: GenerationTimings::default(),
+        stop_sequence: None,
+        logprobs: Vec::new(),
+        choices: Vec::new(),
     }
 }
 
@@ -39,6 +44,9 @@ fn bench_streaming_output_creation(c: &mut Criterion) {
                 for i in 0..count {
                     let _ = black_box(StreamingOutput {
                         token: (i % 50000) as u32,
+                        text: String::new(),
+                        logprob: None,
+                        finish: None,
                         is_final: i == count - 1,
                     });
                 }
@@ -57,6 +65,7 @@ fn bench_finish_reason_matching(c: &mut Criterion) {
         FinishReason::MaxTokens,
         FinishReason::Timeout,
         FinishReason::ContentFiltered,
+        FinishReason::Cancelled,
     ];
 
     group.bench_function("pattern_match", |b| {
@@ -67,6 +76,7 @@ fn bench_finish_reason_matching(c: &mut Criterion) {
                     FinishReason::MaxTokens => 1,
                     FinishReason::Timeout => 2,
                     FinishReason::ContentFiltered => 3,
+                    FinishReason::Cancelled => 4,
                 });
             }
         })
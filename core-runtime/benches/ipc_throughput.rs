@@ -34,6 +34,7 @@ fn fixture_to_request(fixture: &serde_json::Value, request_id: u64) -> Inference
             top_k: 50,
             stream: false,
             timeout_ms: None,
+            ..Default::default()
         },
     }
 }
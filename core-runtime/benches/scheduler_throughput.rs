@@ -19,6 +19,7 @@ fn create_test_request(id: u64, token_count: usize) -> QueuedRequest {
             top_k: 50,
             stream: false,
             timeout_ms: None,
+            ..Default::default()
         },
     )
 }
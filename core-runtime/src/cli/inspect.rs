@@ -0,0 +1,220 @@
+// Copyright 2024-2026 GG-CORE Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Offline model file inspection.
+//!
+//! Reads a GGUF header without loading the model or contacting a running
+//! runtime, so files can be checked on air-gapped hosts before deployment.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::engine::gguf::GgufMetadata;
+
+use super::status::format_bytes;
+
+/// Summary of a GGUF file, as printed by `models inspect --json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct GgufReport {
+    pub path: String,
+    pub file_size: u64,
+    pub gguf_version: u32,
+    pub name: Option<String>,
+    pub architecture: Option<String>,
+    pub file_type: Option<String>,
+    pub parameters: u64,
+    pub context_length: Option<u64>,
+    pub embedding_length: Option<u64>,
+    pub block_count: Option<u64>,
+    pub head_count: Option<u64>,
+    pub head_count_kv: Option<u64>,
+    pub tokenizer: Option<String>,
+    pub vocab_size: Option<usize>,
+    pub chat_template: Option<String>,
+    /// Pooling of embedding and reranking models.
+    pub pooling: Option<String>,
+    pub tensor_bytes: u64,
+    /// Tensor count per element type.
+    pub tensor_types: BTreeMap<String, usize>,
+    pub tensors: Vec<TensorReport>,
+    /// Every metadata value; long arrays are summarized.
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// One tensor table entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct TensorReport {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ggml_type: String,
+    pub shape: Vec<u64>,
+    /// None for element types the reader does not know.
+    pub bytes: Option<u64>,
+}
+
+impl GgufReport {
+    pub fn new(path: &Path, header: &GgufMetadata) -> Self {
+        Self {
+            path: path.display().to_string(),
+            file_size: header.file_size,
+            gguf_version: header.version,
+            name: header.name().map(str::to_string),
+            architecture: header.architecture().map(str::to_string),
+            file_type: header.file_type().map(str::to_string),
+            parameters: header.parameter_count(),
+            context_length: header.context_length(),
+            embedding_length: header.embedding_length(),
+            block_count: header.block_count(),
+            head_count: header.head_count(),
+            head_count_kv: header.head_count_kv(),
+            tokenizer: header.tokenizer_model().map(str::to_string),
+            vocab_size: header.vocab_size(),
+            chat_template: header.chat_template().map(str::to_string),
+            pooling: header.pooling_type().map(|p| p.to_string()),
+            tensor_bytes: header.tensor_bytes(),
+            tensor_types: header
+                .tensor_types()
+                .into_iter()
+                .map(|(ty, count)| (ty.to_string(), count))
+                .collect(),
+            tensors: header
+                .tensors
+                .iter()
+                .map(|t| TensorReport {
+                    name: t.name.clone(),
+                    ggml_type: t.ggml_type.to_string(),
+                    shape: t.shape.clone(),
+                    bytes: t.byte_size(),
+                })
+                .collect(),
+            metadata: header
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.clone(), value.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// Inspect the GGUF file at `path`. Returns 0 for a valid file and 1 when
+/// it cannot be read or is malformed.
+pub fn run_inspect(path: &str, json_output: bool) -> i32 {
+    let path = Path::new(path);
+    match GgufMetadata::read(path) {
+        Ok(header) => {
+            let report = GgufReport::new(path, &header);
+            if json_output {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_report(&report);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            1
+        }
+    }
+}
+
+fn print_report(report: &GgufReport) {
+    let unknown = || "-".to_string();
+    let num = |v: Option<u64>| v.map_or_else(unknown, |v| v.to_string());
+
+    println!("File:            {} ({})", report.path, format_bytes(report.file_size));
+    println!("GGUF version:    {}", report.gguf_version);
+    println!("Name:            {}", report.name.clone().unwrap_or_else(unknown));
+    println!("Architecture:    {}", report.architecture.clone().unwrap_or_else(unknown));
+    println!("Quantization:    {}", report.file_type.clone().unwrap_or_else(unknown));
+    println!("Parameters:      {}", format_count(report.parameters));
+    println!("Context length:  {}", num(report.context_length));
+    println!("Embedding size:  {}", num(report.embedding_length));
+    println!("Layers:          {}", num(report.block_count));
+    println!("Attention heads: {} ({} KV)", num(report.head_count), num(report.head_count_kv));
+    println!(
+        "Tokenizer:       {} ({} tokens)",
+        report.tokenizer.clone().unwrap_or_else(unknown),
+        report.vocab_size.map_or_else(unknown, |v| v.to_string())
+    );
+    match &report.chat_template {
+        Some(template) => println!("Chat template:   embedded ({} bytes)", template.len()),
+        None => println!("Chat template:   none"),
+    }
+    if let Some(pooling) = &report.pooling {
+        println!("Pooling:         {}", pooling);
+    }
+
+    println!("\nTensor types:");
+    for (ty, count) in &report.tensor_types {
+        println!("  {:<10} {:>6} tensors", ty, count);
+    }
+
+    println!(
+        "\nTensors ({}, {} of data):",
+        report.tensors.len(),
+        format_bytes(report.tensor_bytes)
+    );
+    for tensor in &report.tensors {
+        let shape: Vec<String> = tensor.shape.iter().map(u64::to_string).collect();
+        println!(
+            "  {:<40} {:<8} {:<20} {:>10}",
+            tensor.name,
+            tensor.ggml_type,
+            shape.join(" x "),
+            tensor.bytes.map_or_else(unknown, format_bytes)
+        );
+    }
+
+    println!("\nMetadata ({} keys):", report.metadata.len());
+    for (key, value) in &report.metadata {
+        // Chat templates span many lines; the summary above gives the size
+        if key == "tokenizer.chat_template" {
+            continue;
+        }
+        println!("  {} = {}", key, value);
+    }
+}
+
+/// Format a parameter count, e.g. "494.0M".
+fn format_count(count: u64) -> String {
+    const THOUSAND: f64 = 1e3;
+    const MILLION: f64 = 1e6;
+    const BILLION: f64 = 1e9;
+
+    let count = count as f64;
+    if count >= BILLION {
+        format!("{:.2}B", count / BILLION)
+    } else if count >= MILLION {
+        format!("{:.1}M", count / MILLION)
+    } else if count >= THOUSAND {
+        format!("{:.1}K", count / THOUSAND)
+    } else {
+        format!("{}", count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1_500), "1.5K");
+        assert_eq!(format_count(494_032_768), "494.0M");
+        assert_eq!(format_count(7_241_732_096), "7.24B");
+    }
+
+    #[test]
+    fn test_inspect_rejects_missing_and_invalid_files() {
+        assert_eq!(run_inspect("/nonexistent/model.gguf", false), 1);
+
+        let path = std::env::temp_dir().join("gg_core_inspect_not_gguf.bin");
+        std::fs::write(&path, b"not a model").unwrap();
+        assert_eq!(run_inspect(path.to_str().unwrap(), true), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+}
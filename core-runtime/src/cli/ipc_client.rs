@@ -15,7 +15,8 @@ use tokio::time::timeout;
 use crate::engine::InferenceParams;
 use crate::ipc::protocol::{
     decode_message, encode_message, HealthCheckResponse, HealthCheckType, InferenceRequest,
-    IpcMessage, ModelsListResponse, RequestId,
+    IpcMessage, LoadModelRequest, ModelLifecycleResponse, ModelsListResponse, RequestId,
+    RollbackModelRequest, SwapModelRequest, UnloadModelRequest,
 };
 use crate::telemetry::MetricsSnapshot;
 
@@ -42,6 +43,7 @@ pub enum CliError {
 pub struct CliIpcClient {
     socket_path: String,
     timeout_duration: Duration,
+    auth_token: Option<String>,
 }
 
 impl CliIpcClient {
@@ -50,6 +52,7 @@ impl CliIpcClient {
         Self {
             socket_path,
             timeout_duration: Duration::from_secs(5),
+            auth_token: None,
         }
     }
 
@@ -59,6 +62,12 @@ impl CliIpcClient {
         self
     }
 
+    /// Set the token used to authenticate admin requests.
+    pub fn with_auth_token(mut self, token: String) -> Self {
+        self.auth_token = Some(token);
+        self
+    }
+
     /// Perform a health check via IPC.
     pub async fn check_health(&self, check_type: HealthCheckType) -> Result<bool, CliError> {
         let response = self.send_health_request(check_type).await?;
@@ -106,6 +115,98 @@ impl CliIpcClient {
         }
     }
 
+    /// Load a model file and route `model_id` to it.
+    pub async fn load_model(
+        &self,
+        model_id: &str,
+        path: &str,
+        manifest: Option<&str>,
+    ) -> Result<ModelLifecycleResponse, CliError> {
+        let request = LoadModelRequest {
+            request_id: RequestId(1),
+            model_id: model_id.to_string(),
+            path: path.to_string(),
+            manifest: manifest.map(str::to_string),
+        };
+        self.send_lifecycle(IpcMessage::LoadModel(request)).await
+    }
+
+    /// Drain and unload a model.
+    pub async fn unload_model(
+        &self,
+        model_id: &str,
+        drain_timeout: Option<Duration>,
+    ) -> Result<ModelLifecycleResponse, CliError> {
+        let request = UnloadModelRequest {
+            request_id: RequestId(1),
+            model_id: model_id.to_string(),
+            drain_timeout_ms: drain_timeout.map(|d| d.as_millis() as u64),
+        };
+        self.send_lifecycle(IpcMessage::UnloadModel(request)).await
+    }
+
+    /// Hot-swap a loaded model to a new file.
+    pub async fn swap_model(
+        &self,
+        model_id: &str,
+        path: &str,
+        manifest: Option<&str>,
+        drain_timeout: Option<Duration>,
+    ) -> Result<ModelLifecycleResponse, CliError> {
+        let request = SwapModelRequest {
+            request_id: RequestId(1),
+            model_id: model_id.to_string(),
+            path: path.to_string(),
+            manifest: manifest.map(str::to_string),
+            drain_timeout_ms: drain_timeout.map(|d| d.as_millis() as u64),
+        };
+        self.send_lifecycle(IpcMessage::SwapModel(request)).await
+    }
+
+    /// Swap a model back to its previous version.
+    pub async fn rollback_model(
+        &self,
+        model_id: &str,
+        drain_timeout: Option<Duration>,
+    ) -> Result<ModelLifecycleResponse, CliError> {
+        let request = RollbackModelRequest {
+            request_id: RequestId(1),
+            model_id: model_id.to_string(),
+            drain_timeout_ms: drain_timeout.map(|d| d.as_millis() as u64),
+        };
+        self.send_lifecycle(IpcMessage::RollbackModel(request)).await
+    }
+
+    /// Send an admin message after a handshake on the same connection.
+    async fn send_lifecycle(
+        &self,
+        message: IpcMessage,
+    ) -> Result<ModelLifecycleResponse, CliError> {
+        let token = self.auth_token.clone().ok_or_else(|| {
+            CliError::Protocol("auth token required (set CORE_AUTH_TOKEN)".to_string())
+        })?;
+        let handshake = IpcMessage::Handshake {
+            token,
+            protocol_version: None,
+        };
+        let handshake_bytes =
+            encode_message(&handshake).map_err(|e| CliError::Protocol(e.to_string()))?;
+        let request_bytes =
+            encode_message(&message).map_err(|e| CliError::Protocol(e.to_string()))?;
+
+        let response_bytes = self
+            .send_receive_authenticated(&handshake_bytes, &request_bytes)
+            .await?;
+        let response =
+            decode_message(&response_bytes).map_err(|e| CliError::Protocol(e.to_string()))?;
+
+        match response {
+            IpcMessage::ModelLifecycleResponse(resp) => Ok(resp),
+            IpcMessage::Error { message, .. } => Err(CliError::Protocol(message)),
+            _ => Err(CliError::Protocol("Unexpected response type".to_string())),
+        }
+    }
+
     /// Send inference request and return response text.
     pub async fn send_inference(
         &self,
@@ -280,6 +381,58 @@ impl CliIpcClient {
         self.exchange_data(&mut pipe, request).await
     }
 
+    #[cfg(unix)]
+    async fn send_receive_authenticated(
+        &self,
+        handshake: &[u8],
+        request: &[u8],
+    ) -> Result<Vec<u8>, CliError> {
+        use tokio::net::UnixStream;
+
+        let connect_future = UnixStream::connect(&self.socket_path);
+        let mut stream = timeout(self.timeout_duration, connect_future)
+            .await
+            .map_err(|_| CliError::Timeout)?
+            .map_err(|e| CliError::ConnectionFailed(e.to_string()))?;
+
+        self.authenticated_exchange(&mut stream, handshake, request).await
+    }
+
+    #[cfg(windows)]
+    async fn send_receive_authenticated(
+        &self,
+        handshake: &[u8],
+        request: &[u8],
+    ) -> Result<Vec<u8>, CliError> {
+        use tokio::net::windows::named_pipe::ClientOptions;
+
+        let connect_future = ClientOptions::new().open(&self.socket_path);
+        let mut pipe = timeout(self.timeout_duration, async { connect_future })
+            .await
+            .map_err(|_| CliError::Timeout)?
+            .map_err(|e| CliError::ConnectionFailed(e.to_string()))?;
+
+        self.authenticated_exchange(&mut pipe, handshake, request).await
+    }
+
+    async fn authenticated_exchange<S>(
+        &self,
+        stream: &mut S,
+        handshake: &[u8],
+        request: &[u8],
+    ) -> Result<Vec<u8>, CliError>
+    where
+        S: AsyncReadExt + AsyncWriteExt + Unpin,
+    {
+        let ack = self.exchange_data(stream, handshake).await?;
+        match decode_message(&ack).map_err(|e| CliError::Protocol(e.to_string()))? {
+            IpcMessage::HandshakeAck { .. } => {}
+            IpcMessage::Error { message, .. } => return Err(CliError::Protocol(message)),
+            _ => return Err(CliError::Protocol("Handshake rejected".to_string())),
+        }
+        self.exchange_data(stream, request).await
+    }
+
     async fn exchange_data<S>(&self, stream: &mut S, request: &[u8]) -> Result<Vec<u8>, CliError>
     where
         S: AsyncReadExt + AsyncWriteExt + Unpin,
@@ -385,6 +538,13 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_lifecycle_requires_auth_token() {
+        let client = CliIpcClient::new("/nonexistent/socket".to_string());
+        let result = client.unload_model("model", None).await;
+        assert!(matches!(result, Err(CliError::Protocol(msg)) if msg.contains("CORE_AUTH_TOKEN")));
+    }
+
     #[tokio::test]
     async fn test_client_with_short_timeout() {
         let client = CliIpcClient::new("/nonexistent/socket".to_string())
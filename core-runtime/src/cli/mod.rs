@@ -13,13 +13,16 @@
 //! GG-CORE live     # Liveness probe, exits 0 if alive
 //! GG-CORE ready    # Readiness probe, exits 0 if ready
 //! GG-CORE status   # Show system status and statistics
+//! GG-CORE models inspect model.gguf  # Read a GGUF header offline
 //! ```
 
 pub mod health;
+pub mod inspect;
 pub mod ipc_client;
 pub mod status;
 
 pub use health::{run_health, run_liveness, run_readiness};
+pub use inspect::{run_inspect, GgufReport};
 pub use ipc_client::{CliError, CliIpcClient};
 pub use status::{run_status, SystemStatus};
 
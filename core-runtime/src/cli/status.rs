@@ -500,7 +500,7 @@ fn format_uptime(secs: u64) -> String {
 }
 
 /// Format bytes in human-readable form.
-fn format_bytes(bytes: u64) -> String {
+pub(super) fn format_bytes(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
     const GB: u64 = MB * 1024;
@@ -2,7 +2,35 @@
 //!
 //! All fields have safe defaults. Configuration is validated before use.
 
+use std::time::{Duration, Instant};
+
+use tokio_util::sync::CancellationToken;
+
+use super::constraint::OutputConstraint;
+use super::context::ContextStrategy;
 use super::error::InferenceError;
+use super::output::FinishReason;
+use super::stop::StopSequence;
+
+/// Mirostat adaptive sampling mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mirostat {
+    #[default]
+    Disabled,
+    /// Mirostat 1.0 (estimates the Zipf exponent from the top 100 tokens)
+    V1,
+    /// Mirostat 2.0
+    V2,
+}
+
+/// LoRA adapter applied to one request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdapterSelection {
+    /// Name the adapter was loaded under.
+    pub name: String,
+    /// Multiplier on the adapter's weight deltas (0 = base model).
+    pub scale: f32,
+}
 
 /// Per-call inference configuration.
 #[derive(Debug, Clone)]
@@ -21,6 +49,46 @@ pub struct InferenceConfig {
     pub timeout_ms: u64,
     /// Maximum memory allowed for this call (bytes). None = use global limit.
     pub max_memory_bytes: Option<usize>,
+    /// Sampling RNG seed
+    pub seed: u32,
+    /// Flat penalty for tokens already seen (0.0 = none)
+    pub presence_penalty: f32,
+    /// Penalty proportional to a token's count so far (0.0 = none)
+    pub frequency_penalty: f32,
+    /// Window for the penalties in tokens (0 = disabled, -1 = whole context)
+    pub penalty_last_n: i32,
+    /// Min-p threshold relative to the most likely token (0.0 = disabled)
+    pub min_p: f32,
+    /// Locally typical sampling threshold (1.0 = disabled)
+    pub typical_p: f32,
+    /// Mirostat mode; when enabled it replaces top-k/top-p/min-p/typical
+    pub mirostat: Mirostat,
+    /// Mirostat target surprise (tau)
+    pub mirostat_tau: f32,
+    /// Mirostat learning rate (eta)
+    pub mirostat_eta: f32,
+    /// Additive logit bias as (token id, bias) pairs
+    pub logit_bias: Vec<(u32, f32)>,
+    /// Strings and token IDs that end generation
+    pub stop: Vec<StopSequence>,
+    /// Grammar or JSON Schema the output must match
+    pub constraint: Option<OutputConstraint>,
+    /// Handling of prompts that overflow the context window
+    pub context_strategy: ContextStrategy,
+    /// Conversation whose KV state is reused across requests
+    pub conversation: Option<String>,
+    /// Report per-token log-probabilities with this many alternatives
+    pub logprobs: Option<usize>,
+    /// Completions to return
+    pub n: usize,
+    /// Completions to generate and rank by cumulative log-probability (>= n)
+    pub best_of: usize,
+    /// Draft tokens by prompt lookup instead of the paired draft model
+    pub prompt_lookup: bool,
+    /// LoRA adapter of the base model to generate with
+    pub adapter: Option<AdapterSelection>,
+    /// Cooperative abort signal, checked by backends between decode steps.
+    pub cancel: CancellationToken,
 }
 
 impl Default for InferenceConfig {
@@ -33,11 +101,43 @@ impl Default for InferenceConfig {
             repetition_penalty: 1.1,
             timeout_ms: 30_000,
             max_memory_bytes: Some(1024 * 1024 * 1024), // 1GB
+            seed: super::inference::DEFAULT_SEED,
+            presence_penalty: 0.0,
+            frequency_penalty: 0.0,
+            penalty_last_n: 64,
+            min_p: 0.0,
+            typical_p: 1.0,
+            mirostat: Mirostat::Disabled,
+            mirostat_tau: 5.0,
+            mirostat_eta: 0.1,
+            logit_bias: Vec::new(),
+            stop: Vec::new(),
+            constraint: None,
+            context_strategy: ContextStrategy::Reject,
+            conversation: None,
+            logprobs: None,
+            n: 1,
+            best_of: 1,
+            prompt_lookup: false,
+            adapter: None,
+            cancel: CancellationToken::new(),
         }
     }
 }
 
 impl InferenceConfig {
+    /// Reason to stop early: the request was cancelled or `timeout_ms`
+    /// elapsed since `started`.
+    pub fn abort_reason(&self, started: Instant) -> Option<FinishReason> {
+        if self.cancel.is_cancelled() {
+            Some(FinishReason::Cancelled)
+        } else if started.elapsed() >= Duration::from_millis(self.timeout_ms) {
+            Some(FinishReason::Timeout)
+        } else {
+            None
+        }
+    }
+
     /// Validate configuration values. Returns error on invalid values.
     pub fn validate(&self) -> Result<(), InferenceError> {
         if self.temperature < 0.0 || self.temperature > 2.0 {
@@ -55,14 +155,36 @@ impl InferenceConfig {
                 "repetition_penalty must be >= 1.0".into(),
             ));
         }
+        if !(0.0..=1.0).contains(&self.min_p) {
+            return Err(InferenceError::InputValidation(
+                "min_p must be in range [0.0, 1.0]".into(),
+            ));
+        }
+        if self.typical_p <= 0.0 || self.typical_p > 1.0 {
+            return Err(InferenceError::InputValidation(
+                "typical_p must be in range (0.0, 1.0]".into(),
+            ));
+        }
         if self.timeout_ms == 0 {
             return Err(InferenceError::InputValidation(
                 "timeout_ms must be > 0".into(),
             ));
         }
+        if self.n == 0 || self.best_of < self.n {
+            return Err(InferenceError::InputValidation(
+                "best_of must be >= n >= 1".into(),
+            ));
+        }
         Ok(())
     }
 
+    /// Context room needed for completions: `max_tokens` for each of the
+    /// `best_of` sequences, which share the prompt.
+    pub fn completion_budget(&self) -> u32 {
+        let sequences = u32::try_from(self.best_of.max(1)).unwrap_or(u32::MAX);
+        self.max_tokens.unwrap_or(256).saturating_mul(sequences)
+    }
+
     /// Create a config for deterministic classification (no sampling).
     pub fn for_classification() -> Self {
         Self {
@@ -73,6 +195,7 @@ impl InferenceConfig {
             repetition_penalty: 1.0,
             timeout_ms: 5_000,
             max_memory_bytes: Some(512 * 1024 * 1024), // 512MB
+            ..Self::default()
         }
     }
 
@@ -86,6 +209,7 @@ impl InferenceConfig {
             repetition_penalty: 1.0,
             timeout_ms: 2_000,
             max_memory_bytes: Some(256 * 1024 * 1024), // 256MB
+            ..Self::default()
         }
     }
 }
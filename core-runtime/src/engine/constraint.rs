@@ -0,0 +1,759 @@
+//! Output constraints for grammar-guided generation.
+//!
+//! A request may constrain generation with a GBNF grammar or a JSON Schema.
+//! Backends compile the constraint into their sampler so only tokens that
+//! keep the output valid can be sampled. JSON Schemas are converted to GBNF
+//! here; keywords the converter cannot enforce are rejected up front instead
+//! of being silently loosened.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// Largest GBNF grammar accepted, in bytes.
+pub const MAX_GRAMMAR_BYTES: usize = 64 * 1024;
+
+/// Deepest subschema nesting accepted in a JSON Schema.
+pub const MAX_SCHEMA_DEPTH: usize = 32;
+
+/// Largest length or item count bound a JSON Schema may set.
+pub const MAX_SCHEMA_REPEAT: u64 = 4096;
+
+/// Root rule name of request grammars.
+pub const GRAMMAR_ROOT: &str = "root";
+
+/// Keywords the grammar converter would ignore, so the output could violate
+/// the schema.
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "not",
+    "if",
+    "then",
+    "else",
+    "allOf",
+    "pattern",
+    "patternProperties",
+    "propertyNames",
+    "dependencies",
+    "dependentRequired",
+    "dependentSchemas",
+    "unevaluatedProperties",
+    "unevaluatedItems",
+    "minProperties",
+    "maxProperties",
+    "uniqueItems",
+    "contains",
+    "minContains",
+    "maxContains",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "multipleOf",
+];
+
+/// Keywords whose value is a map of subschemas.
+const SCHEMA_MAPS: &[&str] = &["properties", "$defs", "definitions"];
+
+/// Keywords whose value is a list of subschemas.
+const SCHEMA_LISTS: &[&str] = &["anyOf", "oneOf", "prefixItems"];
+
+const TYPES: &[&str] = &["object", "array", "string", "number", "integer", "boolean", "null"];
+
+/// Generic JSON rules as (name, body, rules the body refers to). Whitespace
+/// is bounded so a constrained model cannot pad its output forever.
+const PRIMITIVES: &[(&str, &str, &[&str])] = &[
+    ("ws", r#"[ \t\n]{0,16}"#, &[]),
+    (
+        "value",
+        "object | array | string | number | boolean | null",
+        &["object", "array", "string", "number", "boolean", "null"],
+    ),
+    (
+        "object",
+        r#""{" ws ( string ":" ws value ( "," ws string ":" ws value )* )? "}" ws"#,
+        &["ws", "string", "value"],
+    ),
+    ("array", r#""[" ws ( value ( "," ws value )* )? "]" ws"#, &["ws", "value"]),
+    ("string", r#""\"" char* "\"" ws"#, &["char", "ws"]),
+    ("char", r#"[^"\\\x7F\x00-\x1F] | "\\" ( ["\\/bfnrt] | "u" [0-9a-fA-F]{4} )"#, &[]),
+    ("integral", r#""-"? ( "0" | [1-9] [0-9]{0,15} )"#, &[]),
+    (
+        "number",
+        r#"integral ( "." [0-9]{1,16} )? ( [eE] [-+]? [0-9]{1,3} )? ws"#,
+        &["integral", "ws"],
+    ),
+    ("integer", "integral ws", &["integral", "ws"]),
+    ("boolean", r#"( "true" | "false" ) ws"#, &["ws"]),
+    ("null", r#""null" ws"#, &["ws"]),
+];
+
+/// Constraint applied to generated text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputConstraint {
+    /// GBNF grammar with a `root` rule.
+    Grammar(String),
+    /// JSON Schema the output must satisfy.
+    JsonSchema(Value),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ConstraintError {
+    #[error("grammar and json_schema cannot be combined")]
+    Conflicting,
+
+    #[error("grammar must be 1 to {MAX_GRAMMAR_BYTES} bytes")]
+    GrammarSize,
+
+    #[error("unsupported JSON schema keyword '{keyword}' at {path}")]
+    UnsupportedSchema { keyword: String, path: String },
+
+    #[error("invalid JSON schema at {path}: {reason}")]
+    InvalidSchema { path: String, reason: String },
+}
+
+impl OutputConstraint {
+    /// Build the constraint for a request's `grammar` and `json_schema`.
+    ///
+    /// JSON Schemas are converted once here so unsupported schemas fail
+    /// validation rather than the generation.
+    pub fn from_request(
+        grammar: Option<&str>,
+        json_schema: Option<&Value>,
+    ) -> Result<Option<Self>, ConstraintError> {
+        match (grammar, json_schema) {
+            (Some(_), Some(_)) => Err(ConstraintError::Conflicting),
+            (Some(grammar), None) => {
+                if grammar.trim().is_empty() || grammar.len() > MAX_GRAMMAR_BYTES {
+                    return Err(ConstraintError::GrammarSize);
+                }
+                Ok(Some(Self::Grammar(grammar.to_string())))
+            }
+            (None, Some(schema)) => {
+                json_schema_to_gbnf(schema)?;
+                Ok(Some(Self::JsonSchema(schema.clone())))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// GBNF grammar enforcing this constraint, rooted at `GRAMMAR_ROOT`.
+    pub fn to_gbnf(&self) -> Result<String, ConstraintError> {
+        match self {
+            Self::Grammar(grammar) => Ok(grammar.clone()),
+            Self::JsonSchema(schema) => json_schema_to_gbnf(schema),
+        }
+    }
+
+    /// Returns true if the output must be a JSON document.
+    pub fn is_json(&self) -> bool {
+        matches!(self, Self::JsonSchema(_))
+    }
+}
+
+/// Reject schemas the grammar converter cannot enforce.
+pub fn check_json_schema(schema: &Value) -> Result<(), ConstraintError> {
+    if !schema.is_object() {
+        return Err(invalid("#", "schema must be an object"));
+    }
+    check_subschema(schema, "#", 0)
+}
+
+/// Convert a JSON Schema to a GBNF grammar whose `root` rule matches only
+/// JSON documents that satisfy it.
+///
+/// Properties are generated in declaration order, required ones first, and
+/// properties the schema does not name are never generated.
+pub fn json_schema_to_gbnf(schema: &Value) -> Result<String, ConstraintError> {
+    check_json_schema(schema)?;
+    let mut converter = GbnfConverter {
+        root: schema,
+        rules: BTreeMap::new(),
+        refs: HashMap::new(),
+    };
+    // Reserved so no generated rule takes the name
+    converter.rules.insert(GRAMMAR_ROOT.to_string(), String::new());
+    let root = converter.visit(schema, "#")?;
+    if root == GRAMMAR_ROOT {
+        return Err(invalid("#", "schema only references itself"));
+    }
+
+    let mut grammar = format!("{} ::= {}\n", GRAMMAR_ROOT, root);
+    for (name, body) in &converter.rules {
+        if name != GRAMMAR_ROOT {
+            grammar.push_str(&format!("{} ::= {}\n", name, body));
+        }
+    }
+    Ok(grammar)
+}
+
+fn check_subschema(schema: &Value, path: &str, depth: usize) -> Result<(), ConstraintError> {
+    if depth > MAX_SCHEMA_DEPTH {
+        return Err(invalid(path, "schema is nested too deeply"));
+    }
+    let object = match schema {
+        Value::Object(object) => object,
+        // `true` and `false` are valid schemas with no keywords
+        Value::Bool(_) => return Ok(()),
+        _ => return Err(invalid(path, "subschema must be an object or boolean")),
+    };
+
+    if let Some(keyword) = UNSUPPORTED_KEYWORDS.iter().find(|k| object.contains_key(**k)) {
+        return Err(ConstraintError::UnsupportedSchema {
+            keyword: keyword.to_string(),
+            path: path.to_string(),
+        });
+    }
+    if let Some(reference) = object.get("$ref") {
+        match reference.as_str() {
+            Some(r) if r == "#" || r.starts_with("#/") => {}
+            _ => {
+                return Err(ConstraintError::UnsupportedSchema {
+                    keyword: "$ref (only local references are supported)".into(),
+                    path: path.to_string(),
+                })
+            }
+        }
+    }
+    match object.get("type") {
+        None => {}
+        Some(Value::String(t)) if TYPES.contains(&t.as_str()) => {}
+        Some(Value::Array(types))
+            if !types.is_empty()
+                && types.iter().all(|t| t.as_str().is_some_and(|t| TYPES.contains(&t))) => {}
+        Some(_) => return Err(invalid(path, "unknown type")),
+    }
+
+    for keyword in SCHEMA_MAPS {
+        if let Some(map) = object.get(*keyword) {
+            let map = map
+                .as_object()
+                .ok_or_else(|| invalid(path, &format!("{} must be an object", keyword)))?;
+            for (name, subschema) in map {
+                check_subschema(subschema, &format!("{}/{}/{}", path, keyword, name), depth + 1)?;
+            }
+        }
+    }
+    for keyword in SCHEMA_LISTS {
+        if let Some(list) = object.get(*keyword) {
+            let list = list
+                .as_array()
+                .filter(|list| !list.is_empty())
+                .ok_or_else(|| invalid(path, &format!("{} must be a non-empty array", keyword)))?;
+            for (i, subschema) in list.iter().enumerate() {
+                check_subschema(subschema, &format!("{}/{}/{}", path, keyword, i), depth + 1)?;
+            }
+        }
+    }
+    for keyword in ["items", "additionalProperties"] {
+        if let Some(subschema) = object.get(keyword) {
+            check_subschema(subschema, &format!("{}/{}", path, keyword), depth + 1)?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds GBNF rules for a checked JSON Schema.
+struct GbnfConverter<'a> {
+    root: &'a Value,
+    rules: BTreeMap<String, String>,
+    /// Rule name for each `$ref` target already converted.
+    refs: HashMap<String, String>,
+}
+
+impl GbnfConverter<'_> {
+    /// Expression matching the values `schema` accepts.
+    fn visit(&mut self, schema: &Value, path: &str) -> Result<String, ConstraintError> {
+        let object = match schema {
+            Value::Object(object) => object,
+            Value::Bool(true) => return Ok(self.primitive("value")),
+            _ => return Err(invalid(path, "schema matches no value")),
+        };
+        if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+            return self.reference(reference, path);
+        }
+        if let Some(value) = object.get("const") {
+            return Ok(self.literal(value));
+        }
+        if let Some(values) = object.get("enum") {
+            let values = values
+                .as_array()
+                .filter(|values| !values.is_empty())
+                .ok_or_else(|| invalid(path, "enum must be a non-empty array"))?;
+            let alternatives: Vec<String> = values.iter().map(|v| self.literal(v)).collect();
+            return Ok(group(&alternatives));
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            let Some(list) = object.get(keyword).and_then(Value::as_array) else {
+                continue;
+            };
+            if ["type", "properties", "items"].iter().any(|k| object.contains_key(*k)) {
+                return Err(ConstraintError::UnsupportedSchema {
+                    keyword: format!("{} alongside type keywords", keyword),
+                    path: path.to_string(),
+                });
+            }
+            let mut alternatives = Vec::with_capacity(list.len());
+            for (i, subschema) in list.iter().enumerate() {
+                alternatives.push(self.visit(subschema, &format!("{}/{}/{}", path, keyword, i))?);
+            }
+            return Ok(group(&alternatives));
+        }
+
+        let types: Vec<&str> = match object.get("type") {
+            Some(Value::String(t)) => vec![t.as_str()],
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+            _ if ["properties", "required", "additionalProperties"]
+                .iter()
+                .any(|k| object.contains_key(*k)) =>
+            {
+                vec!["object"]
+            }
+            _ if object.contains_key("items") || object.contains_key("prefixItems") => {
+                vec!["array"]
+            }
+            _ => return Ok(self.primitive("value")),
+        };
+        let mut alternatives = Vec::with_capacity(types.len());
+        for t in types {
+            alternatives.push(match t {
+                "object" => self.object(object, path)?,
+                "array" => self.array(object, path)?,
+                "string" => self.string(object, path)?,
+                other => self.primitive(other),
+            });
+        }
+        Ok(group(&alternatives))
+    }
+
+    fn reference(&mut self, reference: &str, path: &str) -> Result<String, ConstraintError> {
+        if reference == "#" {
+            return Ok(GRAMMAR_ROOT.to_string());
+        }
+        if let Some(name) = self.refs.get(reference) {
+            return Ok(name.clone());
+        }
+        let target = self
+            .root
+            .pointer(&reference[1..])
+            .ok_or_else(|| invalid(path, &format!("unresolved $ref '{}'", reference)))?;
+        // Reserve the name first so recursive references resolve to it
+        let name = self.unique_name(&format!("ref{}", &reference[1..]));
+        self.rules.insert(name.clone(), String::new());
+        self.refs.insert(reference.to_string(), name.clone());
+        let body = self.visit(target, reference)?;
+        self.rules.insert(name.clone(), body);
+        Ok(name)
+    }
+
+    fn object(&mut self, object: &Map<String, Value>, path: &str) -> Result<String, ConstraintError> {
+        let ws = self.primitive("ws");
+        let no_properties = Map::new();
+        let properties = match object.get("properties") {
+            Some(properties) => properties
+                .as_object()
+                .ok_or_else(|| invalid(path, "properties must be an object"))?,
+            None => &no_properties,
+        };
+        let required = match object.get("required") {
+            None => Vec::new(),
+            Some(Value::Array(names)) => names
+                .iter()
+                .map(|name| {
+                    name.as_str()
+                        .ok_or_else(|| invalid(path, "required must be an array of strings"))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(_) => return Err(invalid(path, "required must be an array of strings")),
+        };
+
+        if properties.is_empty() && required.is_empty() {
+            return match object.get("additionalProperties") {
+                Some(Value::Bool(false)) => Ok(format!(r#""{{" {ws} "}}" {ws}"#)),
+                Some(schema @ Value::Object(_)) => {
+                    let value = self.visit(schema, &format!("{}/additionalProperties", path))?;
+                    let key = self.primitive("string");
+                    let entry =
+                        self.add_rule(&format!("{}-entry", path), format!(r#"{key} ":" {ws} {value}"#));
+                    Ok(format!(r#""{{" {ws} ( {entry} ( "," {ws} {entry} )* )? "}}" {ws}"#))
+                }
+                _ => Ok(self.primitive("object")),
+            };
+        }
+
+        // Leaving out unnamed properties satisfies any `additionalProperties`
+        let mut required_members = Vec::new();
+        let mut optional_members = Vec::new();
+        for (name, schema) in properties {
+            let value = self.visit(schema, &format!("{}/properties/{}", path, name))?;
+            let member = self.member(path, name, &value);
+            if required.contains(&name.as_str()) {
+                required_members.push(member);
+            } else {
+                optional_members.push(member);
+            }
+        }
+        for name in required.iter().filter(|name| !properties.contains_key(**name)) {
+            let value = self.primitive("value");
+            required_members.push(self.member(path, name, &value));
+        }
+
+        let separator = format!(r#" "," {} "#, ws);
+        let members = if required_members.is_empty() {
+            // Any optional member may come first; only later ones follow it
+            let alternatives: Vec<String> = (0..optional_members.len())
+                .map(|i| optional_tail(&optional_members[i], &optional_members[i + 1..], &ws))
+                .collect();
+            format!("( {} )?", alternatives.join(" | "))
+        } else {
+            optional_tail(&required_members.join(&separator), &optional_members, &ws)
+        };
+        Ok(format!(r#""{{" {ws} {members} "}}" {ws}"#))
+    }
+
+    fn member(&mut self, path: &str, name: &str, value: &str) -> String {
+        let ws = self.primitive("ws");
+        let key = quote(&Value::from(name).to_string());
+        self.add_rule(
+            &format!("{}/{}-kv", path, name),
+            format!(r#"{key} {ws} ":" {ws} {value}"#),
+        )
+    }
+
+    fn array(&mut self, object: &Map<String, Value>, path: &str) -> Result<String, ConstraintError> {
+        let ws = self.primitive("ws");
+        let (min, max) = bounds(object, "minItems", "maxItems", path)?;
+        let mut elements = Vec::new();
+        if let Some(prefix) = object.get("prefixItems").and_then(Value::as_array) {
+            let limit = max.map_or(usize::MAX, |max| usize::try_from(max).unwrap_or(usize::MAX));
+            for (i, schema) in prefix.iter().enumerate().take(limit) {
+                elements.push(self.visit(schema, &format!("{}/prefixItems/{}", path, i))?);
+            }
+        }
+        let fixed = elements.len() as u64;
+        let item = match object.get("items") {
+            Some(Value::Bool(false)) => None,
+            Some(schema @ Value::Object(_)) => Some(self.visit(schema, &format!("{}/items", path))?),
+            _ => Some(self.primitive("value")),
+        };
+
+        let mut body = elements.join(&format!(r#" "," {} "#, ws));
+        match item {
+            None if min > fixed => {
+                return Err(invalid(path, "minItems exceeds prefixItems and items is false"));
+            }
+            Some(item) if max.is_none_or(|max| max > fixed) => {
+                let item = self.add_rule(&format!("{}-item", path), item);
+                let more = format!(r#"( "," {} {} )"#, ws, item);
+                let min_extra = min.saturating_sub(fixed);
+                let max_extra = max.map(|max| max - fixed);
+                body = if fixed > 0 {
+                    format!("{} {}", body, repeat(&more, min_extra, max_extra))
+                } else {
+                    // The first item has no leading comma
+                    let rest = repeat(&more, min_extra.saturating_sub(1), max_extra.map(|m| m - 1));
+                    let items = format!("{} {}", item, rest);
+                    if min_extra == 0 {
+                        format!("( {} )?", items.trim_end())
+                    } else {
+                        items
+                    }
+                };
+            }
+            _ => {}
+        }
+        Ok(format!(r#""[" {ws} {body} "]" {ws}"#))
+    }
+
+    fn string(&mut self, object: &Map<String, Value>, path: &str) -> Result<String, ConstraintError> {
+        let (min, max) = bounds(object, "minLength", "maxLength", path)?;
+        if min == 0 && max.is_none() {
+            return Ok(self.primitive("string"));
+        }
+        let chars = repeat(&self.primitive("char"), min, max);
+        let ws = self.primitive("ws");
+        Ok(format!(r#""\"" {chars} "\"" {ws}"#))
+    }
+
+    /// Exact JSON text of `value`.
+    fn literal(&mut self, value: &Value) -> String {
+        format!("{} {}", quote(&value.to_string()), self.primitive("ws"))
+    }
+
+    /// Add a generic JSON rule (and the rules it uses) once.
+    fn primitive(&mut self, name: &str) -> String {
+        if !self.rules.contains_key(name) {
+            if let Some((_, body, uses)) = PRIMITIVES.iter().find(|(n, _, _)| *n == name) {
+                self.rules.insert(name.to_string(), body.to_string());
+                for rule in *uses {
+                    self.primitive(rule);
+                }
+            }
+        }
+        name.to_string()
+    }
+
+    /// Name a rule body, reusing an existing rule with the same body.
+    fn add_rule(&mut self, hint: &str, body: String) -> String {
+        let base = rule_name(hint);
+        if self.rules.get(&base) == Some(&body) {
+            return base;
+        }
+        let name = self.unique_name(hint);
+        self.rules.insert(name.clone(), body);
+        name
+    }
+
+    fn unique_name(&self, hint: &str) -> String {
+        let base = rule_name(hint);
+        let mut name = base.clone();
+        let mut n = 1;
+        while self.rules.contains_key(&name) || PRIMITIVES.iter().any(|(p, _, _)| *p == name) {
+            n += 1;
+            name = format!("{}-{}", base, n);
+        }
+        name
+    }
+}
+
+/// GBNF rule name from a schema path: `#/properties/a/b_c-kv` becomes
+/// `root-properties-a-b-c-kv`.
+fn rule_name(hint: &str) -> String {
+    let mut name = String::new();
+    for c in hint.replacen('#', GRAMMAR_ROOT, 1).chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if !name.is_empty() && !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    let name = name.trim_end_matches('-');
+    if name.is_empty() {
+        "rule".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// GBNF string literal matching `text` exactly.
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn group(alternatives: &[String]) -> String {
+    match alternatives {
+        [single] => single.clone(),
+        _ => format!("( {} )", alternatives.join(" | ")),
+    }
+}
+
+/// `item` repeated `min` to `max` times; `item` must be a single term.
+fn repeat(item: &str, min: u64, max: Option<u64>) -> String {
+    match (min, max) {
+        (_, Some(0)) => String::new(),
+        (0, None) => format!("{}*", item),
+        (1, None) => format!("{}+", item),
+        (min, None) => format!("{}{{{},}}", item, min),
+        (min, Some(max)) if min == max => format!("{}{{{}}}", item, min),
+        (min, Some(max)) => format!("{}{{{},{}}}", item, min, max),
+    }
+}
+
+/// `member` followed by each of `optional`, all of which may be left out.
+fn optional_tail(member: &str, optional: &[String], ws: &str) -> String {
+    let mut out = member.to_string();
+    for next in optional {
+        out.push_str(&format!(r#" ( "," {} {} )?"#, ws, next));
+    }
+    out
+}
+
+/// Lower and upper bound keywords such as `minItems` and `maxItems`.
+fn bounds(
+    object: &Map<String, Value>,
+    min_key: &str,
+    max_key: &str,
+    path: &str,
+) -> Result<(u64, Option<u64>), ConstraintError> {
+    let read = |key: &str| match object.get(key) {
+        None => Ok(None),
+        Some(value) => match value.as_u64() {
+            Some(n) if n > MAX_SCHEMA_REPEAT => Err(ConstraintError::UnsupportedSchema {
+                keyword: format!("{} above {}", key, MAX_SCHEMA_REPEAT),
+                path: path.to_string(),
+            }),
+            Some(n) => Ok(Some(n)),
+            None => Err(invalid(path, &format!("{} must be a non-negative integer", key))),
+        },
+    };
+    let min = read(min_key)?.unwrap_or(0);
+    let max = read(max_key)?;
+    if max.is_some_and(|max| max < min) {
+        return Err(invalid(path, &format!("{} exceeds {}", min_key, max_key)));
+    }
+    Ok((min, max))
+}
+
+fn invalid(path: &str, reason: &str) -> ConstraintError {
+    ConstraintError::InvalidSchema {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule<'a>(grammar: &'a str, name: &str) -> &'a str {
+        let prefix = format!("{} ::= ", name);
+        grammar
+            .lines()
+            .find_map(|line| line.strip_prefix(prefix.as_str()))
+            .unwrap_or_else(|| panic!("no rule {} in\n{}", name, grammar))
+    }
+
+    #[test]
+    fn accepts_supported_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "tags": { "type": "array", "items": { "$ref": "#/$defs/tag" } },
+                "age": { "type": ["integer", "null"] }
+            },
+            "required": ["name"],
+            "additionalProperties": false,
+            "$defs": { "tag": { "enum": ["a", "b"] } }
+        });
+        assert!(check_json_schema(&schema).is_ok());
+        assert!(json_schema_to_gbnf(&schema).is_ok());
+    }
+
+    #[test]
+    fn rejects_unsupported_keywords_with_path() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "id": { "type": "integer", "minimum": 0 } }
+        });
+        assert_eq!(
+            check_json_schema(&schema),
+            Err(ConstraintError::UnsupportedSchema {
+                keyword: "minimum".into(),
+                path: "#/properties/id".into(),
+            })
+        );
+
+        let remote = json!({ "$ref": "https://example.com/schema.json" });
+        assert!(matches!(
+            check_json_schema(&remote),
+            Err(ConstraintError::UnsupportedSchema { .. })
+        ));
+        let huge = json!({ "type": "string", "maxLength": MAX_SCHEMA_REPEAT + 1 });
+        assert!(matches!(
+            json_schema_to_gbnf(&huge),
+            Err(ConstraintError::UnsupportedSchema { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_schemas() {
+        assert!(check_json_schema(&json!("object")).is_err());
+        assert!(check_json_schema(&json!({ "type": "decimal" })).is_err());
+        assert!(check_json_schema(&json!({ "anyOf": { "type": "string" } })).is_err());
+        assert!(json_schema_to_gbnf(&json!({ "$ref": "#/$defs/missing" })).is_err());
+        assert!(json_schema_to_gbnf(&json!({ "type": "array", "minItems": 3, "maxItems": 2 }))
+            .is_err());
+
+        let mut deep = json!({ "type": "string" });
+        for _ in 0..=MAX_SCHEMA_DEPTH {
+            deep = json!({ "type": "array", "items": deep });
+        }
+        assert!(check_json_schema(&deep).is_err());
+    }
+
+    #[test]
+    fn converts_objects_with_required_and_optional_members() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "a": { "type": "integer" }, "b": { "type": "boolean" } },
+            "required": ["a"]
+        });
+        let grammar = json_schema_to_gbnf(&schema).unwrap();
+        assert_eq!(
+            rule(&grammar, "root"),
+            r#""{" ws root-a-kv ( "," ws root-b-kv )? "}" ws"#
+        );
+        assert_eq!(rule(&grammar, "root-a-kv"), r#""\"a\"" ws ":" ws integer"#);
+        assert_eq!(rule(&grammar, "integer"), "integral ws");
+
+        let optional = json!({ "properties": { "a": {}, "b": {} } });
+        let grammar = json_schema_to_gbnf(&optional).unwrap();
+        assert_eq!(
+            rule(&grammar, "root"),
+            r#""{" ws ( root-a-kv ( "," ws root-b-kv )? | root-b-kv )? "}" ws"#
+        );
+    }
+
+    #[test]
+    fn converts_literals_arrays_and_references() {
+        let grammar = json_schema_to_gbnf(&json!({ "enum": ["a\"b", 1, null] })).unwrap();
+        assert_eq!(rule(&grammar, "root"), r#"( "\"a\\\"b\"" ws | "1" ws | "null" ws )"#);
+
+        let schema = json!({ "type": "array", "items": { "type": "integer" }, "maxItems": 3 });
+        let grammar = json_schema_to_gbnf(&schema).unwrap();
+        assert_eq!(
+            rule(&grammar, "root"),
+            r#""[" ws ( root-item ( "," ws root-item ){0,2} )? "]" ws"#
+        );
+
+        let recursive = json!({
+            "$ref": "#/$defs/node",
+            "$defs": {
+                "node": {
+                    "type": "object",
+                    "properties": { "next": { "anyOf": [{ "$ref": "#/$defs/node" }, { "type": "null" }] } },
+                    "required": ["next"]
+                }
+            }
+        });
+        let grammar = json_schema_to_gbnf(&recursive).unwrap();
+        assert_eq!(rule(&grammar, "root"), "ref-defs-node");
+        assert_eq!(
+            rule(&grammar, "root-defs-node-next-kv"),
+            r#""\"next\"" ws ":" ws ( ref-defs-node | null )"#
+        );
+    }
+
+    #[test]
+    fn grammar_and_schema_are_exclusive() {
+        let schema = json!({ "type": "boolean" });
+        assert_eq!(
+            OutputConstraint::from_request(Some("root ::= \"x\""), Some(&schema)),
+            Err(ConstraintError::Conflicting)
+        );
+        assert_eq!(
+            OutputConstraint::from_request(Some("  "), None),
+            Err(ConstraintError::GrammarSize)
+        );
+        assert_eq!(OutputConstraint::from_request(None, None), Ok(None));
+        assert_eq!(
+            OutputConstraint::from_request(None, Some(&schema)),
+            Ok(Some(OutputConstraint::JsonSchema(schema.clone())))
+        );
+    }
+}
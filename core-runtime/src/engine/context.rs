@@ -0,0 +1,286 @@
+//! Context window enforcement for text generation.
+//!
+//! A request fits when its prompt tokens plus `max_tokens` fit the model's
+//! context window. Prompts are measured with the model's own tokenizer, and
+//! a request that does not fit is handled by its `ContextStrategy`.
+//! Query-document pairs scored by rerankers are truncated to fit instead.
+
+use serde::{Deserialize, Serialize};
+
+use super::error::InferenceError;
+use super::input::{ChatMessage, ChatRole};
+
+/// What to do when the prompt plus `max_tokens` exceeds the context window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    /// Fail with `ContextExceeded`.
+    #[default]
+    Reject,
+    /// Drop the oldest prompt tokens, keeping the BOS token.
+    TruncateLeft,
+    /// Chat only: keep system messages and the latest message, dropping the
+    /// oldest turns in between. Text prompts are rejected.
+    TrimMiddle,
+    /// Lower `max_tokens` to the space left after the prompt.
+    ClampMaxTokens,
+}
+
+/// How a request was fitted into the context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextFit {
+    /// Leading prompt tokens to drop.
+    pub drop_tokens: usize,
+    /// Tokens the request may generate.
+    pub max_tokens: u32,
+}
+
+/// Fit `prompt_tokens` plus `max_tokens` into `n_ctx` tokens.
+pub fn fit_tokens(
+    prompt_tokens: usize,
+    max_tokens: u32,
+    n_ctx: usize,
+    strategy: ContextStrategy,
+) -> Result<ContextFit, InferenceError> {
+    let exceeded = || InferenceError::ContextExceeded {
+        n_ctx,
+        prompt_tokens,
+        max_tokens: max_tokens as usize,
+    };
+    let total = prompt_tokens + max_tokens as usize;
+    if total <= n_ctx {
+        return Ok(ContextFit { drop_tokens: 0, max_tokens });
+    }
+    match strategy {
+        ContextStrategy::Reject | ContextStrategy::TrimMiddle => Err(exceeded()),
+        ContextStrategy::TruncateLeft => {
+            // At least one prompt token must remain to sample from
+            if max_tokens as usize >= n_ctx {
+                return Err(exceeded());
+            }
+            Ok(ContextFit { drop_tokens: total - n_ctx, max_tokens })
+        }
+        ContextStrategy::ClampMaxTokens => {
+            if prompt_tokens >= n_ctx {
+                return Err(exceeded());
+            }
+            let room = u32::try_from(n_ctx - prompt_tokens).unwrap_or(u32::MAX);
+            Ok(ContextFit { drop_tokens: 0, max_tokens: room })
+        }
+    }
+}
+
+/// Drop the oldest turns of a conversation until its prompt leaves room for
+/// `max_tokens`.
+///
+/// Leading system messages and the latest message are always kept. A turn
+/// is a message and the assistant replies that follow it, so roles keep
+/// alternating. `count` renders and tokenizes a conversation.
+pub fn trim_chat<F>(
+    messages: &[ChatMessage],
+    max_tokens: u32,
+    n_ctx: usize,
+    mut count: F,
+) -> Result<Vec<ChatMessage>, InferenceError>
+where
+    F: FnMut(&[ChatMessage]) -> Result<usize, InferenceError>,
+{
+    let mut kept = messages.to_vec();
+    let first_turn = kept.iter().take_while(|m| m.role == ChatRole::System).count();
+    loop {
+        let prompt_tokens = count(&kept)?;
+        if prompt_tokens + max_tokens as usize <= n_ctx {
+            return Ok(kept);
+        }
+        if kept.len() <= first_turn + 1 {
+            return Err(InferenceError::ContextExceeded {
+                n_ctx,
+                prompt_tokens,
+                max_tokens: max_tokens as usize,
+            });
+        }
+        kept.remove(first_turn);
+        while kept.len() > first_turn + 1 && kept[first_turn].role == ChatRole::Assistant {
+            kept.remove(first_turn);
+        }
+    }
+}
+
+/// Cut the longer of two token sequences, one token at a time from its
+/// end, until both fit `budget` tokens together; `second` is cut on ties.
+/// This is the longest-first truncation of reference pair tokenizers.
+pub fn truncate_pair<T>(first: &mut Vec<T>, second: &mut Vec<T>, budget: usize) {
+    while first.len() + second.len() > budget {
+        if first.len() > second.len() {
+            first.pop();
+        } else {
+            second.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: ChatRole, content: &str) -> ChatMessage {
+        ChatMessage { role, content: content.to_string() }
+    }
+
+    /// One token per word, as a stand-in for a real tokenizer.
+    fn words(messages: &[ChatMessage]) -> Result<usize, InferenceError> {
+        Ok(messages.iter().map(|m| m.content.split_whitespace().count()).sum())
+    }
+
+    #[test]
+    fn fitting_request_is_unchanged() {
+        for strategy in [
+            ContextStrategy::Reject,
+            ContextStrategy::TruncateLeft,
+            ContextStrategy::TrimMiddle,
+            ContextStrategy::ClampMaxTokens,
+        ] {
+            assert_eq!(
+                fit_tokens(100, 28, 128, strategy).unwrap(),
+                ContextFit { drop_tokens: 0, max_tokens: 28 }
+            );
+        }
+    }
+
+    #[test]
+    fn overflow_handled_per_strategy() {
+        assert!(matches!(
+            fit_tokens(120, 16, 128, ContextStrategy::Reject),
+            Err(InferenceError::ContextExceeded { n_ctx: 128, prompt_tokens: 120, max_tokens: 16 })
+        ));
+        assert_eq!(
+            fit_tokens(120, 16, 128, ContextStrategy::TruncateLeft).unwrap(),
+            ContextFit { drop_tokens: 8, max_tokens: 16 }
+        );
+        assert_eq!(
+            fit_tokens(120, 16, 128, ContextStrategy::ClampMaxTokens).unwrap(),
+            ContextFit { drop_tokens: 0, max_tokens: 8 }
+        );
+        assert!(fit_tokens(120, 16, 128, ContextStrategy::TrimMiddle).is_err());
+    }
+
+    #[test]
+    fn overflow_that_cannot_be_fixed_is_rejected() {
+        assert!(fit_tokens(10, 128, 128, ContextStrategy::TruncateLeft).is_err());
+        assert!(fit_tokens(128, 1, 128, ContextStrategy::ClampMaxTokens).is_err());
+    }
+
+    #[test]
+    fn reject_fails_for_any_overflow() {
+        assert!(fit_tokens(64, 64, 128, ContextStrategy::Reject).is_ok());
+        assert!(matches!(
+            fit_tokens(64, 65, 128, ContextStrategy::Reject),
+            Err(InferenceError::ContextExceeded { prompt_tokens: 64, max_tokens: 65, .. })
+        ));
+        assert!(matches!(
+            fit_tokens(300, 1, 128, ContextStrategy::Reject),
+            Err(InferenceError::ContextExceeded { prompt_tokens: 300, .. })
+        ));
+    }
+
+    #[test]
+    fn truncate_left_reserves_max_tokens() {
+        // Prompt alone is longer than the window
+        let fit = fit_tokens(300, 16, 128, ContextStrategy::TruncateLeft).unwrap();
+        assert_eq!(fit, ContextFit { drop_tokens: 188, max_tokens: 16 });
+        assert_eq!(300 - fit.drop_tokens + fit.max_tokens as usize, 128);
+
+        // Leaves exactly one prompt token
+        let fit = fit_tokens(40, 127, 128, ContextStrategy::TruncateLeft).unwrap();
+        assert_eq!(fit, ContextFit { drop_tokens: 39, max_tokens: 127 });
+
+        assert!(matches!(
+            fit_tokens(40, 128, 128, ContextStrategy::TruncateLeft),
+            Err(InferenceError::ContextExceeded { n_ctx: 128, prompt_tokens: 40, max_tokens: 128 })
+        ));
+    }
+
+    #[test]
+    fn clamp_max_tokens_uses_remaining_room() {
+        assert_eq!(
+            fit_tokens(127, 64, 128, ContextStrategy::ClampMaxTokens).unwrap(),
+            ContextFit { drop_tokens: 0, max_tokens: 1 }
+        );
+        // No room left for a single generated token
+        assert!(matches!(
+            fit_tokens(300, 16, 128, ContextStrategy::ClampMaxTokens),
+            Err(InferenceError::ContextExceeded { prompt_tokens: 300, max_tokens: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn trim_middle_rejects_text_prompts_and_trims_chats() {
+        // Text prompts have no turns to drop
+        assert!(matches!(
+            fit_tokens(300, 16, 128, ContextStrategy::TrimMiddle),
+            Err(InferenceError::ContextExceeded { prompt_tokens: 300, .. })
+        ));
+
+        // A conversation longer than the window keeps room for max_tokens
+        let mut messages = vec![message(ChatRole::System, "be brief")];
+        for _ in 0..20 {
+            messages.push(message(ChatRole::User, "one two three four five"));
+            messages.push(message(ChatRole::Assistant, "six seven eight nine ten"));
+        }
+        messages.push(message(ChatRole::User, "last question"));
+        assert!(words(&messages).unwrap() > 64);
+        let trimmed = trim_chat(&messages, 16, 64, words).unwrap();
+        assert!(words(&trimmed).unwrap() + 16 <= 64);
+        assert_eq!(trimmed[0].content, "be brief");
+        assert_eq!(trimmed[1].role, ChatRole::User);
+        assert_eq!(trimmed.last().unwrap().content, "last question");
+
+        // Reserving max_tokens is what forces trimming
+        assert!(matches!(
+            trim_chat(&messages, 61, 64, words),
+            Err(InferenceError::ContextExceeded { prompt_tokens: 4, max_tokens: 61, .. })
+        ));
+    }
+
+    #[test]
+    fn trim_chat_drops_oldest_turns_and_keeps_system() {
+        let messages = vec![
+            message(ChatRole::System, "be brief"),
+            message(ChatRole::User, "one two three"),
+            message(ChatRole::Assistant, "four five"),
+            message(ChatRole::User, "six seven"),
+            message(ChatRole::Assistant, "eight"),
+            message(ChatRole::User, "nine ten"),
+        ];
+        let trimmed = trim_chat(&messages, 4, 12, words).unwrap();
+        let contents: Vec<&str> = trimmed.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["be brief", "six seven", "eight", "nine ten"]);
+
+        let untouched = trim_chat(&messages, 1, 64, words).unwrap();
+        assert_eq!(untouched.len(), messages.len());
+    }
+
+    #[test]
+    fn trim_chat_rejects_when_latest_message_alone_is_too_long() {
+        let messages = vec![
+            message(ChatRole::System, "be brief"),
+            message(ChatRole::User, "one two"),
+            message(ChatRole::User, "three four five six"),
+        ];
+        assert!(matches!(
+            trim_chat(&messages, 4, 8, words),
+            Err(InferenceError::ContextExceeded { prompt_tokens: 6, .. })
+        ));
+    }
+
+    #[test]
+    fn truncate_pair_cuts_the_longer_sequence_first() {
+        let (mut query, mut document) = (vec![1, 2], vec![3, 4, 5, 6, 7, 8]);
+        truncate_pair(&mut query, &mut document, 6);
+        assert_eq!((query.as_slice(), document.as_slice()), (&[1, 2][..], &[3, 4, 5, 6][..]));
+        truncate_pair(&mut query, &mut document, 3);
+        assert_eq!((query.as_slice(), document.as_slice()), (&[1, 2][..], &[3][..]));
+        truncate_pair(&mut query, &mut document, 8);
+        assert_eq!(query.len() + document.len(), 3);
+    }
+}
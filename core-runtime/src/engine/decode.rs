@@ -1,7 +1,9 @@
 //! Decode executor optimized for single-token latency.
 //!
-//! Generates tokens sequentially with minimal latency per step.
+//! Generates tokens sequentially with minimal latency per step. The native
+//! backend evaluates each sampled token through [`DecodeExecutor::forward`].
 
+use crate::engine::native::NativeSession;
 use crate::engine::{FinishReason, InferenceError, SpeculativeConfig};
 use crate::memory::paged::{PageTable, PAGE_TOKENS};
 
@@ -105,6 +107,20 @@ impl DecodeExecutor {
         })
     }
 
+    /// Evaluate the sampled `token` in a native `session` and return the
+    /// next-token logits. Call [`init`](Self::init) with the session's
+    /// position after prefill first.
+    pub fn forward(
+        &mut self,
+        session: &mut NativeSession<'_>,
+        token: u32,
+    ) -> Result<Vec<f32>, InferenceError> {
+        let logits = session.forward(&[token])?;
+        self.current_pos = session.position();
+        self.tokens_generated += 1;
+        Ok(logits)
+    }
+
     fn sample_token(&self) -> Result<u32, InferenceError> {
         // No model loaded - fail rather than return stub data
         // Real implementation requires model forward pass and sampling
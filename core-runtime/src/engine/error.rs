@@ -16,6 +16,15 @@ pub enum InferenceError {
     #[error("Inference timeout after {0}ms")]
     Timeout(u64),
 
+    #[error(
+        "Context length exceeded: {prompt_tokens} prompt tokens + {max_tokens} max_tokens > {n_ctx}"
+    )]
+    ContextExceeded {
+        n_ctx: usize,
+        prompt_tokens: usize,
+        max_tokens: usize,
+    },
+
     #[error("Memory limit exceeded: used {used} bytes, limit {limit} bytes")]
     MemoryExceeded { used: usize, limit: usize },
 
@@ -2,22 +2,56 @@
 //!
 //! Model loading, context creation, and token generation
 //! via the llama-cpp-2 Rust bindings.
+//!
+//! Prefill reuses saved context state where it can: a request naming a
+//! conversation restores that conversation's last turn, and any other
+//! request restores the cached prompt sharing the longest prefix with it.
+//! Only the tokens past the reused prefix are decoded.
+//!
+//! LoRA adapters are loaded once against the model and applied to the
+//! context of each request that selects one. Such requests skip the prompt
+//! cache, and their conversation snapshots only resume under the same
+//! adapter and scale.
+//!
+//! Embedding models decode their inputs in an embeddings context instead,
+//! packed as separate sequences of one batch, and llama.cpp pools each
+//! sequence's token states. Rerankers decode query-document pairs the same
+//! way and read one score per pair from their rank pooling head.
 
+use std::collections::HashMap;
 use std::num::NonZeroU32;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::context::params::{LlamaContextParams, LlamaPoolingType};
 use llama_cpp_2::context::LlamaContext;
 use llama_cpp_2::llama_backend::LlamaBackend;
 use llama_cpp_2::llama_batch::LlamaBatch;
 use llama_cpp_2::model::params::LlamaModelParams;
-use llama_cpp_2::model::{AddBos, LlamaModel};
+use llama_cpp_2::model::{
+    AddBos, LlamaChatMessage, LlamaChatTemplate, LlamaLoraAdapter, LlamaModel,
+};
 use llama_cpp_2::sampling::LlamaSampler;
+use llama_cpp_2::token::logit_bias::LlamaLogitBias;
 use llama_cpp_2::token::LlamaToken;
+use llama_cpp_2::TokenToStringError;
 
+use crate::engine::chat_template::{role_name, GGUF_CHAT_TEMPLATE_KEY};
+use crate::engine::context::{fit_tokens, truncate_pair};
+use crate::engine::logprobs::token_logprobs;
 use crate::engine::{
-    FinishReason, GenerationResult, InferenceConfig, InferenceError,
+    ChatMessage, Completion, FinishReason, GenerationResult, GenerationTimings, InferenceConfig,
+    InferenceError, Mirostat, StopMatcher, StopSequence, StreamFinish, StreamingOutput,
+    TokenCandidate, TokenLogprob, Utf8StreamDecoder, GRAMMAR_ROOT, MAX_BATCH_SIZE,
 };
+use super::GgufPooling;
+use crate::memory::{reusable_prefix, PromptCache, PromptCacheStats, SessionKv, SessionKvStore};
+use crate::telemetry;
+
+/// A loaded LoRA adapter. Requests hold a reference for as long as their
+/// context uses it, so unloading never frees it under them.
+type SharedAdapter = Arc<parking_lot::Mutex<LlamaLoraAdapter>>;
 
 /// Holds the loaded llama-cpp-2 model and backend.
 pub struct LlamaBackendInner {
@@ -25,6 +59,11 @@ pub struct LlamaBackendInner {
     model: LlamaModel,
     n_ctx: u32,
     n_threads: i32,
+    draft_tokens: usize,
+    sessions: SessionKvStore,
+    prompt_cache: parking_lot::Mutex<PromptCache>,
+    /// LoRA adapters by the name requests select them with.
+    adapters: parking_lot::RwLock<HashMap<String, SharedAdapter>>,
 }
 
 // SAFETY: LlamaModel and LlamaBackend are Send+Sync in llama-cpp-2.
@@ -45,59 +84,187 @@ impl LlamaBackendInner {
         let model = LlamaModel::load_from_file(&backend, path, &model_params)
             .map_err(|e| InferenceError::ModelError(format!("load: {e}")))?;
         let n_threads = resolve_threads(config.n_threads);
-        Ok(Self { backend, model, n_ctx: config.n_ctx, n_threads })
+        let sessions = SessionKvStore::new(config.sessions.clone());
+        let prompt_cache = PromptCache::with_config(config.prompt_cache.clone());
+        Ok(Self {
+            backend,
+            model,
+            n_ctx: config.n_ctx,
+            n_threads,
+            draft_tokens: config.draft_tokens,
+            sessions,
+            prompt_cache: parking_lot::Mutex::new(prompt_cache),
+            adapters: parking_lot::RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Load a GGUF LoRA adapter under `name`. Returns its size in bytes.
+    pub fn load_adapter(&self, name: &str, path: &Path) -> Result<usize, InferenceError> {
+        let adapter = self.model.lora_adapter_init(path).map_err(|e| {
+            InferenceError::ModelError(format!("adapter {name}: {e}"))
+        })?;
+        let bytes = std::fs::metadata(path).map_or(0, |m| m.len() as usize);
+        self.adapters
+            .write()
+            .insert(name.to_string(), Arc::new(parking_lot::Mutex::new(adapter)));
+        Ok(bytes)
+    }
+
+    pub fn unload_adapter(&self, name: &str) -> bool {
+        self.adapters.write().remove(name).is_some()
+    }
+
+    pub fn has_adapter(&self, name: &str) -> bool {
+        self.adapters.read().contains_key(name)
+    }
+
+    /// The adapter a request selects, if any.
+    fn request_adapter(
+        &self,
+        config: &InferenceConfig,
+    ) -> Result<Option<SharedAdapter>, InferenceError> {
+        let Some(selection) = &config.adapter else {
+            return Ok(None);
+        };
+        match self.adapters.read().get(&selection.name) {
+            Some(adapter) => Ok(Some(adapter.clone())),
+            None => Err(InferenceError::InputValidation(format!(
+                "adapter '{}' is not loaded",
+                selection.name
+            ))),
+        }
     }
 
     pub fn model_size(&self) -> usize { self.model.size() as usize }
 
+    /// Context window in tokens (the model's training context if unset).
+    pub fn context_size(&self) -> usize {
+        if self.n_ctx == 0 {
+            self.model.n_ctx_train() as usize
+        } else {
+            self.n_ctx as usize
+        }
+    }
+
+    /// Tokens a draft model proposes per verification step.
+    pub(super) fn draft_tokens(&self) -> usize {
+        self.draft_tokens
+    }
+
+    /// Whether `draft` can propose tokens for this model. Drafts are
+    /// compared by token id, so both models must share a vocabulary.
+    pub(super) fn shares_vocab(&self, draft: &Self) -> bool {
+        self.model.n_vocab() == draft.model.n_vocab()
+            && self.model.token_eos() == draft.model.token_eos()
+    }
+
+    /// Whether `tok` ends generation.
+    pub(super) fn is_eog(&self, tok: LlamaToken) -> bool {
+        self.model.is_eog_token(tok)
+    }
+
     /// Generate text from a prompt using llama-cpp-2.
     pub fn generate(
         &self,
         prompt: &str,
         config: &InferenceConfig,
     ) -> Result<GenerationResult, InferenceError> {
-        let tokens = self.tokenize(prompt)?;
-        let max_tok = config.max_tokens.unwrap_or(256);
-        let mut ctx = self.create_context()?;
-        let (out_tokens, reason) =
-            self.sample_loop(&mut ctx, &tokens, max_tok, config)?;
-        let text = self.detokenize(&out_tokens)?;
-        let count = u32::try_from(out_tokens.len()).unwrap_or(u32::MAX);
-        Ok(GenerationResult { text, tokens_generated: count, finish_reason: reason })
+        let started = Instant::now();
+        let (tokens, max_tok) = self.fit_context(self.tokenize(prompt)?, config)?;
+        // Declared before the context so it outlives it
+        let adapter = self.request_adapter(config)?;
+        if config.best_of > 1 {
+            // Sequences diverge after the prompt, leaving no one state to save
+            let config = InferenceConfig { conversation: None, ..config.clone() };
+            let mut ctx = self.create_context(config.best_of as u32)?;
+            apply_adapter(&ctx, adapter.as_ref(), &config)?;
+            return self.sample_many(&mut ctx, &tokens, max_tok, &config, started);
+        }
+        let mut ctx = self.create_context(1)?;
+        apply_adapter(&ctx, adapter.as_ref(), config)?;
+        self.sample_loop(&mut ctx, &tokens, max_tok, config, started)
     }
 
     /// Stream tokens one at a time through a channel.
+    ///
+    /// The final token carries the usage summary.
     pub fn generate_stream(
         &self,
         prompt: &str,
         config: &InferenceConfig,
         sender: crate::engine::TokenStreamSender,
     ) -> Result<(), InferenceError> {
-        let tokens = self.tokenize(prompt)?;
-        let max_tok = config.max_tokens.unwrap_or(256);
-        let mut ctx = self.create_context()?;
-        let mut batch = LlamaBatch::new(tokens.len(), 1);
-        add_seq(&mut batch, &tokens)?;
-        decode(&mut ctx, &mut batch)?;
-        let mut sampler = build_sampler(config);
-        sampler.accept_many(tokens.iter().copied());
-        let mut pos = tokens.len() as i32;
+        let started = Instant::now();
+        let (tokens, max_tok) = self.fit_context(self.tokenize(prompt)?, config)?;
+        let adapter = self.request_adapter(config)?;
+        let mut ctx = self.create_context(1)?;
+        apply_adapter(&ctx, adapter.as_ref(), config)?;
+        let mut batch = self.prefill(&mut ctx, &tokens, config)?;
+        let prefill = started.elapsed();
+        let mut sampler = self.build_sampler(config, &tokens)?;
+        let mut evaluated = tokens.clone();
         let rt = tokio::runtime::Handle::current();
+        let mut utf8 = Utf8StreamDecoder::new();
+        let mut stops = StopMatcher::new(&config.stop);
+        let mut first_token = None;
         for i in 0..max_tok {
             // Use -1 to sample from the last token that had logits computed
             let tok = sampler.sample(&ctx, -1);
             sampler.accept(tok);
+            let first_token = *first_token.get_or_insert_with(|| started.elapsed());
             let eog = self.model.is_eog_token(tok);
-            let is_final = eog || i + 1 == max_tok;
-            if rt.block_on(sender.send(tok.0 as u32, is_final)).is_err() {
+            let (mut text, stop_sequence) = if eog {
+                (String::new(), None)
+            } else if let Some(stop) = stops.stop_token(tok.0 as u32) {
+                (stops.flush() + &utf8.finish(), Some(stop))
+            } else {
+                stops.push(&utf8.push(&self.token_bytes(tok)?))
+            };
+            let reason = if eog || stop_sequence.is_some() {
+                Some(FinishReason::Stop)
+            } else if i + 1 == max_tok {
+                Some(FinishReason::MaxTokens)
+            } else {
+                config.abort_reason(started)
+            };
+            // End-of-generation and stop tokens are not part of the completion
+            let excluded = eog || matches!(stop_sequence, Some(StopSequence::Token(_)));
+            let logprob = match config.logprobs {
+                Some(top_n) if !excluded => {
+                    Some(self.token_logprob(&ctx, batch.n_tokens() - 1, tok, top_n)?)
+                }
+                _ => None,
+            };
+            let sent = match reason {
+                None => rt.block_on(sender.send_text(tok.0 as u32, text, logprob, false)),
+                Some(finish_reason) => {
+                    if stop_sequence.is_none() {
+                        text.push_str(&stops.flush());
+                        text.push_str(&utf8.finish());
+                    }
+                    let finish = StreamFinish {
+                        prompt_tokens: u32::try_from(tokens.len()).unwrap_or(u32::MAX),
+                        completion_tokens: if excluded { i } else { i + 1 },
+                        finish_reason,
+                        timings: GenerationTimings {
+                            prefill,
+                            first_token,
+                            decode: started.elapsed().saturating_sub(prefill),
+                        },
+                        stop_sequence,
+                    };
+                    rt.block_on(sender.finish(tok.0 as u32, text, logprob, finish))
+                }
+            };
+            if sent.is_err() || reason.is_some() {
                 break;
             }
-            if eog { break; }
             batch.clear();
-            add_one(&mut batch, tok, pos)?;
+            add_one(&mut batch, tok, evaluated.len() as i32)?;
             decode(&mut ctx, &mut batch)?;
-            pos += 1;
+            evaluated.push(tok);
         }
+        self.save_session(&ctx, config, &evaluated);
         Ok(())
     }
 
@@ -109,12 +276,11 @@ impl LlamaBackendInner {
     ) -> Result<Vec<u32>, InferenceError> {
         let tokens: Vec<LlamaToken> = context.iter().map(|&t| LlamaToken(t as i32)).collect();
         let config = InferenceConfig::default();
-        let mut ctx = self.create_context()?;
+        let mut ctx = self.create_context(1)?;
         let mut batch = LlamaBatch::new(tokens.len().max(1), 1);
-        add_seq(&mut batch, &tokens)?;
+        add_seq(&mut batch, &tokens, 0)?;
         decode(&mut ctx, &mut batch)?;
-        let mut sampler = build_sampler(&config);
-        sampler.accept_many(tokens.iter().copied());
+        let mut sampler = self.build_sampler(&config, &tokens)?;
         let mut out = Vec::with_capacity(count);
         let mut pos = tokens.len() as i32;
         for _ in 0..count {
@@ -130,47 +296,35 @@ impl LlamaBackendInner {
         Ok(out)
     }
 
-    /// Verify draft tokens against model (for speculative decoding).
-    pub fn verify_tokens(
-        &self,
-        context: &[u32],
-        draft: &[u32],
-    ) -> Result<crate::engine::speculative::VerifyResult, InferenceError> {
-        use crate::engine::speculative::VerifyResult;
-        let all_tokens: Vec<LlamaToken> = context.iter()
-            .chain(draft.iter())
-            .map(|&t| LlamaToken(t as i32))
-            .collect();
-        let config = InferenceConfig::default();
-        let mut ctx = self.create_context()?;
-        // Add all tokens with logits enabled for verification positions
-        let mut batch = LlamaBatch::new(all_tokens.len(), 1);
-        let ctx_len = context.len();
-        for (i, &tok) in all_tokens.iter().enumerate() {
-            // Enable logits for context's last token and all draft positions
-            let needs_logits = i >= ctx_len.saturating_sub(1);
-            batch.add(tok, i as i32, &[0], needs_logits)
-                .map_err(|e| InferenceError::ModelError(format!("batch: {e}")))?;
-        }
-        decode(&mut ctx, &mut batch)?;
-        let mut sampler = build_sampler(&config);
-        // Verify each draft token
-        for (i, &draft_tok) in draft.iter().enumerate() {
-            let logit_idx = (ctx_len - 1 + i) as i32;
-            let predicted = sampler.sample(&ctx, logit_idx);
-            sampler.accept(predicted);
-            if predicted.0 as u32 != draft_tok {
-                return Ok(VerifyResult::diverge_at(i, predicted.0 as u32));
-            }
-        }
-        Ok(VerifyResult::accept_all(draft.len()))
-    }
-
     /// Get EOS token ID.
     pub fn eos_token(&self) -> Option<u32> {
         Some(self.model.token_eos().0 as u32)
     }
 
+    /// Chat template stored in the GGUF metadata, if any.
+    pub fn chat_template_metadata(&self) -> Option<String> {
+        self.model.meta_val_str(GGUF_CHAT_TEMPLATE_KEY).ok()
+    }
+
+    /// Render chat messages with a Jinja template via llama.cpp.
+    pub fn apply_chat_template(
+        &self,
+        template: &str,
+        messages: &[ChatMessage],
+    ) -> Result<String, InferenceError> {
+        let tmpl = LlamaChatTemplate::new(template).map_err(|e| {
+            InferenceError::InvalidFormat(format!("chat template: {e}"))
+        })?;
+        let chat = messages
+            .iter()
+            .map(|m| LlamaChatMessage::new(role_name(m.role).into(), m.content.clone()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| InferenceError::InputValidation(format!("chat message: {e}")))?;
+        self.model.apply_chat_template(&tmpl, &chat, true).map_err(|e| {
+            InferenceError::InvalidFormat(format!("apply chat template: {e}"))
+        })
+    }
+
     /// Tokenize a prompt string.
     pub fn tokenize(&self, text: &str) -> Result<Vec<LlamaToken>, InferenceError> {
         self.model.str_to_token(text, AddBos::Always).map_err(|e| {
@@ -190,84 +344,775 @@ impl LlamaBackendInner {
         Ok(out)
     }
 
-    fn create_context(&self) -> Result<LlamaContext<'_>, InferenceError> {
+    /// Log-probability of `token` and the `top_n` most likely alternatives,
+    /// read from the logits of batch entry `logits`. Must be called before
+    /// the next decode overwrites them.
+    fn token_logprob(
+        &self,
+        ctx: &LlamaContext<'_>,
+        logits: i32,
+        token: LlamaToken,
+        top_n: usize,
+    ) -> Result<TokenLogprob, InferenceError> {
+        let logits = ctx.get_logits_ith(logits);
+        let (logprob, top) = token_logprobs(logits, token.0 as u32, top_n);
+        let text = |t: LlamaToken| -> Result<Option<String>, InferenceError> {
+            Ok(Some(String::from_utf8_lossy(&self.token_bytes(t)?).into_owned()))
+        };
+        let top = top
+            .into_iter()
+            .map(|(id, logprob)| {
+                let text = text(LlamaToken(id as i32))?;
+                Ok(TokenCandidate { token: id, text, logprob })
+            })
+            .collect::<Result<_, InferenceError>>()?;
+        Ok(TokenLogprob { token: token.0 as u32, text: text(token)?, logprob, top })
+    }
+
+    /// Raw bytes of one token's piece, which may end mid-character.
+    fn token_bytes(&self, token: LlamaToken) -> Result<Vec<u8>, InferenceError> {
+        let mut size = 8;
+        loop {
+            match self.model.token_to_piece_bytes(token, size, false, None) {
+                Ok(bytes) => return Ok(bytes),
+                Err(TokenToStringError::InsufficientBufferSpace(needed)) => {
+                    size = needed.unsigned_abs() as usize;
+                }
+                Err(e) => return Err(InferenceError::ModelError(format!("detok: {e}"))),
+            }
+        }
+    }
+
+    /// Fit the prompt and `max_tokens` into the context window per the
+    /// request's `context_strategy`. Each of the `best_of` sequences needs
+    /// room for its own completion; the returned limit is per sequence.
+    pub(super) fn fit_context(
+        &self,
+        mut tokens: Vec<LlamaToken>,
+        config: &InferenceConfig,
+    ) -> Result<(Vec<LlamaToken>, u32), InferenceError> {
+        let budget = config.completion_budget();
+        let n_ctx = self.context_size();
+        let fit = fit_tokens(tokens.len(), budget, n_ctx, config.context_strategy)?;
+        if fit.drop_tokens > 0 {
+            // Keep BOS so the truncated prompt still starts a sequence
+            let start = usize::from(tokens.first() == Some(&self.model.token_bos()));
+            tokens.drain(start..start + fit.drop_tokens);
+        }
+        let sequences = u32::try_from(config.best_of.max(1)).unwrap_or(u32::MAX);
+        Ok((tokens, fit.max_tokens / sequences))
+    }
+
+    /// Evaluate the prompt, leaving its last token's logits ready to sample.
+    ///
+    /// The request's conversation snapshot is restored if it has one,
+    /// otherwise the cached prompt sharing the longest prefix. A prompt with
+    /// no cached prefix is cached once evaluated. One that diverges partway
+    /// through a cached prompt caches the shared part on its own, so a
+    /// common system prompt ends up with an entry of its own.
+    ///
+    /// Snapshots only move between single-sequence contexts: requests with
+    /// `best_of` above 1 run in a context sized for that many sequences,
+    /// whose state layout differs, so they neither use nor fill the cache.
+    fn prefill(
+        &self,
+        ctx: &mut LlamaContext<'_>,
+        tokens: &[LlamaToken],
+        config: &InferenceConfig,
+    ) -> Result<LlamaBatch, InferenceError> {
+        let ids: Vec<u32> = tokens.iter().map(|t| t.0 as u32).collect();
+        let saved = session_key(config).and_then(|key| self.sessions.take(&key));
+        let mut reused = saved.map_or(0, |saved| {
+            restore_state(ctx, &saved.state, reusable_prefix(&saved.tokens, &ids))
+        });
+        // Cached prompts were evaluated without an adapter, in one sequence
+        let lookup = reused == 0 && config.adapter.is_none() && config.best_of <= 1;
+        let mut cache_prompt = false;
+        if lookup {
+            let found = self.prompt_cache.lock().find_prefix(&ids);
+            match found {
+                Some((shared, entry)) => {
+                    let usable = shared.min(ids.len().saturating_sub(1));
+                    reused = restore_state(ctx, entry.kv_data(), usable);
+                    if reused == shared && shared < entry.seq_len() {
+                        // The context now holds exactly the shared prefix
+                        self.cache_prompt(ctx, &ids[..shared]);
+                    }
+                }
+                None => cache_prompt = true,
+            }
+        }
+        let mut batch = LlamaBatch::new(tokens.len(), 1);
+        add_seq(&mut batch, tokens, reused)?;
+        decode(ctx, &mut batch)?;
+        if cache_prompt {
+            self.cache_prompt(ctx, &ids);
+        }
+        if lookup {
+            let bytes = self.prompt_cache.lock().memory_bytes();
+            telemetry::record_prompt_cache(reused, bytes);
+        }
+        Ok(batch)
+    }
+
+    /// Cache the context state under `ids`, the tokens its KV cache holds.
+    fn cache_prompt(&self, ctx: &LlamaContext<'_>, ids: &[u32]) {
+        if !self.prompt_cache.lock().accepts(ids.len()) {
+            return;
+        }
+        let state = state_bytes(ctx);
+        self.prompt_cache.lock().insert(ids, state, ids.len());
+    }
+
+    /// Prompt cache lookup counters and memory use.
+    pub fn prompt_cache_stats(&self) -> PromptCacheStats {
+        self.prompt_cache.lock().stats()
+    }
+
+    /// Snapshot the context for the request's conversation, if it names one.
+    ///
+    /// `evaluated` lists every token decoded into the context, in position
+    /// order.
+    fn save_session(
+        &self,
+        ctx: &LlamaContext<'_>,
+        config: &InferenceConfig,
+        evaluated: &[LlamaToken],
+    ) {
+        let Some(key) = session_key(config) else {
+            return;
+        };
+        let state = state_bytes(ctx);
+        let tokens = evaluated.iter().map(|t| t.0 as u32).collect();
+        self.sessions.put(key, SessionKv { tokens, state });
+    }
+
+    /// Create the context of a continuous batch: `slots` sequences of up to
+    /// the context size each, and a batch large enough for a full prompt
+    /// plus one token per slot.
+    pub(super) fn batch_context(&self, slots: u32) -> Result<LlamaContext<'_>, InferenceError> {
+        let n_ctx = u32::try_from(self.context_size()).unwrap_or(u32::MAX);
+        let p = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(n_ctx.saturating_mul(slots)))
+            .with_n_batch(n_ctx.saturating_add(slots))
+            .with_n_seq_max(slots)
+            .with_n_threads(self.n_threads)
+            .with_n_threads_batch(self.n_threads);
+        self.model.new_context(&self.backend, p)
+            .map_err(|e| InferenceError::ModelError(format!("ctx: {e}")))
+    }
+
+    /// Create a context holding up to `n_seq` sequences.
+    pub(super) fn create_context(&self, n_seq: u32) -> Result<LlamaContext<'_>, InferenceError> {
         // Use same thread count for both - simpler and avoids cache contention
         // llama.cpp internally optimizes based on workload
         let p = LlamaContextParams::default()
             .with_n_ctx(NonZeroU32::new(self.n_ctx))
+            .with_n_seq_max(n_seq)
+            .with_n_threads(self.n_threads)
+            .with_n_threads_batch(self.n_threads);
+        self.model.new_context(&self.backend, p)
+            .map_err(|e| InferenceError::ModelError(format!("ctx: {e}")))
+    }
+
+    /// Create an embeddings context holding up to `n_seq` sequences, which
+    /// decodes a whole context window per batch.
+    fn embedding_context(
+        &self,
+        n_seq: u32,
+        pooling: GgufPooling,
+    ) -> Result<LlamaContext<'_>, InferenceError> {
+        let pooling = match pooling {
+            GgufPooling::None => LlamaPoolingType::None,
+            GgufPooling::Mean => LlamaPoolingType::Mean,
+            GgufPooling::Cls => LlamaPoolingType::Cls,
+            GgufPooling::Last => LlamaPoolingType::Last,
+            GgufPooling::Rank => LlamaPoolingType::Rank,
+        };
+        let n_ctx = u32::try_from(self.context_size()).unwrap_or(u32::MAX);
+        let p = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(n_ctx))
+            .with_n_batch(n_ctx)
+            .with_n_ubatch(n_ctx)
+            .with_n_seq_max(n_seq)
+            .with_embeddings(true)
+            .with_pooling_type(pooling)
             .with_n_threads(self.n_threads)
             .with_n_threads_batch(self.n_threads);
         self.model.new_context(&self.backend, p)
             .map_err(|e| InferenceError::ModelError(format!("ctx: {e}")))
     }
 
+    /// Pooled embedding of each text, truncated to the context window.
+    pub fn embed(
+        &self,
+        texts: &[&str],
+        pooling: GgufPooling,
+    ) -> Result<Vec<Vec<f32>>, InferenceError> {
+        let mut sequences = Vec::with_capacity(texts.len());
+        for text in texts {
+            let mut tokens = self.tokenize(text)?;
+            tokens.truncate(self.context_size());
+            sequences.push(tokens);
+        }
+        self.pool(&sequences, pooling)
+    }
+
+    /// Relevance score of each document for `query` from the model's rank
+    /// pooling head. A pair is joined the way the tokenizer joins two
+    /// sequences (`[CLS] query [SEP] document [SEP]` for BERT-style
+    /// rerankers) and truncated longest-first to the context window.
+    pub fn rerank(&self, query: &str, documents: &[&str]) -> Result<Vec<f32>, InferenceError> {
+        let query = self.tokenize(query)?;
+        let bos = self.model.token_bos();
+        let mut pairs = Vec::with_capacity(documents.len());
+        for document in documents {
+            let mut document = self.tokenize(document)?;
+            if document.first() == Some(&bos) {
+                document.remove(0);
+            }
+            let mut pair = query.clone();
+            truncate_pair(&mut pair, &mut document, self.context_size());
+            pair.extend(document);
+            pairs.push(pair);
+        }
+        self.pool(&pairs, GgufPooling::Rank)?
+            .into_iter()
+            .map(|scores| {
+                scores.first().copied().ok_or_else(|| {
+                    InferenceError::ModelError("rank pooling produced no score".into())
+                })
+            })
+            .collect()
+    }
+
+    /// Decode each token sequence and read its pooled output. Sequences
+    /// are decoded together while they fit one batch.
+    fn pool(
+        &self,
+        sequences: &[Vec<LlamaToken>],
+        pooling: GgufPooling,
+    ) -> Result<Vec<Vec<f32>>, InferenceError> {
+        let n_ctx = self.context_size();
+        let n_seq = sequences.len().clamp(1, MAX_BATCH_SIZE);
+        let mut ctx = self.embedding_context(n_seq as u32, pooling)?;
+        let mut batch = LlamaBatch::new(n_ctx, n_seq as i32);
+        let mut vectors = Vec::with_capacity(sequences.len());
+        let mut seqs = 0;
+        for tokens in sequences {
+            if seqs == n_seq || batch.n_tokens() as usize + tokens.len() > n_ctx {
+                flush_embeddings(&mut ctx, &mut batch, seqs, &mut vectors)?;
+                seqs = 0;
+            }
+            batch.add_sequence(tokens, seqs as i32, false)
+                .map_err(|e| InferenceError::ModelError(format!("batch: {e}")))?;
+            seqs += 1;
+        }
+        flush_embeddings(&mut ctx, &mut batch, seqs, &mut vectors)?;
+        Ok(vectors)
+    }
+
+    /// Build the sampler chain for a request, primed with the prompt.
+    ///
+    /// Order: grammar, logit bias, penalties, then either mirostat or the
+    /// top-k/typical/top-p/min-p filters followed by seeded sampling. The
+    /// grammar is added after the prompt is accepted so it only sees
+    /// generated tokens.
+    pub(super) fn build_sampler(
+        &self,
+        config: &InferenceConfig,
+        prompt: &[LlamaToken],
+    ) -> Result<LlamaSampler, InferenceError> {
+        let n_vocab = self.model.n_vocab();
+        let mut s = Vec::new();
+        if !config.logit_bias.is_empty() {
+            let mut biases = Vec::with_capacity(config.logit_bias.len());
+            for &(token, bias) in &config.logit_bias {
+                if i32::try_from(token).map_or(true, |t| t >= n_vocab) {
+                    return Err(InferenceError::InputValidation(format!(
+                        "logit_bias token {token} is outside the vocabulary ({n_vocab})"
+                    )));
+                }
+                biases.push(LlamaLogitBias::new(LlamaToken(token as i32), bias));
+            }
+            s.push(LlamaSampler::logit_bias(n_vocab, &biases));
+        }
+        let penalize = config.repetition_penalty > 1.0
+            || config.presence_penalty != 0.0
+            || config.frequency_penalty != 0.0;
+        if penalize && config.penalty_last_n != 0 {
+            s.push(LlamaSampler::penalties(
+                config.penalty_last_n,
+                config.repetition_penalty,
+                config.frequency_penalty,
+                config.presence_penalty,
+            ));
+        }
+        match config.mirostat {
+            Mirostat::V1 => {
+                s.push(LlamaSampler::temp(config.temperature));
+                s.push(LlamaSampler::mirostat(
+                    n_vocab,
+                    config.seed,
+                    config.mirostat_tau,
+                    config.mirostat_eta,
+                    100,
+                ));
+            }
+            Mirostat::V2 => {
+                s.push(LlamaSampler::temp(config.temperature));
+                s.push(LlamaSampler::mirostat_v2(
+                    config.seed,
+                    config.mirostat_tau,
+                    config.mirostat_eta,
+                ));
+            }
+            Mirostat::Disabled => {
+                if config.top_k > 0 {
+                    s.push(LlamaSampler::top_k(config.top_k as i32));
+                }
+                if config.typical_p < 1.0 {
+                    s.push(LlamaSampler::typical(config.typical_p, 1));
+                }
+                s.push(LlamaSampler::top_p(config.top_p, 1));
+                if config.min_p > 0.0 {
+                    s.push(LlamaSampler::min_p(config.min_p, 1));
+                }
+                s.push(LlamaSampler::temp(config.temperature));
+                s.push(LlamaSampler::dist(config.seed));
+            }
+        }
+        let mut sampler = LlamaSampler::chain_simple(s);
+        sampler.accept_many(prompt.iter().copied());
+        let Some(constraint) = &config.constraint else {
+            return Ok(sampler);
+        };
+        let gbnf = constraint
+            .to_gbnf()
+            .map_err(|e| InferenceError::InputValidation(e.to_string()))?;
+        let grammar = LlamaSampler::grammar(&self.model, &gbnf, GRAMMAR_ROOT)
+            .map_err(|e| InferenceError::InputValidation(format!("grammar: {e}")))?;
+        Ok(LlamaSampler::chain_simple([grammar, sampler]))
+    }
+
     fn sample_loop(
         &self,
         ctx: &mut LlamaContext<'_>,
         tokens: &[LlamaToken],
         max_tok: u32,
         config: &InferenceConfig,
-    ) -> Result<(Vec<LlamaToken>, FinishReason), InferenceError> {
-        let mut batch = LlamaBatch::new(tokens.len(), 1);
-        add_seq(&mut batch, tokens)?;
-        decode(ctx, &mut batch)?;
-        let mut sampler = build_sampler(config);
-        sampler.accept_many(tokens.iter().copied());
-        let mut out = Vec::new();
-        let mut pos = tokens.len() as i32;
+        started: Instant,
+    ) -> Result<GenerationResult, InferenceError> {
+        let mut batch = self.prefill(ctx, tokens, config)?;
+        let prefill = started.elapsed();
+        let mut sampler = self.build_sampler(config, tokens)?;
+        let mut text = String::new();
+        let mut utf8 = Utf8StreamDecoder::new();
+        let mut stops = StopMatcher::new(&config.stop);
+        let mut stop_sequence = None;
+        let mut generated = 0u32;
+        let mut logprobs = Vec::new();
+        let mut evaluated = tokens.to_vec();
+        let mut first_token = None;
+        let mut reason = FinishReason::MaxTokens;
         for _ in 0..max_tok {
+            if let Some(abort) = config.abort_reason(started) {
+                reason = abort;
+                break;
+            }
             // Use -1 to sample from the last token that had logits computed
             let tok = sampler.sample(ctx, -1);
             sampler.accept(tok);
+            first_token.get_or_insert_with(|| started.elapsed());
             if self.model.is_eog_token(tok) {
-                return Ok((out, FinishReason::Stop));
+                reason = FinishReason::Stop;
+                break;
+            }
+            if let Some(stop) = stops.stop_token(tok.0 as u32) {
+                stop_sequence = Some(stop);
+                reason = FinishReason::Stop;
+                break;
+            }
+            generated += 1;
+            if let Some(top_n) = config.logprobs {
+                logprobs.push(self.token_logprob(ctx, batch.n_tokens() - 1, tok, top_n)?);
+            }
+            let (piece, stop) = stops.push(&utf8.push(&self.token_bytes(tok)?));
+            text.push_str(&piece);
+            if stop.is_some() {
+                stop_sequence = stop;
+                reason = FinishReason::Stop;
+                break;
             }
-            out.push(tok);
             batch.clear();
-            add_one(&mut batch, tok, pos)?;
+            add_one(&mut batch, tok, evaluated.len() as i32)?;
             decode(ctx, &mut batch)?;
-            pos += 1;
+            evaluated.push(tok);
+        }
+        self.save_session(ctx, config, &evaluated);
+        if !matches!(stop_sequence, Some(StopSequence::Text(_))) {
+            text.push_str(&stops.flush());
+            text.push_str(&utf8.finish());
+        }
+        Ok(GenerationResult {
+            text,
+            tokens_generated: generated,
+            finish_reason: reason,
+            prompt_tokens: u32::try_from(tokens.len()).unwrap_or(u32::MAX),
+            timings: GenerationTimings {
+                prefill,
+                first_token: first_token.unwrap_or(prefill),
+                decode: started.elapsed().saturating_sub(prefill),
+            },
+            stop_sequence,
+            logprobs,
+            choices: Vec::new(),
+        })
+    }
+
+    /// Generate `best_of` sequences for one prompt and return the `n` with
+    /// the highest cumulative log-probability.
+    ///
+    /// The prompt is evaluated once into sequence 0 and its KV cells are
+    /// shared with the other sequences, which then decode together, one
+    /// token each per batch. Each sequence samples with its own seed.
+    fn sample_many(
+        &self,
+        ctx: &mut LlamaContext<'_>,
+        tokens: &[LlamaToken],
+        max_tok: u32,
+        config: &InferenceConfig,
+        started: Instant,
+    ) -> Result<GenerationResult, InferenceError> {
+        let batch = self.prefill(ctx, tokens, config)?;
+        let prefill = started.elapsed();
+        let mut seqs = Vec::with_capacity(config.best_of);
+        for id in 0..config.best_of as i32 {
+            if id > 0 {
+                ctx.copy_kv_cache_seq(0, id, None, None)
+                    .map_err(|e| InferenceError::ModelError(format!("kv copy: {e}")))?;
+            }
+            let seeded = InferenceConfig {
+                seed: config.seed.wrapping_add(id as u32),
+                ..config.clone()
+            };
+            let sampler = self.build_sampler(&seeded, tokens)?;
+            seqs.push(Sequence::new(id, sampler, batch.n_tokens() - 1, &config.stop));
+        }
+        let mut batch = LlamaBatch::new(seqs.len(), 1);
+        let mut first_token = None;
+        for step in 0..max_tok {
+            let abort = config.abort_reason(started);
+            batch.clear();
+            for seq in seqs.iter_mut().filter(|s| s.finish.is_none()) {
+                if abort.is_some() {
+                    seq.finish = abort;
+                    continue;
+                }
+                let tok = seq.sampler.sample(ctx, seq.logits);
+                seq.sampler.accept(tok);
+                first_token.get_or_insert_with(|| started.elapsed());
+                if self.advance(ctx, seq, tok, config)? {
+                    continue;
+                }
+                seq.logits = batch.n_tokens();
+                add_token(&mut batch, tok, (tokens.len() + step as usize) as i32, seq.id, true)?;
+            }
+            if batch.n_tokens() == 0 || step + 1 == max_tok {
+                break;
+            }
+            decode(ctx, &mut batch)?;
+        }
+        let tokens_generated = seqs.iter().map(|s| s.generated).sum();
+        let mut choices: Vec<_> = seqs.into_iter().map(Sequence::into_completion).collect();
+        if config.best_of > config.n {
+            choices.sort_by(|a, b| b.cumulative_logprob.total_cmp(&a.cumulative_logprob));
+            choices.truncate(config.n);
+        }
+        let best = choices[0].clone();
+        Ok(GenerationResult {
+            text: best.text,
+            tokens_generated,
+            finish_reason: best.finish_reason,
+            prompt_tokens: u32::try_from(tokens.len()).unwrap_or(u32::MAX),
+            timings: GenerationTimings {
+                prefill,
+                first_token: first_token.unwrap_or(prefill),
+                decode: started.elapsed().saturating_sub(prefill),
+            },
+            stop_sequence: best.stop_sequence,
+            logprobs: best.logprobs,
+            choices: if config.n > 1 { choices } else { Vec::new() },
+        })
+    }
+
+    /// Apply a token sampled for `seq`. Returns true once the sequence has
+    /// finished.
+    ///
+    /// The end-of-generation or stop token that ends a sequence counts
+    /// towards its cumulative log-probability but is not part of the text.
+    /// Log-probabilities are only computed when requested or for ranking.
+    pub(super) fn advance(
+        &self,
+        ctx: &LlamaContext<'_>,
+        seq: &mut Sequence,
+        tok: LlamaToken,
+        config: &InferenceConfig,
+    ) -> Result<bool, InferenceError> {
+        let logprob = if config.logprobs.is_some() || config.best_of > 1 {
+            let top_n = config.logprobs.unwrap_or(0);
+            Some(self.token_logprob(ctx, seq.logits, tok, top_n)?)
+        } else {
+            None
+        };
+        seq.cumulative_logprob += logprob.as_ref().map_or(0.0, |l| l.logprob);
+        if self.model.is_eog_token(tok) {
+            seq.finish = Some(FinishReason::Stop);
+            return Ok(true);
+        }
+        if let Some(stop) = seq.stops.stop_token(tok.0 as u32) {
+            seq.stop_sequence = Some(stop);
+            seq.finish = Some(FinishReason::Stop);
+            return Ok(true);
+        }
+        seq.generated += 1;
+        if config.logprobs.is_some() {
+            seq.logprobs.extend(logprob);
+        }
+        let (piece, stop) = seq.stops.push(&seq.utf8.push(&self.token_bytes(tok)?));
+        seq.text.push_str(&piece);
+        if stop.is_some() {
+            seq.stop_sequence = stop;
+            seq.finish = Some(FinishReason::Stop);
+        }
+        Ok(seq.finish.is_some())
+    }
+}
+
+/// State of one sequence decoded alongside others in a shared context.
+pub(super) struct Sequence {
+    id: i32,
+    pub(super) sampler: LlamaSampler,
+    /// Batch entry whose logits the next token is sampled from.
+    pub(super) logits: i32,
+    text: String,
+    utf8: Utf8StreamDecoder,
+    stops: StopMatcher,
+    generated: u32,
+    logprobs: Vec<TokenLogprob>,
+    cumulative_logprob: f32,
+    stop_sequence: Option<StopSequence>,
+    pub(super) finish: Option<FinishReason>,
+}
+
+impl Sequence {
+    pub(super) fn new(id: i32, sampler: LlamaSampler, logits: i32, stop: &[StopSequence]) -> Self {
+        Self {
+            id,
+            sampler,
+            logits,
+            text: String::new(),
+            utf8: Utf8StreamDecoder::new(),
+            stops: StopMatcher::new(stop),
+            generated: 0,
+            logprobs: Vec::new(),
+            cumulative_logprob: 0.0,
+            stop_sequence: None,
+            finish: None,
+        }
+    }
+
+    /// Append text held back for stop matching or UTF-8 decoding, unless a
+    /// stop string ended the sequence.
+    fn flush(&mut self) {
+        if !matches!(self.stop_sequence, Some(StopSequence::Text(_))) {
+            self.text.push_str(&self.stops.flush());
+            self.text.push_str(&self.utf8.finish());
+        }
+    }
+
+    /// End the sequence with `reason`, releasing any held-back text.
+    pub(super) fn end(&mut self, reason: FinishReason) {
+        self.finish = Some(reason);
+        self.flush();
+    }
+
+    /// Text and log-probability counts, to tell what the next token adds.
+    pub(super) fn mark(&self) -> (usize, usize) {
+        (self.text.len(), self.logprobs.len())
+    }
+
+    /// Stream output for `tok` with what was added since `mark`, and the
+    /// usage summary once the sequence has finished. Prefill overlaps other
+    /// decoding here, so it is reported as the time to the first token.
+    pub(super) fn output(
+        &self,
+        tok: LlamaToken,
+        (text_len, logprobs_len): (usize, usize),
+        prompt_tokens: usize,
+        first_token: Duration,
+        elapsed: Duration,
+    ) -> StreamingOutput {
+        let finish = self.finish.map(|finish_reason| StreamFinish {
+            prompt_tokens: u32::try_from(prompt_tokens).unwrap_or(u32::MAX),
+            completion_tokens: self.generated,
+            finish_reason,
+            timings: GenerationTimings {
+                prefill: first_token,
+                first_token,
+                decode: elapsed.saturating_sub(first_token),
+            },
+            stop_sequence: self.stop_sequence.clone(),
+        });
+        StreamingOutput {
+            token: tok.0 as u32,
+            text: self.text[text_len..].to_string(),
+            is_final: finish.is_some(),
+            logprob: self.logprobs.get(logprobs_len).cloned(),
+            finish,
+        }
+    }
+
+    fn into_completion(mut self) -> Completion {
+        self.flush();
+        Completion {
+            text: self.text,
+            tokens_generated: self.generated,
+            finish_reason: self.finish.unwrap_or(FinishReason::MaxTokens),
+            stop_sequence: self.stop_sequence,
+            logprobs: self.logprobs,
+            cumulative_logprob: self.cumulative_logprob,
         }
-        Ok((out, FinishReason::MaxTokens))
     }
 }
 
-fn add_seq(batch: &mut LlamaBatch, tokens: &[LlamaToken]) -> Result<(), InferenceError> {
+/// Serialize the context state (KV cache included).
+fn state_bytes(ctx: &LlamaContext<'_>) -> Vec<u8> {
+    let mut state = vec![0u8; ctx.get_state_size()];
+    // SAFETY: `state` is sized by `get_state_size` for this context
+    let written = unsafe { ctx.copy_state_data(state.as_mut_ptr()) };
+    state.truncate(written);
+    state
+}
+
+/// Apply the request's adapter, if it selects one, to a fresh context.
+fn apply_adapter(
+    ctx: &LlamaContext<'_>,
+    adapter: Option<&SharedAdapter>,
+    config: &InferenceConfig,
+) -> Result<(), InferenceError> {
+    let (Some(adapter), Some(selection)) = (adapter, &config.adapter) else {
+        return Ok(());
+    };
+    ctx.lora_adapter_set(&mut adapter.lock(), selection.scale).map_err(|e| {
+        InferenceError::ModelError(format!("adapter {}: {e}", selection.name))
+    })
+}
+
+/// Session store key of the request's conversation. Snapshots taken with
+/// an adapter are keyed by it and its scale too, so they never resume
+/// under different weights.
+fn session_key(config: &InferenceConfig) -> Option<String> {
+    let conversation = config.conversation.as_deref()?;
+    Some(match &config.adapter {
+        Some(adapter) => format!("{conversation}\n{}@{}", adapter.name, adapter.scale),
+        None => conversation.to_string(),
+    })
+}
+
+/// Load a state snapshot into `ctx` and drop its KV entries from position
+/// `reused` on. Returns `reused`, or 0 with an empty cache if the snapshot
+/// could not be applied.
+fn restore_state(ctx: &mut LlamaContext<'_>, state: &[u8], reused: usize) -> usize {
+    if reused == 0 {
+        return 0;
+    }
+    // SAFETY: snapshots come from `state_bytes` on a context of the same
+    // model created by `create_context(1)`, and `prefill` only restores
+    // them into such contexts: conversation snapshots and the prompt cache
+    // are both skipped when `best_of` is above 1
+    let read = unsafe { ctx.set_state_data(state) };
+    // Recurrent models cannot drop a suffix and report false
+    let trimmed = read == state.len()
+        && matches!(ctx.clear_kv_cache_seq(Some(0), Some(reused as u32), None), Ok(true));
+    if trimmed {
+        reused
+    } else {
+        ctx.clear_kv_cache();
+        0
+    }
+}
+
+/// Add `tokens[start..]` at their positions in sequence 0.
+fn add_seq(
+    batch: &mut LlamaBatch,
+    tokens: &[LlamaToken],
+    start: usize,
+) -> Result<(), InferenceError> {
+    add_prompt(batch, tokens, start, 0)
+}
+
+/// Add `tokens[start..]` at their positions in sequence `seq`.
+pub(super) fn add_prompt(
+    batch: &mut LlamaBatch,
+    tokens: &[LlamaToken],
+    start: usize,
+    seq: i32,
+) -> Result<(), InferenceError> {
     // Add all tokens except the last with logits=false
     // Add the last token with logits=true so we can sample from it
     let n = tokens.len();
-    if n == 0 {
-        return Ok(());
-    }
-    for (i, &tok) in tokens.iter().enumerate() {
+    for (i, &tok) in tokens.iter().enumerate().skip(start) {
         let logits = i == n - 1; // Only compute logits for last token
-        batch.add(tok, i as i32, &[0], logits)
-            .map_err(|e| InferenceError::ModelError(format!("batch: {e}")))?;
+        add_token(batch, tok, i as i32, seq, logits)?;
     }
     Ok(())
 }
 
 fn add_one(batch: &mut LlamaBatch, tok: LlamaToken, pos: i32) -> Result<(), InferenceError> {
-    batch.add(tok, pos, &[0], true)
-        .map_err(|e| InferenceError::ModelError(format!("batch: {e}")))
+    add_token(batch, tok, pos, 0, true)
 }
 
-fn decode(ctx: &mut LlamaContext<'_>, batch: &mut LlamaBatch) -> Result<(), InferenceError> {
-    ctx.decode(batch).map_err(|e| InferenceError::ModelError(format!("decode: {e}")))
+/// Add a token of sequence `seq` at `pos`, computing its logits if asked.
+pub(super) fn add_token(
+    batch: &mut LlamaBatch,
+    tok: LlamaToken,
+    pos: i32,
+    seq: i32,
+    logits: bool,
+) -> Result<(), InferenceError> {
+    batch.add(tok, pos, &[seq], logits)
+        .map_err(|e| InferenceError::ModelError(format!("batch: {e}")))
 }
 
-fn build_sampler(config: &InferenceConfig) -> LlamaSampler {
-    let mut s = Vec::new();
-    if config.repetition_penalty > 1.0 {
-        s.push(LlamaSampler::penalties(64, config.repetition_penalty, 0.0, 0.0));
+/// Decode the `seqs` sequences of an embedding batch, append their pooled
+/// vectors to `vectors` and empty the batch.
+fn flush_embeddings(
+    ctx: &mut LlamaContext<'_>,
+    batch: &mut LlamaBatch,
+    seqs: usize,
+    vectors: &mut Vec<Vec<f32>>,
+) -> Result<(), InferenceError> {
+    if seqs == 0 {
+        return Ok(());
     }
-    if config.top_k > 0 {
-        s.push(LlamaSampler::top_k(config.top_k as i32));
+    ctx.clear_kv_cache();
+    decode(ctx, batch)?;
+    for seq in 0..seqs {
+        let vector = ctx.embeddings_seq_ith(seq as i32).map_err(|e| {
+            InferenceError::ModelError(format!("embeddings: {e}"))
+        })?;
+        vectors.push(vector.to_vec());
     }
-    s.push(LlamaSampler::top_p(config.top_p as f32, 1));
-    s.push(LlamaSampler::temp(config.temperature));
-    s.push(LlamaSampler::dist(42));
-    LlamaSampler::chain_simple(s)
+    batch.clear();
+    Ok(())
+}
+
+pub(super) fn decode(
+    ctx: &mut LlamaContext<'_>,
+    batch: &mut LlamaBatch,
+) -> Result<(), InferenceError> {
+    ctx.decode(batch).map_err(|e| InferenceError::ModelError(format!("decode: {e}")))
 }
 
 fn resolve_threads(n: u32) -> i32 {
@@ -0,0 +1,433 @@
+//! Continuous batching for GGUF models.
+//!
+//! Each model runs one decode loop on its own thread, owning a context with
+//! a KV sequence per slot. Every step samples one token for each active
+//! sequence and decodes them in a single batch, together with the prompts
+//! of requests admitted at that step. Requests join between steps and leave
+//! as soon as they finish, so throughput grows with concurrent requests
+//! instead of being capped at one sequence per context. Requests cancelled
+//! or timed out while waiting leave the queue without taking a slot.
+//!
+//! A slot keeps its last sequence in the KV cache after it finishes. A new
+//! request is placed in the free slot sharing the longest prefix with its
+//! prompt and only evaluates the rest.
+
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use llama_cpp_2::context::LlamaContext;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::token::LlamaToken;
+
+use super::backend::{add_prompt, add_token, decode, LlamaBackendInner, Sequence};
+use crate::engine::{
+    FinishReason, GenerationResult, GenerationTimings, InferenceConfig, InferenceError,
+    StreamFinish, StreamingOutput, TokenStreamSender,
+};
+use crate::memory::reusable_prefix;
+use crate::scheduler::{ContinuousBatcher, PendingRequest, RequestId};
+
+type Event = Result<StreamingOutput, InferenceError>;
+
+/// A request submitted to the decode loop.
+struct BatchJob {
+    tokens: Vec<LlamaToken>,
+    max_tok: u32,
+    config: InferenceConfig,
+    started: Instant,
+    /// Outputs in order; the receiver is dropped when the client goes away.
+    events: mpsc::Sender<Event>,
+}
+
+impl BatchJob {
+    fn fail(&self, error: InferenceError) {
+        let _ = self.events.send(Err(error));
+    }
+
+    /// End a request that never started decoding. The end-of-generation
+    /// token `eos` closes its stream with no completion tokens.
+    fn end(self, reason: FinishReason, eos: u32) {
+        let finish = StreamFinish {
+            prompt_tokens: u32::try_from(self.tokens.len()).unwrap_or(u32::MAX),
+            completion_tokens: 0,
+            finish_reason: reason,
+            timings: GenerationTimings::default(),
+            stop_sequence: None,
+        };
+        let output = StreamingOutput {
+            token: eos,
+            text: String::new(),
+            is_final: true,
+            logprob: None,
+            finish: Some(finish),
+        };
+        let _ = self.events.send(Ok(output));
+    }
+}
+
+/// Handle to a model's decode loop. The loop stops once the handle is
+/// dropped and its active requests have finished.
+pub struct Batcher {
+    jobs: mpsc::Sender<BatchJob>,
+}
+
+impl Batcher {
+    /// Start the decode loop for `inner` with `slots` sequences. The context
+    /// is created when the first request arrives.
+    pub fn start(inner: Arc<LlamaBackendInner>, slots: usize) -> Result<Self, InferenceError> {
+        let (jobs, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("gguf-batch".into())
+            .spawn(move || run(&inner, slots, receiver))
+            .map_err(|e| InferenceError::ModelError(format!("batch thread: {e}")))?;
+        Ok(Self { jobs })
+    }
+
+    /// Whether a request can be decoded in the shared context. Conversation
+    /// snapshots, `best_of` sequences and LoRA adapters need a context of
+    /// their own.
+    pub fn accepts(config: &InferenceConfig) -> bool {
+        config.conversation.is_none() && config.best_of <= 1 && config.adapter.is_none()
+    }
+
+    /// Generate a completion in the batch.
+    pub fn generate(
+        &self,
+        inner: &LlamaBackendInner,
+        prompt: &str,
+        config: &InferenceConfig,
+    ) -> Result<GenerationResult, InferenceError> {
+        let mut outputs = Vec::new();
+        for event in self.submit(inner, prompt, config)? {
+            let output = event?;
+            let last = output.is_final;
+            outputs.push(output);
+            if last {
+                break;
+            }
+        }
+        GenerationResult::from_outputs(outputs).ok_or_else(stopped)
+    }
+
+    /// Generate a completion in the batch, streaming each token to `sender`.
+    pub fn generate_stream(
+        &self,
+        inner: &LlamaBackendInner,
+        prompt: &str,
+        config: &InferenceConfig,
+        sender: TokenStreamSender,
+    ) -> Result<(), InferenceError> {
+        let events = self.submit(inner, prompt, config)?;
+        let rt = tokio::runtime::Handle::current();
+        for event in events {
+            // Returning drops `events`, which ends the sequence in the loop
+            if rt.block_on(sender.send_output(event?)).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn submit(
+        &self,
+        inner: &LlamaBackendInner,
+        prompt: &str,
+        config: &InferenceConfig,
+    ) -> Result<mpsc::Receiver<Event>, InferenceError> {
+        let started = Instant::now();
+        let (tokens, max_tok) = inner.fit_context(inner.tokenize(prompt)?, config)?;
+        let (events, receiver) = mpsc::channel();
+        let job = BatchJob { tokens, max_tok, config: config.clone(), started, events };
+        self.jobs.send(job).map_err(|_| stopped())?;
+        Ok(receiver)
+    }
+}
+
+fn stopped() -> InferenceError {
+    InferenceError::ModelError("batch loop stopped".into())
+}
+
+/// Decode loop body: waits while idle, otherwise steps until every request
+/// has finished.
+fn run(inner: &LlamaBackendInner, slots: usize, jobs: mpsc::Receiver<BatchJob>) {
+    // Nothing is allocated until the first request arrives
+    let Ok(first) = jobs.recv() else {
+        return;
+    };
+    let mut ctx = match inner.batch_context(u32::try_from(slots).unwrap_or(u32::MAX)) {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            let message = e.to_string();
+            for job in std::iter::once(first).chain(jobs) {
+                job.fail(InferenceError::ModelError(message.clone()));
+            }
+            return;
+        }
+    };
+    let mut state = BatchLoop::new(slots, inner.context_size() + slots);
+    state.enqueue(first);
+    loop {
+        if state.batcher.is_empty() {
+            match jobs.recv() {
+                Ok(job) => state.enqueue(job),
+                Err(_) => return,
+            }
+        }
+        while let Ok(job) = jobs.try_recv() {
+            state.enqueue(job);
+        }
+        state.step(inner, &mut ctx);
+    }
+}
+
+/// A request decoding in a slot.
+struct Active {
+    job: BatchJob,
+    seq: Sequence,
+    sampled: u32,
+    /// Until the first token was sampled, prompt evaluation included.
+    first_token: Option<Duration>,
+}
+
+impl Active {
+    /// Sample the next token and send it to the client. Returns the token to
+    /// decode next, or None once the request has finished.
+    fn sample(
+        &mut self,
+        inner: &LlamaBackendInner,
+        ctx: &LlamaContext<'_>,
+    ) -> Result<Option<LlamaToken>, InferenceError> {
+        let tok = self.seq.sampler.sample(ctx, self.seq.logits);
+        self.seq.sampler.accept(tok);
+        let elapsed = self.job.started.elapsed();
+        let first_token = *self.first_token.get_or_insert(elapsed);
+        self.sampled += 1;
+        let mark = self.seq.mark();
+        let config = &self.job.config;
+        let reason = if inner.advance(ctx, &mut self.seq, tok, config)? {
+            self.seq.finish
+        } else if self.sampled >= self.job.max_tok {
+            Some(FinishReason::MaxTokens)
+        } else {
+            config.abort_reason(self.job.started)
+        };
+        if let Some(reason) = reason {
+            self.seq.end(reason);
+        }
+        let output = self.seq.output(tok, mark, self.job.tokens.len(), first_token, elapsed);
+        // A send fails once the client has gone away
+        let sent = self.job.events.send(Ok(output)).is_ok();
+        Ok((sent && self.seq.finish.is_none()).then_some(tok))
+    }
+}
+
+/// Slot bookkeeping and the batch under construction.
+struct BatchLoop {
+    batcher: ContinuousBatcher,
+    pending: HashMap<RequestId, BatchJob>,
+    next_id: u64,
+    active: Vec<Option<Active>>,
+    /// Tokens held in each slot's KV sequence, in position order.
+    held: Vec<Vec<u32>>,
+    batch: LlamaBatch,
+    capacity: usize,
+}
+
+impl BatchLoop {
+    fn new(slots: usize, capacity: usize) -> Self {
+        Self {
+            batcher: ContinuousBatcher::new(slots),
+            pending: HashMap::new(),
+            next_id: 0,
+            active: (0..slots).map(|_| None).collect(),
+            held: vec![Vec::new(); slots],
+            batch: LlamaBatch::new(capacity, 1),
+            capacity,
+        }
+    }
+
+    fn enqueue(&mut self, job: BatchJob) {
+        let request_id = RequestId(self.next_id);
+        self.next_id += 1;
+        self.batcher.enqueue(PendingRequest {
+            request_id,
+            prompt_tokens: job.tokens.iter().map(|t| t.0 as u32).collect(),
+            max_tokens: job.max_tok as usize,
+        });
+        self.pending.insert(request_id, job);
+    }
+
+    /// Drop waiting requests that were cancelled or timed out in the queue,
+    /// so they neither take a slot nor hold up requests behind them.
+    fn expire_pending(&mut self, eos: u32) {
+        let pending = &self.pending;
+        let mut reasons = Vec::new();
+        let expired = self.batcher.remove_pending(|request| {
+            let reason = pending
+                .get(&request.request_id)
+                .and_then(|job| job.config.abort_reason(job.started));
+            reasons.extend(reason);
+            reason.is_some()
+        });
+        for (request, reason) in expired.into_iter().zip(reasons) {
+            if let Some(job) = self.pending.remove(&request.request_id) {
+                job.end(reason, eos);
+            }
+        }
+    }
+
+    /// Drop expired waiting requests, sample every active sequence, admit
+    /// waiting requests into free slots and decode the resulting batch.
+    fn step(&mut self, inner: &LlamaBackendInner, ctx: &mut LlamaContext<'_>) {
+        self.expire_pending(inner.eos_token().unwrap_or_default());
+        self.batch.clear();
+        for slot in 0..self.active.len() {
+            let Some(active) = &mut self.active[slot] else {
+                continue;
+            };
+            let next = active.sample(inner, ctx).and_then(|next| {
+                let Some(tok) = next else {
+                    return Ok(None);
+                };
+                active.seq.logits = self.batch.n_tokens();
+                let pos = self.held[slot].len() as i32;
+                add_token(&mut self.batch, tok, pos, slot as i32, true)?;
+                self.held[slot].push(tok.0 as u32);
+                Ok(Some(tok))
+            });
+            match next {
+                Ok(Some(_)) => {
+                    if let Some(state) = self.batcher.get_slot_mut(slot) {
+                        state.record_token();
+                    }
+                }
+                Ok(None) => self.complete(slot),
+                Err(e) => {
+                    active.job.fail(e);
+                    self.complete(slot);
+                }
+            }
+        }
+        self.batcher.evict_completed();
+
+        let budget = self.capacity.saturating_sub(self.batch.n_tokens() as usize);
+        let held = &self.held;
+        let admitted = self.batcher.admit_pending_by(budget, |slot, request| {
+            reusable_prefix(&held[slot], &request.prompt_tokens)
+        });
+        for (slot, request) in admitted {
+            if let Some(job) = self.pending.remove(&request.request_id) {
+                self.admit(inner, ctx, slot, job, request.prompt_tokens);
+            }
+        }
+
+        if self.batch.n_tokens() == 0 {
+            return;
+        }
+        if let Err(e) = decode(ctx, &mut self.batch) {
+            // Every sequence in the batch is left without logits
+            let message = e.to_string();
+            for slot in 0..self.active.len() {
+                if let Some(active) = &self.active[slot] {
+                    active.job.fail(InferenceError::ModelError(message.clone()));
+                    self.complete(slot);
+                    self.held[slot].clear();
+                    let _ = ctx.clear_kv_cache_seq(Some(slot as u32), None, None);
+                }
+            }
+            self.batcher.evict_completed();
+        }
+    }
+
+    /// Start `job` in `slot`, keeping the KV prefix it shares with the
+    /// slot's previous sequence and queueing the rest of its prompt.
+    fn admit(
+        &mut self,
+        inner: &LlamaBackendInner,
+        ctx: &mut LlamaContext<'_>,
+        slot: usize,
+        job: BatchJob,
+        ids: Vec<u32>,
+    ) {
+        let seq_id = slot as u32;
+        let mut reused = reusable_prefix(&self.held[slot], &ids);
+        // Recurrent models cannot drop a suffix and report false
+        if !matches!(ctx.clear_kv_cache_seq(Some(seq_id), Some(reused as u32), None), Ok(true)) {
+            let _ = ctx.clear_kv_cache_seq(Some(seq_id), None, None);
+            reused = 0;
+        }
+        self.held[slot].truncate(reused);
+        let started = inner
+            .build_sampler(&job.config, &job.tokens)
+            .and_then(|sampler| {
+                add_prompt(&mut self.batch, &job.tokens, reused, slot as i32)?;
+                let logits = self.batch.n_tokens() - 1;
+                Ok(Sequence::new(slot as i32, sampler, logits, &job.config.stop))
+            });
+        match started {
+            Ok(seq) => {
+                self.held[slot].extend_from_slice(&ids[reused..]);
+                if let Some(state) = self.batcher.get_slot_mut(slot) {
+                    state.finish_prefill();
+                }
+                self.active[slot] = Some(Active { job, seq, sampled: 0, first_token: None });
+            }
+            Err(e) => {
+                job.fail(e);
+                self.complete(slot);
+                self.held[slot].clear();
+                let _ = ctx.clear_kv_cache_seq(Some(seq_id), None, None);
+            }
+        }
+    }
+
+    fn complete(&mut self, slot: usize) {
+        self.active[slot] = None;
+        if let Some(state) = self.batcher.get_slot_mut(slot) {
+            state.mark_complete();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(config: InferenceConfig) -> (BatchJob, mpsc::Receiver<Event>) {
+        let (events, receiver) = mpsc::channel();
+        let tokens = vec![LlamaToken(1), LlamaToken(2)];
+        (BatchJob { tokens, max_tok: 4, config, started: Instant::now(), events }, receiver)
+    }
+
+    #[test]
+    fn cancelled_and_expired_jobs_leave_the_queue() {
+        let mut state = BatchLoop::new(1, 16);
+        let cancelled = InferenceConfig::default();
+        cancelled.cancel.cancel();
+        let (a, cancelled_rx) = job(cancelled);
+        let (b, expired_rx) = job(InferenceConfig { timeout_ms: 0, ..Default::default() });
+        let (c, waiting_rx) = job(InferenceConfig::default());
+        for queued in [a, b, c] {
+            state.enqueue(queued);
+        }
+
+        state.expire_pending(7);
+        assert_eq!(state.batcher.pending_count(), 1);
+        assert_eq!(state.pending.len(), 1);
+        for (rx, reason) in [
+            (cancelled_rx, FinishReason::Cancelled),
+            (expired_rx, FinishReason::Timeout),
+        ] {
+            let output = rx.try_recv().unwrap().unwrap();
+            assert!(output.is_final && output.text.is_empty());
+            assert_eq!(output.token, 7);
+            let finish = output.finish.unwrap();
+            assert_eq!((finish.finish_reason, finish.completion_tokens), (reason, 0));
+            assert_eq!(finish.prompt_tokens, 2);
+            // The job was dropped with its sender
+            assert!(matches!(rx.try_recv(), Err(mpsc::TryRecvError::Disconnected)));
+        }
+        assert!(matches!(waiting_rx.try_recv(), Err(mpsc::TryRecvError::Empty)));
+    }
+}
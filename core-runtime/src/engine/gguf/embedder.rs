@@ -0,0 +1,170 @@
+//! GGUF-based embedding model.
+//!
+//! Opens a llama-cpp-2 model with embeddings enabled and pools its token
+//! states as the model's `{arch}.pooling_type` says, so embedding models
+//! shipped as GGUF (nomic-embed, bge, e5) serve without the ONNX path.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "gguf")]
+use std::sync::Arc;
+
+use super::GgufPooling;
+#[cfg(feature = "gguf")]
+use crate::engine::onnx::l2_normalize;
+use crate::engine::{
+    EmbeddingResult, InferenceCapability, InferenceConfig, InferenceError, InferenceInput,
+    InferenceOutput,
+};
+
+/// GGUF embedding model using llama-cpp-2.
+pub struct GgufEmbedder {
+    model_id: String,
+    memory_bytes: AtomicUsize,
+    pooling: GgufPooling,
+    normalize: bool,
+    #[cfg(feature = "gguf")]
+    inner: Option<Arc<super::backend::LlamaBackendInner>>,
+}
+
+impl GgufEmbedder {
+    /// Create a new embedder (no model loaded yet).
+    pub fn new(model_id: String, pooling: GgufPooling) -> Self {
+        Self {
+            model_id,
+            memory_bytes: AtomicUsize::new(0),
+            pooling,
+            normalize: true,
+            #[cfg(feature = "gguf")]
+            inner: None,
+        }
+    }
+
+    /// Load a model from a GGUF file path in embedding mode. `pooling` is
+    /// the header's pooling type; models without one are mean pooled.
+    #[cfg(feature = "gguf")]
+    pub fn load(
+        model_id: String,
+        path: &std::path::Path,
+        config: &super::GgufConfig,
+        pooling: Option<GgufPooling>,
+    ) -> Result<Self, InferenceError> {
+        let pooling = match pooling {
+            None | Some(GgufPooling::None) => GgufPooling::Mean,
+            Some(GgufPooling::Rank) => {
+                return Err(InferenceError::ModelError(format!(
+                    "{} pools for ranking and cannot embed",
+                    path.display()
+                )))
+            }
+            Some(pooling) => pooling,
+        };
+        let inner = Arc::new(super::backend::LlamaBackendInner::load(path, config)?);
+        Ok(Self {
+            model_id,
+            memory_bytes: AtomicUsize::new(inner.model_size()),
+            pooling,
+            normalize: config.normalize,
+            inner: Some(inner),
+        })
+    }
+
+    /// How token states are pooled into one vector.
+    pub fn pooling(&self) -> GgufPooling {
+        self.pooling
+    }
+
+    /// Embed texts, decoding as many together as fit one batch.
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<EmbeddingResult>, InferenceError> {
+        #[cfg(feature = "gguf")]
+        if let Some(inner) = &self.inner {
+            let vectors = inner.embed(texts, self.pooling)?;
+            return Ok(vectors
+                .into_iter()
+                .map(|mut vector| {
+                    if self.normalize {
+                        l2_normalize(&mut vector);
+                    }
+                    EmbeddingResult { dimensions: vector.len(), vector }
+                })
+                .collect());
+        }
+        let _ = (texts, self.normalize);
+        // No model loaded - fail rather than return mock data
+        Err(InferenceError::ModelError(format!(
+            "model '{}' not loaded - cannot embed",
+            self.model_id
+        )))
+    }
+
+    /// Generate embedding for a single text input.
+    fn embed_text(&self, text: &str) -> Result<EmbeddingResult, InferenceError> {
+        self.embed_batch(&[text])?
+            .pop()
+            .ok_or_else(|| InferenceError::ModelError("model returned no embedding".into()))
+    }
+}
+
+#[async_trait::async_trait]
+impl super::GgufModel for GgufEmbedder {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn capabilities(&self) -> &[InferenceCapability] {
+        &[InferenceCapability::Embedding]
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.memory_bytes.load(Ordering::SeqCst)
+    }
+
+    async fn infer(
+        &self,
+        input: &InferenceInput,
+        _config: &InferenceConfig,
+    ) -> Result<InferenceOutput, InferenceError> {
+        input.validate()?;
+
+        match input {
+            InferenceInput::Text(text) => {
+                let result = self.embed_text(text)?;
+                Ok(InferenceOutput::Embedding(result))
+            }
+            InferenceInput::TextBatch(batch) => {
+                // Single-output API: embed the first item (see infer_batch)
+                let text = batch.first().ok_or_else(|| {
+                    InferenceError::InputValidation("batch cannot be empty".into())
+                })?;
+                let result = self.embed_text(text)?;
+                Ok(InferenceOutput::Embedding(result))
+            }
+            InferenceInput::ChatMessages(_) => Err(InferenceError::CapabilityNotSupported(
+                "chat messages not supported for embedding".into(),
+            )),
+        }
+    }
+
+    async fn infer_batch(
+        &self,
+        texts: &[String],
+        _config: &InferenceConfig,
+    ) -> Result<Vec<InferenceOutput>, InferenceError> {
+        InferenceInput::TextBatch(texts.to_vec()).validate()?;
+        let refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let results = self.embed_batch(&refs)?;
+        Ok(results.into_iter().map(InferenceOutput::Embedding).collect())
+    }
+
+    async fn unload(&mut self) -> Result<(), InferenceError> {
+        self.memory_bytes.store(0, Ordering::SeqCst);
+        #[cfg(feature = "gguf")]
+        {
+            self.inner = None;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
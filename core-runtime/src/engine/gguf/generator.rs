@@ -3,11 +3,20 @@
 //! Wraps llama-cpp-2 for text generation tasks.
 
 use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "gguf")]
+use std::sync::Arc;
 
 use crate::engine::{
-    GenerationResult, InferenceCapability, InferenceConfig,
-    InferenceError, InferenceInput, InferenceOutput,
+    ChatMessage, ChatTemplate, GenerationResult, InferenceCapability,
+    InferenceConfig, InferenceError, InferenceInput, InferenceOutput,
 };
+#[cfg(feature = "gguf")]
+use crate::engine::context::trim_chat;
+#[cfg(feature = "gguf")]
+use crate::engine::{ContextStrategy, PromptLookup};
+
+#[cfg(feature = "gguf")]
+use super::speculative::Drafter;
 
 /// GGUF text generation model using llama-cpp-2.
 pub struct GgufGenerator {
@@ -15,8 +24,12 @@ pub struct GgufGenerator {
     memory_bytes: AtomicUsize,
     #[allow(dead_code)]
     context_size: u32,
+    chat_template: ChatTemplate,
+    #[cfg(feature = "gguf")]
+    inner: Option<Arc<super::backend::LlamaBackendInner>>,
+    /// Decode loop shared by concurrent requests, when `batch_slots > 1`.
     #[cfg(feature = "gguf")]
-    inner: Option<super::backend::LlamaBackendInner>,
+    batcher: Option<super::batcher::Batcher>,
 }
 
 impl GgufGenerator {
@@ -26,11 +39,25 @@ impl GgufGenerator {
             model_id,
             memory_bytes: AtomicUsize::new(0),
             context_size,
+            chat_template: ChatTemplate::default(),
             #[cfg(feature = "gguf")]
             inner: None,
+            #[cfg(feature = "gguf")]
+            batcher: None,
         }
     }
 
+    /// Use a specific chat template instead of the resolved one.
+    pub fn with_chat_template(mut self, template: ChatTemplate) -> Self {
+        self.chat_template = template;
+        self
+    }
+
+    /// Chat template used to render chat requests.
+    pub fn chat_template(&self) -> &ChatTemplate {
+        &self.chat_template
+    }
+
     /// Load a model from a GGUF file path.
     #[cfg(feature = "gguf")]
     pub fn load(
@@ -38,21 +65,32 @@ impl GgufGenerator {
         path: &std::path::Path,
         config: &super::GgufConfig,
     ) -> Result<Self, InferenceError> {
-        let inner = super::backend::LlamaBackendInner::load(path, config)?;
+        let inner = Arc::new(super::backend::LlamaBackendInner::load(path, config)?);
         let mem = inner.model_size();
+        let metadata = inner.chat_template_metadata();
+        let chat_template = ChatTemplate::resolve(
+            config.chat_template.as_deref(),
+            metadata.as_deref(),
+        );
         Ok(Self {
             model_id,
             memory_bytes: AtomicUsize::new(mem),
             context_size: config.n_ctx,
+            chat_template,
+            batcher: (config.batch_slots > 1)
+                .then(|| super::batcher::Batcher::start(inner.clone(), config.batch_slots))
+                .transpose()?,
             inner: Some(inner),
         })
     }
 
-    /// Generate text from a prompt string.
+    /// Generate text from a prompt string, speculating with `draft` when it
+    /// can serve as this model's draft.
     fn generate_text(
         &self,
         prompt: &str,
         config: &InferenceConfig,
+        draft: Option<&GgufGenerator>,
     ) -> Result<GenerationResult, InferenceError> {
         if prompt.is_empty() {
             return Err(InferenceError::InputValidation(
@@ -61,12 +99,25 @@ impl GgufGenerator {
         }
         #[cfg(feature = "gguf")]
         {
+            if let Some((inner, draft)) = self.drafter(draft, config) {
+                let mut outputs = Vec::new();
+                inner.speculate(draft, prompt, config, |output| {
+                    outputs.push(output);
+                    true
+                })?;
+                return GenerationResult::from_outputs(outputs).ok_or_else(|| {
+                    InferenceError::ModelError("speculation ended without output".into())
+                });
+            }
             if let Some(inner) = &self.inner {
+                if let Some(batcher) = self.batcher(config) {
+                    return batcher.generate(inner, prompt, config);
+                }
                 return inner.generate(prompt, config);
             }
         }
         #[cfg(not(feature = "gguf"))]
-        let _ = config; // silence unused warning when gguf disabled
+        let _ = (config, draft); // silence unused warning when gguf disabled
         // No model loaded - fail rather than return mock data
         Err(InferenceError::ModelError(format!(
             "model '{}' not loaded - cannot generate",
@@ -82,12 +133,62 @@ impl GgufGenerator {
         config: &InferenceConfig,
         sender: crate::engine::TokenStreamSender,
     ) -> Result<(), InferenceError> {
+        self.generate_stream_with_draft(prompt, config, None, sender)
+    }
+
+    /// Stream tokens for a prompt, speculating with `draft` when it can
+    /// serve as this model's draft or the request asks for prompt lookup.
+    #[cfg(feature = "gguf")]
+    pub fn generate_stream_with_draft(
+        &self,
+        prompt: &str,
+        config: &InferenceConfig,
+        draft: Option<&GgufGenerator>,
+        sender: crate::engine::TokenStreamSender,
+    ) -> Result<(), InferenceError> {
+        if let Some((inner, draft)) = self.drafter(draft, config) {
+            let rt = tokio::runtime::Handle::current();
+            return inner.speculate(draft, prompt, config, |output| {
+                rt.block_on(sender.send_output(output)).is_ok()
+            });
+        }
         if let Some(inner) = &self.inner {
+            if let Some(batcher) = self.batcher(config) {
+                return batcher.generate_stream(inner, prompt, config, sender);
+            }
             return inner.generate_stream(prompt, config, sender);
         }
         Err(InferenceError::ModelError("no model loaded".into()))
     }
 
+    /// This model's backend and the source of draft tokens to speculate
+    /// with, if the request keeps no conversation state and selects no
+    /// adapter. Prompt lookup is
+    /// used when requested; otherwise `draft` must be loaded and share this
+    /// model's vocabulary.
+    #[cfg(feature = "gguf")]
+    fn drafter<'a>(
+        &'a self,
+        draft: Option<&'a GgufGenerator>,
+        config: &InferenceConfig,
+    ) -> Option<(&'a super::backend::LlamaBackendInner, Drafter<'a>)> {
+        let inner = self.inner.as_deref()?;
+        if config.conversation.is_some() || config.best_of > 1 || config.adapter.is_some() {
+            return None;
+        }
+        if config.prompt_lookup {
+            return Some((inner, Drafter::PromptLookup(PromptLookup::default())));
+        }
+        let draft = draft?.inner.as_deref()?;
+        inner.shares_vocab(draft).then_some((inner, Drafter::Model(draft)))
+    }
+
+    /// Continuous batch to run a request in, if it can share one.
+    #[cfg(feature = "gguf")]
+    fn batcher(&self, config: &InferenceConfig) -> Option<&super::batcher::Batcher> {
+        self.batcher.as_ref().filter(|_| super::batcher::Batcher::accepts(config))
+    }
+
     /// Generate N tokens from token context (for speculative decoding).
     #[cfg(feature = "gguf")]
     pub async fn generate_tokens(
@@ -101,17 +202,10 @@ impl GgufGenerator {
         Err(InferenceError::ModelError("no model loaded".into()))
     }
 
-    /// Verify draft tokens against model (for speculative decoding).
+    /// Loaded backend, for verifying draft tokens against this model.
     #[cfg(feature = "gguf")]
-    pub async fn verify_draft_tokens(
-        &self,
-        context: &[u32],
-        draft: &[u32],
-    ) -> Result<crate::engine::speculative::VerifyResult, InferenceError> {
-        if let Some(inner) = &self.inner {
-            return inner.verify_tokens(context, draft);
-        }
-        Err(InferenceError::ModelError("no model loaded".into()))
+    pub(super) fn backend(&self) -> Option<&Arc<super::backend::LlamaBackendInner>> {
+        self.inner.as_ref()
     }
 
     /// Get EOS token ID (for speculative decoding).
@@ -120,24 +214,78 @@ impl GgufGenerator {
         self.inner.as_ref().and_then(|i| i.eos_token())
     }
 
-    /// Format chat messages into a prompt string.
-    fn format_chat_prompt(
+    /// Render chat messages for generation. Under
+    /// `ContextStrategy::TrimMiddle` the oldest turns are dropped until the
+    /// prompt leaves room for `max_tokens`.
+    pub fn chat_prompt(
         &self,
-        messages: &[crate::engine::ChatMessage],
+        messages: &[ChatMessage],
+        config: &InferenceConfig,
     ) -> Result<String, InferenceError> {
-        let mut prompt = String::new();
-        for msg in messages {
-            let tag = match msg.role {
-                crate::engine::ChatRole::System => "<|system|>",
-                crate::engine::ChatRole::User => "<|user|>",
-                crate::engine::ChatRole::Assistant => "<|assistant|>",
-            };
-            prompt.push_str(tag);
-            prompt.push_str(&msg.content);
-            prompt.push_str("<|end|>\n");
+        #[cfg(feature = "gguf")]
+        {
+            if let (ContextStrategy::TrimMiddle, Some(inner)) =
+                (config.context_strategy, &self.inner)
+            {
+                let budget = config.completion_budget();
+                let kept = trim_chat(messages, budget, inner.context_size(), |kept| {
+                    Ok(inner.tokenize(&self.format_chat_prompt(kept)?)?.len())
+                })?;
+                return self.format_chat_prompt(&kept);
+            }
         }
-        prompt.push_str("<|assistant|>");
-        Ok(prompt)
+        #[cfg(not(feature = "gguf"))]
+        let _ = config;
+        self.format_chat_prompt(messages)
+    }
+
+    /// Run inference, speculating with `draft` when it can serve as this
+    /// model's draft and decoding normally otherwise.
+    pub async fn infer_with_draft(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+        draft: Option<&GgufGenerator>,
+    ) -> Result<InferenceOutput, InferenceError> {
+        input.validate()?;
+        config.validate()?;
+
+        match input {
+            InferenceInput::Text(prompt) => {
+                let result = self.generate_text(prompt, config, draft)?;
+                Ok(InferenceOutput::Generation(result))
+            }
+            InferenceInput::ChatMessages(messages) => {
+                let prompt = self.chat_prompt(messages, config)?;
+                let result = self.generate_text(&prompt, config, draft)?;
+                Ok(InferenceOutput::Generation(result))
+            }
+            InferenceInput::TextBatch(_) => {
+                Err(InferenceError::CapabilityNotSupported(
+                    "batch generation not supported".into(),
+                ))
+            }
+        }
+    }
+
+    /// Format chat messages into a prompt using the model's chat template.
+    ///
+    /// Built-in families render natively; custom Jinja templates are handed
+    /// to llama.cpp.
+    pub fn format_chat_prompt(
+        &self,
+        messages: &[ChatMessage],
+    ) -> Result<String, InferenceError> {
+        #[cfg(feature = "gguf")]
+        {
+            if let (ChatTemplate::Custom(source), Some(inner)) =
+                (&self.chat_template, &self.inner)
+            {
+                self.chat_template.check_messages(messages)?;
+                return inner.apply_chat_template(source, messages);
+            }
+        }
+        self.chat_template.render(messages)
     }
 }
 
@@ -160,31 +308,53 @@ impl super::GgufModel for GgufGenerator {
         input: &InferenceInput,
         config: &InferenceConfig,
     ) -> Result<InferenceOutput, InferenceError> {
-        input.validate()?;
-        config.validate()?;
+        self.infer_with_draft(input, config, None).await
+    }
 
-        match input {
-            InferenceInput::Text(prompt) => {
-                let result = self.generate_text(prompt, config)?;
-                Ok(InferenceOutput::Generation(result))
-            }
-            InferenceInput::ChatMessages(messages) => {
-                let prompt = self.format_chat_prompt(messages)?;
-                let result = self.generate_text(&prompt, config)?;
-                Ok(InferenceOutput::Generation(result))
-            }
+    #[cfg(feature = "gguf")]
+    fn infer_stream(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+        draft: Option<&dyn super::GgufModel>,
+        sender: crate::engine::TokenStreamSender,
+    ) -> Result<(), InferenceError> {
+        let prompt = match input {
+            InferenceInput::Text(prompt) => prompt.clone(),
+            InferenceInput::ChatMessages(messages) => self.chat_prompt(messages, config)?,
             InferenceInput::TextBatch(_) => {
-                Err(InferenceError::CapabilityNotSupported(
+                return Err(InferenceError::CapabilityNotSupported(
                     "batch generation not supported".into(),
                 ))
             }
+        };
+        let draft = draft.and_then(|d| d.as_any().downcast_ref::<GgufGenerator>());
+        self.generate_stream_with_draft(&prompt, config, draft, sender)
+    }
+
+    #[cfg(feature = "gguf")]
+    fn load_adapter(&self, name: &str, path: &std::path::Path) -> Result<usize, InferenceError> {
+        match &self.inner {
+            Some(inner) => inner.load_adapter(name, path),
+            None => Err(InferenceError::ModelError("no model loaded".into())),
         }
     }
 
+    #[cfg(feature = "gguf")]
+    fn unload_adapter(&self, name: &str) -> bool {
+        self.inner.as_ref().is_some_and(|inner| inner.unload_adapter(name))
+    }
+
+    #[cfg(feature = "gguf")]
+    fn has_adapter(&self, name: &str) -> bool {
+        self.inner.as_ref().is_some_and(|inner| inner.has_adapter(name))
+    }
+
     async fn unload(&mut self) -> Result<(), InferenceError> {
         self.memory_bytes.store(0, Ordering::SeqCst);
         #[cfg(feature = "gguf")]
         {
+            self.batcher = None;
             self.inner = None;
         }
         Ok(())
@@ -194,3 +364,25 @@ impl super::GgufModel for GgufGenerator {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::ChatRole;
+
+    #[test]
+    fn format_chat_prompt_uses_configured_template() {
+        let messages = vec![ChatMessage { role: ChatRole::User, content: "Hi".into() }];
+        let generator = GgufGenerator::new("m".into(), 512);
+        assert_eq!(
+            generator.format_chat_prompt(&messages).unwrap(),
+            "<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+        );
+
+        let generator = generator.with_chat_template(ChatTemplate::Llama3);
+        assert!(generator
+            .format_chat_prompt(&messages)
+            .unwrap()
+            .starts_with("<|start_header_id|>user<|end_header_id|>"));
+    }
+}
@@ -0,0 +1,963 @@
+//! Pure-Rust reader for GGUF headers.
+//!
+//! Parses the key/value metadata and the tensor table of a GGUF file
+//! without llama.cpp and without reading tensor data, so model files can be
+//! checked on hosts that never load them. Counts and lengths are bounded
+//! before anything is allocated, and the tensor table is checked against
+//! the file size to catch truncated copies.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use thiserror::Error;
+
+/// First four bytes of every GGUF file.
+pub const GGUF_MAGIC: [u8; 4] = *b"GGUF";
+
+/// Tensor data alignment when `general.alignment` is absent.
+pub const DEFAULT_ALIGNMENT: u64 = 32;
+
+const MAX_KEYS: u64 = 1 << 16;
+const MAX_TENSORS: u64 = 1 << 20;
+const MAX_STRING_BYTES: u64 = 64 << 20;
+const MAX_ARRAY_LEN: u64 = 1 << 24;
+const MAX_ARRAY_DEPTH: usize = 4;
+const MAX_DIMS: u32 = 4;
+
+/// Errors reading a GGUF header.
+#[derive(Debug, Error)]
+pub enum GgufError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("not a GGUF file")]
+    NotGguf,
+
+    #[error("unsupported GGUF version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("file is truncated")]
+    Truncated,
+
+    #[error("malformed GGUF: {0}")]
+    Malformed(String),
+}
+
+/// A metadata value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+    String(String),
+    Array(Vec<GgufValue>),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl GgufValue {
+    /// The value as an unsigned integer, if it is a non-negative integer.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            GgufValue::U8(v) => Some(v.into()),
+            GgufValue::U16(v) => Some(v.into()),
+            GgufValue::U32(v) => Some(v.into()),
+            GgufValue::U64(v) => Some(v),
+            GgufValue::I8(v) => u64::try_from(v).ok(),
+            GgufValue::I16(v) => u64::try_from(v).ok(),
+            GgufValue::I32(v) => u64::try_from(v).ok(),
+            GgufValue::I64(v) => u64::try_from(v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Float value; integers are converted.
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            GgufValue::F32(v) => Some(v),
+            GgufValue::F64(v) => Some(v as f32),
+            _ => self.as_u64().map(|v| v as f32),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            GgufValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[GgufValue]> {
+        match self {
+            GgufValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// GGUF name of the value's type.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            GgufValue::U8(_) => "u8",
+            GgufValue::I8(_) => "i8",
+            GgufValue::U16(_) => "u16",
+            GgufValue::I16(_) => "i16",
+            GgufValue::U32(_) => "u32",
+            GgufValue::I32(_) => "i32",
+            GgufValue::F32(_) => "f32",
+            GgufValue::Bool(_) => "bool",
+            GgufValue::String(_) => "string",
+            GgufValue::Array(_) => "array",
+            GgufValue::U64(_) => "u64",
+            GgufValue::I64(_) => "i64",
+            GgufValue::F64(_) => "f64",
+        }
+    }
+}
+
+/// Arrays longer than this are shown by element type and length.
+const DISPLAY_ARRAY_LEN: usize = 8;
+
+impl fmt::Display for GgufValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GgufValue::U8(v) => write!(f, "{v}"),
+            GgufValue::I8(v) => write!(f, "{v}"),
+            GgufValue::U16(v) => write!(f, "{v}"),
+            GgufValue::I16(v) => write!(f, "{v}"),
+            GgufValue::U32(v) => write!(f, "{v}"),
+            GgufValue::I32(v) => write!(f, "{v}"),
+            GgufValue::F32(v) => write!(f, "{v}"),
+            GgufValue::Bool(v) => write!(f, "{v}"),
+            GgufValue::String(s) => write!(f, "{s:?}"),
+            GgufValue::U64(v) => write!(f, "{v}"),
+            GgufValue::I64(v) => write!(f, "{v}"),
+            GgufValue::F64(v) => write!(f, "{v}"),
+            GgufValue::Array(values) if values.len() > DISPLAY_ARRAY_LEN => {
+                let element = values.first().map_or("?", GgufValue::type_name);
+                write!(f, "[{element}; {}]", values.len())
+            }
+            GgufValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Tensor element type, as numbered by ggml.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GgmlType(pub u32);
+
+/// Known ggml types: id, name, elements per block and bytes per block.
+const GGML_TYPES: &[(u32, &str, u64, u64)] = &[
+    (0, "F32", 1, 4),
+    (1, "F16", 1, 2),
+    (2, "Q4_0", 32, 18),
+    (3, "Q4_1", 32, 20),
+    (6, "Q5_0", 32, 22),
+    (7, "Q5_1", 32, 24),
+    (8, "Q8_0", 32, 34),
+    (9, "Q8_1", 32, 36),
+    (10, "Q2_K", 256, 84),
+    (11, "Q3_K", 256, 110),
+    (12, "Q4_K", 256, 144),
+    (13, "Q5_K", 256, 176),
+    (14, "Q6_K", 256, 210),
+    (15, "Q8_K", 256, 292),
+    (16, "IQ2_XXS", 256, 66),
+    (17, "IQ2_XS", 256, 74),
+    (18, "IQ3_XXS", 256, 98),
+    (19, "IQ1_S", 256, 50),
+    (20, "IQ4_NL", 32, 18),
+    (21, "IQ3_S", 256, 110),
+    (22, "IQ2_S", 256, 82),
+    (23, "IQ4_XS", 256, 136),
+    (24, "I8", 1, 1),
+    (25, "I16", 1, 2),
+    (26, "I32", 1, 4),
+    (27, "I64", 1, 8),
+    (28, "F64", 1, 8),
+    (29, "IQ1_M", 256, 56),
+    (30, "BF16", 1, 2),
+    (34, "TQ1_0", 256, 54),
+    (35, "TQ2_0", 256, 66),
+    (39, "MXFP4", 32, 17),
+];
+
+impl GgmlType {
+    pub const F32: GgmlType = GgmlType(0);
+    pub const F16: GgmlType = GgmlType(1);
+    pub const Q4_0: GgmlType = GgmlType(2);
+    pub const Q8_0: GgmlType = GgmlType(8);
+    pub const Q4_K: GgmlType = GgmlType(12);
+    pub const Q6_K: GgmlType = GgmlType(14);
+
+    fn entry(self) -> Option<&'static (u32, &'static str, u64, u64)> {
+        GGML_TYPES.iter().find(|(id, ..)| *id == self.0)
+    }
+
+    /// Type name, e.g. "Q4_K". None for types this reader does not know.
+    pub fn name(self) -> Option<&'static str> {
+        self.entry().map(|&(_, name, ..)| name)
+    }
+
+    /// Elements per quantization block and bytes per block.
+    pub fn block(self) -> Option<(u64, u64)> {
+        self.entry().map(|&(_, _, elements, bytes)| (elements, bytes))
+    }
+}
+
+impl fmt::Display for GgmlType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "type{}", self.0),
+        }
+    }
+}
+
+/// Name of a llama.cpp `general.file_type`, the quantization a model was
+/// converted with.
+pub fn file_type_name(file_type: u64) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        38 => "MXFP4_MOE",
+        _ => return None,
+    })
+}
+
+/// How an embedding model pools token states into one vector, as numbered
+/// by llama.cpp's `{arch}.pooling_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GgufPooling {
+    /// One state per token; the model does not pool.
+    None,
+    Mean,
+    /// State of the first token.
+    Cls,
+    /// State of the last token.
+    Last,
+    /// Relevance score of a reranker.
+    Rank,
+}
+
+impl GgufPooling {
+    pub fn from_id(id: u64) -> Option<Self> {
+        Some(match id {
+            0 => Self::None,
+            1 => Self::Mean,
+            2 => Self::Cls,
+            3 => Self::Last,
+            4 => Self::Rank,
+            _ => return None,
+        })
+    }
+
+    pub fn id(self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Mean => 1,
+            Self::Cls => 2,
+            Self::Last => 3,
+            Self::Rank => 4,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Mean => "mean",
+            Self::Cls => "cls",
+            Self::Last => "last",
+            Self::Rank => "rank",
+        }
+    }
+}
+
+impl fmt::Display for GgufPooling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An entry of the tensor table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GgufTensorInfo {
+    pub name: String,
+    /// Dimensions, innermost first.
+    pub shape: Vec<u64>,
+    pub ggml_type: GgmlType,
+    /// Offset from the start of the tensor data section.
+    pub offset: u64,
+}
+
+impl GgufTensorInfo {
+    /// Elements in the tensor, saturating at `u64::MAX`. Parsed tensors
+    /// are checked to fit.
+    pub fn element_count(&self) -> u64 {
+        self.checked_element_count().unwrap_or(u64::MAX)
+    }
+
+    /// Bytes of tensor data. None for types this reader does not know, or
+    /// sizes that do not fit in a u64.
+    pub fn byte_size(&self) -> Option<u64> {
+        let (elements, bytes) = self.ggml_type.block()?;
+        (self.checked_element_count()? / elements).checked_mul(bytes)
+    }
+
+    fn checked_element_count(&self) -> Option<u64> {
+        self.shape.iter().try_fold(1u64, |acc, &d| acc.checked_mul(d))
+    }
+}
+
+/// Header of a GGUF file: metadata and tensor table.
+#[derive(Debug, Clone)]
+pub struct GgufMetadata {
+    pub version: u32,
+    pub metadata: BTreeMap<String, GgufValue>,
+    pub tensors: Vec<GgufTensorInfo>,
+    /// File offset where tensor data starts.
+    pub data_offset: u64,
+    pub file_size: u64,
+}
+
+impl GgufMetadata {
+    /// Read the header of the GGUF file at `path`.
+    pub fn read(path: &Path) -> Result<Self, GgufError> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a GGUF header from the start of `reader`.
+    pub fn from_reader<R: Read + Seek>(mut reader: R) -> Result<Self, GgufError> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let mut reader = Reader { inner: reader, pos: 0 };
+
+        let mut magic = [0u8; 4];
+        reader.bytes(&mut magic).map_err(|e| match e {
+            GgufError::Truncated => GgufError::NotGguf,
+            e => e,
+        })?;
+        if magic != GGUF_MAGIC {
+            return Err(GgufError::NotGguf);
+        }
+        let version = reader.u32()?;
+        if !(2..=3).contains(&version) {
+            if (2..=3).contains(&version.swap_bytes()) {
+                return Err(GgufError::Malformed("big-endian files are not supported".into()));
+            }
+            return Err(GgufError::UnsupportedVersion(version));
+        }
+        let tensor_count = reader.count(MAX_TENSORS, "tensor count")?;
+        let key_count = reader.count(MAX_KEYS, "metadata key count")?;
+
+        let mut metadata = BTreeMap::new();
+        for _ in 0..key_count {
+            let key = reader.string()?;
+            let value_type = reader.u32()?;
+            let value = reader.value(value_type, 0)?;
+            if metadata.insert(key.clone(), value).is_some() {
+                return Err(GgufError::Malformed(format!("duplicate key '{key}'")));
+            }
+        }
+
+        let mut tensors = Vec::with_capacity(tensor_count.min(4096) as usize);
+        let mut names = HashSet::new();
+        for _ in 0..tensor_count {
+            let tensor = reader.tensor_info()?;
+            if !names.insert(tensor.name.clone()) {
+                return Err(GgufError::Malformed(format!("duplicate tensor '{}'", tensor.name)));
+            }
+            tensors.push(tensor);
+        }
+
+        let alignment = match metadata.get("general.alignment") {
+            None => DEFAULT_ALIGNMENT,
+            Some(GgufValue::U32(a)) if a.is_power_of_two() => u64::from(*a),
+            Some(_) => {
+                return Err(GgufError::Malformed(
+                    "general.alignment must be a power of two u32".into(),
+                ))
+            }
+        };
+        let header = Self {
+            version,
+            metadata,
+            tensors,
+            data_offset: reader.pos.next_multiple_of(alignment),
+            file_size,
+        };
+        header.check_tensors(alignment)?;
+        Ok(header)
+    }
+
+    /// Check that every tensor is aligned and lies within the file, and
+    /// that the parameter and byte totals fit in a u64.
+    fn check_tensors(&self, alignment: u64) -> Result<(), GgufError> {
+        let overflow = |total: &str| GgufError::Malformed(format!("{total} overflows"));
+        self.tensors
+            .iter()
+            .map(GgufTensorInfo::element_count)
+            .try_fold(0u64, u64::checked_add)
+            .ok_or_else(|| overflow("parameter count"))?;
+        self.tensors
+            .iter()
+            .filter_map(GgufTensorInfo::byte_size)
+            .try_fold(0u64, u64::checked_add)
+            .ok_or_else(|| overflow("tensor data size"))?;
+        for tensor in &self.tensors {
+            if tensor.offset % alignment != 0 {
+                return Err(GgufError::Malformed(format!(
+                    "tensor '{}' is not aligned to {alignment} bytes",
+                    tensor.name
+                )));
+            }
+            let Some(size) = tensor.byte_size() else {
+                continue;
+            };
+            let end = self
+                .data_offset
+                .checked_add(tensor.offset)
+                .and_then(|start| start.checked_add(size))
+                .ok_or_else(|| {
+                    GgufError::Malformed(format!("tensor '{}' offset overflows", tensor.name))
+                })?;
+            if end > self.file_size {
+                return Err(GgufError::Truncated);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&GgufValue> {
+        self.metadata.get(key)
+    }
+
+    fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key)?.as_str()
+    }
+
+    /// A hyperparameter stored under the architecture's prefix.
+    fn hparam(&self, key: &str) -> Option<u64> {
+        self.get(&format!("{}.{key}", self.architecture()?))?.as_u64()
+    }
+
+    fn hparam_f32(&self, key: &str) -> Option<f32> {
+        self.get(&format!("{}.{key}", self.architecture()?))?.as_f32()
+    }
+
+    /// Model architecture, e.g. "llama" or "qwen2".
+    pub fn architecture(&self) -> Option<&str> {
+        self.get_str("general.architecture")
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.get_str("general.name")
+    }
+
+    /// Adapter kind, e.g. "lora", when the file is an adapter for a model
+    /// of `architecture()` rather than a model itself.
+    pub fn adapter_type(&self) -> Option<&str> {
+        if self.get_str("general.type")? != "adapter" {
+            return None;
+        }
+        self.get_str("adapter.type")
+    }
+
+    /// Quantization the model was converted with, e.g. "Q4_K_M".
+    pub fn file_type(&self) -> Option<&'static str> {
+        file_type_name(self.get("general.file_type")?.as_u64()?)
+    }
+
+    /// Context length the model was trained with.
+    pub fn context_length(&self) -> Option<u64> {
+        self.hparam("context_length")
+    }
+
+    pub fn embedding_length(&self) -> Option<u64> {
+        self.hparam("embedding_length")
+    }
+
+    /// Number of transformer blocks.
+    pub fn block_count(&self) -> Option<u64> {
+        self.hparam("block_count")
+    }
+
+    pub fn feed_forward_length(&self) -> Option<u64> {
+        self.hparam("feed_forward_length")
+    }
+
+    pub fn head_count(&self) -> Option<u64> {
+        self.hparam("attention.head_count")
+    }
+
+    /// Key/value heads; equal to `head_count` without grouped-query
+    /// attention.
+    pub fn head_count_kv(&self) -> Option<u64> {
+        self.hparam("attention.head_count_kv").or_else(|| self.head_count())
+    }
+
+    /// Dimensions of each head rotated by RoPE; all of them when absent.
+    pub fn rope_dimension_count(&self) -> Option<u64> {
+        self.hparam("rope.dimension_count")
+    }
+
+    /// RoPE base frequency; 10000 when absent.
+    pub fn rope_freq_base(&self) -> Option<f32> {
+        self.hparam_f32("rope.freq_base")
+    }
+
+    /// Epsilon of the RMS norms.
+    pub fn rms_norm_epsilon(&self) -> Option<f32> {
+        self.hparam_f32("attention.layer_norm_rms_epsilon")
+    }
+
+    /// Pooling of an embedding or reranking model. Generators declare
+    /// none.
+    pub fn pooling_type(&self) -> Option<GgufPooling> {
+        GgufPooling::from_id(self.hparam("pooling_type")?)
+    }
+
+    /// Tokenizer family, e.g. "llama" (SentencePiece) or "gpt2" (BPE).
+    pub fn tokenizer_model(&self) -> Option<&str> {
+        self.get_str("tokenizer.ggml.model")
+    }
+
+    pub fn vocab_size(&self) -> Option<usize> {
+        Some(self.get("tokenizer.ggml.tokens")?.as_array()?.len())
+    }
+
+    /// Jinja chat template embedded by the converter.
+    pub fn chat_template(&self) -> Option<&str> {
+        self.get_str("tokenizer.chat_template")
+    }
+
+    /// Total elements over all tensors, saturating at `u64::MAX`. Parsed
+    /// headers are checked to fit.
+    pub fn parameter_count(&self) -> u64 {
+        self.tensors
+            .iter()
+            .map(GgufTensorInfo::element_count)
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Bytes of tensor data, not counting tensors of unknown type,
+    /// saturating at `u64::MAX`. Parsed headers are checked to fit.
+    pub fn tensor_bytes(&self) -> u64 {
+        self.tensors
+            .iter()
+            .filter_map(GgufTensorInfo::byte_size)
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Tensor count per element type.
+    pub fn tensor_types(&self) -> BTreeMap<GgmlType, usize> {
+        let mut counts = BTreeMap::new();
+        for tensor in &self.tensors {
+            *counts.entry(tensor.ggml_type).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Little-endian reader that tracks its offset.
+struct Reader<R> {
+    inner: R,
+    pos: u64,
+}
+
+impl<R: Read> Reader<R> {
+    fn bytes(&mut self, buf: &mut [u8]) -> Result<(), GgufError> {
+        self.inner.read_exact(buf).map_err(eof_to_truncated)?;
+        self.pos += buf.len() as u64;
+        Ok(())
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], GgufError> {
+        let mut buf = [0u8; N];
+        self.bytes(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn u32(&mut self) -> Result<u32, GgufError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, GgufError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    /// A u64 count, rejected above `max`.
+    fn count(&mut self, max: u64, what: &str) -> Result<u64, GgufError> {
+        let count = self.u64()?;
+        if count > max {
+            return Err(GgufError::Malformed(format!("{what} {count} exceeds {max}")));
+        }
+        Ok(count)
+    }
+
+    fn string(&mut self) -> Result<String, GgufError> {
+        let len = self.count(MAX_STRING_BYTES, "string length")?;
+        // Grows as bytes arrive, so a bogus length cannot allocate up front
+        let mut buf = Vec::new();
+        let read = (&mut self.inner).take(len).read_to_end(&mut buf)?;
+        self.pos += read as u64;
+        if (read as u64) < len {
+            return Err(GgufError::Truncated);
+        }
+        // Token strings are not always valid UTF-8
+        Ok(String::from_utf8(buf)
+            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()))
+    }
+
+    fn value(&mut self, value_type: u32, depth: usize) -> Result<GgufValue, GgufError> {
+        Ok(match value_type {
+            0 => GgufValue::U8(u8::from_le_bytes(self.array()?)),
+            1 => GgufValue::I8(i8::from_le_bytes(self.array()?)),
+            2 => GgufValue::U16(u16::from_le_bytes(self.array()?)),
+            3 => GgufValue::I16(i16::from_le_bytes(self.array()?)),
+            4 => GgufValue::U32(self.u32()?),
+            5 => GgufValue::I32(i32::from_le_bytes(self.array()?)),
+            6 => GgufValue::F32(f32::from_le_bytes(self.array()?)),
+            7 => match self.array::<1>()? {
+                [0] => GgufValue::Bool(false),
+                [1] => GgufValue::Bool(true),
+                [b] => return Err(GgufError::Malformed(format!("invalid bool {b}"))),
+            },
+            8 => GgufValue::String(self.string()?),
+            9 => {
+                if depth >= MAX_ARRAY_DEPTH {
+                    return Err(GgufError::Malformed("arrays nested too deeply".into()));
+                }
+                let element_type = self.u32()?;
+                let len = self.count(MAX_ARRAY_LEN, "array length")?;
+                let mut values = Vec::with_capacity(len.min(4096) as usize);
+                for _ in 0..len {
+                    values.push(self.value(element_type, depth + 1)?);
+                }
+                GgufValue::Array(values)
+            }
+            10 => GgufValue::U64(self.u64()?),
+            11 => GgufValue::I64(i64::from_le_bytes(self.array()?)),
+            12 => GgufValue::F64(f64::from_le_bytes(self.array()?)),
+            other => return Err(GgufError::Malformed(format!("unknown value type {other}"))),
+        })
+    }
+
+    fn tensor_info(&mut self) -> Result<GgufTensorInfo, GgufError> {
+        let name = self.string()?;
+        let n_dims = self.u32()?;
+        if n_dims == 0 || n_dims > MAX_DIMS {
+            return Err(GgufError::Malformed(format!(
+                "tensor '{name}' has {n_dims} dimensions"
+            )));
+        }
+        let mut shape = Vec::with_capacity(n_dims as usize);
+        for _ in 0..n_dims {
+            shape.push(self.u64()?);
+        }
+        let ggml_type = GgmlType(self.u32()?);
+        let offset = self.u64()?;
+        let tensor = GgufTensorInfo { name, shape, ggml_type, offset };
+        let (name, shape) = (&tensor.name, &tensor.shape);
+        if tensor.checked_element_count().is_none() || shape.contains(&0) {
+            return Err(GgufError::Malformed(format!("tensor '{name}' has shape {shape:?}")));
+        }
+        if let Some((block, _)) = ggml_type.block() {
+            if shape[0] % block != 0 {
+                return Err(GgufError::Malformed(format!(
+                    "tensor '{name}' rows of {} do not fill {ggml_type} blocks of {block}",
+                    shape[0]
+                )));
+            }
+            if tensor.byte_size().is_none() {
+                return Err(GgufError::Malformed(format!(
+                    "tensor '{name}' of shape {shape:?} overflows a u64 byte size"
+                )));
+            }
+        }
+        Ok(tensor)
+    }
+}
+
+fn eof_to_truncated(e: io::Error) -> GgufError {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        GgufError::Truncated
+    } else {
+        GgufError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Writes a GGUF header followed by zeroed tensor data.
+    #[derive(Default)]
+    struct Builder {
+        keys: Vec<u8>,
+        key_count: u64,
+        tensors: Vec<u8>,
+        tensor_count: u64,
+        data: u64,
+    }
+
+    fn put_string(buf: &mut Vec<u8>, s: &str) {
+        buf.extend_from_slice(&(s.len() as u64).to_le_bytes());
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    impl Builder {
+        fn key(mut self, key: &str, value_type: u32, value: &[u8]) -> Self {
+            put_string(&mut self.keys, key);
+            self.keys.extend_from_slice(&value_type.to_le_bytes());
+            self.keys.extend_from_slice(value);
+            self.key_count += 1;
+            self
+        }
+
+        fn string(self, key: &str, value: &str) -> Self {
+            let mut buf = Vec::new();
+            put_string(&mut buf, value);
+            self.key(key, 8, &buf)
+        }
+
+        fn u32(self, key: &str, value: u32) -> Self {
+            self.key(key, 4, &value.to_le_bytes())
+        }
+
+        /// A table entry at `offset`, without reserving data for it.
+        fn tensor_at(
+            mut self,
+            name: &str,
+            shape: &[u64],
+            ggml_type: GgmlType,
+            offset: u64,
+        ) -> Self {
+            put_string(&mut self.tensors, name);
+            self.tensors.extend_from_slice(&(shape.len() as u32).to_le_bytes());
+            for dim in shape {
+                self.tensors.extend_from_slice(&dim.to_le_bytes());
+            }
+            self.tensors.extend_from_slice(&ggml_type.0.to_le_bytes());
+            self.tensors.extend_from_slice(&offset.to_le_bytes());
+            self.tensor_count += 1;
+            self
+        }
+
+        fn tensor(self, name: &str, shape: &[u64], ggml_type: GgmlType) -> Self {
+            let (elements, bytes) = ggml_type.block().unwrap();
+            let size = shape.iter().product::<u64>() / elements * bytes;
+            let offset = self.data;
+            let mut builder = self.tensor_at(name, shape, ggml_type, offset);
+            builder.data += size.next_multiple_of(DEFAULT_ALIGNMENT);
+            builder
+        }
+
+        fn build(self) -> Vec<u8> {
+            let mut buf = GGUF_MAGIC.to_vec();
+            buf.extend_from_slice(&3u32.to_le_bytes());
+            buf.extend_from_slice(&self.tensor_count.to_le_bytes());
+            buf.extend_from_slice(&self.key_count.to_le_bytes());
+            buf.extend_from_slice(&self.keys);
+            buf.extend_from_slice(&self.tensors);
+            buf.resize((buf.len() as u64).next_multiple_of(DEFAULT_ALIGNMENT) as usize, 0);
+            buf.resize(buf.len() + self.data as usize, 0);
+            buf
+        }
+    }
+
+    fn model() -> Builder {
+        let mut tokens = 8u32.to_le_bytes().to_vec();
+        tokens.extend_from_slice(&3u64.to_le_bytes());
+        for token in ["<s>", "a", "b"] {
+            put_string(&mut tokens, token);
+        }
+        Builder::default()
+            .string("general.architecture", "llama")
+            .string("general.name", "Tiny")
+            .u32("general.file_type", 15)
+            .u32("llama.context_length", 4096)
+            .u32("llama.embedding_length", 256)
+            .u32("llama.block_count", 2)
+            .u32("llama.attention.head_count", 8)
+            .u32("llama.attention.head_count_kv", 2)
+            .string("tokenizer.ggml.model", "llama")
+            .key("tokenizer.ggml.tokens", 9, &tokens)
+            .string("tokenizer.chat_template", "{{ messages }}")
+            .tensor("token_embd.weight", &[256, 3], GgmlType::Q8_0)
+            .tensor("blk.0.attn_q.weight", &[256, 256], GgmlType::Q4_K)
+            .tensor("output_norm.weight", &[256], GgmlType::F32)
+    }
+
+    fn parse(bytes: Vec<u8>) -> Result<GgufMetadata, GgufError> {
+        GgufMetadata::from_reader(Cursor::new(bytes))
+    }
+
+    #[test]
+    fn reads_metadata_and_tensor_table() {
+        let header = parse(model().build()).unwrap();
+        assert_eq!(header.version, 3);
+        assert_eq!(header.architecture(), Some("llama"));
+        assert_eq!(header.name(), Some("Tiny"));
+        assert_eq!(header.file_type(), Some("Q4_K_M"));
+        assert_eq!(header.context_length(), Some(4096));
+        assert_eq!(header.embedding_length(), Some(256));
+        assert_eq!(header.block_count(), Some(2));
+        assert_eq!(header.head_count(), Some(8));
+        assert_eq!(header.head_count_kv(), Some(2));
+        assert_eq!(header.tokenizer_model(), Some("llama"));
+        assert_eq!(header.vocab_size(), Some(3));
+        assert_eq!(header.chat_template(), Some("{{ messages }}"));
+        assert_eq!(header.pooling_type(), None);
+
+        assert_eq!(header.tensors.len(), 3);
+        let q = &header.tensors[1];
+        assert_eq!(q.name, "blk.0.attn_q.weight");
+        assert_eq!(q.ggml_type.to_string(), "Q4_K");
+        assert_eq!(q.byte_size(), Some(256 * 144));
+        assert_eq!(header.parameter_count(), 256 * 3 + 256 * 256 + 256);
+        assert_eq!(header.tensor_bytes(), 24 * 34 + 256 * 144 + 256 * 4);
+        assert_eq!(header.tensor_types().get(&GgmlType::Q4_K), Some(&1));
+        assert_eq!(header.data_offset % DEFAULT_ALIGNMENT, 0);
+    }
+
+    #[test]
+    fn reads_pooling_type() {
+        let header = parse(model().u32("llama.pooling_type", 2).build()).unwrap();
+        assert_eq!(header.pooling_type(), Some(GgufPooling::Cls));
+        let header = parse(model().u32("llama.pooling_type", 9).build()).unwrap();
+        assert_eq!(header.pooling_type(), None);
+        assert_eq!(GgufPooling::from_id(GgufPooling::Rank.id().into()), Some(GgufPooling::Rank));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(parse(b"PK\x03\x04rest".to_vec()), Err(GgufError::NotGguf)));
+        assert!(matches!(parse(b"GG".to_vec()), Err(GgufError::NotGguf)));
+
+        let mut v1 = model().build();
+        v1[4..8].copy_from_slice(&1u32.to_le_bytes());
+        assert!(matches!(parse(v1), Err(GgufError::UnsupportedVersion(1))));
+    }
+
+    #[test]
+    fn detects_truncated_files() {
+        let bytes = model().build();
+        let data_cut = bytes[..bytes.len() - 1].to_vec();
+        assert!(matches!(parse(data_cut), Err(GgufError::Truncated)));
+        let header_cut = bytes[..40].to_vec();
+        assert!(matches!(parse(header_cut), Err(GgufError::Truncated)));
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        let huge_string = Builder::default().key("k", 8, &u64::MAX.to_le_bytes()).build();
+        assert!(matches!(parse(huge_string), Err(GgufError::Malformed(_))));
+
+        let duplicate = model().u32("llama.block_count", 3).build();
+        assert!(matches!(parse(duplicate), Err(GgufError::Malformed(_))));
+
+        // Rows of 100 do not fill Q8_0 blocks of 32
+        let partial_block = Builder::default().tensor_at("t", &[100], GgmlType::Q8_0, 0);
+        assert!(matches!(parse(partial_block.build()), Err(GgufError::Malformed(_))));
+
+        let misaligned = Builder::default().tensor_at("t", &[8], GgmlType::F32, 4);
+        assert!(matches!(parse(misaligned.build()), Err(GgufError::Malformed(_))));
+    }
+
+    #[test]
+    fn rejects_oversized_tensors() {
+        // 2^63 F32 elements take 2^65 bytes
+        let shape = [1 << 32, 1 << 31];
+        let oversized = Builder::default().tensor_at("t", &shape, GgmlType::F32, 0);
+        assert!(matches!(parse(oversized.build()), Err(GgufError::Malformed(_))));
+        let info = GgufTensorInfo {
+            name: "t".into(),
+            shape: shape.to_vec(),
+            ggml_type: GgmlType::F32,
+            offset: 0,
+        };
+        assert_eq!(info.element_count(), 1 << 63);
+        assert_eq!(info.byte_size(), None);
+
+        // Each tensor fits, but the totals do not
+        let unknown = GgmlType(9999);
+        let parameters = Builder::default()
+            .tensor_at("a", &shape, unknown, 0)
+            .tensor_at("b", &shape, unknown, 0);
+        assert!(matches!(parse(parameters.build()), Err(GgufError::Malformed(_))));
+        let half = [1 << 30, 1 << 31];
+        let bytes = Builder::default()
+            .tensor_at("a", &half, GgmlType::F32, 0)
+            .tensor_at("b", &half, GgmlType::F32, 0);
+        assert!(matches!(parse(bytes.build()), Err(GgufError::Malformed(_))));
+
+        let header = GgufMetadata {
+            version: 3,
+            metadata: BTreeMap::new(),
+            tensors: vec![info.clone(), info.clone(), info],
+            data_offset: 0,
+            file_size: 0,
+        };
+        assert_eq!(header.parameter_count(), u64::MAX);
+        assert_eq!(header.tensor_bytes(), 0);
+    }
+
+    #[test]
+    fn displays_values() {
+        assert_eq!(GgufValue::String("x".into()).to_string(), "\"x\"");
+        let short = GgufValue::Array(vec![GgufValue::U32(1), GgufValue::U32(2)]);
+        assert_eq!(short.to_string(), "[1, 2]");
+        let long = GgufValue::Array(vec![GgufValue::F32(0.0); 100]);
+        assert_eq!(long.to_string(), "[f32; 100]");
+        assert_eq!(GgmlType(99).to_string(), "type99");
+    }
+}
@@ -1,14 +1,27 @@
 //! GGUF inference backend using llama-cpp-rs.
 //!
-//! Provides text generation models via llama.cpp bindings.
+//! Provides text generation, embedding and reranking models via llama.cpp
+//! bindings, and a deterministic n-gram model for tests that needs neither
+//! (`ngram`). Generation models can instead run on the native Rust backend
+//! (`GgufBackend::Native`).
 
 #[cfg(feature = "gguf")]
 pub mod backend;
+#[cfg(feature = "gguf")]
+mod batcher;
+mod embedder;
 mod generator;
+pub mod metadata;
+pub mod ngram;
+mod reranker;
 #[cfg(feature = "gguf")]
 pub mod speculative;
 
+pub use embedder::GgufEmbedder;
 pub use generator::GgufGenerator;
+pub use metadata::{GgmlType, GgufError, GgufMetadata, GgufPooling, GgufTensorInfo, GgufValue};
+pub use ngram::{NgramFixture, NgramModel};
+pub use reranker::GgufReranker;
 #[cfg(feature = "gguf")]
 pub use backend::LlamaBackendInner;
 #[cfg(feature = "gguf")]
@@ -17,26 +30,77 @@ pub use speculative::{GgufDraftModel, GgufTargetModel};
 use std::path::Path;
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+
+use crate::engine::native::NativeGenerator;
 use crate::engine::{InferenceCapability, InferenceConfig, InferenceError};
-use crate::engine::{InferenceInput, InferenceOutput};
+use crate::memory::{PromptCacheConfig, SessionKvConfig};
+use crate::engine::{InferenceInput, InferenceOutput, TokenStreamSender};
+
+/// Backend that runs a GGUF generation model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GgufBackend {
+    /// llama.cpp, with the `gguf` feature.
+    #[default]
+    LlamaCpp,
+    /// The native Rust forward pass ([`NativeGenerator`]): Llama and Qwen2
+    /// models on the CPU, in any build.
+    Native,
+}
 
 /// Configuration for GGUF model loading.
 #[derive(Debug, Clone)]
 pub struct GgufConfig {
+    /// Backend for generation models. Embedding and reranking models always
+    /// use llama.cpp.
+    pub backend: GgufBackend,
     /// Number of threads for inference (0 = auto).
     pub n_threads: u32,
     /// Context size for generation (max tokens in context window).
     pub n_ctx: u32,
     /// Number of layers to offload to GPU (0 = CPU only).
     pub n_gpu_layers: u32,
+    /// Chat template override: a built-in name ("llama3", "chatml", "phi3")
+    /// or a Jinja template. None = use the GGUF `tokenizer.chat_template`.
+    pub chat_template: Option<String>,
+    /// Budget for conversation KV snapshots kept between requests.
+    pub sessions: SessionKvConfig,
+    /// Budget for KV snapshots of shared prompt prefixes.
+    pub prompt_cache: PromptCacheConfig,
+    /// Sequences decoded together in the model's continuous batch (1 =
+    /// every request gets a context of its own). KV memory grows with
+    /// `batch_slots * n_ctx`, and batched requests reuse prompts only
+    /// through their slots, not the prompt cache.
+    pub batch_slots: usize,
+    /// Tokens a paired draft model proposes per verification step when
+    /// this model decodes speculatively.
+    pub draft_tokens: usize,
+    /// Open the model for embeddings rather than generation, pooling
+    /// token states as its `{arch}.pooling_type` says (mean when unset).
+    pub embedding: bool,
+    /// L2-normalize embeddings.
+    pub normalize: bool,
+    /// Open the model as a reranker, scoring query-document pairs with its
+    /// rank pooling head. Takes precedence over `embedding`.
+    pub rerank: bool,
 }
 
 impl Default for GgufConfig {
     fn default() -> Self {
         Self {
+            backend: GgufBackend::LlamaCpp,
             n_threads: 0,    // Auto-detect
             n_ctx: 2048,     // Default context
             n_gpu_layers: 0, // CPU only for sandbox
+            chat_template: None,
+            sessions: SessionKvConfig::default(),
+            prompt_cache: PromptCacheConfig::default(),
+            batch_slots: 1,
+            draft_tokens: 4,
+            embedding: false,
+            normalize: true,
+            rerank: false,
         }
     }
 }
@@ -54,17 +118,89 @@ pub trait GgufModel: Send + Sync {
         config: &InferenceConfig,
     ) -> Result<InferenceOutput, InferenceError>;
 
+    /// Run inference on each text, returning one output per text.
+    ///
+    /// The default runs texts one at a time; embedders override this to
+    /// decode them together.
+    async fn infer_batch(
+        &self,
+        texts: &[String],
+        config: &InferenceConfig,
+    ) -> Result<Vec<InferenceOutput>, InferenceError> {
+        let mut outputs = Vec::with_capacity(texts.len());
+        for text in texts {
+            outputs.push(self.infer(&InferenceInput::Text(text.clone()), config).await?);
+        }
+        Ok(outputs)
+    }
+
+    /// Relevance score of each document for `query`, in input order, for
+    /// at most `MAX_BATCH_SIZE` documents. Each query-document pair is
+    /// truncated to fit the model.
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<f32>, InferenceError> {
+        let _ = (query, documents);
+        Err(InferenceError::CapabilityNotSupported(
+            "model does not support reranking".into(),
+        ))
+    }
+
+    /// Stream a generation to `sender`, one output per token, speculating
+    /// with `draft` when it can serve as this model's draft. Blocks, so run
+    /// it on a blocking thread.
+    fn infer_stream(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+        draft: Option<&dyn GgufModel>,
+        sender: TokenStreamSender,
+    ) -> Result<(), InferenceError> {
+        let _ = (input, config, draft, sender);
+        Err(InferenceError::CapabilityNotSupported(
+            "model does not support streaming".into(),
+        ))
+    }
+
+    /// Load the LoRA adapter at `path` under `name`, replacing any adapter
+    /// of that name, so requests can select it without reloading the base
+    /// weights. Returns the bytes it occupies. Blocks, so run it on a
+    /// blocking thread.
+    fn load_adapter(&self, name: &str, path: &Path) -> Result<usize, InferenceError> {
+        let _ = (name, path);
+        Err(InferenceError::CapabilityNotSupported(
+            "model does not support LoRA adapters".into(),
+        ))
+    }
+
+    /// Release the adapter loaded under `name`; false if there is none.
+    /// Requests already using it finish with it.
+    fn unload_adapter(&self, name: &str) -> bool {
+        let _ = name;
+        false
+    }
+
+    fn has_adapter(&self, name: &str) -> bool {
+        let _ = name;
+        false
+    }
+
     async fn unload(&mut self) -> Result<(), InferenceError>;
 
     /// Downcast support for streaming access to concrete type.
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
-/// Load a GGUF model from a file path using llama-cpp-2.
+/// Load a GGUF model from a file path using llama-cpp-2, as a
+/// [`GgufReranker`] when `config.rerank` is set or a [`GgufEmbedder`] when
+/// `config.embedding` is. Otherwise, with `config.backend` set to
+/// [`GgufBackend::Native`], it loads as a [`NativeGenerator`]. N-gram test fixtures load as
+/// [`NgramModel`]. Both work with or without the `gguf` feature.
 ///
 /// # Errors
 /// Returns error if model file is missing, invalid, or fails to load.
-#[cfg(feature = "gguf")]
 pub fn load_gguf_model(
     path: &Path,
     model_id: &str,
@@ -75,6 +211,40 @@ pub fn load_gguf_model(
             format!("model file not found: {}", path.display()),
         ));
     }
+    let header = GgufMetadata::read(path).map_err(|e| {
+        InferenceError::ModelError(format!("invalid GGUF file {}: {}", path.display(), e))
+    })?;
+    if ngram::is_ngram(&header) {
+        let fixture = NgramFixture::from_header(&header)?;
+        let model = NgramModel::from_fixture(model_id.to_string(), &fixture, config)?;
+        return Ok(Arc::new(model));
+    }
+    if config.backend == GgufBackend::Native && !config.embedding && !config.rerank {
+        let generator = NativeGenerator::load(model_id.to_string(), path, &header, config)?;
+        return Ok(Arc::new(generator));
+    }
+    load_llama_model(path, model_id, config, &header)
+}
+
+#[cfg(feature = "gguf")]
+fn load_llama_model(
+    path: &Path,
+    model_id: &str,
+    config: &GgufConfig,
+    header: &GgufMetadata,
+) -> Result<Arc<dyn GgufModel>, InferenceError> {
+    if config.rerank {
+        let reranker = GgufReranker::load(
+            model_id.to_string(), path, config, header.pooling_type(),
+        )?;
+        return Ok(Arc::new(reranker));
+    }
+    if config.embedding {
+        let embedder = GgufEmbedder::load(
+            model_id.to_string(), path, config, header.pooling_type(),
+        )?;
+        return Ok(Arc::new(embedder));
+    }
     let generator = GgufGenerator::load(
         model_id.to_string(), path, config,
     )?;
@@ -83,25 +253,25 @@ pub fn load_gguf_model(
 
 /// Stub for non-gguf builds.
 #[cfg(not(feature = "gguf"))]
-pub fn load_gguf_model(
+fn load_llama_model(
     _path: &Path,
     _model_id: &str,
     _config: &GgufConfig,
+    _header: &GgufMetadata,
 ) -> Result<Arc<dyn GgufModel>, InferenceError> {
     Err(InferenceError::ModelError(
         "GGUF support not compiled in. Enable 'gguf' feature.".into(),
     ))
 }
 
-/// Validate that a file has the GGUF magic bytes.
+/// Validate that a file has the GGUF magic bytes. `GgufMetadata::read`
+/// checks the whole header.
 pub fn is_valid_gguf(path: &Path) -> Result<bool, std::io::Error> {
     use std::fs::File;
     use std::io::Read;
 
-    const GGUF_MAGIC: [u8; 4] = [0x47, 0x47, 0x55, 0x46]; // "GGUF"
-
     let mut file = File::open(path)?;
     let mut magic = [0u8; 4];
     file.read_exact(&mut magic)?;
-    Ok(magic == GGUF_MAGIC)
+    Ok(magic == metadata::GGUF_MAGIC)
 }
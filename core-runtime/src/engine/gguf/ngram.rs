@@ -0,0 +1,1186 @@
+//! Deterministic n-gram test backend.
+//!
+//! A byte-level n-gram model counted from a corpus stored in a tiny GGUF
+//! fixture with `general.architecture = "ngram"`. It is loaded by
+//! `load_gguf_model` like any GGUF file and serves generation, chat and
+//! streaming through the same engine paths as llama.cpp models, without
+//! the `gguf` feature or a real model file. Output depends only on the
+//! corpus, the prompt and the request's sampling parameters, and an
+//! optional per-token latency makes timeouts and cancellation testable.
+//!
+//! Tokens 0-255 are bytes and 256 ends generation. The next token is drawn
+//! from the counts that followed the longest context of up to `order - 1`
+//! tokens seen in the corpus; the corpus ends with the end token. Of the
+//! sampling parameters only temperature, top-k, top-p and seed apply.
+//!
+//! A fixture written with `adapter` set is a LoRA adapter for n-gram
+//! models instead. Its corpus is counted with the base model's order, and
+//! a request selecting it at scale `s` adds `s` times its counts to the
+//! base counts.
+//!
+//! Loaded with `GgufConfig::embedding`, the model embeds text instead. Each
+//! token's state is the next-token distribution after it, folded into
+//! `NGRAM_EMBEDDING_DIM` buckets, and states are pooled as the fixture's
+//! `pooling` says (mean when absent). With `GgufConfig::rerank` and a rank
+//! pooling fixture it scores query-document pairs instead, as the cosine of
+//! the two texts' mean-pooled states.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::metadata::{GgufMetadata, GgufPooling, GGUF_MAGIC};
+use super::GgufConfig;
+use crate::engine::context::{fit_tokens, trim_chat, truncate_pair};
+use crate::engine::onnx::l2_normalize;
+use crate::engine::{
+    ChatMessage, ChatTemplate, Completion, ContextStrategy, EmbeddingResult, FinishReason,
+    GenerationResult,
+    GenerationTimings, InferenceCapability, InferenceConfig, InferenceError, InferenceInput,
+    InferenceOutput, StopMatcher, StopSequence, StreamFinish, StreamingOutput, TokenCandidate,
+    TokenLogprob, TokenStreamSender, Utf8StreamDecoder,
+};
+
+/// `general.architecture` of n-gram fixtures.
+pub const NGRAM_ARCHITECTURE: &str = "ngram";
+
+/// End-of-generation token; lower ids are bytes.
+pub const NGRAM_EOS: u32 = 256;
+
+/// Dimensions of n-gram embeddings.
+pub const NGRAM_EMBEDDING_DIM: usize = 32;
+
+/// Longest context the model can condition on, plus one.
+const MAX_ORDER: u32 = 8;
+
+/// Next-token counts per context, most frequent first (lowest token on
+/// ties).
+type Counts = HashMap<Vec<u32>, Vec<(u32, u32)>>;
+
+/// Contents of an n-gram fixture file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NgramFixture {
+    pub name: String,
+    /// Text the model's counts are taken from.
+    pub corpus: String,
+    /// Tokens per n-gram, context included (1-8).
+    pub order: u32,
+    pub context_length: u32,
+    /// Delay before each generated token, in milliseconds.
+    pub token_latency_ms: u32,
+    /// Chat template name or Jinja template; ChatML when absent.
+    pub chat_template: Option<String>,
+    /// Written as a LoRA adapter for n-gram models rather than a model.
+    pub adapter: bool,
+    /// Pooling of embedding mode, written as `ngram.pooling_type`.
+    pub pooling: Option<GgufPooling>,
+}
+
+impl Default for NgramFixture {
+    fn default() -> Self {
+        Self {
+            name: "ngram".into(),
+            corpus: String::new(),
+            order: 3,
+            context_length: 2048,
+            token_latency_ms: 0,
+            chat_template: None,
+            adapter: false,
+            pooling: None,
+        }
+    }
+}
+
+impl NgramFixture {
+    /// Read a fixture from a parsed GGUF header.
+    pub fn from_header(header: &GgufMetadata) -> Result<Self, InferenceError> {
+        if header.architecture() != Some(NGRAM_ARCHITECTURE) {
+            return Err(InferenceError::ModelError("not an n-gram fixture".into()));
+        }
+        let defaults = Self::default();
+        let number = |key: &str, default: u32| {
+            header
+                .get(key)
+                .and_then(|v| v.as_u64())
+                .map_or(Ok(default), u32::try_from)
+                .map_err(|_| InferenceError::ModelError(format!("{key} is out of range")))
+        };
+        let fixture = Self {
+            name: header.name().map_or(defaults.name, str::to_string),
+            corpus: header.get("ngram.corpus").and_then(|v| v.as_str()).unwrap_or_default().into(),
+            order: number("ngram.order", defaults.order)?,
+            context_length: number("ngram.context_length", defaults.context_length)?,
+            token_latency_ms: number("ngram.token_latency_ms", defaults.token_latency_ms)?,
+            chat_template: header.chat_template().map(str::to_string),
+            adapter: header.adapter_type() == Some("lora"),
+            pooling: header.pooling_type(),
+        };
+        fixture.validate()?;
+        Ok(fixture)
+    }
+
+    fn validate(&self) -> Result<(), InferenceError> {
+        if self.corpus.is_empty() {
+            return Err(InferenceError::ModelError("ngram.corpus cannot be empty".into()));
+        }
+        if !(1..=MAX_ORDER).contains(&self.order) {
+            return Err(InferenceError::ModelError(format!(
+                "ngram.order must be between 1 and {MAX_ORDER}"
+            )));
+        }
+        if self.context_length < 2 {
+            return Err(InferenceError::ModelError(
+                "ngram.context_length must be at least 2".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Encode the fixture as a GGUF file with no tensors.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut keys = Vec::new();
+        let mut count = 0u64;
+        let mut string = |keys: &mut Vec<u8>, key: &str, value: &str| {
+            put_string(keys, key);
+            keys.extend_from_slice(&8u32.to_le_bytes());
+            put_string(keys, value);
+            count += 1;
+        };
+        string(&mut keys, "general.architecture", NGRAM_ARCHITECTURE);
+        string(&mut keys, "general.name", &self.name);
+        string(&mut keys, "tokenizer.ggml.model", "bytes");
+        string(&mut keys, "ngram.corpus", &self.corpus);
+        if let Some(template) = &self.chat_template {
+            string(&mut keys, "tokenizer.chat_template", template);
+        }
+        if self.adapter {
+            string(&mut keys, "general.type", "adapter");
+            string(&mut keys, "adapter.type", "lora");
+        }
+        let pooling = self.pooling.map(|p| ("ngram.pooling_type", p.id()));
+        for (key, value) in [
+            ("ngram.order", self.order),
+            ("ngram.context_length", self.context_length),
+            ("ngram.token_latency_ms", self.token_latency_ms),
+        ]
+        .into_iter()
+        .chain(pooling)
+        {
+            put_string(&mut keys, key);
+            keys.extend_from_slice(&4u32.to_le_bytes());
+            keys.extend_from_slice(&value.to_le_bytes());
+            count += 1;
+        }
+
+        let mut bytes = GGUF_MAGIC.to_vec();
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(&keys);
+        bytes
+    }
+
+    /// Write the fixture to `path`.
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u64).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Whether a parsed GGUF header is an n-gram fixture.
+pub fn is_ngram(header: &GgufMetadata) -> bool {
+    header.architecture() == Some(NGRAM_ARCHITECTURE)
+}
+
+/// Byte-level n-gram model implementing [`super::GgufModel`].
+pub struct NgramModel {
+    model_id: String,
+    order: usize,
+    n_ctx: usize,
+    latency: Duration,
+    chat_template: ChatTemplate,
+    /// Every context of length 0 to `order - 1` in the corpus.
+    counts: Counts,
+    memory_bytes: usize,
+    /// Counts of loaded LoRA adapters, by name.
+    adapters: parking_lot::RwLock<HashMap<String, Arc<Counts>>>,
+    /// Pooling when loaded as an embedding or reranking model.
+    pooling: Option<GgufPooling>,
+    normalize: bool,
+}
+
+impl NgramModel {
+    /// Count the fixture's corpus. `config.chat_template` overrides the
+    /// fixture's template, a nonzero `config.n_ctx` below the fixture's
+    /// context length shrinks it, and `config.rerank` or `config.embedding`
+    /// loads it as a reranking or embedding model.
+    pub fn from_fixture(
+        model_id: String,
+        fixture: &NgramFixture,
+        config: &GgufConfig,
+    ) -> Result<Self, InferenceError> {
+        fixture.validate()?;
+        if fixture.adapter {
+            return Err(InferenceError::ModelError(
+                "n-gram adapters cannot be loaded as models".into(),
+            ));
+        }
+        let chat_template = ChatTemplate::resolve(
+            config.chat_template.as_deref(),
+            fixture.chat_template.as_deref(),
+        );
+        if let ChatTemplate::Custom(_) = chat_template {
+            return Err(InferenceError::ModelError(
+                "n-gram models only render built-in chat templates".into(),
+            ));
+        }
+        let pooling = match (config.rerank, config.embedding, fixture.pooling) {
+            (true, _, Some(GgufPooling::Rank)) => Some(GgufPooling::Rank),
+            (true, _, _) => {
+                return Err(InferenceError::ModelError(
+                    "n-gram reranking needs a rank pooling fixture".into(),
+                ))
+            }
+            (false, false, _) => None,
+            (false, true, Some(GgufPooling::Rank)) => {
+                return Err(InferenceError::ModelError(
+                    "rank pooling models cannot embed".into(),
+                ))
+            }
+            (false, true, None | Some(GgufPooling::None)) => Some(GgufPooling::Mean),
+            (false, true, pooling) => pooling,
+        };
+        let order = fixture.order as usize;
+        let (counts, memory_bytes) = count(&fixture.corpus, order);
+
+        let n_ctx = match config.n_ctx {
+            0 => fixture.context_length,
+            n => n.min(fixture.context_length),
+        };
+        Ok(Self {
+            model_id,
+            order,
+            n_ctx: n_ctx as usize,
+            latency: Duration::from_millis(fixture.token_latency_ms.into()),
+            chat_template,
+            counts,
+            memory_bytes,
+            adapters: parking_lot::RwLock::new(HashMap::new()),
+            pooling,
+            normalize: config.normalize,
+        })
+    }
+
+    pub fn context_size(&self) -> usize {
+        self.n_ctx
+    }
+
+    /// One token per byte.
+    pub fn tokenize(&self, text: &str) -> Vec<u32> {
+        text.bytes().map(u32::from).collect()
+    }
+
+    /// Next-token counts after `history`, from its longest suffix seen in
+    /// the corpus. With an adapter, `scale` times its counts are added and
+    /// the longest suffix with any merged count is used.
+    fn next_counts<'a>(
+        &'a self,
+        history: &[u32],
+        adapter: Option<(&Counts, f32)>,
+    ) -> Cow<'a, [(u32, u32)]> {
+        let longest = history.len().min(self.order - 1);
+        let mut contexts = (0..=longest).rev().map(|len| &history[history.len() - len..]);
+        let Some((adapter, scale)) = adapter else {
+            let counts = contexts.find_map(|context| self.counts.get(context));
+            return Cow::Borrowed(counts.map_or(&[], Vec::as_slice));
+        };
+        for context in contexts {
+            let mut merged: HashMap<u32, u32> = HashMap::new();
+            for &(tok, count) in self.counts.get(context).into_iter().flatten() {
+                *merged.entry(tok).or_insert(0) += count;
+            }
+            for &(tok, count) in adapter.get(context).into_iter().flatten() {
+                *merged.entry(tok).or_insert(0) += (count as f32 * scale).round() as u32;
+            }
+            merged.retain(|_, count| *count > 0);
+            if !merged.is_empty() {
+                return Cow::Owned(ranked(merged));
+            }
+        }
+        Cow::Borrowed(&[])
+    }
+
+    /// Embed `text`, truncated to the context window, by pooling the
+    /// states of its tokens.
+    pub fn embed(&self, text: &str, pooling: GgufPooling) -> EmbeddingResult {
+        let mut tokens = self.tokenize(text);
+        tokens.truncate(self.n_ctx);
+        let mut vector = self.pool(&tokens, pooling);
+        if self.normalize {
+            l2_normalize(&mut vector);
+        }
+        EmbeddingResult { dimensions: vector.len(), vector }
+    }
+
+    /// Relevance of `document` to `query`: the cosine of their mean-pooled
+    /// states, with the longer text cut first when the pair overflows the
+    /// context window.
+    pub fn score(&self, query: &str, document: &str) -> f32 {
+        let (mut query, mut document) = (self.tokenize(query), self.tokenize(document));
+        truncate_pair(&mut query, &mut document, self.n_ctx);
+        let mut query = self.pool(&query, GgufPooling::Mean);
+        let mut document = self.pool(&document, GgufPooling::Mean);
+        l2_normalize(&mut query);
+        l2_normalize(&mut document);
+        query.iter().zip(&document).map(|(q, d)| q * d).sum()
+    }
+
+    /// Pool the states of `tokens` into one unnormalized vector.
+    fn pool(&self, tokens: &[u32], pooling: GgufPooling) -> Vec<f32> {
+        let state = |end: usize| {
+            let counts = self.next_counts(&tokens[..end], None);
+            let total: u32 = counts.iter().map(|&(_, c)| c).sum();
+            let mut state = [0.0f32; NGRAM_EMBEDDING_DIM];
+            for &(tok, count) in counts.iter() {
+                state[tok as usize % NGRAM_EMBEDDING_DIM] += count as f32 / total.max(1) as f32;
+            }
+            state
+        };
+        let states: Vec<_> = match pooling {
+            GgufPooling::Cls => vec![state(1.min(tokens.len()))],
+            GgufPooling::Last => vec![state(tokens.len())],
+            _ => (1..=tokens.len()).map(state).collect(),
+        };
+        let mut vector = vec![0.0f32; NGRAM_EMBEDDING_DIM];
+        for state in &states {
+            for (v, s) in vector.iter_mut().zip(state) {
+                *v += s / states.len() as f32;
+            }
+        }
+        vector
+    }
+
+    /// Counts of the adapter the request selects, if any.
+    fn request_adapter(
+        &self,
+        config: &InferenceConfig,
+    ) -> Result<Option<Arc<Counts>>, InferenceError> {
+        let Some(selection) = &config.adapter else {
+            return Ok(None);
+        };
+        match self.adapters.read().get(&selection.name) {
+            Some(counts) => Ok(Some(counts.clone())),
+            None => Err(InferenceError::InputValidation(format!(
+                "adapter '{}' is not loaded",
+                selection.name
+            ))),
+        }
+    }
+
+    /// Render chat messages with the model's template. Under
+    /// `ContextStrategy::TrimMiddle` the oldest turns are dropped until the
+    /// prompt leaves room for `max_tokens`.
+    pub fn chat_prompt(
+        &self,
+        messages: &[ChatMessage],
+        config: &InferenceConfig,
+    ) -> Result<String, InferenceError> {
+        if config.context_strategy == ContextStrategy::TrimMiddle {
+            let budget = config.completion_budget();
+            let kept = trim_chat(messages, budget, self.n_ctx, |kept| {
+                Ok(self.chat_template.render(kept)?.len())
+            })?;
+            return self.chat_template.render(&kept);
+        }
+        self.chat_template.render(messages)
+    }
+
+    fn prompt(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+    ) -> Result<String, InferenceError> {
+        let prompt = match input {
+            InferenceInput::Text(prompt) => prompt.clone(),
+            InferenceInput::ChatMessages(messages) => self.chat_prompt(messages, config)?,
+            InferenceInput::TextBatch(_) => {
+                return Err(InferenceError::CapabilityNotSupported(
+                    "batch generation not supported".into(),
+                ))
+            }
+        };
+        if prompt.is_empty() {
+            return Err(InferenceError::InputValidation("prompt cannot be empty".into()));
+        }
+        Ok(prompt)
+    }
+
+    /// Tokenize and fit the prompt; returns the tokens and the tokens each
+    /// sequence may generate.
+    fn start(
+        &self,
+        prompt: &str,
+        config: &InferenceConfig,
+    ) -> Result<(Vec<u32>, u32), InferenceError> {
+        if config.constraint.is_some() {
+            return Err(InferenceError::CapabilityNotSupported(
+                "n-gram models do not support constrained output".into(),
+            ));
+        }
+        let mut tokens = self.tokenize(prompt);
+        let budget = config.completion_budget();
+        let fit = fit_tokens(tokens.len(), budget, self.n_ctx, config.context_strategy)?;
+        tokens.drain(..fit.drop_tokens);
+        let sequences = u32::try_from(config.best_of.max(1)).unwrap_or(u32::MAX);
+        Ok((tokens, fit.max_tokens / sequences))
+    }
+
+    /// Generate a completion, or `best_of` completions ranked by cumulative
+    /// log-probability, advancing every sequence one token per step.
+    pub async fn generate(
+        &self,
+        prompt: &str,
+        config: &InferenceConfig,
+    ) -> Result<GenerationResult, InferenceError> {
+        let started = Instant::now();
+        let (tokens, max_tok) = self.start(prompt, config)?;
+        let adapter = self.request_adapter(config)?;
+        let mut seqs: Vec<Sequence> = (0..config.best_of.max(1))
+            .map(|i| Sequence::new(&tokens, config.seed.wrapping_add(i as u32), &config.stop))
+            .collect();
+        let prefill = started.elapsed();
+        let mut first_token = None;
+        for _ in 0..max_tok {
+            if seqs.iter().all(|s| s.finish.is_some()) {
+                break;
+            }
+            if !self.latency.is_zero() {
+                tokio::time::sleep(self.latency).await;
+            }
+            let abort = config.abort_reason(started);
+            for seq in seqs.iter_mut().filter(|s| s.finish.is_none()) {
+                if let Some(reason) = abort {
+                    seq.end(reason);
+                    continue;
+                }
+                first_token.get_or_insert_with(|| started.elapsed());
+                self.step(seq, config, max_tok, adapter.as_deref());
+            }
+        }
+        for seq in seqs.iter_mut().filter(|s| s.finish.is_none()) {
+            seq.end(FinishReason::MaxTokens);
+        }
+
+        let tokens_generated = seqs.iter().map(|s| s.generated).sum();
+        let mut choices: Vec<_> = seqs.into_iter().map(Sequence::into_completion).collect();
+        if config.best_of > config.n {
+            choices.sort_by(|a, b| b.cumulative_logprob.total_cmp(&a.cumulative_logprob));
+            choices.truncate(config.n);
+        }
+        let best = choices[0].clone();
+        Ok(GenerationResult {
+            text: best.text,
+            tokens_generated,
+            finish_reason: best.finish_reason,
+            prompt_tokens: u32::try_from(tokens.len()).unwrap_or(u32::MAX),
+            timings: GenerationTimings {
+                prefill,
+                first_token: first_token.unwrap_or(prefill),
+                decode: started.elapsed().saturating_sub(prefill),
+            },
+            stop_sequence: best.stop_sequence,
+            logprobs: best.logprobs,
+            choices: if config.n > 1 { choices } else { Vec::new() },
+        })
+    }
+
+    /// Stream a completion to `sender`, one output per token; the final one
+    /// carries the usage summary. Blocks, so run it on a blocking thread.
+    pub fn generate_stream(
+        &self,
+        prompt: &str,
+        config: &InferenceConfig,
+        sender: TokenStreamSender,
+    ) -> Result<(), InferenceError> {
+        if config.best_of > 1 {
+            return Err(InferenceError::InputValidation(
+                "best_of above 1 cannot be streamed".into(),
+            ));
+        }
+        let started = Instant::now();
+        let (tokens, max_tok) = self.start(prompt, config)?;
+        let adapter = self.request_adapter(config)?;
+        let mut seq = Sequence::new(&tokens, config.seed, &config.stop);
+        let prefill = started.elapsed();
+        let rt = tokio::runtime::Handle::current();
+        let mut first_token = None;
+        while seq.finish.is_none() {
+            std::thread::sleep(self.latency);
+            let mark = seq.mark();
+            let tok = match config.abort_reason(started) {
+                Some(reason) => {
+                    // Nothing more is sampled; the end token closes the stream
+                    seq.end(reason);
+                    NGRAM_EOS
+                }
+                None => {
+                    first_token.get_or_insert_with(|| started.elapsed());
+                    self.step(&mut seq, config, max_tok, adapter.as_deref())
+                }
+            };
+            let timings = GenerationTimings {
+                prefill,
+                first_token: first_token.unwrap_or(prefill),
+                decode: started.elapsed().saturating_sub(prefill),
+            };
+            let output = seq.output(tok, mark, tokens.len(), timings);
+            if rt.block_on(sender.send_output(output)).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sample and apply the next token of `seq`, ending it at the end
+    /// token, a stop sequence or `max_tok` tokens. Returns the token.
+    fn step(
+        &self,
+        seq: &mut Sequence,
+        config: &InferenceConfig,
+        max_tok: u32,
+        adapter: Option<&Counts>,
+    ) -> u32 {
+        let scale = config.adapter.as_ref().map_or(1.0, |a| a.scale);
+        let counts = self.next_counts(&seq.history, adapter.map(|a| (a, scale)));
+        let tok = seq.sample(&counts, config);
+        seq.apply(tok, &counts, config);
+        if seq.finish.is_none() && seq.sampled >= max_tok {
+            seq.end(FinishReason::MaxTokens);
+        }
+        tok
+    }
+}
+
+/// One sequence being generated.
+struct Sequence {
+    /// Prompt and generated tokens.
+    history: Vec<u32>,
+    rng: SplitMix64,
+    text: String,
+    utf8: Utf8StreamDecoder,
+    stops: StopMatcher,
+    sampled: u32,
+    generated: u32,
+    logprobs: Vec<TokenLogprob>,
+    cumulative_logprob: f32,
+    stop_sequence: Option<StopSequence>,
+    finish: Option<FinishReason>,
+}
+
+impl Sequence {
+    fn new(prompt: &[u32], seed: u32, stop: &[StopSequence]) -> Self {
+        Self {
+            history: prompt.to_vec(),
+            rng: SplitMix64(seed.into()),
+            text: String::new(),
+            utf8: Utf8StreamDecoder::new(),
+            stops: StopMatcher::new(stop),
+            sampled: 0,
+            generated: 0,
+            logprobs: Vec::new(),
+            cumulative_logprob: 0.0,
+            stop_sequence: None,
+            finish: None,
+        }
+    }
+
+    /// Draw a token from `counts`: the most frequent at temperature 0,
+    /// otherwise from the top-k/top-p candidates with probabilities raised
+    /// to `1 / temperature`.
+    fn sample(&mut self, counts: &[(u32, u32)], config: &InferenceConfig) -> u32 {
+        let Some(&(most_likely, _)) = counts.first() else {
+            return NGRAM_EOS;
+        };
+        if config.temperature <= 0.0 {
+            return most_likely;
+        }
+        let total: f64 = counts.iter().map(|&(_, c)| f64::from(c)).sum();
+        let top_k = match config.top_k {
+            0 => counts.len(),
+            k => counts.len().min(k as usize),
+        };
+        let mut mass = 0.0;
+        let mut kept = 0;
+        for &(_, count) in &counts[..top_k] {
+            kept += 1;
+            mass += f64::from(count) / total;
+            if mass >= f64::from(config.top_p) {
+                break;
+            }
+        }
+        let weights: Vec<f64> = counts[..kept]
+            .iter()
+            .map(|&(_, c)| (f64::from(c) / total).powf(1.0 / f64::from(config.temperature)))
+            .collect();
+        let mut target = self.rng.next_f64() * weights.iter().sum::<f64>();
+        for (&(tok, _), weight) in counts.iter().zip(&weights) {
+            if target < *weight {
+                return tok;
+            }
+            target -= weight;
+        }
+        counts[kept - 1].0
+    }
+
+    /// Record a sampled token, which ends the sequence if it is the end
+    /// token or a stop sequence completes.
+    fn apply(&mut self, tok: u32, counts: &[(u32, u32)], config: &InferenceConfig) {
+        self.sampled += 1;
+        self.history.push(tok);
+        let logprob = (config.logprobs.is_some() || config.best_of > 1)
+            .then(|| token_logprob(counts, tok, config.logprobs.unwrap_or(0)));
+        self.cumulative_logprob += logprob.as_ref().map_or(0.0, |l| l.logprob);
+        if tok == NGRAM_EOS {
+            self.end(FinishReason::Stop);
+            return;
+        }
+        if let Some(stop) = self.stops.stop_token(tok) {
+            self.stop_sequence = Some(stop);
+            self.end(FinishReason::Stop);
+            return;
+        }
+        self.generated += 1;
+        if config.logprobs.is_some() {
+            self.logprobs.extend(logprob);
+        }
+        let (piece, stop) = self.stops.push(&self.utf8.push(&[tok as u8]));
+        self.text.push_str(&piece);
+        if stop.is_some() {
+            self.stop_sequence = stop;
+            self.end(FinishReason::Stop);
+        }
+    }
+
+    /// End the sequence, releasing held-back text unless a stop string
+    /// ended it.
+    fn end(&mut self, reason: FinishReason) {
+        self.finish = Some(reason);
+        if !matches!(self.stop_sequence, Some(StopSequence::Text(_))) {
+            self.text.push_str(&self.stops.flush());
+            self.text.push_str(&self.utf8.finish());
+        }
+    }
+
+    /// Text and log-probability counts, to tell what the next token adds.
+    fn mark(&self) -> (usize, usize) {
+        (self.text.len(), self.logprobs.len())
+    }
+
+    fn output(
+        &self,
+        tok: u32,
+        (text_len, logprobs_len): (usize, usize),
+        prompt_tokens: usize,
+        timings: GenerationTimings,
+    ) -> StreamingOutput {
+        let finish = self.finish.map(|finish_reason| StreamFinish {
+            prompt_tokens: u32::try_from(prompt_tokens).unwrap_or(u32::MAX),
+            completion_tokens: self.generated,
+            finish_reason,
+            timings,
+            stop_sequence: self.stop_sequence.clone(),
+        });
+        StreamingOutput {
+            token: tok,
+            text: self.text[text_len..].to_string(),
+            is_final: finish.is_some(),
+            logprob: self.logprobs.get(logprobs_len).cloned(),
+            finish,
+        }
+    }
+
+    fn into_completion(self) -> Completion {
+        Completion {
+            text: self.text,
+            tokens_generated: self.generated,
+            finish_reason: self.finish.unwrap_or(FinishReason::MaxTokens),
+            stop_sequence: self.stop_sequence,
+            logprobs: self.logprobs,
+            cumulative_logprob: self.cumulative_logprob,
+        }
+    }
+}
+
+/// Next-token counts of every context of 0 to `order - 1` tokens in
+/// `corpus`, which is followed by the end token, and their size in bytes.
+fn count(corpus: &str, order: usize) -> (Counts, usize) {
+    let mut tokens: Vec<u32> = corpus.bytes().map(u32::from).collect();
+    tokens.push(NGRAM_EOS);
+
+    let mut tallies: HashMap<Vec<u32>, HashMap<u32, u32>> = HashMap::new();
+    for (i, &next) in tokens.iter().enumerate() {
+        for len in 0..order.min(i + 1) {
+            let context = tokens[i - len..i].to_vec();
+            *tallies.entry(context).or_default().entry(next).or_insert(0) += 1;
+        }
+    }
+    let mut memory_bytes = 0;
+    let counts = tallies
+        .into_iter()
+        .map(|(context, next)| {
+            let next = ranked(next);
+            memory_bytes += (context.len() + 2 * next.len()) * std::mem::size_of::<u32>();
+            (context, next)
+        })
+        .collect();
+    (counts, memory_bytes)
+}
+
+/// Tallies most frequent first, lowest token on ties.
+fn ranked(tallies: HashMap<u32, u32>) -> Vec<(u32, u32)> {
+    let mut next: Vec<(u32, u32)> = tallies.into_iter().collect();
+    next.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    next
+}
+
+/// Log-probability of `tok` and the `top_n` most frequent tokens under
+/// `counts`.
+fn token_logprob(counts: &[(u32, u32)], tok: u32, top_n: usize) -> TokenLogprob {
+    let total: f64 = counts.iter().map(|&(_, c)| f64::from(c)).sum();
+    let logprob = |count: u32| (f64::from(count) / total).ln() as f32;
+    let count = counts.iter().find(|&&(t, _)| t == tok).map_or(0, |&(_, c)| c);
+    TokenLogprob {
+        token: tok,
+        text: Some(token_text(tok)),
+        logprob: logprob(count),
+        top: counts
+            .iter()
+            .take(top_n)
+            .map(|&(token, count)| TokenCandidate {
+                token,
+                text: Some(token_text(token)),
+                logprob: logprob(count),
+            })
+            .collect(),
+    }
+}
+
+fn token_text(tok: u32) -> String {
+    match u8::try_from(tok) {
+        Ok(byte) => String::from_utf8_lossy(&[byte]).into_owned(),
+        Err(_) => String::new(),
+    }
+}
+
+/// SplitMix64, so sampled output does not change with dependency versions.
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[async_trait::async_trait]
+impl super::GgufModel for NgramModel {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn capabilities(&self) -> &[InferenceCapability] {
+        match self.pooling {
+            Some(GgufPooling::Rank) => &[InferenceCapability::Rerank],
+            Some(_) => &[InferenceCapability::Embedding],
+            None => &[InferenceCapability::TextGeneration],
+        }
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.memory_bytes
+    }
+
+    async fn infer(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+    ) -> Result<InferenceOutput, InferenceError> {
+        input.validate()?;
+        config.validate()?;
+        if let Some(GgufPooling::Rank) = self.pooling {
+            return Err(InferenceError::CapabilityNotSupported(
+                "reranking models only score query-document pairs".into(),
+            ));
+        }
+        if let Some(pooling) = self.pooling {
+            let text = match input {
+                InferenceInput::Text(text) => text,
+                // Single-output API: embed the first item
+                InferenceInput::TextBatch(batch) => &batch[0],
+                InferenceInput::ChatMessages(_) => {
+                    return Err(InferenceError::CapabilityNotSupported(
+                        "chat messages not supported for embedding".into(),
+                    ))
+                }
+            };
+            return Ok(InferenceOutput::Embedding(self.embed(text, pooling)));
+        }
+        let prompt = self.prompt(input, config)?;
+        Ok(InferenceOutput::Generation(self.generate(&prompt, config).await?))
+    }
+
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<f32>, InferenceError> {
+        if self.pooling != Some(GgufPooling::Rank) {
+            return Err(InferenceError::CapabilityNotSupported(
+                "model does not support reranking".into(),
+            ));
+        }
+        InferenceInput::Text(query.to_string()).validate()?;
+        InferenceInput::TextBatch(documents.to_vec()).validate()?;
+        Ok(documents.iter().map(|document| self.score(query, document)).collect())
+    }
+
+    fn infer_stream(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+        _draft: Option<&dyn super::GgufModel>,
+        sender: TokenStreamSender,
+    ) -> Result<(), InferenceError> {
+        config.validate()?;
+        let prompt = self.prompt(input, config)?;
+        self.generate_stream(&prompt, config, sender)
+    }
+
+    /// Load an n-gram adapter fixture, counted with this model's order.
+    fn load_adapter(&self, name: &str, path: &Path) -> Result<usize, InferenceError> {
+        let header = GgufMetadata::read(path)
+            .map_err(|e| InferenceError::ModelError(format!("adapter {name}: {e}")))?;
+        if !is_ngram(&header) || header.adapter_type() != Some("lora") {
+            return Err(InferenceError::ModelError(format!(
+                "adapter {name}: not an n-gram LoRA adapter"
+            )));
+        }
+        let fixture = NgramFixture::from_header(&header)?;
+        let (counts, memory_bytes) = count(&fixture.corpus, self.order);
+        self.adapters.write().insert(name.to_string(), Arc::new(counts));
+        Ok(memory_bytes)
+    }
+
+    fn unload_adapter(&self, name: &str) -> bool {
+        self.adapters.write().remove(name).is_some()
+    }
+
+    fn has_adapter(&self, name: &str) -> bool {
+        self.adapters.read().contains_key(name)
+    }
+
+    async fn unload(&mut self) -> Result<(), InferenceError> {
+        self.adapters.write().clear();
+        self.counts.clear();
+        self.memory_bytes = 0;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TokenStream;
+
+    const CORPUS: &str = "the cat sat on the mat. the cat ate the rat!";
+
+    fn load(fixture: NgramFixture) -> NgramModel {
+        NgramModel::from_fixture("ngram".into(), &fixture, &GgufConfig::default()).unwrap()
+    }
+
+    fn model(order: u32) -> NgramModel {
+        load(NgramFixture { corpus: CORPUS.into(), order, ..Default::default() })
+    }
+
+    fn greedy(max_tokens: u32) -> InferenceConfig {
+        InferenceConfig { temperature: 0.0, max_tokens: Some(max_tokens), ..Default::default() }
+    }
+
+    #[test]
+    fn fixture_round_trips_through_gguf() {
+        let fixture = NgramFixture {
+            name: "tiny".into(),
+            corpus: CORPUS.into(),
+            order: 4,
+            context_length: 512,
+            token_latency_ms: 5,
+            chat_template: Some("llama3".into()),
+            adapter: false,
+            pooling: Some(GgufPooling::Last),
+        };
+        let bytes = fixture.to_bytes();
+        let header = GgufMetadata::from_reader(std::io::Cursor::new(bytes)).unwrap();
+        assert!(is_ngram(&header));
+        assert_eq!(NgramFixture::from_header(&header).unwrap(), fixture);
+    }
+
+    #[test]
+    fn rejects_invalid_fixtures() {
+        let config = GgufConfig::default();
+        let empty = NgramFixture::default();
+        assert!(NgramModel::from_fixture("m".into(), &empty, &config).is_err());
+        let order = NgramFixture { corpus: "x".into(), order: 9, ..Default::default() };
+        assert!(NgramModel::from_fixture("m".into(), &order, &config).is_err());
+        let custom = NgramFixture {
+            corpus: "x".into(),
+            chat_template: Some("{{ messages }}".into()),
+            ..Default::default()
+        };
+        assert!(NgramModel::from_fixture("m".into(), &custom, &config).is_err());
+    }
+
+    #[tokio::test]
+    async fn greedy_output_follows_the_corpus() {
+        // "at " was followed once by "s" and once by "a"; ties go to the
+        // lower token
+        let result = model(4).generate("the c", &greedy(6)).await.unwrap();
+        assert_eq!(result.text, "at ate");
+        assert_eq!(result.tokens_generated, 6);
+        assert_eq!(result.finish_reason, FinishReason::MaxTokens);
+        assert_eq!(result.prompt_tokens, 5);
+
+        // The corpus ends with the end token
+        let result = model(4).generate("the r", &greedy(50)).await.unwrap();
+        assert_eq!(result.text, "at!");
+        assert_eq!(result.finish_reason, FinishReason::Stop);
+    }
+
+    #[tokio::test]
+    async fn sampling_is_deterministic_per_seed() {
+        let config = InferenceConfig {
+            temperature: 1.0,
+            top_p: 1.0,
+            top_k: 0,
+            max_tokens: Some(40),
+            ..Default::default()
+        };
+        let model = model(2);
+        let first = model.generate("the", &config).await.unwrap();
+        let again = model.generate("the", &config).await.unwrap();
+        assert_eq!(first.text, again.text);
+
+        let texts: std::collections::HashSet<String> = futures::future::join_all(
+            (0..8).map(|seed| {
+                let config = InferenceConfig { seed, ..config.clone() };
+                let model = &model;
+                async move { model.generate("the", &config).await.unwrap().text }
+            }),
+        )
+        .await
+        .into_iter()
+        .collect();
+        assert!(texts.len() > 1);
+    }
+
+    #[tokio::test]
+    async fn stop_sequences_and_logprobs() {
+        let config = InferenceConfig {
+            stop: vec![StopSequence::Text(" the".into())],
+            logprobs: Some(2),
+            ..greedy(20)
+        };
+        let result = model(4).generate("the c", &config).await.unwrap();
+        assert_eq!(result.text, "at ate");
+        assert_eq!(result.stop_sequence, Some(StopSequence::Text(" the".into())));
+        assert_eq!(result.logprobs.len(), result.tokens_generated as usize);
+        // "the c" was followed by "a" both times
+        assert_eq!(result.logprobs[0].logprob, 0.0);
+        assert_eq!(result.logprobs[0].top.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn best_of_returns_ranked_choices() {
+        let config = InferenceConfig {
+            temperature: 1.0,
+            n: 2,
+            best_of: 3,
+            max_tokens: Some(30),
+            ..Default::default()
+        };
+        let result = model(2).generate("the", &config).await.unwrap();
+        assert_eq!(result.choices.len(), 2);
+        assert!(result.choices[0].cumulative_logprob >= result.choices[1].cumulative_logprob);
+        assert_eq!(result.text, result.choices[0].text);
+    }
+
+    #[tokio::test]
+    async fn context_window_is_enforced() {
+        let model = load(NgramFixture {
+            corpus: CORPUS.into(),
+            context_length: 8,
+            ..Default::default()
+        });
+        let err = model.generate("the cat", &greedy(4)).await.unwrap_err();
+        assert!(matches!(err, InferenceError::ContextExceeded { .. }));
+
+        let clamp = InferenceConfig {
+            context_strategy: ContextStrategy::ClampMaxTokens,
+            ..greedy(4)
+        };
+        let result = model.generate("the cat", &clamp).await.unwrap();
+        assert_eq!(result.tokens_generated, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_matches_generate() {
+        let model = std::sync::Arc::new(model(4));
+        let expected = model.generate("the c", &greedy(8)).await.unwrap();
+
+        let (sender, mut stream) = TokenStream::new(4);
+        let streaming = std::sync::Arc::clone(&model);
+        let task = tokio::task::spawn_blocking(move || {
+            streaming.generate_stream("the c", &greedy(8), sender)
+        });
+        let mut text = String::new();
+        let mut last = None;
+        while let Some(output) = stream.next().await {
+            text.push_str(&output.text);
+            if output.is_final {
+                last = output.finish;
+            }
+        }
+        task.await.unwrap().unwrap();
+        assert_eq!(text, expected.text);
+        let finish = last.unwrap();
+        assert_eq!(finish.completion_tokens, 8);
+        assert_eq!(finish.finish_reason, FinishReason::MaxTokens);
+    }
+
+    #[tokio::test]
+    async fn adapters_add_scaled_counts() {
+        use super::super::GgufModel;
+        use crate::engine::AdapterSelection;
+
+        let path = std::env::temp_dir().join("gg_core_ngram_adapter.gguf");
+        let adapter =
+            NgramFixture { corpus: "the cow".into(), adapter: true, ..Default::default() };
+        adapter.write(&path).unwrap();
+        let header = GgufMetadata::read(&path).unwrap();
+        assert_eq!(header.adapter_type(), Some("lora"));
+        let err = NgramModel::from_fixture("m".into(), &adapter, &GgufConfig::default());
+        assert!(err.is_err());
+
+        let model = model(3);
+        assert!(model.load_adapter("cow", &path).unwrap() > 0);
+        assert!(model.has_adapter("cow"));
+        let with = |scale| InferenceConfig {
+            adapter: Some(AdapterSelection { name: "cow".into(), scale }),
+            ..greedy(3)
+        };
+        // " c" was followed by "a" twice in the base and "o" once in the
+        // adapter
+        let result = model.generate("the", &with(3.0)).await.unwrap();
+        assert_eq!(result.text, " co");
+        let base = model.generate("the", &greedy(3)).await.unwrap();
+        let scaled_out = model.generate("the", &with(0.0)).await.unwrap();
+        assert_eq!(scaled_out.text, base.text);
+
+        assert!(model.unload_adapter("cow"));
+        let err = model.generate("the", &with(1.0)).await.unwrap_err();
+        assert!(matches!(err, InferenceError::InputValidation(_)));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn embedding_mode_pools_token_states() {
+        use super::super::GgufModel;
+
+        let config = GgufConfig { embedding: true, ..Default::default() };
+        let embedder = |pooling| {
+            let fixture = NgramFixture { corpus: CORPUS.into(), pooling, ..Default::default() };
+            NgramModel::from_fixture("e".into(), &fixture, &config)
+        };
+        let mean = embedder(None).unwrap();
+        assert_eq!(mean.capabilities(), &[InferenceCapability::Embedding]);
+        let input = InferenceInput::TextBatch(vec!["the cat".into(), "a cat".into()]);
+        let output = mean.infer(&input, &InferenceConfig::for_embedding()).await.unwrap();
+        let InferenceOutput::Embedding(the_cat) = output else { panic!("{output:?}") };
+        assert_eq!(the_cat.dimensions, NGRAM_EMBEDDING_DIM);
+        let norm: f32 = the_cat.vector.iter().map(|v| v * v).sum();
+        assert!((norm - 1.0).abs() < 1e-5);
+
+        // Both texts end in "at", so only their last states agree
+        assert_ne!(mean.embed("a cat", GgufPooling::Mean).vector, the_cat.vector);
+        let last = embedder(Some(GgufPooling::Last)).unwrap();
+        let output = last.infer(&input, &InferenceConfig::for_embedding()).await.unwrap();
+        let InferenceOutput::Embedding(the_cat) = output else { panic!("{output:?}") };
+        assert_eq!(last.embed("a cat", GgufPooling::Last).vector, the_cat.vector);
+
+        assert!(embedder(Some(GgufPooling::Rank)).is_err());
+        let messages = InferenceInput::ChatMessages(vec![ChatMessage {
+            role: crate::engine::ChatRole::User,
+            content: "the".into(),
+        }]);
+        let err = mean.infer(&messages, &InferenceConfig::for_embedding()).await.unwrap_err();
+        assert!(matches!(err, InferenceError::CapabilityNotSupported(_)));
+    }
+
+    #[tokio::test]
+    async fn rerank_mode_scores_query_document_pairs() {
+        use super::super::GgufModel;
+
+        let fixture = NgramFixture {
+            corpus: CORPUS.into(),
+            pooling: Some(GgufPooling::Rank),
+            ..Default::default()
+        };
+        let config = GgufConfig { rerank: true, ..Default::default() };
+        let model = NgramModel::from_fixture("r".into(), &fixture, &config).unwrap();
+        assert_eq!(model.capabilities(), &[InferenceCapability::Rerank]);
+
+        let documents = vec!["the cat".to_string(), "!!!".to_string()];
+        let scores = model.rerank("the cat", &documents).await.unwrap();
+        assert!((scores[0] - 1.0).abs() < 1e-5);
+        assert!(scores[1] < scores[0]);
+        let input = InferenceInput::Text("the".into());
+        let err = model.infer(&input, &InferenceConfig::default()).await.unwrap_err();
+        assert!(matches!(err, InferenceError::CapabilityNotSupported(_)));
+
+        let plain = NgramFixture { pooling: None, ..fixture };
+        assert!(NgramModel::from_fixture("r".into(), &plain, &config).is_err());
+        let chat = NgramModel::from_fixture("c".into(), &plain, &GgufConfig::default()).unwrap();
+        let err = chat.rerank("the", &documents).await.unwrap_err();
+        assert!(matches!(err, InferenceError::CapabilityNotSupported(_)));
+    }
+
+    #[tokio::test]
+    async fn latency_makes_timeouts_observable() {
+        let model = load(NgramFixture {
+            corpus: CORPUS.into(),
+            token_latency_ms: 20,
+            ..Default::default()
+        });
+        let config = InferenceConfig { timeout_ms: 50, ..greedy(100) };
+        let result = model.generate("the", &config).await.unwrap();
+        assert_eq!(result.finish_reason, FinishReason::Timeout);
+        assert!(result.tokens_generated < 10);
+    }
+}
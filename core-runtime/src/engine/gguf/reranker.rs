@@ -0,0 +1,122 @@
+//! GGUF-based reranking model.
+//!
+//! Opens a llama-cpp-2 cross-encoder (bge-reranker and similar) whose
+//! `{arch}.pooling_type` is rank, and scores query-document pairs with its
+//! classification head.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "gguf")]
+use std::sync::Arc;
+
+#[cfg(feature = "gguf")]
+use super::GgufPooling;
+use crate::engine::{
+    InferenceCapability, InferenceConfig, InferenceError, InferenceInput, InferenceOutput,
+};
+
+/// GGUF reranking model using llama-cpp-2.
+pub struct GgufReranker {
+    model_id: String,
+    memory_bytes: AtomicUsize,
+    #[cfg(feature = "gguf")]
+    inner: Option<Arc<super::backend::LlamaBackendInner>>,
+}
+
+impl GgufReranker {
+    /// Create a new reranker (no model loaded yet).
+    pub fn new(model_id: String) -> Self {
+        Self {
+            model_id,
+            memory_bytes: AtomicUsize::new(0),
+            #[cfg(feature = "gguf")]
+            inner: None,
+        }
+    }
+
+    /// Load a model from a GGUF file path as a reranker. `pooling` is the
+    /// header's pooling type, which must be rank.
+    #[cfg(feature = "gguf")]
+    pub fn load(
+        model_id: String,
+        path: &std::path::Path,
+        config: &super::GgufConfig,
+        pooling: Option<GgufPooling>,
+    ) -> Result<Self, InferenceError> {
+        if pooling != Some(GgufPooling::Rank) {
+            return Err(InferenceError::ModelError(format!(
+                "{} has no rank pooling head and cannot rerank",
+                path.display()
+            )));
+        }
+        let inner = Arc::new(super::backend::LlamaBackendInner::load(path, config)?);
+        Ok(Self {
+            model_id,
+            memory_bytes: AtomicUsize::new(inner.model_size()),
+            inner: Some(inner),
+        })
+    }
+
+    /// Score documents against `query`, decoding as many pairs together as
+    /// fit one batch.
+    fn score(&self, query: &str, documents: &[&str]) -> Result<Vec<f32>, InferenceError> {
+        #[cfg(feature = "gguf")]
+        if let Some(inner) = &self.inner {
+            return inner.rerank(query, documents);
+        }
+        let _ = (query, documents);
+        // No model loaded - fail rather than return mock data
+        Err(InferenceError::ModelError(format!(
+            "model '{}' not loaded - cannot rerank",
+            self.model_id
+        )))
+    }
+}
+
+#[async_trait::async_trait]
+impl super::GgufModel for GgufReranker {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn capabilities(&self) -> &[InferenceCapability] {
+        &[InferenceCapability::Rerank]
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.memory_bytes.load(Ordering::SeqCst)
+    }
+
+    async fn infer(
+        &self,
+        _input: &InferenceInput,
+        _config: &InferenceConfig,
+    ) -> Result<InferenceOutput, InferenceError> {
+        Err(InferenceError::CapabilityNotSupported(
+            "reranking models only score query-document pairs".into(),
+        ))
+    }
+
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<f32>, InferenceError> {
+        InferenceInput::Text(query.to_string()).validate()?;
+        InferenceInput::TextBatch(documents.to_vec()).validate()?;
+        let refs: Vec<&str> = documents.iter().map(String::as_str).collect();
+        self.score(query, &refs)
+    }
+
+    async fn unload(&mut self) -> Result<(), InferenceError> {
+        self.memory_bytes.store(0, Ordering::SeqCst);
+        #[cfg(feature = "gguf")]
+        {
+            self.inner = None;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -2,12 +2,32 @@
 //!
 //! Implements DraftModel and TargetModel traits for GgufGenerator,
 //! enabling 2-3x speedup on CPU by predicting multiple tokens at once.
+//!
+//! The serving path uses `LlamaBackendInner::speculate`, which keeps a
+//! context open on each model for the whole request. A draft model proposes
+//! a few tokens greedily, or prompt lookup copies the continuation of an
+//! earlier n-gram match, and the target evaluates them in one batch,
+//! sampling each position with the request's own sampler. Proposals are kept up to
+//! the first one the target did not sample itself, so the output follows
+//! the target's distribution and greedy output is unchanged.
+//!
+//! `GgufTargetModel` verifies the same way: a thread holds the target's
+//! context and the request's sampler across rounds, so each round only
+//! evaluates the tokens the context has not seen.
 
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::time::Instant;
 
-use crate::engine::speculative::{DraftModel, TargetModel, VerifyResult};
-use crate::engine::InferenceError;
+use llama_cpp_2::context::LlamaContext;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::sampling::LlamaSampler;
+use llama_cpp_2::token::LlamaToken;
+
+use super::backend::{add_prompt, add_token, decode, LlamaBackendInner, Sequence};
 use super::GgufGenerator;
+use crate::engine::speculative::{DraftModel, TargetModel, VerifyResult};
+use crate::engine::{FinishReason, InferenceConfig, InferenceError, PromptLookup, StreamingOutput};
+use crate::telemetry;
 
 /// Wrapper for using GgufGenerator as a draft model.
 pub struct GgufDraftModel {
@@ -32,13 +52,64 @@ impl DraftModel for GgufDraftModel {
 }
 
 /// Wrapper for using GgufGenerator as a target model.
+///
+/// Each instance serves one request: its context and sampler carry over
+/// from one call to the next.
 pub struct GgufTargetModel {
     generator: Arc<GgufGenerator>,
+    config: InferenceConfig,
+    /// Rounds for the verification thread, started on first use.
+    session: Mutex<Option<mpsc::Sender<Round>>>,
 }
 
 impl GgufTargetModel {
     pub fn new(generator: Arc<GgufGenerator>) -> Self {
-        Self { generator }
+        Self::with_config(generator, InferenceConfig::default())
+    }
+
+    /// Sample with the sampler `config` describes.
+    pub fn with_config(generator: Arc<GgufGenerator>, config: InferenceConfig) -> Self {
+        Self { generator, config, session: Mutex::new(None) }
+    }
+
+    /// Sample the positions following `context` until one differs from
+    /// `draft`, or the one after `context` when `draft` is empty.
+    fn round(&self, context: &[u32], draft: &[u32]) -> Result<Vec<u32>, InferenceError> {
+        if context.is_empty() {
+            return Err(InferenceError::InputValidation("context cannot be empty".into()));
+        }
+        let rounds = {
+            let mut session = self.session.lock().unwrap_or_else(PoisonError::into_inner);
+            match session.as_ref() {
+                Some(rounds) => rounds.clone(),
+                None => session.insert(self.start()?).clone(),
+            }
+        };
+        let (reply, sampled) = mpsc::channel();
+        let round = Round {
+            tokens: context.iter().map(|&t| LlamaToken(t as i32)).collect(),
+            drafts: draft.iter().map(|&t| LlamaToken(t as i32)).collect(),
+            reply,
+        };
+        let stopped = || InferenceError::ModelError("verification thread stopped".into());
+        rounds.send(round).map_err(|_| stopped())?;
+        let sampled = sampled.recv().map_err(|_| stopped())??;
+        Ok(sampled.into_iter().map(|t| t.0 as u32).collect())
+    }
+
+    fn start(&self) -> Result<mpsc::Sender<Round>, InferenceError> {
+        let target = self
+            .generator
+            .backend()
+            .cloned()
+            .ok_or_else(|| InferenceError::ModelError("no model loaded".into()))?;
+        let config = self.config.clone();
+        let (rounds, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("gguf-verify".into())
+            .spawn(move || serve(&target, &config, receiver))
+            .map_err(|e| InferenceError::ModelError(format!("verify thread: {e}")))?;
+        Ok(rounds)
     }
 }
 
@@ -49,12 +120,16 @@ impl TargetModel for GgufTargetModel {
         context: &[u32],
         draft: &[u32],
     ) -> Result<VerifyResult, InferenceError> {
-        self.generator.verify_draft_tokens(context, draft).await
+        let sampled = self.round(context, draft)?;
+        if sampled == draft {
+            return Ok(VerifyResult::accept_all(draft.len()));
+        }
+        let accepted = sampled.len() - 1;
+        Ok(VerifyResult::diverge_at(accepted, sampled[accepted]))
     }
 
     async fn generate_one(&self, context: &[u32]) -> Result<u32, InferenceError> {
-        let tokens = self.generator.generate_tokens(context, 1).await?;
-        tokens.into_iter().next().ok_or_else(|| {
+        self.round(context, &[])?.pop().ok_or_else(|| {
             InferenceError::ModelError("failed to generate token".into())
         })
     }
@@ -63,3 +138,277 @@ impl TargetModel for GgufTargetModel {
         self.generator.eos_token_id()
     }
 }
+
+/// Draft tokens to check against the target, with the channel for the
+/// tokens it sampled.
+struct Round {
+    tokens: Vec<LlamaToken>,
+    drafts: Vec<LlamaToken>,
+    reply: mpsc::Sender<Result<Vec<LlamaToken>, InferenceError>>,
+}
+
+/// Answer the rounds of one request in a single context, sampling with
+/// the sampler `config` describes. Runs until the target model is dropped.
+fn serve(target: &LlamaBackendInner, config: &InferenceConfig, rounds: mpsc::Receiver<Round>) {
+    let Ok(first) = rounds.recv() else {
+        return;
+    };
+    let capacity = first.tokens.len() + first.drafts.len();
+    let setup = TargetContext::new(target, capacity)
+        .and_then(|ctx| Ok((ctx, target.build_sampler(config, &first.tokens)?)));
+    let (mut ctx, mut sampler) = match setup {
+        Ok(setup) => setup,
+        Err(e) => {
+            let _ = first.reply.send(Err(e));
+            return;
+        }
+    };
+    for round in std::iter::once(first).chain(rounds) {
+        let _ = round.reply.send(ctx.verify(&mut sampler, &round.tokens, &round.drafts));
+    }
+}
+
+/// The target's context for one request. It keeps what it has evaluated
+/// between rounds and only decodes what changed.
+struct TargetContext<'a> {
+    ctx: LlamaContext<'a>,
+    batch: LlamaBatch,
+    capacity: usize,
+    /// Tokens the context has evaluated.
+    seen: Vec<LlamaToken>,
+}
+
+impl<'a> TargetContext<'a> {
+    fn new(target: &'a LlamaBackendInner, capacity: usize) -> Result<Self, InferenceError> {
+        let capacity = capacity.max(1);
+        Ok(Self {
+            ctx: target.create_context(1)?,
+            batch: LlamaBatch::new(capacity, 1),
+            capacity,
+            seen: Vec::new(),
+        })
+    }
+
+    /// Evaluate `tokens` followed by `drafts`, keeping the prefix already
+    /// evaluated, and return the batch index of the logits for the first
+    /// position after `tokens`.
+    fn evaluate(
+        &mut self,
+        tokens: &[LlamaToken],
+        drafts: &[LlamaToken],
+    ) -> Result<i32, InferenceError> {
+        let start = rewind(&mut self.ctx, &mut self.seen, tokens)?;
+        let needed = tokens.len() + drafts.len() - start;
+        if needed > self.capacity {
+            self.batch = LlamaBatch::new(needed, 1);
+            self.capacity = needed;
+        }
+        self.batch.clear();
+        for (pos, &tok) in tokens.iter().chain(drafts).enumerate().skip(start) {
+            add_token(&mut self.batch, tok, pos as i32, 0, pos + 1 >= tokens.len())?;
+        }
+        decode(&mut self.ctx, &mut self.batch)?;
+        self.seen.extend_from_slice(&tokens[start..]);
+        self.seen.extend_from_slice(drafts);
+        Ok(self.batch.n_tokens() - 1 - drafts.len() as i32)
+    }
+
+    /// Sample the positions after `tokens` until one differs from `drafts`,
+    /// or just the next one when there are no drafts.
+    fn verify(
+        &mut self,
+        sampler: &mut LlamaSampler,
+        tokens: &[LlamaToken],
+        drafts: &[LlamaToken],
+    ) -> Result<Vec<LlamaToken>, InferenceError> {
+        let first = self.evaluate(tokens, drafts)?;
+        let mut sampled = Vec::with_capacity(drafts.len().max(1));
+        for i in 0..drafts.len().max(1) {
+            let tok = sampler.sample(&self.ctx, first + i as i32);
+            sampler.accept(tok);
+            sampled.push(tok);
+            if drafts.get(i) != Some(&tok) {
+                break;
+            }
+        }
+        Ok(sampled)
+    }
+}
+
+/// Source of the tokens proposed to the target.
+pub(super) enum Drafter<'a> {
+    /// A smaller model sharing the target's vocabulary, decoding greedily.
+    Model(&'a LlamaBackendInner),
+    /// Continuations of earlier n-gram matches in the prompt and output.
+    PromptLookup(PromptLookup),
+}
+
+/// A drafter with its per-request state.
+enum Proposer<'a> {
+    Model {
+        draft: &'a LlamaBackendInner,
+        ctx: LlamaContext<'a>,
+        batch: LlamaBatch,
+        greedy: LlamaSampler,
+        /// Tokens the draft's context has evaluated.
+        seen: Vec<LlamaToken>,
+    },
+    PromptLookup(PromptLookup),
+}
+
+impl<'a> Proposer<'a> {
+    fn new(
+        drafter: Drafter<'a>,
+        prompt_len: usize,
+        max_draft: usize,
+    ) -> Result<Self, InferenceError> {
+        Ok(match drafter {
+            Drafter::Model(draft) => Self::Model {
+                draft,
+                ctx: draft.create_context(1)?,
+                batch: LlamaBatch::new(prompt_len + max_draft + 1, 1),
+                greedy: LlamaSampler::greedy(),
+                seen: Vec::new(),
+            },
+            Drafter::PromptLookup(lookup) => Self::PromptLookup(lookup),
+        })
+    }
+
+    /// Most tokens proposed at once.
+    fn max_draft(drafter: &Drafter<'_>, draft_tokens: usize) -> usize {
+        match drafter {
+            Drafter::Model(_) => draft_tokens.max(1),
+            Drafter::PromptLookup(lookup) => lookup.max_draft,
+        }
+    }
+
+    /// Propose up to `wanted` tokens to follow `tokens`.
+    fn propose(
+        &mut self,
+        tokens: &[LlamaToken],
+        wanted: usize,
+    ) -> Result<Vec<LlamaToken>, InferenceError> {
+        match self {
+            Self::PromptLookup(lookup) => {
+                let ids: Vec<u32> = tokens.iter().map(|t| t.0 as u32).collect();
+                let drafts = lookup.propose(&ids, wanted);
+                Ok(drafts.into_iter().map(|t| LlamaToken(t as i32)).collect())
+            }
+            Self::Model { draft, ctx, batch, greedy, seen } => {
+                // Stop drafting once the draft's context is full
+                let wanted = wanted.min(draft.context_size().saturating_sub(tokens.len()));
+                let mut drafts = Vec::with_capacity(wanted);
+                if wanted == 0 {
+                    return Ok(drafts);
+                }
+                let start = rewind(ctx, seen, tokens)?;
+                batch.clear();
+                add_prompt(batch, tokens, start, 0)?;
+                seen.extend_from_slice(&tokens[start..]);
+                loop {
+                    decode(ctx, batch)?;
+                    let tok = greedy.sample(ctx, batch.n_tokens() - 1);
+                    if draft.is_eog(tok) {
+                        break;
+                    }
+                    drafts.push(tok);
+                    if drafts.len() == wanted {
+                        break;
+                    }
+                    batch.clear();
+                    add_token(batch, tok, seen.len() as i32, 0, true)?;
+                    seen.push(tok);
+                }
+                Ok(drafts)
+            }
+        }
+    }
+}
+
+impl LlamaBackendInner {
+    /// Generate with `drafter` proposing tokens for this model to verify,
+    /// passing each output to `emit` until it returns false.
+    pub(super) fn speculate(
+        &self,
+        drafter: Drafter<'_>,
+        prompt: &str,
+        config: &InferenceConfig,
+        mut emit: impl FnMut(StreamingOutput) -> bool,
+    ) -> Result<(), InferenceError> {
+        let started = Instant::now();
+        let (prompt, max_tok) = self.fit_context(self.tokenize(prompt)?, config)?;
+        if max_tok == 0 {
+            return Ok(());
+        }
+        let max_draft = Proposer::max_draft(&drafter, self.draft_tokens());
+        let mut proposer = Proposer::new(drafter, prompt.len(), max_draft)?;
+        let mut target = TargetContext::new(self, prompt.len() + max_draft + 1)?;
+        let mut seq = Sequence::new(0, self.build_sampler(config, &prompt)?, 0, &config.stop);
+        // Prompt and accepted tokens
+        let mut tokens = prompt.clone();
+        let mut first_token = None;
+        let mut sampled = 0;
+        loop {
+            // Leave the last token of the completion to the target
+            let wanted = max_draft.min(max_tok.saturating_sub(sampled + 1) as usize);
+            let drafts = proposer.propose(&tokens, wanted)?;
+            let first_logits = target.evaluate(&tokens, &drafts)?;
+
+            // Sample each position until the target disagrees with the draft
+            let mut accepted = 0;
+            let finished = loop {
+                seq.logits = first_logits + accepted as i32;
+                let tok = seq.sampler.sample(&target.ctx, seq.logits);
+                seq.sampler.accept(tok);
+                let elapsed = started.elapsed();
+                let first_token = *first_token.get_or_insert(elapsed);
+                sampled += 1;
+                let mark = seq.mark();
+                let reason = if self.advance(&target.ctx, &mut seq, tok, config)? {
+                    seq.finish
+                } else if sampled >= max_tok {
+                    Some(FinishReason::MaxTokens)
+                } else {
+                    config.abort_reason(started)
+                };
+                if let Some(reason) = reason {
+                    seq.end(reason);
+                }
+                let output = seq.output(tok, mark, prompt.len(), first_token, elapsed);
+                // Stop once the client has gone away
+                if !emit(output) || seq.finish.is_some() {
+                    break true;
+                }
+                tokens.push(tok);
+                if drafts.get(accepted) != Some(&tok) {
+                    break false;
+                }
+                accepted += 1;
+            };
+            if !drafts.is_empty() {
+                telemetry::record_speculative_cycle(accepted, drafts.len() - accepted);
+            }
+            if finished {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Drop the KV cells past the prefix `seen` shares with `tokens`, leaving
+/// at least the last token to evaluate, and return that prefix's length.
+fn rewind(
+    ctx: &mut LlamaContext<'_>,
+    seen: &mut Vec<LlamaToken>,
+    tokens: &[LlamaToken],
+) -> Result<usize, InferenceError> {
+    let shared = seen.iter().zip(tokens).take_while(|(a, b)| a == b).count();
+    let keep = shared.min(tokens.len().saturating_sub(1));
+    if keep < seen.len()
+        && !matches!(ctx.clear_kv_cache_seq(Some(0), Some(keep as u32), None), Ok(true))
+    {
+        return Err(InferenceError::ModelError("speculation: KV cache cannot be trimmed".into()));
+    }
+    seen.truncate(keep);
+    Ok(keep)
+}
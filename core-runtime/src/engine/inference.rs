@@ -1,12 +1,19 @@
 //! Core inference execution with real model delegation.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
-use crate::engine::gguf::GgufModel;
-use crate::engine::{InferenceConfig, InferenceInput, InferenceOutput};
+use crate::engine::gguf::{GgufGenerator, GgufModel};
+use crate::engine::onnx::OnnxModel;
+use crate::engine::{ChatMessage, InferenceConfig, InferenceInput, InferenceOutput, Mirostat};
+use crate::engine::{ClassificationResult, Completion, EmbeddingResult, InferenceCapability};
+use crate::engine::{validate_rerank, RerankResult, MAX_BATCH_SIZE};
+use crate::engine::{AdapterSelection, ConstraintError, ContextStrategy, OutputConstraint};
+use crate::engine::{FinishReason, GenerationTimings, StopSequence, TokenLogprob};
+use crate::engine::{MAX_STOP_SEQUENCES, MAX_STOP_SEQUENCE_BYTES, MAX_TOP_LOGPROBS};
 use crate::models::ModelHandle;
 
 #[derive(Error, Debug)]
@@ -20,10 +27,44 @@ pub enum InferenceError {
     #[error("Inference failed: {0}")]
     ExecutionFailed(String),
 
-    #[error("Context length exceeded: max {max}, got {got}")]
-    ContextExceeded { max: usize, got: usize },
+    #[error(
+        "Context length exceeded: {prompt_tokens} prompt tokens + {max_tokens} max_tokens > {n_ctx}"
+    )]
+    ContextExceeded {
+        n_ctx: usize,
+        prompt_tokens: usize,
+        max_tokens: usize,
+    },
+
+    #[error("Capability not supported: {0}")]
+    CapabilityNotSupported(String),
+
+    #[error("Unsupported output constraint: {0}")]
+    UnsupportedConstraint(#[from] ConstraintError),
 }
 
+/// Seed used when a request does not set one, so unseeded requests stay
+/// reproducible.
+pub const DEFAULT_SEED: u32 = 42;
+
+/// Most `logit_bias` entries accepted per request.
+pub const MAX_LOGIT_BIAS_ENTRIES: usize = 1024;
+
+/// Largest `logit_bias` magnitude; -100 effectively bans a token.
+pub const MAX_LOGIT_BIAS: f32 = 100.0;
+
+/// Longest `conversation_id` accepted, in bytes.
+pub const MAX_CONVERSATION_ID_BYTES: usize = 128;
+
+/// Most sequences (`best_of`, and so `n`) generated for one request.
+pub const MAX_COMPLETIONS: usize = 16;
+
+/// Longest adapter name accepted, in bytes.
+pub const MAX_ADAPTER_NAME_BYTES: usize = 128;
+
+/// Largest `adapter_scale`.
+pub const MAX_ADAPTER_SCALE: f32 = 4.0;
+
 /// Parameters controlling inference behavior (IPC protocol).
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct InferenceParams {
@@ -37,6 +78,105 @@ pub struct InferenceParams {
     /// Request timeout in milliseconds. None = no timeout.
     #[serde(default)]
     pub timeout_ms: Option<u64>,
+    /// Sampling RNG seed. None = `DEFAULT_SEED`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+    /// Multiplicative penalty for recently seen tokens (1.0 = none).
+    #[serde(default = "default_repetition_penalty")]
+    pub repetition_penalty: f32,
+    /// Flat penalty for tokens that appeared at least once (-2.0..=2.0).
+    #[serde(default)]
+    pub presence_penalty: f32,
+    /// Penalty scaled by how often a token appeared (-2.0..=2.0).
+    #[serde(default)]
+    pub frequency_penalty: f32,
+    /// Tokens looked back over for the penalties (0 = off, -1 = whole context).
+    #[serde(default = "default_penalty_last_n")]
+    pub penalty_last_n: i32,
+    /// Drop tokens below this fraction of the top token's probability (0 = off).
+    #[serde(default)]
+    pub min_p: f32,
+    /// Locally typical sampling threshold (1.0 = off).
+    #[serde(default = "default_typical_p")]
+    pub typical_p: f32,
+    /// Mirostat mode: 0 = off, 1 = v1, 2 = v2. Replaces top-k/top-p/min-p/typical.
+    #[serde(default)]
+    pub mirostat: u8,
+    /// Mirostat target surprise.
+    #[serde(default = "default_mirostat_tau")]
+    pub mirostat_tau: f32,
+    /// Mirostat learning rate.
+    #[serde(default = "default_mirostat_eta")]
+    pub mirostat_eta: f32,
+    /// Additive logit bias by token id, in [-100, 100].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub logit_bias: BTreeMap<u32, f32>,
+    /// Strings and token IDs that end generation. Excluded from the output.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<StopSequence>,
+    /// GBNF grammar (with a `root` rule) the output must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grammar: Option<String>,
+    /// JSON Schema the output must satisfy. Exclusive with `grammar`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<serde_json::Value>,
+    /// What to do when the prompt plus `max_tokens` exceeds the model's context.
+    #[serde(default)]
+    pub context_strategy: ContextStrategy,
+    /// Named conversation whose KV state is kept between requests, so each
+    /// turn only evaluates the tokens added since the last one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    /// Return each generated token's log-probability with this many of the
+    /// most likely alternatives (0 to 20). None = off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<usize>,
+    /// Completions to return.
+    #[serde(default = "default_n")]
+    pub n: usize,
+    /// Completions to generate, of which the `n` with the highest cumulative
+    /// log-probability are returned. None = `n`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub best_of: Option<usize>,
+    /// Decode speculatively with tokens copied from earlier n-gram matches
+    /// in the prompt and output, for outputs that repeat their input.
+    #[serde(default)]
+    pub prompt_lookup: bool,
+    /// LoRA adapter to generate with, by the name it was loaded under on
+    /// this model. None = the base model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adapter: Option<String>,
+    /// Multiplier on the adapter's weight deltas (0 to 4).
+    #[serde(default = "default_adapter_scale")]
+    pub adapter_scale: f32,
+}
+
+fn default_repetition_penalty() -> f32 {
+    1.1
+}
+
+fn default_penalty_last_n() -> i32 {
+    64
+}
+
+fn default_typical_p() -> f32 {
+    1.0
+}
+
+fn default_n() -> usize {
+    1
+}
+
+fn default_mirostat_tau() -> f32 {
+    5.0
+}
+
+fn default_mirostat_eta() -> f32 {
+    0.1
+}
+
+fn default_adapter_scale() -> f32 {
+    1.0
 }
 
 impl Default for InferenceParams {
@@ -48,6 +188,28 @@ impl Default for InferenceParams {
             top_k: 40,
             stream: false,
             timeout_ms: None,
+            seed: None,
+            repetition_penalty: default_repetition_penalty(),
+            presence_penalty: 0.0,
+            frequency_penalty: 0.0,
+            penalty_last_n: default_penalty_last_n(),
+            min_p: 0.0,
+            typical_p: default_typical_p(),
+            mirostat: 0,
+            mirostat_tau: default_mirostat_tau(),
+            mirostat_eta: default_mirostat_eta(),
+            logit_bias: BTreeMap::new(),
+            stop: Vec::new(),
+            grammar: None,
+            json_schema: None,
+            context_strategy: ContextStrategy::Reject,
+            conversation_id: None,
+            logprobs: None,
+            n: default_n(),
+            best_of: None,
+            prompt_lookup: false,
+            adapter: None,
+            adapter_scale: default_adapter_scale(),
         }
     }
 }
@@ -57,25 +219,175 @@ impl InferenceParams {
         if self.max_tokens == 0 {
             return Err(InferenceError::InvalidParams("max_tokens must be > 0".into()));
         }
-        if self.temperature < 0.0 {
+        if u32::try_from(self.max_tokens).is_err() {
+            return Err(InferenceError::InvalidParams(format!(
+                "max_tokens must be at most {}",
+                u32::MAX
+            )));
+        }
+        if self.temperature.is_nan() || self.temperature < 0.0 {
             return Err(InferenceError::InvalidParams("temperature must be >= 0".into()));
         }
-        if self.top_p <= 0.0 || self.top_p > 1.0 {
+        if !(self.top_p > 0.0 && self.top_p <= 1.0) {
             return Err(InferenceError::InvalidParams("top_p must be in (0, 1]".into()));
         }
+        if !(1.0..=f32::MAX).contains(&self.repetition_penalty) {
+            return Err(InferenceError::InvalidParams("repetition_penalty must be >= 1".into()));
+        }
+        if !(-2.0..=2.0).contains(&self.presence_penalty) {
+            return Err(InferenceError::InvalidParams(
+                "presence_penalty must be in [-2, 2]".into(),
+            ));
+        }
+        if !(-2.0..=2.0).contains(&self.frequency_penalty) {
+            return Err(InferenceError::InvalidParams(
+                "frequency_penalty must be in [-2, 2]".into(),
+            ));
+        }
+        if self.penalty_last_n < -1 {
+            return Err(InferenceError::InvalidParams("penalty_last_n must be >= -1".into()));
+        }
+        if !(0.0..=1.0).contains(&self.min_p) {
+            return Err(InferenceError::InvalidParams("min_p must be in [0, 1]".into()));
+        }
+        if self.typical_p.is_nan() || self.typical_p <= 0.0 || self.typical_p > 1.0 {
+            return Err(InferenceError::InvalidParams("typical_p must be in (0, 1]".into()));
+        }
+        if self.mirostat > 2 {
+            return Err(InferenceError::InvalidParams("mirostat must be 0, 1 or 2".into()));
+        }
+        if !self.mirostat_tau.is_finite() || self.mirostat_tau <= 0.0 {
+            return Err(InferenceError::InvalidParams("mirostat_tau must be > 0".into()));
+        }
+        if !self.mirostat_eta.is_finite() || self.mirostat_eta <= 0.0 {
+            return Err(InferenceError::InvalidParams("mirostat_eta must be > 0".into()));
+        }
+        if self.logit_bias.len() > MAX_LOGIT_BIAS_ENTRIES {
+            return Err(InferenceError::InvalidParams(format!(
+                "logit_bias has more than {} entries",
+                MAX_LOGIT_BIAS_ENTRIES
+            )));
+        }
+        if let Some((token, bias)) = self
+            .logit_bias
+            .iter()
+            .find(|(_, b)| !(-MAX_LOGIT_BIAS..=MAX_LOGIT_BIAS).contains(*b))
+        {
+            return Err(InferenceError::InvalidParams(format!(
+                "logit_bias for token {} must be in [-{}, {}], got {}",
+                token, MAX_LOGIT_BIAS, MAX_LOGIT_BIAS, bias
+            )));
+        }
+        if self.stop.len() > MAX_STOP_SEQUENCES {
+            return Err(InferenceError::InvalidParams(format!(
+                "stop has more than {} sequences",
+                MAX_STOP_SEQUENCES
+            )));
+        }
+        for stop in &self.stop {
+            if let StopSequence::Text(text) = stop {
+                if text.is_empty() || text.len() > MAX_STOP_SEQUENCE_BYTES {
+                    return Err(InferenceError::InvalidParams(format!(
+                        "stop strings must be 1 to {} bytes",
+                        MAX_STOP_SEQUENCE_BYTES
+                    )));
+                }
+            }
+        }
+        if let Some(id) = &self.conversation_id {
+            if id.is_empty() || id.len() > MAX_CONVERSATION_ID_BYTES {
+                return Err(InferenceError::InvalidParams(format!(
+                    "conversation_id must be 1 to {} bytes",
+                    MAX_CONVERSATION_ID_BYTES
+                )));
+            }
+        }
+        if self.logprobs.is_some_and(|n| n > MAX_TOP_LOGPROBS) {
+            return Err(InferenceError::InvalidParams(format!(
+                "logprobs must be 0 to {}",
+                MAX_TOP_LOGPROBS
+            )));
+        }
+        if self.n == 0 || self.best_of() > MAX_COMPLETIONS {
+            return Err(InferenceError::InvalidParams(format!(
+                "n and best_of must be 1 to {}",
+                MAX_COMPLETIONS
+            )));
+        }
+        if self.best_of() < self.n {
+            return Err(InferenceError::InvalidParams("best_of must be >= n".into()));
+        }
+        if self.stream && self.best_of() > 1 {
+            return Err(InferenceError::InvalidParams(
+                "n and best_of above 1 cannot be streamed".into(),
+            ));
+        }
+        if let Some(name) = &self.adapter {
+            if name.is_empty() || name.len() > MAX_ADAPTER_NAME_BYTES {
+                return Err(InferenceError::InvalidParams(format!(
+                    "adapter must be 1 to {} bytes",
+                    MAX_ADAPTER_NAME_BYTES
+                )));
+            }
+        }
+        if !(0.0..=MAX_ADAPTER_SCALE).contains(&self.adapter_scale) {
+            return Err(InferenceError::InvalidParams(format!(
+                "adapter_scale must be in [0, {}]",
+                MAX_ADAPTER_SCALE
+            )));
+        }
+        self.constraint()?;
         Ok(())
     }
 
+    /// Sequences to generate: `best_of`, or `n` when it is unset.
+    pub fn best_of(&self) -> usize {
+        self.best_of.unwrap_or(self.n)
+    }
+
+    /// Output constraint from `grammar` or `json_schema`, if either is set.
+    pub fn constraint(&self) -> Result<Option<OutputConstraint>, ConstraintError> {
+        OutputConstraint::from_request(self.grammar.as_deref(), self.json_schema.as_ref())
+    }
+
     /// Convert to internal InferenceConfig format.
     pub fn to_config(&self) -> InferenceConfig {
         InferenceConfig {
-            max_tokens: Some(self.max_tokens as u32),
+            max_tokens: Some(u32::try_from(self.max_tokens).unwrap_or(u32::MAX)),
             temperature: self.temperature,
             top_p: self.top_p,
             top_k: self.top_k as u32,
-            repetition_penalty: 1.1,
+            repetition_penalty: self.repetition_penalty,
             timeout_ms: self.timeout_ms.unwrap_or(30_000),
             max_memory_bytes: None,
+            seed: self.seed.unwrap_or(DEFAULT_SEED),
+            presence_penalty: self.presence_penalty,
+            frequency_penalty: self.frequency_penalty,
+            penalty_last_n: self.penalty_last_n,
+            min_p: self.min_p,
+            typical_p: self.typical_p,
+            mirostat: match self.mirostat {
+                1 => Mirostat::V1,
+                2 => Mirostat::V2,
+                _ => Mirostat::Disabled,
+            },
+            mirostat_tau: self.mirostat_tau,
+            mirostat_eta: self.mirostat_eta,
+            logit_bias: self.logit_bias.iter().map(|(&t, &b)| (t, b)).collect(),
+            stop: self.stop.clone(),
+            // Unsupported constraints are rejected by `validate`
+            constraint: self.constraint().ok().flatten(),
+            context_strategy: self.context_strategy,
+            conversation: self.conversation_id.clone(),
+            logprobs: self.logprobs,
+            n: self.n,
+            best_of: self.best_of(),
+            prompt_lookup: self.prompt_lookup,
+            adapter: self.adapter.as_ref().map(|name| AdapterSelection {
+                name: name.clone(),
+                scale: self.adapter_scale,
+            }),
+            cancel: CancellationToken::new(),
         }
     }
 }
@@ -87,15 +399,136 @@ pub struct InferenceResult {
     pub output: String,
     pub tokens_generated: usize,
     pub finished: bool,
+    /// Why generation stopped.
+    pub finish_reason: FinishReason,
+    /// Number of prompt tokens evaluated.
+    pub prompt_tokens: usize,
+    /// Backend prefill, first-token and decode times.
+    pub timings: GenerationTimings,
+    /// Stop sequence that ended generation, if any.
+    pub stop_sequence: Option<StopSequence>,
+    /// Per-token log-probabilities, when requested.
+    pub logprobs: Vec<TokenLogprob>,
+    /// Every returned completion, best first, when `n > 1`.
+    pub choices: Vec<Completion>,
+}
+
+/// A registered model from either backend family.
+#[derive(Clone)]
+pub enum EngineModel {
+    /// llama.cpp generation, embedding or reranking model.
+    Gguf(Arc<dyn GgufModel>),
+    /// Candle ONNX encoder (embedding, classification or reranking).
+    Onnx(Arc<dyn OnnxModel>),
+}
+
+impl EngineModel {
+    pub fn model_id(&self) -> &str {
+        match self {
+            Self::Gguf(m) => m.model_id(),
+            Self::Onnx(m) => m.model_id(),
+        }
+    }
+
+    pub fn capabilities(&self) -> &[InferenceCapability] {
+        match self {
+            Self::Gguf(m) => m.capabilities(),
+            Self::Onnx(m) => m.capabilities(),
+        }
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            Self::Gguf(m) => m.memory_usage(),
+            Self::Onnx(m) => m.memory_usage(),
+        }
+    }
+
+    /// Returns true if the model advertises `capability`.
+    pub fn supports(&self, capability: InferenceCapability) -> bool {
+        self.capabilities().contains(&capability)
+    }
+
+    /// Load a LoRA adapter requests can select by `name`; returns the
+    /// bytes it occupies. Only GGUF generators take adapters. Blocks, so
+    /// run it on a blocking thread.
+    pub fn load_adapter(
+        &self,
+        name: &str,
+        path: &std::path::Path,
+    ) -> Result<usize, crate::engine::InferenceError> {
+        match self {
+            Self::Gguf(m) => m.load_adapter(name, path),
+            Self::Onnx(_) => Err(crate::engine::InferenceError::CapabilityNotSupported(
+                "ONNX models do not take LoRA adapters".into(),
+            )),
+        }
+    }
+
+    /// Release an adapter. Requests already using it finish with it.
+    pub fn unload_adapter(&self, name: &str) -> bool {
+        match self {
+            Self::Gguf(m) => m.unload_adapter(name),
+            Self::Onnx(_) => false,
+        }
+    }
+
+    pub fn has_adapter(&self, name: &str) -> bool {
+        match self {
+            Self::Gguf(m) => m.has_adapter(name),
+            Self::Onnx(_) => false,
+        }
+    }
+
+    async fn infer(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+    ) -> Result<InferenceOutput, crate::engine::InferenceError> {
+        match self {
+            Self::Gguf(m) => m.infer(input, config).await,
+            Self::Onnx(m) => m.infer(input, config).await,
+        }
+    }
+
+    /// One output per text; ONNX encoders and GGUF embedders run these as
+    /// batches.
+    async fn infer_batch(
+        &self,
+        texts: &[String],
+        config: &InferenceConfig,
+    ) -> Result<Vec<InferenceOutput>, crate::engine::InferenceError> {
+        match self {
+            Self::Onnx(m) => m.infer_batch(texts, config).await,
+            Self::Gguf(m) => m.infer_batch(texts, config).await,
+        }
+    }
+
+    /// Relevance score of each document for `query`, in input order.
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<f32>, crate::engine::InferenceError> {
+        match self {
+            Self::Onnx(m) => m.rerank(query, documents).await,
+            Self::Gguf(m) => m.rerank(query, documents).await,
+        }
+    }
 }
 
 /// Executes model inference by delegating to registered models.
+///
+/// Holds GGUF and ONNX models side by side; each entry point checks that
+/// the target model advertises the capability it needs.
 pub struct InferenceEngine {
     max_context_length: usize,
     /// Models indexed by model_id for lookup.
-    models: Arc<RwLock<HashMap<String, Arc<dyn GgufModel>>>>,
+    models: Arc<RwLock<HashMap<String, EngineModel>>>,
     /// ModelHandle to model_id mapping.
     handle_to_id: Arc<RwLock<HashMap<u64, String>>>,
+    /// Draft model_id for each target decoded speculatively.
+    drafts: Arc<RwLock<HashMap<String, String>>>,
 }
 
 impl InferenceEngine {
@@ -104,9 +537,34 @@ impl InferenceEngine {
             max_context_length,
             models: Arc::new(RwLock::new(HashMap::new())),
             handle_to_id: Arc::new(RwLock::new(HashMap::new())),
+            drafts: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Pair targets with draft models: each key is a target model_id and
+    /// its value the model_id of the draft proposing its tokens.
+    pub fn with_draft_models(mut self, drafts: HashMap<String, String>) -> Self {
+        self.drafts = Arc::new(RwLock::new(drafts));
+        self
+    }
+
+    /// Pair `model_id` with a draft model, or decode it normally with None.
+    ///
+    /// The pairing outlives both models. While the draft is not loaded, or
+    /// cannot draft for the target, requests are decoded normally.
+    pub async fn set_draft_model(&self, model_id: &str, draft_id: Option<String>) {
+        let mut drafts = self.drafts.write().await;
+        match draft_id {
+            Some(draft_id) => drafts.insert(model_id.to_string(), draft_id),
+            None => drafts.remove(model_id),
+        };
+    }
+
+    /// Draft model paired with `model_id`, if any.
+    pub async fn draft_model(&self, model_id: &str) -> Option<String> {
+        self.drafts.read().await.get(model_id).cloned()
+    }
+
     /// Register a model for inference.
     pub async fn register_model(
         &self,
@@ -114,8 +572,27 @@ impl InferenceEngine {
         handle: ModelHandle,
         model: Arc<dyn GgufModel>,
     ) {
-        self.models.write().await.insert(model_id.clone(), model);
-        self.handle_to_id.write().await.insert(handle.id(), model_id);
+        self.register(model_id, handle, EngineModel::Gguf(model)).await;
+    }
+
+    /// Register an ONNX encoder for embedding or classification.
+    pub async fn register_onnx_model(
+        &self,
+        model_id: String,
+        handle: ModelHandle,
+        model: Arc<dyn OnnxModel>,
+    ) {
+        self.register(model_id, handle, EngineModel::Onnx(model)).await;
+    }
+
+    /// Register a model of either family, replacing any model (and handle)
+    /// already registered under `model_id`.
+    pub async fn register(&self, model_id: String, handle: ModelHandle, model: EngineModel) {
+        let mut models = self.models.write().await;
+        let mut handles = self.handle_to_id.write().await;
+        handles.retain(|_, v| *v != model_id);
+        handles.insert(handle.id(), model_id.clone());
+        models.insert(model_id, model);
     }
 
     /// Unregister a model.
@@ -132,37 +609,78 @@ impl InferenceEngine {
         prompt: &str,
         params: &InferenceParams,
     ) -> Result<InferenceResult, InferenceError> {
-        params.validate()?;
+        self.run_input(model_id, InferenceInput::Text(prompt.to_string()), params)
+            .await
+    }
 
-        // Look up model by ID
-        let models = self.models.read().await;
-        let model = models.get(model_id).ok_or_else(|| {
-            InferenceError::ModelNotLoaded(model_id.to_string())
-        })?;
+    /// Run inference on chat messages, rendered with the model's chat template.
+    pub async fn run_chat(
+        &self,
+        model_id: &str,
+        messages: &[ChatMessage],
+        params: &InferenceParams,
+    ) -> Result<InferenceResult, InferenceError> {
+        self.run_input(model_id, InferenceInput::ChatMessages(messages.to_vec()), params)
+            .await
+    }
 
-        // Check context length (approximate by bytes)
-        if prompt.len() > self.max_context_length {
-            return Err(InferenceError::ContextExceeded {
-                max: self.max_context_length,
-                got: prompt.len(),
-            });
-        }
+    /// Run inference on any input the model accepts.
+    pub async fn run_input(
+        &self,
+        model_id: &str,
+        input: InferenceInput,
+        params: &InferenceParams,
+    ) -> Result<InferenceResult, InferenceError> {
+        self.run_input_cancellable(model_id, input, params, CancellationToken::new())
+            .await
+    }
+
+    /// Run inference, stopping early once `cancel` fires.
+    ///
+    /// The backend checks `cancel` and the request timeout between decode
+    /// steps and returns the partial output with `FinishReason::Cancelled`
+    /// or `FinishReason::Timeout`.
+    pub async fn run_input_cancellable(
+        &self,
+        model_id: &str,
+        input: InferenceInput,
+        params: &InferenceParams,
+        cancel: CancellationToken,
+    ) -> Result<InferenceResult, InferenceError> {
+        params.validate()?;
+
+        let model = self.model_with(model_id, InferenceCapability::TextGeneration).await?;
 
         // Convert params to internal config
-        let config = params.to_config();
-        let input = InferenceInput::Text(prompt.to_string());
+        let config = InferenceConfig { cancel, ..params.to_config() };
+        Self::check_adapter(model_id, &config, |name| model.has_adapter(name))?;
 
-        // Delegate to actual model
-        let output = model.infer(&input, &config).await.map_err(|e| {
-            InferenceError::ExecutionFailed(e.to_string())
-        })?;
+        // Delegate to actual model, which tokenizes the prompt and fits it to
+        // its context window per `context_strategy`
+        let draft = self.paired_draft(model_id).await;
+        let output = match (generator(&model), draft.as_ref().and_then(generator)) {
+            (Some(target), Some(draft)) => {
+                target.infer_with_draft(&input, &config, Some(draft)).await
+            }
+            _ => model.infer(&input, &config).await,
+        };
+        let output = output.map_err(Self::generation_error)?;
 
         // Extract generation result
         match output {
             InferenceOutput::Generation(gen) => Ok(InferenceResult {
                 output: gen.text,
                 tokens_generated: gen.tokens_generated as usize,
-                finished: true,
+                finished: !matches!(
+                    gen.finish_reason,
+                    FinishReason::Timeout | FinishReason::Cancelled
+                ),
+                finish_reason: gen.finish_reason,
+                prompt_tokens: gen.prompt_tokens as usize,
+                timings: gen.timings,
+                stop_sequence: gen.stop_sequence,
+                logprobs: gen.logprobs,
+                choices: gen.choices,
             }),
             _ => Err(InferenceError::ExecutionFailed(
                 "Model returned non-generation output".into(),
@@ -170,6 +688,162 @@ impl InferenceEngine {
         }
     }
 
+    /// Embed each text with an embedding-capable model.
+    pub async fn embed(
+        &self,
+        model_id: &str,
+        texts: &[String],
+    ) -> Result<Vec<EmbeddingResult>, InferenceError> {
+        let model = self.model_with(model_id, InferenceCapability::Embedding).await?;
+        let outputs = self
+            .run_batch(&model, texts, &InferenceConfig::for_embedding())
+            .await?;
+        outputs
+            .into_iter()
+            .map(|output| match output {
+                InferenceOutput::Embedding(result) => Ok(result),
+                _ => Err(InferenceError::ExecutionFailed(
+                    "Model returned non-embedding output".into(),
+                )),
+            })
+            .collect()
+    }
+
+    /// Classify each text with a classification-capable model.
+    pub async fn classify(
+        &self,
+        model_id: &str,
+        texts: &[String],
+    ) -> Result<Vec<ClassificationResult>, InferenceError> {
+        let model = self
+            .model_with(model_id, InferenceCapability::TextClassification)
+            .await?;
+        let outputs = self
+            .run_batch(&model, texts, &InferenceConfig::for_classification())
+            .await?;
+        outputs
+            .into_iter()
+            .map(|output| match output {
+                InferenceOutput::Classification(result) => Ok(result),
+                _ => Err(InferenceError::ExecutionFailed(
+                    "Model returned non-classification output".into(),
+                )),
+            })
+            .collect()
+    }
+
+    /// Score each document's relevance to `query` with a reranking model
+    /// and rank them. Documents are scored in batches of `MAX_BATCH_SIZE`
+    /// pairs, each pair truncated to fit the model.
+    pub async fn rerank(
+        &self,
+        model_id: &str,
+        query: &str,
+        documents: &[String],
+    ) -> Result<RerankResult, InferenceError> {
+        let model = self.model_with(model_id, InferenceCapability::Rerank).await?;
+        validate_rerank(query, documents).map_err(Self::encoder_error)?;
+        self.check_input_bytes(documents.iter().map(String::len).chain([query.len()]))?;
+        let mut scores = Vec::with_capacity(documents.len());
+        for chunk in documents.chunks(MAX_BATCH_SIZE) {
+            let batch = model.rerank(query, chunk).await.map_err(Self::encoder_error)?;
+            if batch.len() != chunk.len() {
+                return Err(InferenceError::ExecutionFailed(format!(
+                    "model returned {} scores for {} documents",
+                    batch.len(),
+                    chunk.len()
+                )));
+            }
+            scores.extend(batch);
+        }
+        Ok(RerankResult::from_scores(scores))
+    }
+
+    /// Registered draft model paired with `model_id`.
+    async fn paired_draft(&self, model_id: &str) -> Option<EngineModel> {
+        let draft_id = self.draft_model(model_id).await?;
+        self.models.read().await.get(&draft_id).cloned()
+    }
+
+    /// Look up a model and check that it supports `capability`.
+    async fn model_with(
+        &self,
+        model_id: &str,
+        capability: InferenceCapability,
+    ) -> Result<EngineModel, InferenceError> {
+        let models = self.models.read().await;
+        let model = models.get(model_id).ok_or_else(|| {
+            InferenceError::ModelNotLoaded(model_id.to_string())
+        })?;
+        if !model.supports(capability) {
+            return Err(InferenceError::CapabilityNotSupported(format!(
+                "model '{}' does not support {:?}",
+                model_id, capability
+            )));
+        }
+        Ok(model.clone())
+    }
+
+    async fn run_batch(
+        &self,
+        model: &EngineModel,
+        texts: &[String],
+        config: &InferenceConfig,
+    ) -> Result<Vec<InferenceOutput>, InferenceError> {
+        self.check_input_bytes(texts.iter().map(String::len))?;
+        model.infer_batch(texts, config).await.map_err(Self::encoder_error)
+    }
+
+    /// Each text is encoded independently; check each against the limit.
+    fn check_input_bytes(
+        &self,
+        lengths: impl Iterator<Item = usize>,
+    ) -> Result<(), InferenceError> {
+        if let Some(longest) = lengths.max() {
+            if longest > self.max_context_length {
+                return Err(InferenceError::InvalidParams(format!(
+                    "input of {} bytes exceeds the {} byte limit",
+                    longest, self.max_context_length
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Map an encoder backend error, keeping invalid inputs distinct.
+    fn encoder_error(error: crate::engine::InferenceError) -> InferenceError {
+        match error {
+            crate::engine::InferenceError::InputValidation(msg) => {
+                InferenceError::InvalidParams(msg)
+            }
+            other => InferenceError::ExecutionFailed(other.to_string()),
+        }
+    }
+
+    /// Reject a request naming an adapter that is not loaded on its model.
+    fn check_adapter(
+        model_id: &str,
+        config: &InferenceConfig,
+        loaded: impl FnOnce(&str) -> bool,
+    ) -> Result<(), InferenceError> {
+        match &config.adapter {
+            Some(adapter) if !loaded(&adapter.name) => Err(InferenceError::InvalidParams(
+                format!("model '{}' has no adapter '{}'", model_id, adapter.name),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Map a generation backend error, keeping context overflows distinct.
+    fn generation_error(error: crate::engine::InferenceError) -> InferenceError {
+        match error {
+            crate::engine::InferenceError::ContextExceeded { n_ctx, prompt_tokens, max_tokens } => {
+                InferenceError::ContextExceeded { n_ctx, prompt_tokens, max_tokens }
+            }
+            other => InferenceError::ExecutionFailed(other.to_string()),
+        }
+    }
+
     /// Run inference by handle (legacy API compatibility).
     pub async fn run_by_handle(
         &self,
@@ -177,11 +851,13 @@ impl InferenceEngine {
         prompt: &str,
         params: &InferenceParams,
     ) -> Result<InferenceResult, InferenceError> {
-        let handles = self.handle_to_id.read().await;
-        let model_id = handles.get(&handle.id()).ok_or_else(|| {
+        // Release the handle map before running: register/unregister take
+        // both locks.
+        let model_id = self.handle_to_id.read().await.get(&handle.id()).cloned();
+        let model_id = model_id.ok_or_else(|| {
             InferenceError::ModelNotLoaded(format!("handle {}", handle.id()))
         })?;
-        self.run(model_id, prompt, params).await
+        self.run(&model_id, prompt, params).await
     }
 
     pub fn max_context_length(&self) -> usize {
@@ -193,6 +869,17 @@ impl InferenceEngine {
         self.models.read().await.contains_key(model_id)
     }
 
+    /// The model registered under `model_id`.
+    pub async fn model(&self, model_id: &str) -> Option<EngineModel> {
+        self.models.read().await.get(model_id).cloned()
+    }
+
+    /// Capabilities of a registered model (None if not registered).
+    pub async fn model_capabilities(&self, model_id: &str) -> Option<Vec<InferenceCapability>> {
+        let models = self.models.read().await;
+        models.get(model_id).map(|m| m.capabilities().to_vec())
+    }
+
     /// Get the ModelHandle for a model_id (for metrics attribution).
     pub async fn get_handle(&self, model_id: &str) -> Option<ModelHandle> {
         let handles = self.handle_to_id.read().await;
@@ -206,32 +893,47 @@ impl InferenceEngine {
 
     /// Run streaming inference, sending tokens to the provided sender.
     ///
-    /// This method looks up the model, downcasts to GgufGenerator, and calls
-    /// generate_stream(). Designed for use with spawn_blocking.
-    #[cfg(feature = "gguf")]
+    /// This method looks up the model and its paired draft and calls the
+    /// model's `infer_stream`, which renders chat input with the model's
+    /// template. Designed for use with spawn_blocking.
     pub fn run_stream_sync(
         &self,
         model_id: &str,
-        prompt: &str,
+        input: &InferenceInput,
         config: &InferenceConfig,
         sender: crate::engine::TokenStreamSender,
     ) -> Result<(), InferenceError> {
-        use crate::engine::gguf::GgufGenerator;
-
         // Get runtime handle for async model lookup
         let rt = tokio::runtime::Handle::current();
         let models = rt.block_on(self.models.read());
-        let model = models.get(model_id).ok_or_else(|| {
-            InferenceError::ModelNotLoaded(model_id.to_string())
-        })?;
+        let model = match models.get(model_id) {
+            Some(EngineModel::Gguf(model)) => model,
+            Some(EngineModel::Onnx(_)) => {
+                return Err(InferenceError::ExecutionFailed(
+                    "model does not support streaming".into(),
+                ))
+            }
+            None => return Err(InferenceError::ModelNotLoaded(model_id.to_string())),
+        };
+        if let InferenceInput::TextBatch(_) = input {
+            return Err(InferenceError::InvalidParams("batch input cannot be streamed".into()));
+        }
+        Self::check_adapter(model_id, config, |name| model.has_adapter(name))?;
+        let drafts = rt.block_on(self.drafts.read());
+        let draft = match drafts.get(model_id).and_then(|id| models.get(id)) {
+            Some(EngineModel::Gguf(draft)) => Some(draft.as_ref()),
+            _ => None,
+        };
 
-        // Downcast to GgufGenerator for streaming access
-        let generator = model.as_any().downcast_ref::<GgufGenerator>().ok_or_else(|| {
-            InferenceError::ExecutionFailed("model does not support streaming".into())
-        })?;
+        model.infer_stream(input, config, draft, sender).map_err(Self::generation_error)
+    }
+}
 
-        generator.generate_stream(prompt, config, sender)
-            .map_err(|e| InferenceError::ExecutionFailed(e.to_string()))
+/// The llama.cpp generator behind a model, if it is one.
+fn generator(model: &EngineModel) -> Option<&GgufGenerator> {
+    match model {
+        EngineModel::Gguf(m) => m.as_any().downcast_ref::<GgufGenerator>(),
+        EngineModel::Onnx(_) => None,
     }
 }
 
@@ -278,6 +980,274 @@ mod tests {
         assert!(params.validate().is_err());
     }
 
+    #[test]
+    fn inference_params_rejects_invalid_sampling_params() {
+        let invalid = [
+            InferenceParams { repetition_penalty: 0.9, ..Default::default() },
+            InferenceParams { presence_penalty: 2.5, ..Default::default() },
+            InferenceParams { frequency_penalty: -2.5, ..Default::default() },
+            InferenceParams { penalty_last_n: -2, ..Default::default() },
+            InferenceParams { min_p: 1.5, ..Default::default() },
+            InferenceParams { typical_p: 0.0, ..Default::default() },
+            InferenceParams { mirostat: 3, ..Default::default() },
+            InferenceParams { mirostat_tau: 0.0, ..Default::default() },
+            InferenceParams { mirostat_eta: -0.1, ..Default::default() },
+            InferenceParams {
+                logit_bias: BTreeMap::from([(7, 101.0)]),
+                ..Default::default()
+            },
+        ];
+        for params in invalid {
+            assert!(params.validate().is_err(), "{:?}", params);
+        }
+    }
+
+    #[test]
+    fn inference_params_sampling_ranges_are_enforced() {
+        let base = InferenceParams::default;
+        let bias = |b: f32| BTreeMap::from([(7, b)]);
+        let too_many_biases = (0..=MAX_LOGIT_BIAS_ENTRIES as u32).map(|t| (t, 1.0)).collect();
+        let invalid = [
+            ("max_tokens", InferenceParams { max_tokens: usize::MAX, ..base() }),
+            ("temperature", InferenceParams { temperature: f32::NAN, ..base() }),
+            ("top_p", InferenceParams { top_p: f32::NAN, ..base() }),
+            ("repetition_penalty", InferenceParams { repetition_penalty: f32::NAN, ..base() }),
+            ("presence_penalty", InferenceParams { presence_penalty: -2.01, ..base() }),
+            ("presence_penalty", InferenceParams { presence_penalty: f32::NAN, ..base() }),
+            ("frequency_penalty", InferenceParams { frequency_penalty: 2.01, ..base() }),
+            ("min_p", InferenceParams { min_p: -0.01, ..base() }),
+            ("min_p", InferenceParams { min_p: f32::NAN, ..base() }),
+            ("typical_p", InferenceParams { typical_p: 1.01, ..base() }),
+            ("typical_p", InferenceParams { typical_p: f32::NAN, ..base() }),
+            ("mirostat", InferenceParams { mirostat: u8::MAX, ..base() }),
+            ("mirostat_tau", InferenceParams { mirostat_tau: f32::INFINITY, ..base() }),
+            ("mirostat_eta", InferenceParams { mirostat_eta: 0.0, ..base() }),
+            ("mirostat_eta", InferenceParams { mirostat_eta: f32::NAN, ..base() }),
+            ("logit_bias", InferenceParams { logit_bias: bias(-100.5), ..base() }),
+            ("logit_bias", InferenceParams { logit_bias: bias(f32::NAN), ..base() }),
+            ("logit_bias", InferenceParams { logit_bias: too_many_biases, ..base() }),
+        ];
+        for (field, params) in invalid {
+            match params.validate() {
+                Err(InferenceError::InvalidParams(msg)) => assert!(msg.contains(field), "{msg}"),
+                other => panic!("{field}: expected InvalidParams, got {:?}", other),
+            }
+        }
+
+        // Bounds are inclusive where documented
+        let valid = [
+            InferenceParams { repetition_penalty: 1.0, ..base() },
+            InferenceParams { presence_penalty: -2.0, frequency_penalty: 2.0, ..base() },
+            InferenceParams { penalty_last_n: -1, ..base() },
+            InferenceParams { min_p: 1.0, ..base() },
+            InferenceParams { typical_p: 1.0, ..base() },
+            InferenceParams { mirostat: 1, ..base() },
+            InferenceParams { logit_bias: bias(-MAX_LOGIT_BIAS), ..base() },
+            InferenceParams { logit_bias: bias(MAX_LOGIT_BIAS), ..base() },
+        ];
+        for params in valid {
+            assert!(params.validate().is_ok(), "{:?}", params);
+        }
+    }
+
+    #[test]
+    fn inference_params_to_config_carries_every_field() {
+        let params = InferenceParams {
+            max_tokens: 12,
+            temperature: 0.3,
+            top_p: 0.8,
+            top_k: 5,
+            stream: false,
+            timeout_ms: Some(1_500),
+            seed: Some(99),
+            repetition_penalty: 1.2,
+            presence_penalty: -0.4,
+            frequency_penalty: 0.6,
+            penalty_last_n: 0,
+            min_p: 0.1,
+            typical_p: 0.7,
+            mirostat: 1,
+            mirostat_tau: 4.0,
+            mirostat_eta: 0.3,
+            logit_bias: BTreeMap::from([(3, 1.5)]),
+            stop: vec![StopSequence::Text("END".into()), StopSequence::Token(2)],
+            grammar: Some(r#"root ::= "a""#.into()),
+            json_schema: None,
+            context_strategy: ContextStrategy::TruncateLeft,
+            conversation_id: Some("c1".into()),
+            logprobs: Some(2),
+            n: 2,
+            best_of: Some(3),
+            prompt_lookup: true,
+            adapter: Some("style".into()),
+            adapter_scale: 0.5,
+        };
+        assert!(params.validate().is_ok());
+
+        let config = params.to_config();
+        assert_eq!(config.max_tokens, Some(12));
+        assert_eq!(config.temperature, 0.3);
+        assert_eq!(config.top_p, 0.8);
+        assert_eq!(config.top_k, 5);
+        assert_eq!(config.timeout_ms, 1_500);
+        assert_eq!(config.seed, 99);
+        assert_eq!(config.repetition_penalty, 1.2);
+        assert_eq!(config.presence_penalty, -0.4);
+        assert_eq!(config.frequency_penalty, 0.6);
+        assert_eq!(config.penalty_last_n, 0);
+        assert_eq!(config.min_p, 0.1);
+        assert_eq!(config.typical_p, 0.7);
+        assert_eq!(config.mirostat, Mirostat::V1);
+        assert_eq!(config.mirostat_tau, 4.0);
+        assert_eq!(config.mirostat_eta, 0.3);
+        assert_eq!(config.logit_bias, vec![(3, 1.5)]);
+        assert_eq!(config.stop, params.stop);
+        assert!(matches!(config.constraint, Some(OutputConstraint::Grammar(_))));
+        assert_eq!(config.context_strategy, ContextStrategy::TruncateLeft);
+        assert_eq!(config.conversation.as_deref(), Some("c1"));
+        assert_eq!(config.logprobs, Some(2));
+        assert_eq!((config.n, config.best_of), (2, 3));
+        assert!(config.prompt_lookup);
+        let adapter = config.adapter.unwrap();
+        assert_eq!((adapter.name.as_str(), adapter.scale), ("style", 0.5));
+    }
+
+    #[test]
+    fn inference_params_sampling_fields_default_when_omitted() {
+        let json = r#"{"max_tokens":16,"temperature":0.5,"top_p":1.0,"top_k":0}"#;
+        let params: InferenceParams = serde_json::from_str(json).unwrap();
+        assert!(params.validate().is_ok());
+
+        let config = params.to_config();
+        assert_eq!(config.seed, DEFAULT_SEED);
+        assert_eq!(config.repetition_penalty, 1.1);
+        assert_eq!(config.penalty_last_n, 64);
+        assert_eq!(config.typical_p, 1.0);
+        assert_eq!(config.mirostat, Mirostat::Disabled);
+        assert!(config.logit_bias.is_empty());
+        assert!(!config.prompt_lookup);
+
+        let json = r#"{"max_tokens":16,"temperature":0.5,"top_p":1.0,"top_k":0,
+            "prompt_lookup":true}"#;
+        let params: InferenceParams = serde_json::from_str(json).unwrap();
+        assert!(params.to_config().prompt_lookup);
+    }
+
+    #[test]
+    fn inference_params_to_config_maps_sampling_fields() {
+        let json = r#"{
+            "max_tokens": 16, "temperature": 0.5, "top_p": 1.0, "top_k": 0,
+            "seed": 7, "repetition_penalty": 1.3, "presence_penalty": 0.5,
+            "frequency_penalty": -0.5, "penalty_last_n": -1, "min_p": 0.05,
+            "typical_p": 0.9, "mirostat": 2, "mirostat_tau": 3.0,
+            "mirostat_eta": 0.2, "logit_bias": {"15": -100, "2": 5.5}
+        }"#;
+        let params: InferenceParams = serde_json::from_str(json).unwrap();
+        assert!(params.validate().is_ok());
+
+        let config = params.to_config();
+        assert_eq!(config.seed, 7);
+        assert_eq!(config.repetition_penalty, 1.3);
+        assert_eq!(config.presence_penalty, 0.5);
+        assert_eq!(config.frequency_penalty, -0.5);
+        assert_eq!(config.penalty_last_n, -1);
+        assert_eq!(config.min_p, 0.05);
+        assert_eq!(config.typical_p, 0.9);
+        assert_eq!(config.mirostat, Mirostat::V2);
+        assert_eq!(config.mirostat_tau, 3.0);
+        assert_eq!(config.mirostat_eta, 0.2);
+        assert_eq!(config.logit_bias, vec![(2, 5.5), (15, -100.0)]);
+    }
+
+    #[test]
+    fn inference_params_constraint_maps_to_config() {
+        let json = r#"{
+            "max_tokens": 16, "temperature": 0.5, "top_p": 1.0, "top_k": 0,
+            "json_schema": {"type": "object", "properties": {"ok": {"type": "boolean"}}}
+        }"#;
+        let params: InferenceParams = serde_json::from_str(json).unwrap();
+        assert!(params.validate().is_ok());
+        assert!(params.to_config().constraint.is_some_and(|c| c.is_json()));
+
+        let grammar = InferenceParams {
+            grammar: Some(r#"root ::= "yes" | "no""#.into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            grammar.to_config().constraint,
+            Some(OutputConstraint::Grammar(_))
+        ));
+        assert!(InferenceParams::default().to_config().constraint.is_none());
+    }
+
+    #[test]
+    fn inference_params_rejects_unsupported_constraint() {
+        let unsupported = InferenceParams {
+            json_schema: Some(serde_json::json!({"type": "integer", "minimum": 1})),
+            ..Default::default()
+        };
+        assert!(matches!(
+            unsupported.validate(),
+            Err(InferenceError::UnsupportedConstraint(ConstraintError::UnsupportedSchema { .. }))
+        ));
+
+        let both = InferenceParams {
+            grammar: Some(r#"root ::= "x""#.into()),
+            json_schema: Some(serde_json::json!({"type": "string"})),
+            ..Default::default()
+        };
+        assert!(matches!(
+            both.validate(),
+            Err(InferenceError::UnsupportedConstraint(ConstraintError::Conflicting))
+        ));
+    }
+
+    #[test]
+    fn inference_params_conversation_id_is_bounded() {
+        let params: InferenceParams = serde_json::from_value(serde_json::json!({
+            "max_tokens": 16, "temperature": 0.7, "top_p": 0.9, "top_k": 40,
+            "conversation_id": "support-chat"
+        }))
+        .unwrap();
+        assert!(params.validate().is_ok());
+        assert_eq!(params.to_config().conversation.as_deref(), Some("support-chat"));
+
+        for id in [String::new(), "x".repeat(MAX_CONVERSATION_ID_BYTES + 1)] {
+            let params = InferenceParams { conversation_id: Some(id), ..Default::default() };
+            assert!(matches!(params.validate(), Err(InferenceError::InvalidParams(_))));
+        }
+    }
+
+    #[test]
+    fn inference_params_logprobs_are_bounded() {
+        let params = InferenceParams { logprobs: Some(MAX_TOP_LOGPROBS), ..Default::default() };
+        assert!(params.validate().is_ok());
+        assert_eq!(params.to_config().logprobs, Some(MAX_TOP_LOGPROBS));
+
+        let params = InferenceParams { logprobs: Some(MAX_TOP_LOGPROBS + 1), ..Default::default() };
+        assert!(matches!(params.validate(), Err(InferenceError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn inference_params_best_of_covers_n() {
+        let params = InferenceParams { n: 3, ..Default::default() };
+        assert!(params.validate().is_ok());
+        let config = params.to_config();
+        assert_eq!((config.n, config.best_of), (3, 3));
+
+        let params = InferenceParams { n: 2, best_of: Some(5), ..Default::default() };
+        assert_eq!(params.to_config().best_of, 5);
+
+        for params in [
+            InferenceParams { n: 0, ..Default::default() },
+            InferenceParams { n: 3, best_of: Some(2), ..Default::default() },
+            InferenceParams { best_of: Some(MAX_COMPLETIONS + 1), ..Default::default() },
+            InferenceParams { n: 2, stream: true, ..Default::default() },
+        ] {
+            assert!(matches!(params.validate(), Err(InferenceError::InvalidParams(_))));
+        }
+    }
+
     #[tokio::test]
     async fn engine_new_creates_empty_engine() {
         let engine = InferenceEngine::new(4096);
@@ -299,6 +1269,163 @@ mod tests {
         assert!(matches!(result, Err(InferenceError::ModelNotLoaded(_))));
     }
 
+    #[tokio::test]
+    async fn engine_run_chat_fails_for_unloaded_model() {
+        use crate::engine::ChatRole;
+
+        let engine = InferenceEngine::new(4096);
+        let params = InferenceParams::default();
+        let messages = vec![ChatMessage { role: ChatRole::User, content: "hi".into() }];
+        let result = engine.run_chat("missing-model", &messages, &params).await;
+        assert!(matches!(result, Err(InferenceError::ModelNotLoaded(_))));
+    }
+
+    #[tokio::test]
+    async fn engine_dispatches_by_capability() {
+        use crate::engine::OnnxEmbedder;
+
+        let engine = InferenceEngine::new(4096);
+        let embedder = Arc::new(OnnxEmbedder::new("minilm".into(), 384));
+        engine
+            .register_onnx_model("minilm".into(), ModelHandle::new(1), embedder)
+            .await;
+
+        assert_eq!(
+            engine.model_capabilities("minilm").await,
+            Some(vec![InferenceCapability::Embedding])
+        );
+        let texts = vec!["hello".to_string()];
+        let result = engine.classify("minilm", &texts).await;
+        assert!(matches!(result, Err(InferenceError::CapabilityNotSupported(_))));
+        let result = engine.run("minilm", "hello", &InferenceParams::default()).await;
+        assert!(matches!(result, Err(InferenceError::CapabilityNotSupported(_))));
+
+        // Capability matches; the embedder itself has no encoder loaded
+        let result = engine.embed("minilm", &texts).await;
+        assert!(matches!(result, Err(InferenceError::ExecutionFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn engine_embed_rejects_invalid_batch() {
+        use crate::engine::OnnxEmbedder;
+
+        let engine = InferenceEngine::new(4096);
+        let embedder = Arc::new(OnnxEmbedder::new("minilm".into(), 384));
+        engine
+            .register_onnx_model("minilm".into(), ModelHandle::new(1), embedder)
+            .await;
+
+        let result = engine.embed("minilm", &[String::new()]).await;
+        assert!(matches!(result, Err(InferenceError::InvalidParams(_))));
+        let result = engine.embed("minilm", &["x".repeat(5000)]).await;
+        assert!(matches!(result, Err(InferenceError::InvalidParams(_))));
+    }
+
+    /// One token per character in an 8-token context window.
+    struct CharWindow;
+
+    #[async_trait::async_trait]
+    impl GgufModel for CharWindow {
+        fn model_id(&self) -> &str {
+            "char-window"
+        }
+
+        fn capabilities(&self) -> &[InferenceCapability] {
+            &[InferenceCapability::TextGeneration]
+        }
+
+        fn memory_usage(&self) -> usize {
+            0
+        }
+
+        async fn infer(
+            &self,
+            input: &InferenceInput,
+            config: &InferenceConfig,
+        ) -> Result<InferenceOutput, crate::engine::InferenceError> {
+            let prompt_tokens = input.joined_text().chars().count();
+            let max_tokens = config.max_tokens.unwrap_or(0);
+            let fit = crate::engine::context::fit_tokens(
+                prompt_tokens,
+                max_tokens,
+                8,
+                config.context_strategy,
+            )?;
+            Ok(InferenceOutput::Generation(crate::engine::GenerationResult {
+                text: String::new(),
+                tokens_generated: fit.max_tokens,
+                finish_reason: FinishReason::MaxTokens,
+                prompt_tokens: (prompt_tokens - fit.drop_tokens) as u32,
+                timings: GenerationTimings::default(),
+                stop_sequence: None,
+                logprobs: Vec::new(),
+                choices: Vec::new(),
+            }))
+        }
+
+        async fn unload(&mut self) -> Result<(), crate::engine::InferenceError> {
+            Ok(())
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    #[tokio::test]
+    async fn engine_checks_context_in_tokens() {
+        // The byte limit no longer applies to generation prompts
+        let engine = InferenceEngine::new(4);
+        engine
+            .register_model("char-window".into(), ModelHandle::new(1), Arc::new(CharWindow))
+            .await;
+
+        let params = InferenceParams { max_tokens: 4, ..Default::default() };
+        let result = engine.run("char-window", "ééé", &params).await.unwrap();
+        assert_eq!(result.prompt_tokens, 3);
+
+        let params = InferenceParams { max_tokens: 6, ..Default::default() };
+        let result = engine.run("char-window", "ééé", &params).await;
+        assert!(matches!(
+            result,
+            Err(InferenceError::ContextExceeded { n_ctx: 8, prompt_tokens: 3, max_tokens: 6 })
+        ));
+
+        let clamp = InferenceParams {
+            context_strategy: ContextStrategy::ClampMaxTokens,
+            ..params.clone()
+        };
+        let result = engine.run("char-window", "ééé", &clamp).await.unwrap();
+        assert_eq!(result.tokens_generated, 5);
+
+        let truncate =
+            InferenceParams { context_strategy: ContextStrategy::TruncateLeft, ..params };
+        let result = engine.run("char-window", "ééé", &truncate).await.unwrap();
+        assert_eq!((result.prompt_tokens, result.tokens_generated), (2, 6));
+    }
+
+    #[tokio::test]
+    async fn engine_decodes_normally_without_usable_draft() {
+        let engine = InferenceEngine::new(4096)
+            .with_draft_models(HashMap::from([("char-window".into(), "missing".into())]));
+        engine
+            .register_model("char-window".into(), ModelHandle::new(1), Arc::new(CharWindow))
+            .await;
+        let params = InferenceParams { max_tokens: 4, ..Default::default() };
+        assert_eq!(engine.run("char-window", "abc", &params).await.unwrap().prompt_tokens, 3);
+
+        // A draft that is not a llama.cpp generator cannot propose tokens
+        engine
+            .register_model("draft".into(), ModelHandle::new(2), Arc::new(CharWindow))
+            .await;
+        engine.set_draft_model("char-window", Some("draft".into())).await;
+        assert_eq!(engine.draft_model("char-window").await.as_deref(), Some("draft"));
+        assert_eq!(engine.run("char-window", "abc", &params).await.unwrap().prompt_tokens, 3);
+
+        engine.set_draft_model("char-window", None).await;
+        assert_eq!(engine.draft_model("char-window").await, None);
+    }
+
     #[tokio::test]
     async fn engine_run_by_handle_fails_for_unknown_handle() {
         let engine = InferenceEngine::new(4096);
@@ -11,6 +11,10 @@ pub const MAX_TEXT_BYTES: usize = 65_536;
 /// Maximum batch size for batch operations.
 pub const MAX_BATCH_SIZE: usize = 32;
 
+/// Maximum documents per rerank request; models score them in batches of
+/// `MAX_BATCH_SIZE`.
+pub const MAX_RERANK_DOCUMENTS: usize = 256;
+
 /// Maximum token count per input.
 pub const MAX_INPUT_TOKENS: usize = 4096;
 
@@ -26,14 +30,15 @@ pub enum InferenceInput {
 }
 
 /// A single message in a chat conversation.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ChatMessage {
     pub role: ChatRole,
     pub content: String,
 }
 
 /// Typed chat roles — prevents invalid role strings at compile time.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ChatRole {
     System,
     User,
@@ -58,6 +63,42 @@ impl InferenceInput {
             Self::ChatMessages(m) => m.iter().map(|m| m.content.len()).sum(),
         }
     }
+
+    /// All input text joined by newlines (for scanning and queueing).
+    pub fn joined_text(&self) -> String {
+        match self {
+            Self::Text(t) => t.clone(),
+            Self::TextBatch(b) => b.join("\n"),
+            Self::ChatMessages(m) => m
+                .iter()
+                .map(|m| m.content.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Validate a rerank request: a query and 1 to `MAX_RERANK_DOCUMENTS`
+/// documents, each held to the same limits as text inputs.
+pub fn validate_rerank(query: &str, documents: &[String]) -> Result<(), InferenceError> {
+    validate_text(query)
+        .map_err(|e| InferenceError::InputValidation(format!("query: {}", e)))?;
+    if documents.is_empty() {
+        return Err(InferenceError::InputValidation("documents cannot be empty".into()));
+    }
+    if documents.len() > MAX_RERANK_DOCUMENTS {
+        return Err(InferenceError::InputValidation(format!(
+            "documents exceed maximum count: {} > {}",
+            documents.len(),
+            MAX_RERANK_DOCUMENTS
+        )));
+    }
+    for (i, document) in documents.iter().enumerate() {
+        validate_text(document).map_err(|e| {
+            InferenceError::InputValidation(format!("document {}: {}", i, e))
+        })?;
+    }
+    Ok(())
 }
 
 fn validate_text(text: &str) -> Result<(), InferenceError> {
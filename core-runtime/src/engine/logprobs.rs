@@ -0,0 +1,114 @@
+//! Per-token log-probabilities for generated text.
+//!
+//! Log-probabilities are taken from the softmax of the model's raw logits,
+//! before temperature, penalties or any sampling filter, so they describe
+//! the model rather than the sampler settings of one request.
+
+use serde::{Deserialize, Serialize};
+
+/// Most alternatives that can be requested per generated token.
+pub const MAX_TOP_LOGPROBS: usize = 20;
+
+/// A candidate token and its log-probability.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenCandidate {
+    pub token: u32,
+    /// Token text; a piece of a multi-byte character decodes lossily.
+    /// Omitted when the output was (or, streamed, may be) redacted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    pub logprob: f32,
+}
+
+/// Log-probability of a generated token and the most likely tokens at its
+/// position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    pub logprob: f32,
+    /// Most likely tokens, most likely first. May include the chosen token.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top: Vec<TokenCandidate>,
+}
+
+impl TokenLogprob {
+    /// Drop token text, keeping ids and log-probabilities.
+    pub fn without_text(mut self) -> Self {
+        self.text = None;
+        for candidate in &mut self.top {
+            candidate.text = None;
+        }
+        self
+    }
+}
+
+/// Log-probability of `chosen` and the `top_n` most likely tokens, most
+/// likely first, under the softmax of `logits`.
+pub fn token_logprobs(logits: &[f32], chosen: u32, top_n: usize) -> (f32, Vec<(u32, f32)>) {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum: f64 = logits.iter().map(|&l| f64::from(l - max).exp()).sum();
+    // Subtract max and log-sum separately so large logits keep precision
+    let logprob = |logit: f32| (logit - max) - sum.ln() as f32;
+
+    // Keep the top_n logits seen so far, sorted descending
+    let mut top: Vec<(u32, f32)> = Vec::with_capacity(top_n + 1);
+    for (id, &logit) in logits.iter().enumerate() {
+        if top.len() == top_n && top.last().is_none_or(|&(_, l)| logit <= l) {
+            continue;
+        }
+        let at = top.partition_point(|&(_, l)| l >= logit);
+        top.insert(at, (id as u32, logit));
+        top.truncate(top_n);
+    }
+    let chosen = logits.get(chosen as usize).map_or(f32::NEG_INFINITY, |&l| logprob(l));
+    let top = top.into_iter().map(|(id, l)| (id, logprob(l))).collect();
+    (chosen, top)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logprobs_are_normalized() {
+        let logits = [1.0f32, 2.0, 3.0, 0.5];
+        let (_, top) = token_logprobs(&logits, 0, logits.len());
+        let total: f32 = top.iter().map(|(_, lp)| lp.exp()).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn top_alternatives_are_sorted_and_bounded() {
+        let logits = [0.1f32, 5.0, -1.0, 3.0, 4.0];
+        let (chosen, top) = token_logprobs(&logits, 3, 2);
+        let ids: Vec<u32> = top.iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, [1, 4]);
+        assert!(top[0].1 > top[1].1 && top[1].1 > chosen);
+
+        let (_, none) = token_logprobs(&logits, 3, 0);
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn large_logits_do_not_overflow() {
+        let logits = [1000.0f32, 1000.0];
+        let (chosen, _) = token_logprobs(&logits, 1, 0);
+        assert!((chosen - 0.5f32.ln()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn without_text_keeps_ids_and_logprobs() {
+        let entry = TokenLogprob {
+            token: 7,
+            text: Some("secret".into()),
+            logprob: -0.5,
+            top: vec![TokenCandidate { token: 7, text: Some("secret".into()), logprob: -0.5 }],
+        };
+        let redacted = entry.without_text();
+        assert_eq!(redacted.text, None);
+        assert_eq!(redacted.top[0].text, None);
+        assert_eq!((redacted.top[0].token, redacted.top[0].logprob), (7, -0.5));
+    }
+}
@@ -3,7 +3,10 @@
 //! Handles tokenization, inference execution, and token streaming.
 //! Provides the `InferenceModel` trait and supporting types.
 
+pub mod chat_template;
 pub mod config;
+pub mod constraint;
+pub mod context;
 pub mod decode;
 pub mod error;
 pub mod filter;
@@ -12,9 +15,12 @@ pub mod flash_attn_gpu;
 pub mod gguf;
 pub mod gpu;
 pub mod input;
+pub mod logprobs;
+pub mod native;
 pub mod onnx;
 pub mod output;
 pub mod prefill;
+pub mod prompt_lookup;
 pub mod quantize;
 pub mod simd_matmul;
 mod simd_neon;
@@ -22,6 +28,7 @@ pub mod simd_tokenizer;
 pub mod simd_tokenizer_v2;
 pub mod speculative;
 pub mod speculative_v2;
+pub mod stop;
 
 // GPU backend modules (conditionally compiled)
 #[cfg(feature = "cuda")]
@@ -35,18 +42,30 @@ pub mod inference;
 mod streaming;
 mod tokenizer;
 
-pub use config::InferenceConfig;
+pub use chat_template::ChatTemplate;
+pub use config::{AdapterSelection, InferenceConfig, Mirostat};
+pub use constraint::{ConstraintError, OutputConstraint, GRAMMAR_ROOT, MAX_GRAMMAR_BYTES};
+pub use context::{ContextFit, ContextStrategy};
 pub use decode::{DecodeConfig, DecodeExecutor, DecodeStepResult};
 pub use error::InferenceError;
 pub use filter::{FilterConfig, OutputFilter};
 pub use flash_attn::{FlashAttn, FlashAttnConfig};
 pub use flash_attn_gpu::{FlashAttnGpuConfig, FlashAttnGpuError, FlashAttnGpuKernel};
-pub use inference::{InferenceEngine, InferenceParams, InferenceResult};
-pub use input::{ChatMessage, ChatRole, InferenceInput};
-pub use input::{MAX_BATCH_SIZE, MAX_INPUT_TOKENS, MAX_TEXT_BYTES};
-pub use output::{ClassificationResult, EmbeddingResult, EntityResult};
-pub use output::{FinishReason, GenerationResult, InferenceOutput};
+pub use inference::{
+    EngineModel, InferenceEngine, InferenceParams, InferenceResult, DEFAULT_SEED,
+    MAX_ADAPTER_NAME_BYTES, MAX_ADAPTER_SCALE, MAX_COMPLETIONS, MAX_CONVERSATION_ID_BYTES,
+    MAX_LOGIT_BIAS, MAX_LOGIT_BIAS_ENTRIES,
+};
+pub use input::{validate_rerank, ChatMessage, ChatRole, InferenceInput};
+pub use input::{MAX_BATCH_SIZE, MAX_INPUT_TOKENS, MAX_RERANK_DOCUMENTS, MAX_TEXT_BYTES};
+pub use logprobs::{TokenCandidate, TokenLogprob, MAX_TOP_LOGPROBS};
+pub use native::{
+    NativeConfig, NativeFixture, NativeGenerator, NativeModel, NativeSession, NativeTokenizer,
+};
+pub use output::{ClassificationResult, Completion, EmbeddingResult, EntityResult, RerankResult};
+pub use output::{FinishReason, GenerationResult, GenerationTimings, InferenceOutput};
 pub use prefill::{PrefillConfig, PrefillExecutor, PrefillResult};
+pub use prompt_lookup::PromptLookup;
 pub use quantize::{QuantFormat, QuantizedTensor, QUANT_BLOCK_SIZE};
 pub use simd_matmul::{dot_q4, dot_q8, init_simd};
 pub use simd_tokenizer::SimdTokenizer;
@@ -60,15 +79,23 @@ pub use speculative_v2::{
     SpeculativeConfig as SpeculativeV2Config, SpeculativeDecoder as SpeculativeV2Decoder,
     SpeculativeStats,
 };
-pub use streaming::{StreamingOutput, TokenStream, TokenStreamSender};
+pub use stop::{StopMatcher, StopSequence, MAX_STOP_SEQUENCES, MAX_STOP_SEQUENCE_BYTES};
+pub use streaming::{
+    StreamFinish, StreamingOutput, TokenStream, TokenStreamSender, Utf8StreamDecoder,
+};
 pub use tokenizer::{TokenizerError, TokenizerWrapper};
 
 // Backend re-exports
-pub use gguf::{GgufConfig, GgufGenerator, GgufModel};
+pub use gguf::{
+    GgufBackend, GgufConfig, GgufEmbedder, GgufGenerator, GgufModel, GgufPooling, GgufReranker,
+    NgramFixture, NgramModel,
+};
 #[cfg(feature = "gguf")]
 pub use gguf::LlamaBackendInner;
 pub use gpu::{GpuBackend, GpuConfig, GpuDevice, GpuError, GpuManager, GpuMemory, GpuMemoryPool};
-pub use onnx::{OnnxClassifier, OnnxConfig, OnnxEmbedder, OnnxModel};
+pub use onnx::{
+    OnnxClassifier, OnnxConfig, OnnxEmbedder, OnnxModel, OnnxPooling, OnnxReranker, OnnxTask,
+};
 
 // CUDA backend re-exports
 #[cfg(feature = "cuda")]
@@ -102,4 +129,6 @@ pub enum InferenceCapability {
     TextGeneration,
     Embedding,
     NamedEntityRecognition,
+    /// Relevance scores of documents for a query (cross-encoders).
+    Rerank,
 }
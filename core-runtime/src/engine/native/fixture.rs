@@ -0,0 +1,204 @@
+//! Tiny native models for tests.
+//!
+//! [`NativeFixture`] writes a two-layer Llama GGUF file with F32 weights
+//! drawn from a seeded pattern and a SentencePiece vocabulary: `<unk>`,
+//! `<s>`, `</s>`, the ChatML control tokens, a byte token per byte and
+//! every prefix of each of the fixture's words. Outputs are meaningless but
+//! depend only on the fixture and the request, so the native backend can be
+//! tested without a real model file.
+
+use std::path::Path;
+
+use crate::engine::gguf::metadata::GGUF_MAGIC;
+use crate::engine::gguf::GgmlType;
+
+const N_EMBD: usize = 32;
+const N_HEAD: usize = 4;
+const N_HEAD_KV: usize = 2;
+const N_FF: usize = 64;
+const N_LAYER: usize = 2;
+const ALIGNMENT: usize = 32;
+
+/// `tokenizer.ggml.token_type` of each kind of token.
+const NORMAL: i32 = 1;
+const UNKNOWN: i32 = 2;
+const CONTROL: i32 = 3;
+const BYTE: i32 = 6;
+
+/// Contents of a native test model file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeFixture {
+    pub name: String,
+    /// Words with vocabulary entries, each with a leading space.
+    pub words: Vec<String>,
+    pub context_length: u32,
+    /// Chat template name or Jinja template; ChatML when absent.
+    pub chat_template: Option<String>,
+    /// Varies the weights.
+    pub seed: u32,
+}
+
+impl Default for NativeFixture {
+    fn default() -> Self {
+        Self {
+            name: "native".into(),
+            words: ["the", "quick", "brown", "fox", "jumps", "over", "lazy", "dog"]
+                .map(String::from)
+                .to_vec(),
+            context_length: 256,
+            chat_template: None,
+            seed: 0,
+        }
+    }
+}
+
+impl NativeFixture {
+    /// Vocabulary pieces, their token types and scores. Longer pieces
+    /// score higher, so whole words win.
+    fn vocab(&self) -> Vec<(String, i32, f32)> {
+        let mut vocab = vec![
+            ("<unk>".to_string(), UNKNOWN, 0.0),
+            ("<s>".to_string(), CONTROL, 0.0),
+            ("</s>".to_string(), CONTROL, 0.0),
+            ("<|im_start|>".to_string(), CONTROL, 0.0),
+            ("<|im_end|>".to_string(), CONTROL, 0.0),
+        ];
+        vocab.extend((0..=255u8).map(|b| (format!("<0x{b:02X}>"), BYTE, 0.0)));
+        vocab.push(("\u{2581}".to_string(), NORMAL, 0.0));
+        for word in &self.words {
+            let piece = format!("\u{2581}{word}");
+            for (end, _) in piece.char_indices().skip(1).chain([(piece.len(), ' ')]) {
+                let prefix = &piece[..end];
+                if !vocab.iter().any(|(p, ..)| p == prefix) {
+                    vocab.push((prefix.to_string(), NORMAL, prefix.chars().count() as f32));
+                }
+            }
+        }
+        vocab
+    }
+
+    /// Tokens in the fixture's vocabulary.
+    pub fn n_vocab(&self) -> usize {
+        self.vocab().len()
+    }
+
+    /// Encode the fixture as a GGUF file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let vocab = self.vocab();
+        let mut keys = Vec::new();
+        let mut count = 0u64;
+        let mut key = |keys: &mut Vec<u8>, name: &str, value_type: u32| {
+            put_string(keys, name);
+            keys.extend_from_slice(&value_type.to_le_bytes());
+            count += 1;
+        };
+        let strings = [
+            ("general.architecture", "llama"),
+            ("general.name", self.name.as_str()),
+            ("tokenizer.ggml.model", "llama"),
+        ];
+        let template = self.chat_template.as_deref().map(|t| ("tokenizer.chat_template", t));
+        for (name, value) in strings.into_iter().chain(template) {
+            key(&mut keys, name, 8);
+            put_string(&mut keys, value);
+        }
+        let numbers = [
+            ("llama.embedding_length", N_EMBD as u32),
+            ("llama.block_count", N_LAYER as u32),
+            ("llama.feed_forward_length", N_FF as u32),
+            ("llama.attention.head_count", N_HEAD as u32),
+            ("llama.attention.head_count_kv", N_HEAD_KV as u32),
+            ("llama.context_length", self.context_length),
+            ("tokenizer.ggml.unknown_token_id", 0),
+            ("tokenizer.ggml.bos_token_id", 1),
+            ("tokenizer.ggml.eos_token_id", 2),
+        ];
+        for (name, value) in numbers {
+            key(&mut keys, name, 4);
+            keys.extend_from_slice(&value.to_le_bytes());
+        }
+        key(&mut keys, "llama.attention.layer_norm_rms_epsilon", 6);
+        keys.extend_from_slice(&1e-5f32.to_le_bytes());
+
+        // Arrays: value type 9, then element type and count
+        key(&mut keys, "tokenizer.ggml.tokens", 9);
+        keys.extend_from_slice(&8u32.to_le_bytes());
+        keys.extend_from_slice(&(vocab.len() as u64).to_le_bytes());
+        vocab.iter().for_each(|(piece, ..)| put_string(&mut keys, piece));
+        key(&mut keys, "tokenizer.ggml.token_type", 9);
+        keys.extend_from_slice(&5u32.to_le_bytes());
+        keys.extend_from_slice(&(vocab.len() as u64).to_le_bytes());
+        vocab.iter().for_each(|(_, kind, _)| keys.extend_from_slice(&kind.to_le_bytes()));
+        key(&mut keys, "tokenizer.ggml.scores", 9);
+        keys.extend_from_slice(&6u32.to_le_bytes());
+        keys.extend_from_slice(&(vocab.len() as u64).to_le_bytes());
+        vocab.iter().for_each(|(.., score)| keys.extend_from_slice(&score.to_le_bytes()));
+
+        let tensors = self.tensors(vocab.len());
+        let mut table = Vec::new();
+        let mut data = Vec::new();
+        for (name, shape, values) in &tensors {
+            put_string(&mut table, name);
+            table.extend_from_slice(&(shape.len() as u32).to_le_bytes());
+            shape.iter().for_each(|&d| table.extend_from_slice(&(d as u64).to_le_bytes()));
+            table.extend_from_slice(&GgmlType::F32.0.to_le_bytes());
+            table.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            values.iter().for_each(|v| data.extend_from_slice(&v.to_le_bytes()));
+            data.resize(data.len().next_multiple_of(ALIGNMENT), 0);
+        }
+
+        let mut bytes = GGUF_MAGIC.to_vec();
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&(tensors.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend(keys);
+        bytes.extend(table);
+        bytes.resize(bytes.len().next_multiple_of(ALIGNMENT), 0);
+        bytes.extend(data);
+        bytes
+    }
+
+    /// Weights by tensor name, with GGUF shapes (columns first).
+    fn tensors(&self, n_vocab: usize) -> Vec<(String, Vec<usize>, Vec<f32>)> {
+        let kv_dim = N_HEAD_KV * (N_EMBD / N_HEAD);
+        let mut index = 0;
+        let mut tensor = |name: String, shape: &[usize], scale: f32| {
+            index += 1;
+            let offset = index as f32 * 78.233 + self.seed as f32 * 39.425;
+            let values = (0..shape.iter().product())
+                .map(|i: usize| ((i as f32 + 1.0) * 12.9898 + offset).sin() * scale)
+                .collect();
+            (name, shape.to_vec(), values)
+        };
+        let mut tensors = vec![
+            tensor("token_embd.weight".into(), &[N_EMBD, n_vocab], 1.0),
+            tensor("output_norm.weight".into(), &[N_EMBD], 1.0),
+            tensor("output.weight".into(), &[N_EMBD, n_vocab], 0.5),
+        ];
+        for i in 0..N_LAYER {
+            let name = |suffix: &str| format!("blk.{i}.{suffix}");
+            tensors.extend([
+                tensor(name("attn_norm.weight"), &[N_EMBD], 1.0),
+                tensor(name("attn_q.weight"), &[N_EMBD, N_EMBD], 0.4),
+                tensor(name("attn_k.weight"), &[N_EMBD, kv_dim], 0.4),
+                tensor(name("attn_v.weight"), &[N_EMBD, kv_dim], 0.4),
+                tensor(name("attn_output.weight"), &[N_EMBD, N_EMBD], 0.2),
+                tensor(name("ffn_norm.weight"), &[N_EMBD], 1.0),
+                tensor(name("ffn_gate.weight"), &[N_EMBD, N_FF], 0.3),
+                tensor(name("ffn_up.weight"), &[N_EMBD, N_FF], 0.3),
+                tensor(name("ffn_down.weight"), &[N_FF, N_EMBD], 0.2),
+            ]);
+        }
+        tensors
+    }
+
+    /// Write the fixture to `path`.
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u64).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
@@ -0,0 +1,641 @@
+//! Text generation with the native backend.
+//!
+//! [`NativeGenerator`] serves a Llama-family GGUF model through the same
+//! [`GgufModel`] interface as llama.cpp models; `load_gguf_model` returns
+//! one when `GgufConfig::backend` is [`GgufBackend::Native`]. The prompt is
+//! evaluated by a [`PrefillExecutor`] a chunk at a time and each sampled
+//! token by a [`DecodeExecutor`].
+//!
+//! Sampling applies logit bias, the repetition, presence and frequency
+//! penalties over the prompt and output, temperature, top-k, top-p, min-p
+//! and the seed; mirostat and typical-p are ignored. `best_of` sequences
+//! are generated one after another, sequence `i` with seed `seed + i`.
+//! Constraints and LoRA adapters are rejected, KV state is not kept
+//! between requests of a conversation, and chat prompts only render
+//! built-in templates.
+//!
+//! [`GgufBackend::Native`]: crate::engine::gguf::GgufBackend::Native
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use super::model::{NativeConfig, NativeModel, NativeSession};
+use super::tokenizer::NativeTokenizer;
+use crate::engine::context::{fit_tokens, trim_chat};
+use crate::engine::gguf::ngram::SplitMix64;
+use crate::engine::gguf::{GgufConfig, GgufMetadata, GgufModel};
+use crate::engine::logprobs::token_logprobs;
+use crate::engine::{
+    ChatMessage, ChatTemplate, Completion, ContextStrategy, DecodeConfig, DecodeExecutor,
+    FinishReason, GenerationResult, GenerationTimings, InferenceCapability, InferenceConfig,
+    InferenceError, InferenceInput, InferenceOutput, PrefillConfig, PrefillExecutor, StopMatcher,
+    StopSequence, StreamFinish, StreamingOutput, TokenCandidate, TokenLogprob, TokenStreamSender,
+    Utf8StreamDecoder,
+};
+
+/// A GGUF generation model run by the native backend.
+pub struct NativeGenerator {
+    model_id: String,
+    /// None once unloaded.
+    model: Option<NativeModel>,
+    tokenizer: NativeTokenizer,
+    chat_template: ChatTemplate,
+    prefill: PrefillExecutor,
+    decode: DecodeConfig,
+}
+
+impl NativeGenerator {
+    /// Load the model at `path`, whose parsed header is `header`.
+    /// `config.n_ctx` (0 = the model's training context), `n_threads` and
+    /// `chat_template` apply; GPU offload does not.
+    pub fn load(
+        model_id: String,
+        path: &Path,
+        header: &GgufMetadata,
+        config: &GgufConfig,
+    ) -> Result<Self, InferenceError> {
+        if config.embedding || config.rerank {
+            return Err(InferenceError::ModelError(
+                "the native backend only serves generation models".into(),
+            ));
+        }
+        let tokenizer = NativeTokenizer::from_header(header)?;
+        let native_config = NativeConfig {
+            n_ctx: config.n_ctx as usize,
+            n_threads: config.n_threads as usize,
+            ..NativeConfig::default()
+        };
+        let model = NativeModel::load(path, &native_config)?;
+        let n_embd = model.hparams().n_embd;
+        if tokenizer.n_vocab() > model.hparams().n_vocab {
+            return Err(InferenceError::ModelError(format!(
+                "tokenizer has {} tokens but the model embeds {}",
+                tokenizer.n_vocab(),
+                model.hparams().n_vocab
+            )));
+        }
+        Ok(Self {
+            model_id,
+            model: Some(model),
+            chat_template: ChatTemplate::resolve(
+                config.chat_template.as_deref(),
+                header.chat_template(),
+            ),
+            prefill: PrefillExecutor::new(PrefillConfig {
+                hidden_dim: n_embd,
+                ..PrefillConfig::default()
+            }),
+            decode: DecodeConfig {
+                hidden_dim: n_embd,
+                eos_token: tokenizer.eos().unwrap_or_default(),
+                speculative: None,
+            },
+            tokenizer,
+        })
+    }
+
+    fn model(&self) -> Result<&NativeModel, InferenceError> {
+        self.model
+            .as_ref()
+            .ok_or_else(|| InferenceError::ModelError("model is unloaded".into()))
+    }
+
+    pub fn context_size(&self) -> usize {
+        self.model.as_ref().map_or(0, NativeModel::context_size)
+    }
+
+    pub fn tokenizer(&self) -> &NativeTokenizer {
+        &self.tokenizer
+    }
+
+    /// Render chat messages with the model's template. Under
+    /// `ContextStrategy::TrimMiddle` the oldest turns are dropped until the
+    /// prompt leaves room for `max_tokens`.
+    pub fn chat_prompt(
+        &self,
+        messages: &[ChatMessage],
+        config: &InferenceConfig,
+    ) -> Result<String, InferenceError> {
+        if config.context_strategy == ContextStrategy::TrimMiddle {
+            let budget = config.completion_budget();
+            let kept = trim_chat(messages, budget, self.context_size(), |kept| {
+                Ok(self.tokenizer.encode(&self.chat_template.render(kept)?).len())
+            })?;
+            return self.chat_template.render(&kept);
+        }
+        self.chat_template.render(messages)
+    }
+
+    fn prompt(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+    ) -> Result<String, InferenceError> {
+        let prompt = match input {
+            InferenceInput::Text(prompt) => prompt.clone(),
+            InferenceInput::ChatMessages(messages) => self.chat_prompt(messages, config)?,
+            InferenceInput::TextBatch(_) => {
+                return Err(InferenceError::CapabilityNotSupported(
+                    "batch generation not supported".into(),
+                ))
+            }
+        };
+        if prompt.is_empty() {
+            return Err(InferenceError::InputValidation("prompt cannot be empty".into()));
+        }
+        Ok(prompt)
+    }
+
+    /// Tokenize and fit the prompt; returns the tokens and the tokens each
+    /// sequence may generate.
+    fn start(
+        &self,
+        prompt: &str,
+        config: &InferenceConfig,
+    ) -> Result<(Vec<u32>, u32), InferenceError> {
+        if config.constraint.is_some() {
+            return Err(InferenceError::CapabilityNotSupported(
+                "native models do not support constrained output".into(),
+            ));
+        }
+        if config.adapter.is_some() {
+            return Err(InferenceError::CapabilityNotSupported(
+                "native models do not support LoRA adapters".into(),
+            ));
+        }
+        let mut tokens = self.tokenizer.encode(prompt);
+        let budget = config.completion_budget();
+        let fit = fit_tokens(tokens.len(), budget, self.context_size(), config.context_strategy)?;
+        tokens.drain(..fit.drop_tokens);
+        let sequences = u32::try_from(config.best_of.max(1)).unwrap_or(u32::MAX);
+        Ok((tokens, fit.max_tokens / sequences))
+    }
+
+    /// Start a sequence: evaluate the prompt, unless the request is aborted
+    /// first or may not generate anything.
+    fn sequence<'a>(
+        &self,
+        model: &'a NativeModel,
+        prompt: &[u32],
+        seed: u32,
+        config: &InferenceConfig,
+        max_tok: u32,
+        started: Instant,
+    ) -> Result<Sequence<'a>, InferenceError> {
+        let mut seq = Sequence {
+            session: model.session(),
+            decode: DecodeExecutor::new(self.decode.clone()),
+            logits: Vec::new(),
+            max_tok,
+            history: prompt.to_vec(),
+            rng: SplitMix64(seed.into()),
+            text: String::new(),
+            utf8: Utf8StreamDecoder::new(),
+            stops: StopMatcher::new(&config.stop),
+            sampled: 0,
+            generated: 0,
+            logprobs: Vec::new(),
+            cumulative_logprob: 0.0,
+            stop_sequence: None,
+            finish: None,
+        };
+        if max_tok == 0 {
+            seq.end(FinishReason::MaxTokens);
+            return Ok(seq);
+        }
+        let mut abort = None;
+        let logits = self.prefill.forward(&mut seq.session, prompt, || {
+            abort = config.abort_reason(started);
+            abort.is_none()
+        })?;
+        match logits {
+            Some(logits) => {
+                seq.logits = logits;
+                seq.decode.init(seq.session.position());
+            }
+            None => seq.end(abort.unwrap_or(FinishReason::Cancelled)),
+        }
+        Ok(seq)
+    }
+
+    /// Generate a completion, or `best_of` completions ranked by cumulative
+    /// log-probability.
+    pub fn generate(
+        &self,
+        prompt: &str,
+        config: &InferenceConfig,
+    ) -> Result<GenerationResult, InferenceError> {
+        let started = Instant::now();
+        let model = self.model()?;
+        let (tokens, max_tok) = self.start(prompt, config)?;
+        let mut prefill = None;
+        let mut first_token = None;
+        let mut tokens_generated = 0;
+        let mut choices = Vec::with_capacity(config.best_of.max(1));
+        for i in 0..config.best_of.max(1) {
+            let seed = config.seed.wrapping_add(i as u32);
+            let mut seq = self.sequence(model, &tokens, seed, config, max_tok, started)?;
+            prefill.get_or_insert_with(|| started.elapsed());
+            while seq.finish.is_none() {
+                if let Some(reason) = config.abort_reason(started) {
+                    seq.end(reason);
+                    break;
+                }
+                first_token.get_or_insert_with(|| started.elapsed());
+                seq.step(&self.tokenizer, config)?;
+            }
+            tokens_generated += seq.generated;
+            choices.push(seq.into_completion());
+        }
+
+        if config.best_of > config.n {
+            choices.sort_by(|a, b| b.cumulative_logprob.total_cmp(&a.cumulative_logprob));
+            choices.truncate(config.n);
+        }
+        let prefill = prefill.unwrap_or_default();
+        let best = choices[0].clone();
+        Ok(GenerationResult {
+            text: best.text,
+            tokens_generated,
+            finish_reason: best.finish_reason,
+            prompt_tokens: u32::try_from(tokens.len()).unwrap_or(u32::MAX),
+            timings: GenerationTimings {
+                prefill,
+                first_token: first_token.unwrap_or(prefill),
+                decode: started.elapsed().saturating_sub(prefill),
+            },
+            stop_sequence: best.stop_sequence,
+            logprobs: best.logprobs,
+            choices: if config.n > 1 { choices } else { Vec::new() },
+        })
+    }
+
+    /// Stream a completion to `sender`, one output per token; the final one
+    /// carries the usage summary. Blocks, so run it on a blocking thread.
+    pub fn generate_stream(
+        &self,
+        prompt: &str,
+        config: &InferenceConfig,
+        sender: TokenStreamSender,
+    ) -> Result<(), InferenceError> {
+        if config.best_of > 1 {
+            return Err(InferenceError::InputValidation(
+                "best_of above 1 cannot be streamed".into(),
+            ));
+        }
+        let started = Instant::now();
+        let model = self.model()?;
+        let (tokens, max_tok) = self.start(prompt, config)?;
+        let mut seq = self.sequence(model, &tokens, config.seed, config, max_tok, started)?;
+        let prefill = started.elapsed();
+        let eos = self.tokenizer.eos().unwrap_or_default();
+        let rt = tokio::runtime::Handle::current();
+        let mut first_token = None;
+        let mut is_final = false;
+        while !is_final {
+            let mark = seq.mark();
+            let tok = if seq.finish.is_some() {
+                // Ended before the first token; the end token closes the stream
+                eos
+            } else if let Some(reason) = config.abort_reason(started) {
+                seq.end(reason);
+                eos
+            } else {
+                first_token.get_or_insert_with(|| started.elapsed());
+                seq.step(&self.tokenizer, config)?
+            };
+            let timings = GenerationTimings {
+                prefill,
+                first_token: first_token.unwrap_or(prefill),
+                decode: started.elapsed().saturating_sub(prefill),
+            };
+            let output = seq.output(tok, mark, tokens.len(), timings);
+            is_final = output.is_final;
+            if rt.block_on(sender.send_output(output)).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One sequence being generated.
+struct Sequence<'a> {
+    session: NativeSession<'a>,
+    decode: DecodeExecutor,
+    /// Logits for the next token.
+    logits: Vec<f32>,
+    max_tok: u32,
+    /// Prompt and generated tokens, for the penalties.
+    history: Vec<u32>,
+    rng: SplitMix64,
+    text: String,
+    utf8: Utf8StreamDecoder,
+    stops: StopMatcher,
+    sampled: u32,
+    generated: u32,
+    logprobs: Vec<TokenLogprob>,
+    cumulative_logprob: f32,
+    stop_sequence: Option<StopSequence>,
+    finish: Option<FinishReason>,
+}
+
+impl Sequence<'_> {
+    /// Sample and apply the next token, then evaluate it unless the
+    /// sequence ended. Returns the token.
+    fn step(
+        &mut self,
+        tokenizer: &NativeTokenizer,
+        config: &InferenceConfig,
+    ) -> Result<u32, InferenceError> {
+        let tok = sample(&self.logits, &self.history, config, &mut self.rng);
+        self.apply(tok, tokenizer, config);
+        if self.finish.is_none() {
+            if self.sampled >= self.max_tok {
+                self.end(FinishReason::MaxTokens);
+            } else {
+                self.logits = self.decode.forward(&mut self.session, tok)?;
+            }
+        }
+        Ok(tok)
+    }
+
+    /// Record a sampled token, which ends the sequence if it ends
+    /// generation or a stop sequence completes.
+    fn apply(&mut self, tok: u32, tokenizer: &NativeTokenizer, config: &InferenceConfig) {
+        self.sampled += 1;
+        self.history.push(tok);
+        let logprob = (config.logprobs.is_some() || config.best_of > 1)
+            .then(|| token_logprob(tokenizer, &self.logits, tok, config.logprobs.unwrap_or(0)));
+        self.cumulative_logprob += logprob.as_ref().map_or(0.0, |l| l.logprob);
+        if tokenizer.is_end_of_generation(tok) {
+            self.end(FinishReason::Stop);
+            return;
+        }
+        if let Some(stop) = self.stops.stop_token(tok) {
+            self.stop_sequence = Some(stop);
+            self.end(FinishReason::Stop);
+            return;
+        }
+        self.generated += 1;
+        if config.logprobs.is_some() {
+            self.logprobs.extend(logprob);
+        }
+        let (piece, stop) = self.stops.push(&self.utf8.push(tokenizer.token_bytes(tok)));
+        self.text.push_str(&piece);
+        if stop.is_some() {
+            self.stop_sequence = stop;
+            self.end(FinishReason::Stop);
+        }
+    }
+
+    /// End the sequence, releasing held-back text unless a stop string
+    /// ended it.
+    fn end(&mut self, reason: FinishReason) {
+        self.finish = Some(reason);
+        if !matches!(self.stop_sequence, Some(StopSequence::Text(_))) {
+            self.text.push_str(&self.stops.flush());
+            self.text.push_str(&self.utf8.finish());
+        }
+    }
+
+    /// Text and log-probability counts, to tell what the next token adds.
+    fn mark(&self) -> (usize, usize) {
+        (self.text.len(), self.logprobs.len())
+    }
+
+    fn output(
+        &self,
+        tok: u32,
+        (text_len, logprobs_len): (usize, usize),
+        prompt_tokens: usize,
+        timings: GenerationTimings,
+    ) -> StreamingOutput {
+        let finish = self.finish.map(|finish_reason| StreamFinish {
+            prompt_tokens: u32::try_from(prompt_tokens).unwrap_or(u32::MAX),
+            completion_tokens: self.generated,
+            finish_reason,
+            timings,
+            stop_sequence: self.stop_sequence.clone(),
+        });
+        StreamingOutput {
+            token: tok,
+            text: self.text[text_len..].to_string(),
+            is_final: finish.is_some(),
+            logprob: self.logprobs.get(logprobs_len).cloned(),
+            finish,
+        }
+    }
+
+    fn into_completion(self) -> Completion {
+        Completion {
+            text: self.text,
+            tokens_generated: self.generated,
+            finish_reason: self.finish.unwrap_or(FinishReason::MaxTokens),
+            stop_sequence: self.stop_sequence,
+            logprobs: self.logprobs,
+            cumulative_logprob: self.cumulative_logprob,
+        }
+    }
+}
+
+/// Draw the next token from `logits` after `history`: the most likely at
+/// temperature 0, otherwise from the candidates top-k, top-p and min-p
+/// keep, with probabilities sharpened or flattened by the temperature.
+fn sample(logits: &[f32], history: &[u32], config: &InferenceConfig, rng: &mut SplitMix64) -> u32 {
+    let mut logits = logits.to_vec();
+    for &(token, bias) in &config.logit_bias {
+        if let Some(logit) = logits.get_mut(token as usize) {
+            *logit += bias;
+        }
+    }
+    apply_penalties(&mut logits, history, config);
+
+    let mut candidates: Vec<(u32, f32)> =
+        logits.iter().enumerate().map(|(token, &logit)| (token as u32, logit)).collect();
+    // Most likely first, lowest token on ties
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let Some(&(most_likely, max)) = candidates.first() else {
+        return 0;
+    };
+    if config.temperature <= 0.0 || max == f32::NEG_INFINITY {
+        return most_likely;
+    }
+    if config.top_k > 0 {
+        candidates.truncate(config.top_k as usize);
+    }
+    let probs: Vec<f64> = candidates.iter().map(|&(_, l)| f64::from(l - max).exp()).collect();
+    let total: f64 = probs.iter().sum();
+    let mut mass = 0.0;
+    let mut kept = 0;
+    for p in &probs {
+        kept += 1;
+        mass += p / total;
+        if mass >= f64::from(config.top_p) {
+            break;
+        }
+    }
+    // probs[0] is the most likely token's, scaled to 1
+    let floor = f64::from(config.min_p);
+    kept = probs[..kept].iter().take_while(|&&p| p >= floor).count().max(1);
+
+    let weights: Vec<f64> = probs[..kept]
+        .iter()
+        .map(|p| p.powf(1.0 / f64::from(config.temperature)))
+        .collect();
+    let mut target = rng.next_f64() * weights.iter().sum::<f64>();
+    for (&(token, _), weight) in candidates.iter().zip(&weights) {
+        if target < *weight {
+            return token;
+        }
+        target -= weight;
+    }
+    candidates[kept - 1].0
+}
+
+/// Apply the repetition, frequency and presence penalties to the tokens in
+/// the last `penalty_last_n` of `history`.
+fn apply_penalties(logits: &mut [f32], history: &[u32], config: &InferenceConfig) {
+    let window = match usize::try_from(config.penalty_last_n) {
+        Ok(0) => return,
+        Ok(n) => n.min(history.len()),
+        Err(_) => history.len(),
+    };
+    let mut counts: HashMap<u32, u32> = HashMap::new();
+    for &token in &history[history.len() - window..] {
+        *counts.entry(token).or_insert(0) += 1;
+    }
+    for (token, count) in counts {
+        let Some(logit) = logits.get_mut(token as usize) else {
+            continue;
+        };
+        if *logit > 0.0 {
+            *logit /= config.repetition_penalty;
+        } else {
+            *logit *= config.repetition_penalty;
+        }
+        *logit -= count as f32 * config.frequency_penalty + config.presence_penalty;
+    }
+}
+
+/// Log-probability of `tok` and the `top_n` most likely tokens under the
+/// raw `logits`.
+fn token_logprob(
+    tokenizer: &NativeTokenizer,
+    logits: &[f32],
+    tok: u32,
+    top_n: usize,
+) -> TokenLogprob {
+    let text = |token| Some(String::from_utf8_lossy(tokenizer.token_bytes(token)).into_owned());
+    let (logprob, top) = token_logprobs(logits, tok, top_n);
+    TokenLogprob {
+        token: tok,
+        text: text(tok),
+        logprob,
+        top: top
+            .into_iter()
+            .map(|(token, logprob)| TokenCandidate { token, text: text(token), logprob })
+            .collect(),
+    }
+}
+
+#[async_trait::async_trait]
+impl GgufModel for NativeGenerator {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn capabilities(&self) -> &[InferenceCapability] {
+        &[InferenceCapability::TextGeneration]
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.model.as_ref().map_or(0, NativeModel::memory_usage)
+    }
+
+    async fn infer(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+    ) -> Result<InferenceOutput, InferenceError> {
+        input.validate()?;
+        config.validate()?;
+        let prompt = self.prompt(input, config)?;
+        Ok(InferenceOutput::Generation(self.generate(&prompt, config)?))
+    }
+
+    fn infer_stream(
+        &self,
+        input: &InferenceInput,
+        config: &InferenceConfig,
+        _draft: Option<&dyn GgufModel>,
+        sender: TokenStreamSender,
+    ) -> Result<(), InferenceError> {
+        config.validate()?;
+        let prompt = self.prompt(input, config)?;
+        self.generate_stream(&prompt, config, sender)
+    }
+
+    async fn unload(&mut self) -> Result<(), InferenceError> {
+        self.model = None;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn penalties_lower_repeated_tokens() {
+        let config = InferenceConfig {
+            repetition_penalty: 2.0,
+            frequency_penalty: 0.5,
+            presence_penalty: 0.25,
+            penalty_last_n: 2,
+            ..Default::default()
+        };
+        let mut logits = [4.0, -1.0, 3.0, 1.0];
+        // Token 3 falls outside the window
+        apply_penalties(&mut logits, &[3, 1, 0, 0], &config);
+        assert_eq!(logits, [4.0 / 2.0 - 1.25, -1.0, 3.0, 1.0]);
+
+        let mut logits = [4.0, -1.0, 3.0, 1.0];
+        apply_penalties(&mut logits, &[3, 1], &InferenceConfig { penalty_last_n: -1, ..config });
+        assert_eq!(logits, [4.0, -2.0 - 0.75, 3.0, 0.5 - 0.75]);
+    }
+
+    #[test]
+    fn sampling_respects_filters_and_seed() {
+        let logits = [1.0, 3.0, 2.5, 0.0, 2.9];
+        let greedy = InferenceConfig { temperature: 0.0, ..Default::default() };
+        assert_eq!(sample(&logits, &[], &greedy, &mut SplitMix64(1)), 1);
+        let biased = InferenceConfig { logit_bias: vec![(3, 5.0)], ..greedy.clone() };
+        assert_eq!(sample(&logits, &[], &biased, &mut SplitMix64(1)), 3);
+
+        let sampled = |config: &InferenceConfig, seed: u64| {
+            let mut rng = SplitMix64(seed);
+            (0..200).map(|_| sample(&logits, &[], config, &mut rng)).collect::<Vec<_>>()
+        };
+        let hot = InferenceConfig {
+            temperature: 1.5,
+            top_k: 0,
+            top_p: 1.0,
+            repetition_penalty: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(sampled(&hot, 7), sampled(&hot, 7));
+        assert!(sampled(&hot, 7).contains(&3));
+
+        let top_k = InferenceConfig { top_k: 2, ..hot.clone() };
+        assert!(sampled(&top_k, 7).iter().all(|t| [1, 4].contains(t)));
+        // exp(-0.5) of the most likely token's probability excludes token 2
+        let min_p = InferenceConfig { min_p: 0.65, ..hot };
+        assert!(sampled(&min_p, 7).iter().all(|t| [1, 4].contains(t)));
+    }
+}
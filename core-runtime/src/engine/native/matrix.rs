@@ -0,0 +1,335 @@
+//! GGUF weight matrices read in place from a memory-mapped file.
+//!
+//! Rows are dequantized block by block while they are multiplied, so
+//! quantized weights stay quantized in memory.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use half::f16;
+use memmap2::Mmap;
+
+use crate::engine::gguf::{GgmlType, GgufTensorInfo};
+use crate::engine::InferenceError;
+
+/// Elements per Q8_0 and Q4_0 block.
+const QK: usize = 32;
+
+/// Smallest matrix, in elements, whose products are split across threads.
+const PARALLEL_MIN_ELEMENTS: usize = 1 << 18;
+
+/// Whether the native backend can compute with tensors of `ggml_type`.
+pub fn is_supported(ggml_type: GgmlType) -> bool {
+    matches!(
+        ggml_type,
+        GgmlType::F32 | GgmlType::F16 | GgmlType::Q8_0 | GgmlType::Q4_0
+    )
+}
+
+/// A 2-D weight tensor; each row holds `cols` elements.
+#[derive(Clone)]
+pub struct Matrix {
+    ggml_type: GgmlType,
+    rows: usize,
+    cols: usize,
+    /// Bytes of one row.
+    row_bytes: usize,
+    data: Arc<Mmap>,
+    range: Range<usize>,
+}
+
+impl std::fmt::Debug for Matrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Matrix")
+            .field("ggml_type", &self.ggml_type)
+            .field("rows", &self.rows)
+            .field("cols", &self.cols)
+            .finish()
+    }
+}
+
+impl Matrix {
+    /// The tensor `info` of the file mapped at `data`, whose tensor data
+    /// starts at `data_offset`. 1-D tensors are a single row.
+    pub fn new(
+        info: &GgufTensorInfo,
+        data: Arc<Mmap>,
+        data_offset: u64,
+    ) -> Result<Self, InferenceError> {
+        let invalid = |reason: String| {
+            InferenceError::ModelError(format!("tensor {}: {}", info.name, reason))
+        };
+        if !is_supported(info.ggml_type) {
+            return Err(invalid(format!("type {} is not supported", info.ggml_type)));
+        }
+        let (cols, rows) = match info.shape[..] {
+            [cols] => (cols, 1),
+            [cols, rows] => (cols, rows),
+            _ => {
+                return Err(invalid(format!(
+                    "expected 2 dimensions, got {:?}",
+                    info.shape
+                )))
+            }
+        };
+        let (block_elements, block_bytes) = info.ggml_type.block().unwrap_or((1, 4));
+        if cols % block_elements != 0 {
+            return Err(invalid(format!("row of {cols} elements splits a block")));
+        }
+        let row_bytes = usize::try_from(cols / block_elements * block_bytes)
+            .map_err(|_| invalid("row too large".into()))?;
+        let start = usize::try_from(data_offset + info.offset)
+            .map_err(|_| invalid("offset too large".into()))?;
+        let end = usize::try_from(rows)
+            .ok()
+            .and_then(|rows| rows.checked_mul(row_bytes))
+            .and_then(|len| len.checked_add(start))
+            .filter(|&end| end <= data.len())
+            .ok_or_else(|| invalid("data extends past the end of the file".into()))?;
+        Ok(Self {
+            ggml_type: info.ggml_type,
+            rows: rows as usize,
+            cols: cols as usize,
+            row_bytes,
+            data,
+            range: start..end,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn ggml_type(&self) -> GgmlType {
+        self.ggml_type
+    }
+
+    fn row_data(&self, row: usize) -> &[u8] {
+        let start = self.range.start + row * self.row_bytes;
+        &self.data[start..start + self.row_bytes]
+    }
+
+    /// Dot product of row `row` with `x`, which holds `cols` elements.
+    pub fn row_dot(&self, row: usize, x: &[f32]) -> f32 {
+        let data = self.row_data(row);
+        match self.ggml_type {
+            GgmlType::F32 => data
+                .chunks_exact(4)
+                .zip(x)
+                .map(|(w, x)| f32::from_le_bytes([w[0], w[1], w[2], w[3]]) * x)
+                .sum(),
+            GgmlType::F16 => data
+                .chunks_exact(2)
+                .zip(x)
+                .map(|(w, x)| f16::from_le_bytes([w[0], w[1]]).to_f32() * x)
+                .sum(),
+            GgmlType::Q8_0 => data
+                .chunks_exact(2 + QK)
+                .zip(x.chunks_exact(QK))
+                .map(|(block, x)| {
+                    let sum: f32 = block[2..]
+                        .iter()
+                        .zip(x)
+                        .map(|(&q, x)| q as i8 as f32 * x)
+                        .sum();
+                    block_scale(block) * sum
+                })
+                .sum(),
+            GgmlType::Q4_0 => data
+                .chunks_exact(2 + QK / 2)
+                .zip(x.chunks_exact(QK))
+                .map(|(block, x)| {
+                    let (low, high) = x.split_at(QK / 2);
+                    let sum: f32 = block[2..]
+                        .iter()
+                        .zip(low.iter().zip(high))
+                        .map(|(&q, (lo, hi))| {
+                            ((q & 0x0f) as i32 - 8) as f32 * lo + ((q >> 4) as i32 - 8) as f32 * hi
+                        })
+                        .sum();
+                    block_scale(block) * sum
+                })
+                .sum(),
+            _ => unreachable!("checked by Matrix::new"),
+        }
+    }
+
+    /// Dequantize row `row` into `out`, which holds `cols` elements.
+    pub fn dequantize_row(&self, row: usize, out: &mut [f32]) {
+        let data = self.row_data(row);
+        match self.ggml_type {
+            GgmlType::F32 => {
+                for (o, w) in out.iter_mut().zip(data.chunks_exact(4)) {
+                    *o = f32::from_le_bytes([w[0], w[1], w[2], w[3]]);
+                }
+            }
+            GgmlType::F16 => {
+                for (o, w) in out.iter_mut().zip(data.chunks_exact(2)) {
+                    *o = f16::from_le_bytes([w[0], w[1]]).to_f32();
+                }
+            }
+            GgmlType::Q8_0 => {
+                for (out, block) in out.chunks_exact_mut(QK).zip(data.chunks_exact(2 + QK)) {
+                    let d = block_scale(block);
+                    for (o, &q) in out.iter_mut().zip(&block[2..]) {
+                        *o = d * q as i8 as f32;
+                    }
+                }
+            }
+            GgmlType::Q4_0 => {
+                for (out, block) in out.chunks_exact_mut(QK).zip(data.chunks_exact(2 + QK / 2)) {
+                    let d = block_scale(block);
+                    let (low, high) = out.split_at_mut(QK / 2);
+                    for ((lo, hi), &q) in low.iter_mut().zip(high).zip(&block[2..]) {
+                        *lo = d * ((q & 0x0f) as i32 - 8) as f32;
+                        *hi = d * ((q >> 4) as i32 - 8) as f32;
+                    }
+                }
+            }
+            _ => unreachable!("checked by Matrix::new"),
+        }
+    }
+
+    /// The whole tensor as f32, row after row.
+    pub fn to_vec(&self) -> Vec<f32> {
+        let mut out = vec![0.0; self.rows * self.cols];
+        for (row, chunk) in out.chunks_exact_mut(self.cols).enumerate() {
+            self.dequantize_row(row, chunk);
+        }
+        out
+    }
+
+    /// `out = self · x`, split across up to `threads` threads for large
+    /// matrices.
+    pub fn matvec(&self, x: &[f32], out: &mut [f32], threads: usize) {
+        debug_assert_eq!(x.len(), self.cols);
+        debug_assert_eq!(out.len(), self.rows);
+        if threads <= 1 || self.rows * self.cols < PARALLEL_MIN_ELEMENTS {
+            for (row, o) in out.iter_mut().enumerate() {
+                *o = self.row_dot(row, x);
+            }
+            return;
+        }
+        let chunk = self.rows.div_ceil(threads);
+        std::thread::scope(|scope| {
+            for (i, out) in out.chunks_mut(chunk).enumerate() {
+                scope.spawn(move || {
+                    for (j, o) in out.iter_mut().enumerate() {
+                        *o = self.row_dot(i * chunk + j, x);
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// The f16 scale leading a Q8_0 or Q4_0 block.
+fn block_scale(block: &[u8]) -> f32 {
+    f16::from_le_bytes([block[0], block[1]]).to_f32()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Map `bytes` as the data of one tensor.
+    fn matrix(bytes: &[u8], shape: &[u64], ggml_type: GgmlType) -> Matrix {
+        let mut file = tempfile::tempfile().unwrap();
+        std::io::Write::write_all(&mut file, bytes).unwrap();
+        // SAFETY: the temporary file is private to this test and not modified
+        let data = Arc::new(unsafe { Mmap::map(&file) }.unwrap());
+        let info = GgufTensorInfo {
+            name: "w".into(),
+            shape: shape.to_vec(),
+            ggml_type,
+            offset: 0,
+        };
+        Matrix::new(&info, data, 0).unwrap()
+    }
+
+    fn x() -> Vec<f32> {
+        (0..QK).map(|i| i as f32 * 0.25 - 2.0).collect()
+    }
+
+    fn reference_dot(weights: &[f32], x: &[f32]) -> f32 {
+        weights.iter().zip(x).map(|(w, x)| w * x).sum()
+    }
+
+    #[test]
+    fn q8_0_block_dequantizes_and_multiplies() {
+        let mut block = f16::from_f32(0.5).to_le_bytes().to_vec();
+        block.extend((0..QK as i32).map(|i| (i - 16) as i8 as u8));
+        let m = matrix(&block, &[QK as u64], GgmlType::Q8_0);
+
+        let mut row = vec![0.0; QK];
+        m.dequantize_row(0, &mut row);
+        assert_eq!(row[0], -8.0);
+        assert_eq!(row[31], 7.5);
+        assert!((m.row_dot(0, &x()) - reference_dot(&row, &x())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn q4_0_block_splits_nibbles_into_halves() {
+        let mut block = f16::from_f32(2.0).to_le_bytes().to_vec();
+        // Low nibbles fill elements 0-15, high nibbles elements 16-31
+        block.extend((0..16u8).map(|i| i | (15 - i) << 4));
+        let m = matrix(&block, &[QK as u64], GgmlType::Q4_0);
+
+        let mut row = vec![0.0; QK];
+        m.dequantize_row(0, &mut row);
+        assert_eq!(row[0], -16.0);
+        assert_eq!(row[15], 14.0);
+        assert_eq!(row[16], 14.0);
+        assert_eq!(row[31], -16.0);
+        assert!((m.row_dot(0, &x()) - reference_dot(&row, &x())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn float_matrices_multiply_by_row() {
+        let weights: Vec<f32> = (0..2 * QK).map(|i| (i as f32).sin()).collect();
+        let f32_bytes: Vec<u8> = weights.iter().flat_map(|w| w.to_le_bytes()).collect();
+        let f16_bytes: Vec<u8> = weights
+            .iter()
+            .flat_map(|&w| f16::from_f32(w).to_le_bytes())
+            .collect();
+
+        for (bytes, ggml_type, tolerance) in [
+            (f32_bytes, GgmlType::F32, 1e-5),
+            (f16_bytes, GgmlType::F16, 1e-2),
+        ] {
+            let m = matrix(&bytes, &[QK as u64, 2], ggml_type);
+            let mut out = [0.0; 2];
+            m.matvec(&x(), &mut out, 4);
+            for (row, &o) in out.iter().enumerate() {
+                let expected = reference_dot(&weights[row * QK..(row + 1) * QK], &x());
+                assert!(
+                    (o - expected).abs() < tolerance,
+                    "{ggml_type}: {o} vs {expected}"
+                );
+            }
+            assert_eq!(m.to_vec().len(), 2 * QK);
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_and_out_of_bounds_tensors() {
+        let mut file = tempfile::tempfile().unwrap();
+        std::io::Write::write_all(&mut file, &[0u8; 64]).unwrap();
+        // SAFETY: the temporary file is private to this test and not modified
+        let data = Arc::new(unsafe { Mmap::map(&file) }.unwrap());
+        let info = |shape: &[u64], ggml_type| GgufTensorInfo {
+            name: "w".into(),
+            shape: shape.to_vec(),
+            ggml_type,
+            offset: 0,
+        };
+        assert!(Matrix::new(&info(&[256], GgmlType::Q4_K), data.clone(), 0).is_err());
+        assert!(Matrix::new(&info(&[16, 2], GgmlType::F32), data.clone(), 0).is_err());
+        assert!(Matrix::new(&info(&[4, 2, 2], GgmlType::F32), data.clone(), 0).is_err());
+        assert!(Matrix::new(&info(&[8, 2], GgmlType::F32), data, 0).is_ok());
+    }
+}
@@ -0,0 +1,25 @@
+//! Native Rust inference for small Llama-family GGUF models.
+//!
+//! Runs the transformer forward pass on the CPU straight from the mapped
+//! GGUF file, with no llama.cpp in the process. It is much slower than the
+//! llama.cpp backend and is meant for small models where keeping C++ out of
+//! the trusted code matters more than throughput.
+//!
+//! A model is served by this backend when `GgufConfig::backend`, or its
+//! manifest's `backend`, selects it: [`NativeGenerator`] tokenizes with the
+//! vocabulary in the GGUF metadata and generates through the same engine
+//! paths as llama.cpp models.
+
+pub mod fixture;
+pub mod generator;
+pub mod matrix;
+pub mod model;
+pub mod tokenizer;
+
+pub use fixture::NativeFixture;
+pub use generator::NativeGenerator;
+pub use matrix::Matrix;
+pub use model::{
+    NativeArchitecture, NativeConfig, NativeHparams, NativeModel, NativeSession, RopeStyle,
+};
+pub use tokenizer::NativeTokenizer;
@@ -0,0 +1,971 @@
+//! Llama-family transformer forward pass.
+//!
+//! Each token runs RMSNorm, RoPE, grouped-query attention over the paged
+//! KV cache (through [`FlashAttn`]) and a SwiGLU feed-forward block per
+//! layer, then the output projection. Prompts are evaluated one token at a
+//! time, which is fine for the small models this backend is meant for.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use super::matrix::Matrix;
+use crate::engine::gguf::{GgufMetadata, GgufTensorInfo};
+use crate::engine::{FlashAttn, FlashAttnConfig, InferenceError};
+use crate::memory::{EvictionPolicy, KvCacheConfig, KvCacheManager, SequenceId, PAGE_TOKENS};
+
+/// Settings for running a model natively.
+#[derive(Debug, Clone)]
+pub struct NativeConfig {
+    /// Context window of each session (0 = the model's training context).
+    pub n_ctx: usize,
+    /// Store cached keys and values in Q8 instead of f32: about 4x less
+    /// memory for slightly different logits.
+    pub quantize_kv: bool,
+    /// Threads for matrix products (0 = one per core).
+    pub n_threads: usize,
+}
+
+impl Default for NativeConfig {
+    fn default() -> Self {
+        Self {
+            n_ctx: 2048,
+            quantize_kv: false,
+            n_threads: 0,
+        }
+    }
+}
+
+/// How RoPE pairs up the dimensions of a head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RopeStyle {
+    /// Adjacent dimensions (2i, 2i + 1), as in Llama conversions.
+    Interleaved,
+    /// Dimension i with i + n/2, as in Qwen2 (GPT-NeoX style).
+    Neox,
+}
+
+/// Architectures the native backend runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeArchitecture {
+    /// `llama`: Llama 2/3, Mistral, TinyLlama and other conversions.
+    Llama,
+    /// `qwen2`: Qwen2 and Qwen2.5, with attention biases.
+    Qwen2,
+}
+
+impl NativeArchitecture {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "llama" => Some(Self::Llama),
+            "qwen2" => Some(Self::Qwen2),
+            _ => None,
+        }
+    }
+
+    pub fn rope_style(self) -> RopeStyle {
+        match self {
+            Self::Llama => RopeStyle::Interleaved,
+            Self::Qwen2 => RopeStyle::Neox,
+        }
+    }
+}
+
+/// Model dimensions, from the GGUF header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NativeHparams {
+    pub architecture: NativeArchitecture,
+    pub n_vocab: usize,
+    pub n_embd: usize,
+    pub n_layer: usize,
+    pub n_head: usize,
+    pub n_head_kv: usize,
+    pub head_dim: usize,
+    pub n_ff: usize,
+    /// Context length the model was trained with.
+    pub n_ctx_train: usize,
+    /// Dimensions of each head rotated by RoPE.
+    pub rope_dim: usize,
+    pub rope_base: f32,
+    pub rms_eps: f32,
+}
+
+impl NativeHparams {
+    fn from_header(header: &GgufMetadata) -> Result<Self, InferenceError> {
+        let arch = header.architecture().unwrap_or("unknown");
+        let architecture = NativeArchitecture::from_name(arch).ok_or_else(|| {
+            InferenceError::ModelError(format!(
+                "architecture {arch} is not supported by the native backend"
+            ))
+        })?;
+        let required = |value: Option<u64>, key: &str| {
+            value
+                .map(|v| v as usize)
+                .filter(|&v| v > 0)
+                .ok_or_else(|| InferenceError::ModelError(format!("missing {arch}.{key}")))
+        };
+        let n_embd = required(header.embedding_length(), "embedding_length")?;
+        let n_head = required(header.head_count(), "attention.head_count")?;
+        let n_head_kv = required(header.head_count_kv(), "attention.head_count_kv")?;
+        if n_embd % n_head != 0 || n_head % n_head_kv != 0 {
+            return Err(InferenceError::ModelError(format!(
+                "{n_head} heads and {n_head_kv} KV heads do not divide {n_embd} dimensions"
+            )));
+        }
+        let head_dim = n_embd / n_head;
+        let rope_dim = header
+            .rope_dimension_count()
+            .map_or(head_dim, |v| v as usize);
+        if rope_dim > head_dim || rope_dim % 2 == 1 {
+            return Err(InferenceError::ModelError(format!(
+                "RoPE dimension {rope_dim} does not fit heads of {head_dim}"
+            )));
+        }
+        Ok(Self {
+            architecture,
+            // Set from the embedding table
+            n_vocab: 0,
+            n_embd,
+            n_layer: required(header.block_count(), "block_count")?,
+            n_head,
+            n_head_kv,
+            head_dim,
+            n_ff: required(header.feed_forward_length(), "feed_forward_length")?,
+            n_ctx_train: header.context_length().map_or(2048, |v| v as usize),
+            rope_dim,
+            rope_base: header.rope_freq_base().unwrap_or(10_000.0),
+            rms_eps: header.rms_norm_epsilon().unwrap_or(1e-5),
+        })
+    }
+
+    /// Width of the keys (and values) cached per token and layer.
+    pub fn kv_dim(&self) -> usize {
+        self.n_head_kv * self.head_dim
+    }
+}
+
+/// Weights of one transformer block.
+struct Layer {
+    attn_norm: Vec<f32>,
+    wq: Matrix,
+    wk: Matrix,
+    wv: Matrix,
+    bq: Option<Vec<f32>>,
+    bk: Option<Vec<f32>>,
+    bv: Option<Vec<f32>>,
+    wo: Matrix,
+    ffn_norm: Vec<f32>,
+    ffn_gate: Matrix,
+    ffn_up: Matrix,
+    ffn_down: Matrix,
+}
+
+/// A Llama-family GGUF model run in Rust, without llama.cpp.
+///
+/// Supports F32, F16, Q8_0 and Q4_0 weights. Tokenization and sampling are
+/// left to the caller: a session maps token IDs to next-token logits.
+pub struct NativeModel {
+    hparams: NativeHparams,
+    config: NativeConfig,
+    token_embd: Matrix,
+    output_norm: Vec<f32>,
+    /// The embedding table when the model ties them.
+    output: Matrix,
+    layers: Vec<Layer>,
+    attn: FlashAttn,
+    file_bytes: usize,
+}
+
+impl NativeModel {
+    /// Map the GGUF file at `path` and check its tensors.
+    pub fn load(path: &Path, config: &NativeConfig) -> Result<Self, InferenceError> {
+        let model_error = |e: &dyn std::fmt::Display| {
+            InferenceError::ModelError(format!("{}: {}", path.display(), e))
+        };
+        let header = GgufMetadata::read(path).map_err(|e| model_error(&e))?;
+        let file = std::fs::File::open(path).map_err(|e| model_error(&e))?;
+        // SAFETY: the file is opened read-only and model files are not
+        // modified while they are served
+        let data = Arc::new(unsafe { Mmap::map(&file) }.map_err(|e| model_error(&e))?);
+        Self::from_parts(&header, data, config)
+    }
+
+    fn from_parts(
+        header: &GgufMetadata,
+        data: Arc<Mmap>,
+        config: &NativeConfig,
+    ) -> Result<Self, InferenceError> {
+        let mut hparams = NativeHparams::from_header(header)?;
+        let tensors = Tensors {
+            infos: header
+                .tensors
+                .iter()
+                .map(|t| (t.name.as_str(), t))
+                .collect(),
+            data: &data,
+            data_offset: header.data_offset,
+        };
+        let token_embd = tensors.matrix_with_cols("token_embd.weight", hparams.n_embd)?;
+        hparams.n_vocab = token_embd.rows();
+        let (n_embd, n_vocab, n_ff) = (hparams.n_embd, hparams.n_vocab, hparams.n_ff);
+        let (q_dim, kv_dim) = (hparams.n_head * hparams.head_dim, hparams.kv_dim());
+
+        let output = match tensors.get("output.weight") {
+            Some(_) => tensors.matrix("output.weight", n_embd, n_vocab)?,
+            None => token_embd.clone(),
+        };
+        let layers = (0..hparams.n_layer)
+            .map(|i| {
+                let name = |suffix: &str| format!("blk.{i}.{suffix}");
+                Ok(Layer {
+                    attn_norm: tensors.vector(&name("attn_norm.weight"), n_embd)?,
+                    wq: tensors.matrix(&name("attn_q.weight"), n_embd, q_dim)?,
+                    wk: tensors.matrix(&name("attn_k.weight"), n_embd, kv_dim)?,
+                    wv: tensors.matrix(&name("attn_v.weight"), n_embd, kv_dim)?,
+                    bq: tensors.optional_vector(&name("attn_q.bias"), q_dim)?,
+                    bk: tensors.optional_vector(&name("attn_k.bias"), kv_dim)?,
+                    bv: tensors.optional_vector(&name("attn_v.bias"), kv_dim)?,
+                    wo: tensors.matrix(&name("attn_output.weight"), q_dim, n_embd)?,
+                    ffn_norm: tensors.vector(&name("ffn_norm.weight"), n_embd)?,
+                    ffn_gate: tensors.matrix(&name("ffn_gate.weight"), n_embd, n_ff)?,
+                    ffn_up: tensors.matrix(&name("ffn_up.weight"), n_embd, n_ff)?,
+                    ffn_down: tensors.matrix(&name("ffn_down.weight"), n_ff, n_embd)?,
+                })
+            })
+            .collect::<Result<_, InferenceError>>()?;
+
+        let mut config = config.clone();
+        if config.n_ctx == 0 || config.n_ctx > hparams.n_ctx_train {
+            config.n_ctx = hparams.n_ctx_train;
+        }
+        if config.n_threads == 0 {
+            config.n_threads = num_cpus::get();
+        }
+        Ok(Self {
+            attn: FlashAttn::new(FlashAttnConfig {
+                block_size: 64,
+                head_dim: hparams.head_dim,
+            }),
+            output_norm: tensors.vector("output_norm.weight", n_embd)?,
+            hparams,
+            config,
+            token_embd,
+            output,
+            layers,
+            file_bytes: data.len(),
+        })
+    }
+
+    pub fn hparams(&self) -> &NativeHparams {
+        &self.hparams
+    }
+
+    /// Context window of each session.
+    pub fn context_size(&self) -> usize {
+        self.config.n_ctx
+    }
+
+    /// Bytes of the mapped model file.
+    pub fn memory_usage(&self) -> usize {
+        self.file_bytes
+    }
+
+    /// Start an empty sequence.
+    pub fn session(&self) -> NativeSession<'_> {
+        let cache_config = KvCacheConfig {
+            hidden_dim: self.hparams.kv_dim(),
+            max_pages: self.config.n_ctx.div_ceil(PAGE_TOKENS),
+            max_seq_len: self.config.n_ctx,
+            num_heads: self.hparams.n_head_kv,
+            head_dim: self.hparams.head_dim,
+            enable_quantization: self.config.quantize_kv,
+            enable_paged: true,
+            eviction_policy: EvictionPolicy::Lru,
+        };
+        let caches = (0..self.hparams.n_layer)
+            .map(|_| {
+                let cache = KvCacheManager::new(cache_config.clone());
+                let seq = cache.allocate_sequence();
+                (cache, seq)
+            })
+            .collect();
+        NativeSession {
+            model: self,
+            caches,
+            pos: 0,
+        }
+    }
+
+    /// Next-token logits after `tokens`, evaluated in a fresh session.
+    pub fn logits(&self, tokens: &[u32]) -> Result<Vec<f32>, InferenceError> {
+        self.session().forward(tokens)
+    }
+}
+
+/// Tensor table lookups with shape checks.
+struct Tensors<'a> {
+    infos: HashMap<&'a str, &'a GgufTensorInfo>,
+    data: &'a Arc<Mmap>,
+    data_offset: u64,
+}
+
+impl Tensors<'_> {
+    fn get(&self, name: &str) -> Option<&GgufTensorInfo> {
+        self.infos.get(name).copied()
+    }
+
+    fn load(&self, name: &str, shape: Option<&[u64]>) -> Result<Matrix, InferenceError> {
+        let info = self
+            .get(name)
+            .ok_or_else(|| InferenceError::ModelError(format!("missing tensor {name}")))?;
+        if let Some(shape) = shape.filter(|&shape| info.shape != shape) {
+            return Err(InferenceError::ModelError(format!(
+                "tensor {name} has shape {:?}, expected {:?}",
+                info.shape, shape
+            )));
+        }
+        Matrix::new(info, Arc::clone(self.data), self.data_offset)
+    }
+
+    /// A `rows` x `cols` matrix, stored as GGUF shape `[cols, rows]`.
+    fn matrix(&self, name: &str, cols: usize, rows: usize) -> Result<Matrix, InferenceError> {
+        self.load(name, Some(&[cols as u64, rows as u64]))
+    }
+
+    /// A matrix with any number of rows.
+    fn matrix_with_cols(&self, name: &str, cols: usize) -> Result<Matrix, InferenceError> {
+        let matrix = self.load(name, None)?;
+        if matrix.cols() != cols || self.get(name).map_or(0, |t| t.shape.len()) != 2 {
+            return Err(InferenceError::ModelError(format!(
+                "tensor {name} must have rows of {cols} elements"
+            )));
+        }
+        Ok(matrix)
+    }
+
+    fn vector(&self, name: &str, len: usize) -> Result<Vec<f32>, InferenceError> {
+        Ok(self.load(name, Some(&[len as u64]))?.to_vec())
+    }
+
+    fn optional_vector(&self, name: &str, len: usize) -> Result<Option<Vec<f32>>, InferenceError> {
+        self.get(name).map(|_| self.vector(name, len)).transpose()
+    }
+}
+
+/// One sequence being evaluated, with a paged KV cache per layer.
+pub struct NativeSession<'a> {
+    model: &'a NativeModel,
+    caches: Vec<(KvCacheManager, SequenceId)>,
+    pos: usize,
+}
+
+impl NativeSession<'_> {
+    /// Tokens evaluated so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Evaluate `tokens` after those already in the sequence and return
+    /// the next-token logits, one per vocabulary entry.
+    pub fn forward(&mut self, tokens: &[u32]) -> Result<Vec<f32>, InferenceError> {
+        let hp = &self.model.hparams;
+        if tokens.is_empty() {
+            return Err(InferenceError::InputValidation(
+                "no tokens to evaluate".into(),
+            ));
+        }
+        if let Some(&token) = tokens.iter().find(|&&t| t as usize >= hp.n_vocab) {
+            return Err(InferenceError::InputValidation(format!(
+                "token {token} is outside the vocabulary of {}",
+                hp.n_vocab
+            )));
+        }
+        let n_ctx = self.model.config.n_ctx;
+        if self.pos + tokens.len() > n_ctx {
+            return Err(InferenceError::ContextExceeded {
+                n_ctx,
+                prompt_tokens: self.pos + tokens.len(),
+                max_tokens: 0,
+            });
+        }
+        let mut x = vec![0.0; hp.n_embd];
+        for &token in tokens {
+            self.model.token_embd.dequantize_row(token as usize, &mut x);
+            self.step(&mut x)?;
+        }
+
+        let mut normed = vec![0.0; hp.n_embd];
+        rms_norm(&x, &self.model.output_norm, hp.rms_eps, &mut normed);
+        let mut logits = vec![0.0; hp.n_vocab];
+        self.model
+            .output
+            .matvec(&normed, &mut logits, self.model.config.n_threads);
+        Ok(logits)
+    }
+
+    /// Run the hidden state `x` of the token at the current position
+    /// through every layer, caching its keys and values.
+    fn step(&mut self, x: &mut [f32]) -> Result<(), InferenceError> {
+        let model = self.model;
+        let hp = &model.hparams;
+        let threads = model.config.n_threads;
+        let (head_dim, kv_dim) = (hp.head_dim, hp.kv_dim());
+        let group = hp.n_head / hp.n_head_kv;
+        let seq_len = self.pos + 1;
+        let scale = 1.0 / (head_dim as f32).sqrt();
+
+        let mut h = vec![0.0; hp.n_embd];
+        let mut q = vec![0.0; hp.n_head * head_dim];
+        let mut k = vec![0.0; kv_dim];
+        let mut v = vec![0.0; kv_dim];
+        let mut attn = vec![0.0; hp.n_head * head_dim];
+        let mut proj = vec![0.0; hp.n_embd];
+        let mut gate = vec![0.0; hp.n_ff];
+        let mut up = vec![0.0; hp.n_ff];
+        let mut keys = vec![0.0; seq_len * kv_dim];
+        let mut values = vec![0.0; seq_len * kv_dim];
+        let mut head_keys = vec![0.0; seq_len * head_dim];
+        let mut head_values = vec![0.0; seq_len * head_dim];
+
+        for (layer, (cache, seq)) in model.layers.iter().zip(&self.caches) {
+            rms_norm(x, &layer.attn_norm, hp.rms_eps, &mut h);
+            for (w, b, out) in [
+                (&layer.wq, &layer.bq, &mut q),
+                (&layer.wk, &layer.bk, &mut k),
+                (&layer.wv, &layer.bv, &mut v),
+            ] {
+                w.matvec(&h, out, threads);
+                if let Some(bias) = b {
+                    out.iter_mut().zip(bias).for_each(|(o, b)| *o += b);
+                }
+            }
+            let style = hp.architecture.rope_style();
+            rope(&mut q, head_dim, hp.rope_dim, self.pos, hp.rope_base, style);
+            rope(&mut k, head_dim, hp.rope_dim, self.pos, hp.rope_base, style);
+
+            cache.append_kv(*seq, &k, &v).map_err(kv_error)?;
+            for (pos, (keys, values)) in keys
+                .chunks_exact_mut(kv_dim)
+                .zip(values.chunks_exact_mut(kv_dim))
+                .enumerate()
+            {
+                cache.read_kv(*seq, pos, keys, values).map_err(kv_error)?;
+            }
+
+            for kv_head in 0..hp.n_head_kv {
+                let cols = kv_head * head_dim..(kv_head + 1) * head_dim;
+                for pos in 0..seq_len {
+                    let row = pos * kv_dim;
+                    head_keys[pos * head_dim..(pos + 1) * head_dim]
+                        .copy_from_slice(&keys[row + cols.start..row + cols.end]);
+                    head_values[pos * head_dim..(pos + 1) * head_dim]
+                        .copy_from_slice(&values[row + cols.start..row + cols.end]);
+                }
+                for head in kv_head * group..(kv_head + 1) * group {
+                    let range = head * head_dim..(head + 1) * head_dim;
+                    // FlashAttn takes raw dot products; scale the query instead
+                    let query: Vec<f32> = q[range.clone()].iter().map(|q| q * scale).collect();
+                    model
+                        .attn
+                        .forward(&query, &head_keys, &head_values, seq_len, &mut attn[range]);
+                }
+            }
+            layer.wo.matvec(&attn, &mut proj, threads);
+            x.iter_mut().zip(&proj).for_each(|(x, p)| *x += p);
+
+            rms_norm(x, &layer.ffn_norm, hp.rms_eps, &mut h);
+            layer.ffn_gate.matvec(&h, &mut gate, threads);
+            layer.ffn_up.matvec(&h, &mut up, threads);
+            gate.iter_mut()
+                .zip(&up)
+                .for_each(|(g, u)| *g = silu(*g) * u);
+            layer.ffn_down.matvec(&gate, &mut proj, threads);
+            x.iter_mut().zip(&proj).for_each(|(x, p)| *x += p);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+}
+
+fn kv_error(e: crate::memory::KvCacheError) -> InferenceError {
+    InferenceError::ModelError(format!("KV cache: {e}"))
+}
+
+/// `out = x / rms(x) * weight`.
+pub fn rms_norm(x: &[f32], weight: &[f32], eps: f32, out: &mut [f32]) {
+    let mean_square = x.iter().map(|v| v * v).sum::<f32>() / x.len() as f32;
+    let inv = 1.0 / (mean_square + eps).sqrt();
+    for ((o, x), w) in out.iter_mut().zip(x).zip(weight) {
+        *o = x * inv * w;
+    }
+}
+
+/// Rotate the first `rope_dim` dimensions of each head in `x` for
+/// position `pos`.
+pub fn rope(
+    x: &mut [f32],
+    head_dim: usize,
+    rope_dim: usize,
+    pos: usize,
+    base: f32,
+    style: RopeStyle,
+) {
+    let half = rope_dim / 2;
+    for head in x.chunks_exact_mut(head_dim) {
+        for i in 0..half {
+            let theta = pos as f64 * f64::from(base).powf(-2.0 * i as f64 / rope_dim as f64);
+            let (sin, cos) = theta.sin_cos();
+            let (sin, cos) = (sin as f32, cos as f32);
+            let (a, b) = match style {
+                RopeStyle::Interleaved => (2 * i, 2 * i + 1),
+                RopeStyle::Neox => (i, i + half),
+            };
+            let (x0, x1) = (head[a], head[b]);
+            head[a] = x0 * cos - x1 * sin;
+            head[b] = x0 * sin + x1 * cos;
+        }
+    }
+}
+
+fn silu(x: f32) -> f32 {
+    x / (1.0 + (-x).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::gguf::GgmlType;
+    use half::f16;
+
+    /// Dimensions of the test models; rows fit whole quantization blocks.
+    const N_VOCAB: usize = 16;
+    const N_EMBD: usize = 32;
+    const N_HEAD: usize = 4;
+    const N_HEAD_KV: usize = 2;
+    const HEAD_DIM: usize = N_EMBD / N_HEAD;
+    const KV_DIM: usize = N_HEAD_KV * HEAD_DIM;
+    const N_FF: usize = 64;
+    const N_LAYER: usize = 2;
+    const EPS: f32 = 1e-5;
+
+    /// f32 weights of a test model, by tensor name.
+    struct Weights(Vec<(String, Vec<u64>, Vec<f32>)>);
+
+    impl Weights {
+        fn random(with_bias: bool) -> Self {
+            let mut seed = 0u32;
+            let mut tensor = |name: String, shape: &[usize], scale: f32| {
+                seed += 1;
+                let len = shape.iter().product();
+                let values = (0..len)
+                    .map(|i| ((i as f32 + 1.0) * 12.9898 + seed as f32 * 78.233).sin() * scale)
+                    .collect();
+                (name, shape.iter().map(|&d| d as u64).collect(), values)
+            };
+            let mut tensors = vec![
+                tensor("token_embd.weight".into(), &[N_EMBD, N_VOCAB], 1.0),
+                tensor("output_norm.weight".into(), &[N_EMBD], 1.0),
+                tensor("output.weight".into(), &[N_EMBD, N_VOCAB], 0.5),
+            ];
+            for i in 0..N_LAYER {
+                let name = |suffix: &str| format!("blk.{i}.{suffix}");
+                tensors.extend([
+                    tensor(name("attn_norm.weight"), &[N_EMBD], 1.0),
+                    tensor(name("attn_q.weight"), &[N_EMBD, N_EMBD], 0.4),
+                    tensor(name("attn_k.weight"), &[N_EMBD, KV_DIM], 0.4),
+                    tensor(name("attn_v.weight"), &[N_EMBD, KV_DIM], 0.4),
+                    tensor(name("attn_output.weight"), &[N_EMBD, N_EMBD], 0.2),
+                    tensor(name("ffn_norm.weight"), &[N_EMBD], 1.0),
+                    tensor(name("ffn_gate.weight"), &[N_EMBD, N_FF], 0.3),
+                    tensor(name("ffn_up.weight"), &[N_EMBD, N_FF], 0.3),
+                    tensor(name("ffn_down.weight"), &[N_FF, N_EMBD], 0.2),
+                ]);
+                if with_bias {
+                    tensors.extend([
+                        tensor(name("attn_q.bias"), &[N_EMBD], 0.1),
+                        tensor(name("attn_k.bias"), &[KV_DIM], 0.1),
+                        tensor(name("attn_v.bias"), &[KV_DIM], 0.1),
+                    ]);
+                }
+            }
+            Self(tensors)
+        }
+
+        /// The weights as stored in a `matrix_type` file, so the reference
+        /// forward pass sees exactly what the model loads.
+        fn rounded(mut self, matrix_type: GgmlType) -> Self {
+            for (_, shape, values) in &mut self.0 {
+                if shape.len() == 2 {
+                    *values = quantize(values, matrix_type).1;
+                }
+            }
+            self
+        }
+
+        fn get(&self, name: &str) -> &[f32] {
+            &self.0.iter().find(|(n, ..)| n == name).unwrap().2
+        }
+
+        /// Write a GGUF file with 2-D weights stored as `matrix_type` and
+        /// vectors as F32.
+        fn write(&self, path: &Path, arch: &str, matrix_type: GgmlType) {
+            fn string(buf: &mut Vec<u8>, s: &str) {
+                buf.extend_from_slice(&(s.len() as u64).to_le_bytes());
+                buf.extend_from_slice(s.as_bytes());
+            }
+            let mut keys = Vec::new();
+            string(&mut keys, "general.architecture");
+            keys.extend_from_slice(&8u32.to_le_bytes());
+            string(&mut keys, arch);
+            let hparams = [
+                ("embedding_length", N_EMBD),
+                ("block_count", N_LAYER),
+                ("feed_forward_length", N_FF),
+                ("attention.head_count", N_HEAD),
+                ("attention.head_count_kv", N_HEAD_KV),
+                ("context_length", 64),
+            ];
+            for (key, value) in hparams {
+                string(&mut keys, &format!("{arch}.{key}"));
+                keys.extend_from_slice(&4u32.to_le_bytes());
+                keys.extend_from_slice(&(value as u32).to_le_bytes());
+            }
+            string(
+                &mut keys,
+                &format!("{arch}.attention.layer_norm_rms_epsilon"),
+            );
+            keys.extend_from_slice(&6u32.to_le_bytes());
+            keys.extend_from_slice(&EPS.to_le_bytes());
+
+            let mut table = Vec::new();
+            let mut data = Vec::new();
+            for (name, shape, values) in &self.0 {
+                let ggml_type = if shape.len() == 2 {
+                    matrix_type
+                } else {
+                    GgmlType::F32
+                };
+                string(&mut table, name);
+                table.extend_from_slice(&(shape.len() as u32).to_le_bytes());
+                shape
+                    .iter()
+                    .for_each(|d| table.extend_from_slice(&d.to_le_bytes()));
+                table.extend_from_slice(&ggml_type.0.to_le_bytes());
+                table.extend_from_slice(&(data.len() as u64).to_le_bytes());
+                data.extend(quantize(values, ggml_type).0);
+                data.resize(data.len().next_multiple_of(32), 0);
+            }
+
+            let mut file = b"GGUF".to_vec();
+            file.extend_from_slice(&3u32.to_le_bytes());
+            file.extend_from_slice(&(self.0.len() as u64).to_le_bytes());
+            file.extend_from_slice(&(hparams.len() as u64 + 2).to_le_bytes());
+            file.extend(keys);
+            file.extend(table);
+            file.resize(file.len().next_multiple_of(32), 0);
+            file.extend(data);
+            std::fs::write(path, file).unwrap();
+        }
+    }
+
+    /// Encode `values` as `ggml_type`, returning the bytes and the values
+    /// they decode to.
+    fn quantize(values: &[f32], ggml_type: GgmlType) -> (Vec<u8>, Vec<f32>) {
+        let mut bytes = Vec::new();
+        let mut decoded = Vec::new();
+        match ggml_type {
+            GgmlType::F32 => {
+                values
+                    .iter()
+                    .for_each(|v| bytes.extend_from_slice(&v.to_le_bytes()));
+                decoded.extend_from_slice(values);
+            }
+            GgmlType::F16 => {
+                for &v in values {
+                    bytes.extend_from_slice(&f16::from_f32(v).to_le_bytes());
+                    decoded.push(f16::from_f32(v).to_f32());
+                }
+            }
+            GgmlType::Q8_0 => {
+                for block in values.chunks(32) {
+                    let d = f16::from_f32(block.iter().fold(0.0f32, |m, v| m.max(v.abs())) / 127.0);
+                    bytes.extend_from_slice(&d.to_le_bytes());
+                    let d = d.to_f32();
+                    let inv = if d > 0.0 { 1.0 / d } else { 0.0 };
+                    let q: Vec<i8> = block.iter().map(|v| (v * inv).round() as i8).collect();
+                    bytes.extend(q.iter().map(|&q| q as u8));
+                    decoded.extend(q.iter().map(|&q| f32::from(q) * d));
+                }
+            }
+            GgmlType::Q4_0 => {
+                for block in values.chunks(32) {
+                    let max = block
+                        .iter()
+                        .fold(0.0f32, |m, &v| if v.abs() > m.abs() { v } else { m });
+                    let d = f16::from_f32(max / -8.0);
+                    bytes.extend_from_slice(&d.to_le_bytes());
+                    let d = d.to_f32();
+                    let inv = if d != 0.0 { 1.0 / d } else { 0.0 };
+                    let q: Vec<u8> = block
+                        .iter()
+                        .map(|v| ((v * inv + 8.5) as u8).min(15))
+                        .collect();
+                    bytes.extend((0..16).map(|j| q[j] | q[j + 16] << 4));
+                    decoded.extend(q.iter().map(|&q| (f32::from(q) - 8.0) * d));
+                }
+            }
+            _ => unreachable!(),
+        }
+        (bytes, decoded)
+    }
+
+    fn load(
+        weights: &Weights,
+        arch: &str,
+        matrix_type: GgmlType,
+        config: &NativeConfig,
+    ) -> NativeModel {
+        let path = std::env::temp_dir().join(format!(
+            "gg_core_native_{arch}_{matrix_type}_{}.gguf",
+            std::process::id()
+        ));
+        weights.write(&path, arch, matrix_type);
+        let model = NativeModel::load(&path, config).unwrap();
+        let _ = std::fs::remove_file(&path);
+        model
+    }
+
+    fn matvec(w: &[f32], x: &[f32]) -> Vec<f32> {
+        w.chunks_exact(x.len())
+            .map(|row| row.iter().zip(x).map(|(w, x)| w * x).sum())
+            .collect()
+    }
+
+    /// Straightforward forward pass over the whole sequence, with explicit
+    /// softmax attention and no cache.
+    fn reference_logits(w: &Weights, tokens: &[u32], style: RopeStyle) -> Vec<f32> {
+        let bias = |name: String, x: &mut Vec<f32>| {
+            if let Some((.., b)) = w.0.iter().find(|(n, ..)| *n == name) {
+                x.iter_mut().zip(b).for_each(|(x, b)| *x += b);
+            }
+        };
+        let embd = w.get("token_embd.weight");
+        let mut xs: Vec<Vec<f32>> = tokens
+            .iter()
+            .map(|&t| embd[t as usize * N_EMBD..(t as usize + 1) * N_EMBD].to_vec())
+            .collect();
+        for i in 0..N_LAYER {
+            let get = |suffix: &str| w.get(&format!("blk.{i}.{suffix}"));
+            let mut ks = Vec::new();
+            let mut vs = Vec::new();
+            let mut qs = Vec::new();
+            for (pos, x) in xs.iter().enumerate() {
+                let mut h = vec![0.0; N_EMBD];
+                rms_norm(x, get("attn_norm.weight"), EPS, &mut h);
+                let mut q = matvec(get("attn_q.weight"), &h);
+                let mut k = matvec(get("attn_k.weight"), &h);
+                let mut v = matvec(get("attn_v.weight"), &h);
+                bias(format!("blk.{i}.attn_q.bias"), &mut q);
+                bias(format!("blk.{i}.attn_k.bias"), &mut k);
+                bias(format!("blk.{i}.attn_v.bias"), &mut v);
+                rope(&mut q, HEAD_DIM, HEAD_DIM, pos, 10_000.0, style);
+                rope(&mut k, HEAD_DIM, HEAD_DIM, pos, 10_000.0, style);
+                qs.push(q);
+                ks.push(k);
+                vs.push(v);
+            }
+            for (pos, x) in xs.iter_mut().enumerate() {
+                let mut attn = vec![0.0; N_EMBD];
+                for head in 0..N_HEAD {
+                    let kv = head / (N_HEAD / N_HEAD_KV) * HEAD_DIM;
+                    let q = &qs[pos][head * HEAD_DIM..(head + 1) * HEAD_DIM];
+                    let scores: Vec<f32> = ks[..=pos]
+                        .iter()
+                        .map(|k| {
+                            let dot: f32 = q
+                                .iter()
+                                .zip(&k[kv..kv + HEAD_DIM])
+                                .map(|(a, b)| a * b)
+                                .sum();
+                            dot / (HEAD_DIM as f32).sqrt()
+                        })
+                        .collect();
+                    let max = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                    let exps: Vec<f32> = scores.iter().map(|s| (s - max).exp()).collect();
+                    let total: f32 = exps.iter().sum();
+                    for (p, e) in exps.iter().enumerate() {
+                        for d in 0..HEAD_DIM {
+                            attn[head * HEAD_DIM + d] += e / total * vs[p][kv + d];
+                        }
+                    }
+                }
+                let out = matvec(get("attn_output.weight"), &attn);
+                x.iter_mut().zip(out).for_each(|(x, o)| *x += o);
+
+                let mut h = vec![0.0; N_EMBD];
+                rms_norm(x, get("ffn_norm.weight"), EPS, &mut h);
+                let gate = matvec(get("ffn_gate.weight"), &h);
+                let up = matvec(get("ffn_up.weight"), &h);
+                let act: Vec<f32> = gate.iter().zip(&up).map(|(g, u)| silu(*g) * u).collect();
+                let out = matvec(get("ffn_down.weight"), &act);
+                x.iter_mut().zip(out).for_each(|(x, o)| *x += o);
+            }
+        }
+        let mut h = vec![0.0; N_EMBD];
+        rms_norm(xs.last().unwrap(), w.get("output_norm.weight"), EPS, &mut h);
+        let output = w.0.iter().find(|(n, ..)| n == "output.weight");
+        matvec(output.map_or(embd, |(.., values)| values), &h)
+    }
+
+    fn max_diff(a: &[f32], b: &[f32]) -> f32 {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max)
+    }
+
+    fn argmax(logits: &[f32]) -> usize {
+        (0..logits.len())
+            .max_by(|&a, &b| logits[a].total_cmp(&logits[b]))
+            .unwrap()
+    }
+
+    const TOKENS: [u32; 7] = [1, 5, 9, 2, 14, 3, 7];
+
+    #[test]
+    fn rope_rotates_pairs_per_style() {
+        let mut x = [1.0, 0.0, 1.0, 0.0];
+        rope(&mut x, 4, 4, 0, 10_000.0, RopeStyle::Interleaved);
+        assert_eq!(x, [1.0, 0.0, 1.0, 0.0]);
+
+        // Position 1 rotates the first pair by 1 radian
+        let mut x = [1.0, 0.0, 0.0, 0.0];
+        rope(&mut x, 4, 4, 1, 10_000.0, RopeStyle::Interleaved);
+        assert!((x[0] - 1f32.cos()).abs() < 1e-6 && (x[1] - 1f32.sin()).abs() < 1e-6);
+        let mut x = [1.0, 0.0, 0.0, 0.0];
+        rope(&mut x, 4, 4, 1, 10_000.0, RopeStyle::Neox);
+        assert!((x[0] - 1f32.cos()).abs() < 1e-6 && (x[2] - 1f32.sin()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn llama_logits_match_reference() {
+        let weights = Weights::random(false);
+        let model = load(&weights, "llama", GgmlType::F32, &NativeConfig::default());
+        assert_eq!(model.hparams().n_vocab, N_VOCAB);
+        assert_eq!(model.context_size(), 64);
+
+        let expected = reference_logits(&weights, &TOKENS, RopeStyle::Interleaved);
+        let logits = model.logits(&TOKENS).unwrap();
+        assert!(
+            max_diff(&logits, &expected) < 1e-4,
+            "{logits:?} vs {expected:?}"
+        );
+    }
+
+    #[test]
+    fn qwen2_uses_biases_and_neox_rope() {
+        // Qwen2 conversions of small models tie the output to the embeddings
+        let mut weights = Weights::random(true);
+        weights.0.retain(|(name, ..)| name != "output.weight");
+        let model = load(&weights, "qwen2", GgmlType::F32, &NativeConfig::default());
+
+        let expected = reference_logits(&weights, &TOKENS, RopeStyle::Neox);
+        let logits = model.logits(&TOKENS).unwrap();
+        assert!(
+            max_diff(&logits, &expected) < 1e-4,
+            "{logits:?} vs {expected:?}"
+        );
+    }
+
+    #[test]
+    fn incremental_forward_matches_whole_prompt() {
+        let weights = Weights::random(false);
+        let model = load(&weights, "llama", GgmlType::F32, &NativeConfig::default());
+        let whole = model.logits(&TOKENS).unwrap();
+
+        let mut session = model.session();
+        session.forward(&TOKENS[..4]).unwrap();
+        let logits = session.forward(&TOKENS[4..]).unwrap();
+        assert_eq!(session.position(), TOKENS.len());
+        assert_eq!(logits, whole);
+    }
+
+    #[test]
+    fn quantized_weights_match_reference() {
+        for matrix_type in [GgmlType::F16, GgmlType::Q8_0, GgmlType::Q4_0] {
+            let weights = Weights::random(false).rounded(matrix_type);
+            let model = load(&weights, "llama", matrix_type, &NativeConfig::default());
+
+            let expected = reference_logits(&weights, &TOKENS, RopeStyle::Interleaved);
+            let logits = model.logits(&TOKENS).unwrap();
+            assert!(
+                max_diff(&logits, &expected) < 1e-4,
+                "{matrix_type}: {logits:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn quantized_kv_cache_stays_close() {
+        let weights = Weights::random(false);
+        let expected = load(&weights, "llama", GgmlType::F32, &NativeConfig::default())
+            .logits(&TOKENS)
+            .unwrap();
+
+        let config = NativeConfig {
+            quantize_kv: true,
+            ..Default::default()
+        };
+        let logits = load(&weights, "llama", GgmlType::F32, &config)
+            .logits(&TOKENS)
+            .unwrap();
+        assert!(
+            max_diff(&logits, &expected) < 0.01,
+            "{logits:?} vs {expected:?}"
+        );
+        assert_eq!(argmax(&logits), argmax(&expected));
+    }
+
+    #[test]
+    fn rejects_bad_input_and_models() {
+        let weights = Weights::random(false);
+        let config = NativeConfig {
+            n_ctx: 8,
+            ..Default::default()
+        };
+        let model = load(&weights, "llama", GgmlType::F32, &config);
+        assert!(matches!(
+            model.logits(&[]),
+            Err(InferenceError::InputValidation(_))
+        ));
+        assert!(matches!(
+            model.logits(&[16]),
+            Err(InferenceError::InputValidation(_))
+        ));
+        let mut session = model.session();
+        session.forward(&TOKENS).unwrap();
+        assert!(matches!(
+            session.forward(&[1, 2]),
+            Err(InferenceError::ContextExceeded {
+                n_ctx: 8,
+                prompt_tokens: 9,
+                ..
+            })
+        ));
+
+        let path =
+            std::env::temp_dir().join(format!("gg_core_native_gpt2_{}.gguf", std::process::id()));
+        weights.write(&path, "gpt2", GgmlType::F32);
+        let err = NativeModel::load(&path, &config).err().unwrap();
+        assert!(err.to_string().contains("not supported"), "{err}");
+
+        let mut missing = Weights::random(false);
+        missing.0.retain(|(name, ..)| name != "blk.1.ffn_up.weight");
+        missing.write(&path, "llama", GgmlType::F32);
+        let err = NativeModel::load(&path, &config).err().unwrap();
+        assert!(err.to_string().contains("blk.1.ffn_up.weight"), "{err}");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,543 @@
+//! Tokenizers stored in GGUF metadata.
+//!
+//! Two vocabularies are supported. SentencePiece (`tokenizer.ggml.model =
+//! "llama"`) starts from characters and keeps merging the adjacent pair
+//! whose joined piece scores highest, falling back to `<0xXX>` byte tokens
+//! for pieces outside the vocabulary. Byte-level BPE (`"gpt2"`) splits text
+//! into words as Llama 3 and Qwen2 do, maps each byte to a printable
+//! character and merges pairs by rank. Other `tokenizer.ggml.pre` values
+//! are split with the same rules, so a few texts may tokenize differently
+//! from llama.cpp.
+//!
+//! Control and user-defined tokens written in the text, such as chat
+//! template markers, become their own tokens.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::engine::gguf::{GgufMetadata, GgufValue};
+use crate::engine::InferenceError;
+
+/// `tokenizer.ggml.token_type` values.
+const TOKEN_CONTROL: u64 = 3;
+const TOKEN_USER_DEFINED: u64 = 4;
+const TOKEN_BYTE: u64 = 6;
+
+/// Control tokens that end a turn in common chat formats, besides the
+/// declared end-of-sequence and end-of-turn tokens.
+const END_OF_TURN: [&str; 6] = [
+    "<|eot_id|>",
+    "<|im_end|>",
+    "<|end|>",
+    "<|endoftext|>",
+    "<|end_of_text|>",
+    "<end_of_turn>",
+];
+
+/// SentencePiece's word boundary marker.
+const SPM_SPACE: char = '\u{2581}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Vocab {
+    SentencePiece,
+    Bpe,
+}
+
+/// Tokenizer of a GGUF model.
+#[derive(Debug, Clone)]
+pub struct NativeTokenizer {
+    vocab: Vocab,
+    ids: HashMap<String, u32>,
+    /// SentencePiece piece scores, by token.
+    scores: Vec<f32>,
+    /// BPE merge ranks, keyed by the two pieces joined with a space.
+    ranks: HashMap<String, usize>,
+    /// Bytes each token decodes to; empty for control tokens.
+    bytes: Vec<Vec<u8>>,
+    /// Token of each byte, for pieces outside the vocabulary.
+    byte_tokens: [Option<u32>; 256],
+    /// Control and user-defined tokens, longest text first.
+    specials: Vec<(String, u32)>,
+    end_of_generation: HashSet<u32>,
+    bos: Option<u32>,
+    eos: Option<u32>,
+    unk: Option<u32>,
+    add_bos: bool,
+    add_space_prefix: bool,
+    /// Longest run of digits in one BPE word.
+    max_digits: usize,
+}
+
+impl NativeTokenizer {
+    /// Read the vocabulary from `tokenizer.ggml.*` metadata.
+    pub fn from_header(header: &GgufMetadata) -> Result<Self, InferenceError> {
+        let invalid = |what: &str| InferenceError::ModelError(format!("tokenizer: {what}"));
+        let vocab = match header.tokenizer_model() {
+            Some("llama") => Vocab::SentencePiece,
+            Some("gpt2") => Vocab::Bpe,
+            Some(other) => return Err(invalid(&format!("{other} vocabularies are not supported"))),
+            None => return Err(invalid("missing tokenizer.ggml.model")),
+        };
+        let array = |key: &str| header.get(key).and_then(GgufValue::as_array);
+        let pieces: Vec<&str> = array("tokenizer.ggml.tokens")
+            .ok_or_else(|| invalid("missing tokenizer.ggml.tokens"))?
+            .iter()
+            .map(|v| v.as_str().ok_or_else(|| invalid("tokens must be strings")))
+            .collect::<Result<_, _>>()?;
+        let n_vocab = pieces.len();
+        let types: Vec<u64> = match array("tokenizer.ggml.token_type") {
+            Some(types) if types.len() == n_vocab => {
+                types.iter().map(|t| t.as_u64().unwrap_or(1)).collect()
+            }
+            Some(_) => return Err(invalid("token_type and tokens differ in length")),
+            None => vec![1; n_vocab],
+        };
+        let scores = match array("tokenizer.ggml.scores") {
+            Some(scores) if scores.len() == n_vocab => {
+                scores.iter().map(|s| s.as_f32().unwrap_or(0.0)).collect()
+            }
+            Some(_) => return Err(invalid("scores and tokens differ in length")),
+            None => vec![0.0; n_vocab],
+        };
+        let ranks = array("tokenizer.ggml.merges")
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .filter_map(|(rank, merge)| Some((merge.as_str()?.to_string(), rank)))
+            .collect();
+        let id = |key: &str| {
+            header
+                .get(key)
+                .and_then(GgufValue::as_u64)
+                .and_then(|id| u32::try_from(id).ok())
+                .filter(|&id| (id as usize) < n_vocab)
+        };
+        let flag = |key: &str, default: bool| match header.get(key) {
+            Some(GgufValue::Bool(value)) => *value,
+            _ => default,
+        };
+
+        let byte_chars = byte_chars();
+        let char_bytes: HashMap<char, u8> =
+            byte_chars.iter().enumerate().map(|(b, &c)| (c, b as u8)).collect();
+        let mut bytes = Vec::with_capacity(n_vocab);
+        let mut byte_tokens = [None; 256];
+        let mut specials = Vec::new();
+        for (token, (&piece, &kind)) in pieces.iter().zip(&types).enumerate() {
+            let token = token as u32;
+            let decoded = match kind {
+                TOKEN_CONTROL => Vec::new(),
+                TOKEN_USER_DEFINED => piece.as_bytes().to_vec(),
+                TOKEN_BYTE => match parse_byte_token(piece) {
+                    Some(byte) => vec![byte],
+                    None => piece.as_bytes().to_vec(),
+                },
+                _ if vocab == Vocab::Bpe => piece
+                    .chars()
+                    .flat_map(|c| match char_bytes.get(&c) {
+                        Some(&b) => vec![b],
+                        None => c.to_string().into_bytes(),
+                    })
+                    .collect(),
+                _ => piece.replace(SPM_SPACE, " ").into_bytes(),
+            };
+            bytes.push(decoded);
+            if matches!(kind, TOKEN_CONTROL | TOKEN_USER_DEFINED) && !piece.is_empty() {
+                specials.push((piece.to_string(), token));
+            }
+        }
+        for (byte, slot) in byte_tokens.iter_mut().enumerate() {
+            let piece = match vocab {
+                Vocab::SentencePiece => format!("<0x{byte:02X}>"),
+                Vocab::Bpe => byte_chars[byte].to_string(),
+            };
+            *slot = pieces.iter().position(|&p| p == piece).map(|t| t as u32);
+        }
+        specials.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then(a.1.cmp(&b.1)));
+
+        let mut end_of_generation: HashSet<u32> = ["eos", "eot", "eom"]
+            .iter()
+            .filter_map(|name| id(&format!("tokenizer.ggml.{name}_token_id")))
+            .collect();
+        end_of_generation.extend(
+            specials
+                .iter()
+                .filter(|(text, token)| {
+                    types[*token as usize] == TOKEN_CONTROL && END_OF_TURN.contains(&text.as_str())
+                })
+                .map(|&(_, token)| token),
+        );
+        let pre = header.get("tokenizer.ggml.pre").and_then(GgufValue::as_str);
+        Ok(Self {
+            vocab,
+            ids: pieces.iter().enumerate().map(|(t, &p)| (p.to_string(), t as u32)).collect(),
+            scores,
+            ranks,
+            bytes,
+            byte_tokens,
+            specials,
+            end_of_generation,
+            bos: id("tokenizer.ggml.bos_token_id"),
+            eos: id("tokenizer.ggml.eos_token_id"),
+            unk: id("tokenizer.ggml.unknown_token_id"),
+            add_bos: flag("tokenizer.ggml.add_bos_token", vocab == Vocab::SentencePiece),
+            add_space_prefix: flag("tokenizer.ggml.add_space_prefix", true),
+            max_digits: if matches!(pre, Some("llama-bpe" | "llama3")) { 3 } else { 1 },
+        })
+    }
+
+    pub fn n_vocab(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// End-of-sequence token, if the vocabulary declares one.
+    pub fn eos(&self) -> Option<u32> {
+        self.eos
+    }
+
+    /// Whether generation ends at `token`: end of sequence or of a turn.
+    pub fn is_end_of_generation(&self, token: u32) -> bool {
+        self.end_of_generation.contains(&token)
+    }
+
+    /// Tokenize `text`, starting with the BOS token when the model asks for
+    /// one.
+    pub fn encode(&self, text: &str) -> Vec<u32> {
+        let mut tokens = Vec::new();
+        if self.add_bos {
+            tokens.extend(self.bos);
+        }
+        let mut after_special = true;
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (start, special) = self.next_special(rest);
+            if start > 0 {
+                self.encode_text(&rest[..start], after_special, &mut tokens);
+            }
+            match special {
+                Some((text, token)) => {
+                    tokens.push(token);
+                    rest = &rest[start + text.len()..];
+                    after_special = true;
+                }
+                None => break,
+            }
+        }
+        tokens
+    }
+
+    /// Bytes `token` stands for; empty for control tokens and ids outside
+    /// the vocabulary.
+    pub fn token_bytes(&self, token: u32) -> &[u8] {
+        self.bytes.get(token as usize).map_or(&[], Vec::as_slice)
+    }
+
+    /// Text of `tokens`; a split multi-byte character decodes lossily.
+    pub fn decode(&self, tokens: &[u32]) -> String {
+        let bytes: Vec<u8> = tokens.iter().flat_map(|&t| self.token_bytes(t)).copied().collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// The first special token in `text` (the longest at that position)
+    /// and its byte offset; the length of `text` when there is none.
+    fn next_special(&self, text: &str) -> (usize, Option<(&str, u32)>) {
+        for (start, _) in text.char_indices() {
+            let found = self
+                .specials
+                .iter()
+                .find(|(special, _)| text[start..].starts_with(special.as_str()));
+            if let Some((special, token)) = found {
+                return (start, Some((special, *token)));
+            }
+        }
+        (text.len(), None)
+    }
+
+    fn encode_text(&self, text: &str, after_special: bool, tokens: &mut Vec<u32>) {
+        match self.vocab {
+            Vocab::SentencePiece => {
+                let mut piece = String::with_capacity(text.len() + 3);
+                if self.add_space_prefix && after_special {
+                    piece.push(SPM_SPACE);
+                }
+                piece.extend(text.chars().map(|c| if c == ' ' { SPM_SPACE } else { c }));
+                let symbols = piece.chars().map(String::from).collect();
+                let merged = merge(symbols, |a, b| {
+                    let joined = format!("{a}{b}");
+                    // Highest score first: rank by the negated score
+                    self.ids.get(&joined).map(|&t| -self.scores[t as usize])
+                });
+                self.push_symbols(&merged, tokens);
+            }
+            Vocab::Bpe => {
+                let byte_chars = byte_chars();
+                for word in split_words(text, self.max_digits) {
+                    let symbols =
+                        word.bytes().map(|b| byte_chars[b as usize].to_string()).collect();
+                    let merged = merge(symbols, |a, b| {
+                        self.ranks.get(&format!("{a} {b}")).map(|&rank| rank as f32)
+                    });
+                    self.push_symbols(&merged, tokens);
+                }
+            }
+        }
+    }
+
+    /// Look up each merged symbol, spelling unknown ones out in byte tokens
+    /// (the unknown token when some byte has none).
+    fn push_symbols(&self, symbols: &[String], tokens: &mut Vec<u32>) {
+        for symbol in symbols {
+            if let Some(&token) = self.ids.get(symbol) {
+                tokens.push(token);
+                continue;
+            }
+            match self.vocab {
+                Vocab::SentencePiece => {
+                    let text = symbol.replace(SPM_SPACE, " ");
+                    let bytes: Option<Vec<u32>> =
+                        text.bytes().map(|b| self.byte_tokens[b as usize]).collect();
+                    match bytes {
+                        Some(bytes) => tokens.extend(bytes),
+                        None => tokens.extend(self.unk),
+                    }
+                }
+                Vocab::Bpe => {
+                    let chars = symbol.chars().filter_map(|c| self.ids.get(&c.to_string()));
+                    tokens.extend(chars);
+                }
+            }
+        }
+    }
+}
+
+/// Repeatedly join the adjacent pair `rank` ranks lowest (leftmost on
+/// ties) until no pair has a rank.
+fn merge(mut symbols: Vec<String>, rank: impl Fn(&str, &str) -> Option<f32>) -> Vec<String> {
+    loop {
+        let best = symbols
+            .windows(2)
+            .enumerate()
+            .filter_map(|(i, pair)| Some((i, rank(&pair[0], &pair[1])?)))
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        let Some((i, _)) = best else {
+            return symbols;
+        };
+        let next = symbols.remove(i + 1);
+        symbols[i].push_str(&next);
+    }
+}
+
+/// The value of a `<0xXX>` byte token.
+fn parse_byte_token(piece: &str) -> Option<u8> {
+    let hex = piece.strip_prefix("<0x")?.strip_suffix('>')?;
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// GPT-2's printable character for each byte: printable Latin-1 bytes map
+/// to themselves and the rest to code points from 256 up.
+fn byte_chars() -> [char; 256] {
+    let mut chars = ['\0'; 256];
+    let mut next = 256;
+    for (byte, c) in chars.iter_mut().enumerate() {
+        let printable = matches!(byte, 0x21..=0x7e | 0xa1..=0xac | 0xae..=0xff);
+        let code = if printable {
+            byte as u32
+        } else {
+            next += 1;
+            next - 1
+        };
+        *c = char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER);
+    }
+    chars
+}
+
+/// Split `text` into BPE words with the Llama 3 / Qwen2 pattern:
+/// contractions, letters with one leading non-letter, runs of up to
+/// `max_digits` digits, punctuation with an optional leading space, and
+/// whitespace, where a run before a word leaves its last space to it.
+fn split_words(text: &str, max_digits: usize) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let offset = |i: usize| chars.get(i).map_or(text.len(), |&(b, _)| b);
+    let at = |i: usize| chars.get(i).map(|&(_, c)| c);
+    let run = |mut i: usize, f: &dyn Fn(char) -> bool| {
+        while at(i).is_some_and(f) {
+            i += 1;
+        }
+        i
+    };
+    let letter = |c: char| c.is_alphabetic();
+    let digit = |c: char| c.is_numeric();
+    let newline = |c: char| c == '\r' || c == '\n';
+    let symbol = |c: char| !c.is_whitespace() && !c.is_alphabetic() && !c.is_numeric();
+
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        let next = at(i + 1);
+        let end = if let Some(len) = contraction(&chars[i..]) {
+            i + len
+        } else if letter(c) {
+            run(i, &letter)
+        } else if !newline(c) && !digit(c) && next.is_some_and(letter) {
+            run(i + 1, &letter)
+        } else if digit(c) {
+            let mut end = i;
+            while end < i + max_digits && at(end).is_some_and(digit) {
+                end += 1;
+            }
+            end
+        } else if symbol(c) || (c == ' ' && next.is_some_and(symbol)) {
+            let start = if c == ' ' { i + 1 } else { i };
+            run(run(start, &symbol), &newline)
+        } else {
+            let end = run(i, &char::is_whitespace);
+            match (i..end).rev().find(|&k| newline(chars[k].1)) {
+                Some(last_newline) => last_newline + 1,
+                None if end == chars.len() || end - i == 1 => end,
+                None => end - 1,
+            }
+        };
+        words.push(&text[offset(i)..offset(end)]);
+        i = end;
+    }
+    words
+}
+
+/// Length in characters of an English contraction suffix at the start of
+/// `chars`, such as `'s` or `'ll`.
+fn contraction(chars: &[(usize, char)]) -> Option<usize> {
+    if chars.first()?.1 != '\'' {
+        return None;
+    }
+    let lower = |k: usize| chars.get(k).map(|&(_, c)| c.to_ascii_lowercase());
+    match (lower(1)?, lower(2)) {
+        ('s' | 't' | 'm' | 'd', _) => Some(2),
+        ('r' | 'v', Some('e')) | ('l', Some('l')) => Some(3),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn strings(values: &[&str]) -> GgufValue {
+        GgufValue::Array(values.iter().map(|s| GgufValue::String(s.to_string())).collect())
+    }
+
+    fn header(entries: Vec<(&str, GgufValue)>) -> GgufMetadata {
+        GgufMetadata {
+            version: 3,
+            metadata: entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect::<BTreeMap<_, _>>(),
+            tensors: Vec::new(),
+            data_offset: 0,
+            file_size: 0,
+        }
+    }
+
+    fn spm() -> NativeTokenizer {
+        let pieces = [
+            "<unk>", "<s>", "</s>", "<0x21>", "<0xC3>", "<0xA9>", "\u{2581}", "h", "e", "l",
+            "o", "\u{2581}h", "ll", "\u{2581}he", "llo", "\u{2581}hello", "w",
+        ];
+        let mut types = vec![GgufValue::I32(1); pieces.len()];
+        types[0] = GgufValue::I32(2);
+        types[1] = GgufValue::I32(3);
+        types[2] = GgufValue::I32(3);
+        for t in &mut types[3..6] {
+            *t = GgufValue::I32(6);
+        }
+        let scores = (0..pieces.len()).map(|i| GgufValue::F32(i as f32)).collect();
+        NativeTokenizer::from_header(&header(vec![
+            ("tokenizer.ggml.model", GgufValue::String("llama".into())),
+            ("tokenizer.ggml.tokens", strings(&pieces)),
+            ("tokenizer.ggml.token_type", GgufValue::Array(types)),
+            ("tokenizer.ggml.scores", GgufValue::Array(scores)),
+            ("tokenizer.ggml.bos_token_id", GgufValue::U32(1)),
+            ("tokenizer.ggml.eos_token_id", GgufValue::U32(2)),
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn sentencepiece_merges_by_score_and_falls_back_to_bytes() {
+        let tokenizer = spm();
+        // "▁hello" wins over its parts; "!" and "é" are spelled in bytes
+        assert_eq!(tokenizer.encode("hello"), [1, 15]);
+        assert_eq!(tokenizer.encode("hello!"), [1, 15, 3]);
+        assert_eq!(tokenizer.encode("hello w\u{e9}"), [1, 15, 6, 16, 4, 5]);
+        assert_eq!(tokenizer.decode(&tokenizer.encode("hello w\u{e9}!")), " hello w\u{e9}!");
+
+        // Control tokens in the text stay whole and decode to nothing
+        assert_eq!(tokenizer.encode("hello</s>hello"), [1, 15, 2, 15]);
+        assert_eq!(tokenizer.decode(&[15, 2]), " hello");
+        assert!(tokenizer.is_end_of_generation(2));
+        assert!(!tokenizer.is_end_of_generation(15));
+    }
+
+    fn bpe(pre: &str) -> NativeTokenizer {
+        let byte_chars = byte_chars();
+        let mut pieces: Vec<String> = byte_chars.iter().map(char::to_string).collect();
+        let space = byte_chars[b' ' as usize];
+        let merges = ["h e", "l l", "he ll", "hell o", &format!("{space} hello")];
+        for merge in merges {
+            pieces.push(merge.replace(' ', ""));
+        }
+        pieces.extend(["<|im_start|>".to_string(), "<|im_end|>".to_string()]);
+        let mut types = vec![GgufValue::I32(1); pieces.len()];
+        let n = types.len();
+        types[n - 2] = GgufValue::I32(3);
+        types[n - 1] = GgufValue::I32(3);
+        let pieces: Vec<&str> = pieces.iter().map(String::as_str).collect();
+        NativeTokenizer::from_header(&header(vec![
+            ("tokenizer.ggml.model", GgufValue::String("gpt2".into())),
+            ("tokenizer.ggml.pre", GgufValue::String(pre.into())),
+            ("tokenizer.ggml.tokens", strings(&pieces)),
+            ("tokenizer.ggml.token_type", GgufValue::Array(types)),
+            ("tokenizer.ggml.merges", strings(&merges)),
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn bpe_merges_words_by_rank() {
+        let tokenizer = bpe("qwen2");
+        let hello = 256 + 3;
+        let space_hello = 256 + 4;
+        // No BOS by default; the space joins the following word
+        assert_eq!(tokenizer.encode("hello hello"), [hello, space_hello]);
+        assert_eq!(tokenizer.encode("<|im_start|>hello<|im_end|>"), [261, hello, 262]);
+        assert!(tokenizer.is_end_of_generation(262));
+
+        let text = "caf\u{e9} 12345\n\n  x";
+        assert_eq!(tokenizer.decode(&tokenizer.encode(text)), text);
+    }
+
+    #[test]
+    fn bpe_words_follow_the_pretokenizer_pattern() {
+        assert_eq!(
+            split_words("Hello world, it's 2024!\n\n  ok   ", 1),
+            [
+                "Hello", " world", ",", " it", "'s", " ", "2", "0", "2", "4", "!\n\n", " ", " ok",
+                "   "
+            ]
+        );
+        assert_eq!(split_words("x 12345", 3), ["x", " ", "123", "45"]);
+        assert_eq!(split_words("a  \n b", 3), ["a", "  \n", " b"]);
+    }
+
+    #[test]
+    fn rejects_unsupported_vocabularies() {
+        let err = NativeTokenizer::from_header(&header(vec![(
+            "tokenizer.ggml.model",
+            GgufValue::String("bert".into()),
+        )]))
+        .unwrap_err();
+        assert!(err.to_string().contains("bert"), "{err}");
+        assert!(NativeTokenizer::from_header(&header(Vec::new())).is_err());
+    }
+}
@@ -4,6 +4,7 @@
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use super::softmax;
 use crate::engine::{
     ClassificationResult, InferenceCapability, InferenceConfig, InferenceError,
     InferenceInput, InferenceOutput,
@@ -12,11 +13,11 @@ use crate::engine::{
 /// ONNX classification model using Candle.
 pub struct OnnxClassifier {
     model_id: String,
-    #[allow(dead_code)]
     labels: Vec<String>,
     memory_bytes: AtomicUsize,
+    max_batch_size: usize,
     #[cfg(feature = "onnx")]
-    _model: Option<()>, // Placeholder for candle model
+    encoder: Option<super::encoder::OnnxEncoder>,
 }
 
 impl OnnxClassifier {
@@ -26,20 +27,111 @@ impl OnnxClassifier {
             model_id,
             labels,
             memory_bytes: AtomicUsize::new(0),
+            max_batch_size: crate::engine::MAX_BATCH_SIZE,
             #[cfg(feature = "onnx")]
-            _model: None,
+            encoder: None,
         }
     }
 
-    /// Run classification on a single text input.
-    fn classify_text(&self, _text: &str) -> Result<ClassificationResult, InferenceError> {
+    /// Load a sequence-classification encoder from an ONNX file.
+    ///
+    /// Labels come from the config, then the `id2label` map of a sibling
+    /// `config.json`, then `LABEL_<n>`. A probe run checks that the label
+    /// count matches the logits width.
+    #[cfg(feature = "onnx")]
+    pub fn load(
+        model_id: String,
+        path: &std::path::Path,
+        config: &super::OnnxConfig,
+    ) -> Result<Self, InferenceError> {
+        let encoder = super::encoder::OnnxEncoder::load(path, config)?;
+        let mem = std::fs::metadata(path).map(|m| m.len() as usize).unwrap_or(0);
+
+        let probe = encoder.run(&["probe"])?;
+        let num_labels = probe.rows()?[0].len();
+        let labels = if !config.labels.is_empty() {
+            config.labels.clone()
+        } else {
+            read_id2label(&path.with_file_name("config.json"))
+                .unwrap_or_else(|| (0..num_labels).map(|i| format!("LABEL_{}", i)).collect())
+        };
+        if labels.len() != num_labels {
+            return Err(InferenceError::InvalidFormat(format!(
+                "model has {} outputs but {} labels were configured",
+                num_labels,
+                labels.len()
+            )));
+        }
+
+        Ok(Self {
+            model_id,
+            labels,
+            memory_bytes: AtomicUsize::new(mem),
+            max_batch_size: config.max_batch_size.max(1),
+            encoder: Some(encoder),
+        })
+    }
+
+    /// Labels by output index.
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Classify texts in padded batches of at most `max_batch_size`.
+    fn classify_batch(&self, texts: &[&str]) -> Result<Vec<ClassificationResult>, InferenceError> {
+        #[cfg(feature = "onnx")]
+        if let Some(encoder) = &self.encoder {
+            let mut results = Vec::with_capacity(texts.len());
+            for chunk in texts.chunks(self.max_batch_size) {
+                let output = encoder.run(chunk)?;
+                for logits in output.rows()? {
+                    results.push(self.rank_labels(logits));
+                }
+            }
+            return Ok(results);
+        }
+        let _ = (texts, self.max_batch_size);
         // ONNX model not loaded - fail rather than return mock data
-        // Real implementation requires candle-onnx with loaded model
         Err(InferenceError::ModelError(format!(
             "ONNX model '{}' not loaded - enable 'onnx' feature and load model",
             self.model_id
         )))
     }
+
+    /// Run classification on a single text input.
+    fn classify_text(&self, text: &str) -> Result<ClassificationResult, InferenceError> {
+        self.classify_batch(&[text])?
+            .pop()
+            .ok_or_else(|| InferenceError::ModelError("encoder returned no logits".into()))
+    }
+
+    /// Softmax the logits and sort labels by confidence.
+    #[cfg_attr(not(feature = "onnx"), allow(dead_code))]
+    fn rank_labels(&self, logits: &[f32]) -> ClassificationResult {
+        let mut all_labels: Vec<(String, f32)> = self
+            .labels
+            .iter()
+            .cloned()
+            .zip(softmax(logits))
+            .collect();
+        all_labels.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let (label, confidence) = all_labels.first().cloned().unwrap_or_default();
+        ClassificationResult { label, confidence, all_labels }
+    }
+}
+
+/// Read `id2label` from a Hugging Face `config.json`, ordered by index.
+#[cfg(feature = "onnx")]
+fn read_id2label(path: &std::path::Path) -> Option<Vec<String>> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let json: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    let map = json.get("id2label")?.as_object()?;
+    let mut labels = vec![String::new(); map.len()];
+    for (index, label) in map {
+        let slot = labels.get_mut(index.parse::<usize>().ok()?)?;
+        *slot = label.as_str()?.to_string();
+    }
+    Some(labels)
 }
 
 #[async_trait::async_trait]
@@ -69,7 +161,7 @@ impl super::OnnxModel for OnnxClassifier {
                 Ok(InferenceOutput::Classification(result))
             }
             InferenceInput::TextBatch(batch) => {
-                // Classify first item for now (batch support would aggregate)
+                // Single-output API: classify the first item (see infer_batch)
                 let text = batch.first().ok_or_else(|| {
                     InferenceError::InputValidation("batch cannot be empty".into())
                 })?;
@@ -82,12 +174,40 @@ impl super::OnnxModel for OnnxClassifier {
         }
     }
 
+    async fn infer_batch(
+        &self,
+        texts: &[String],
+        _config: &InferenceConfig,
+    ) -> Result<Vec<InferenceOutput>, InferenceError> {
+        InferenceInput::TextBatch(texts.to_vec()).validate()?;
+        let refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let results = self.classify_batch(&refs)?;
+        Ok(results.into_iter().map(InferenceOutput::Classification).collect())
+    }
+
     async fn unload(&mut self) -> Result<(), InferenceError> {
         self.memory_bytes.store(0, Ordering::SeqCst);
         #[cfg(feature = "onnx")]
         {
-            self._model = None;
+            self.encoder = None;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rank_labels_sorts_by_confidence() {
+        let classifier = OnnxClassifier::new(
+            "sentiment".into(),
+            vec!["negative".into(), "positive".into()],
+        );
+        let result = classifier.rank_labels(&[-1.0, 2.0]);
+        assert_eq!(result.label, "positive");
+        assert!(result.confidence > 0.9);
+        assert_eq!(result.all_labels[1].0, "negative");
+    }
+}
@@ -4,6 +4,7 @@
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use super::OnnxPooling;
 use crate::engine::{
     EmbeddingResult, InferenceCapability, InferenceConfig, InferenceError,
     InferenceInput, InferenceOutput,
@@ -12,11 +13,13 @@ use crate::engine::{
 /// ONNX embedding model using Candle.
 pub struct OnnxEmbedder {
     model_id: String,
-    #[allow(dead_code)]
     embedding_dim: usize,
     memory_bytes: AtomicUsize,
+    pooling: OnnxPooling,
+    normalize: bool,
+    max_batch_size: usize,
     #[cfg(feature = "onnx")]
-    _model: Option<()>, // Placeholder for candle model
+    encoder: Option<super::encoder::OnnxEncoder>,
 }
 
 impl OnnxEmbedder {
@@ -26,20 +29,71 @@ impl OnnxEmbedder {
             model_id,
             embedding_dim,
             memory_bytes: AtomicUsize::new(0),
+            pooling: OnnxPooling::default(),
+            normalize: true,
+            max_batch_size: crate::engine::MAX_BATCH_SIZE,
             #[cfg(feature = "onnx")]
-            _model: None,
+            encoder: None,
         }
     }
 
-    /// Generate embedding for a single text input.
-    fn embed_text(&self, _text: &str) -> Result<EmbeddingResult, InferenceError> {
+    /// Load an encoder from an ONNX file and probe its embedding dimension.
+    #[cfg(feature = "onnx")]
+    pub fn load(
+        model_id: String,
+        path: &std::path::Path,
+        config: &super::OnnxConfig,
+    ) -> Result<Self, InferenceError> {
+        let encoder = super::encoder::OnnxEncoder::load(path, config)?;
+        let mem = std::fs::metadata(path).map(|m| m.len() as usize).unwrap_or(0);
+        let mut embedder = Self {
+            model_id,
+            embedding_dim: 0,
+            memory_bytes: AtomicUsize::new(mem),
+            pooling: config.pooling,
+            normalize: config.normalize,
+            max_batch_size: config.max_batch_size.max(1),
+            encoder: Some(encoder),
+        };
+        let probe = embedder.embed_batch(&["probe"])?;
+        embedder.embedding_dim = probe[0].dimensions;
+        Ok(embedder)
+    }
+
+    /// Dimensionality of the produced vectors.
+    pub fn embedding_dim(&self) -> usize {
+        self.embedding_dim
+    }
+
+    /// Embed texts in padded batches of at most `max_batch_size`.
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<EmbeddingResult>, InferenceError> {
+        #[cfg(feature = "onnx")]
+        if let Some(encoder) = &self.encoder {
+            let mut results = Vec::with_capacity(texts.len());
+            for chunk in texts.chunks(self.max_batch_size) {
+                let output = encoder.run(chunk)?;
+                let vectors = output.sentence_embeddings(self.pooling, self.normalize)?;
+                results.extend(vectors.into_iter().map(|vector| EmbeddingResult {
+                    dimensions: vector.len(),
+                    vector,
+                }));
+            }
+            return Ok(results);
+        }
+        let _ = (texts, self.pooling, self.normalize, self.max_batch_size);
         // ONNX model not loaded - fail rather than return mock data
-        // Real implementation requires candle-onnx with loaded model
         Err(InferenceError::ModelError(format!(
             "ONNX model '{}' not loaded - enable 'onnx' feature and load model",
             self.model_id
         )))
     }
+
+    /// Generate embedding for a single text input.
+    fn embed_text(&self, text: &str) -> Result<EmbeddingResult, InferenceError> {
+        self.embed_batch(&[text])?
+            .pop()
+            .ok_or_else(|| InferenceError::ModelError("encoder returned no embedding".into()))
+    }
 }
 
 #[async_trait::async_trait]
@@ -69,7 +123,7 @@ impl super::OnnxModel for OnnxEmbedder {
                 Ok(InferenceOutput::Embedding(result))
             }
             InferenceInput::TextBatch(batch) => {
-                // Embed first item for now (batch support would return multiple)
+                // Single-output API: embed the first item (see infer_batch)
                 let text = batch.first().ok_or_else(|| {
                     InferenceError::InputValidation("batch cannot be empty".into())
                 })?;
@@ -82,11 +136,22 @@ impl super::OnnxModel for OnnxEmbedder {
         }
     }
 
+    async fn infer_batch(
+        &self,
+        texts: &[String],
+        _config: &InferenceConfig,
+    ) -> Result<Vec<InferenceOutput>, InferenceError> {
+        InferenceInput::TextBatch(texts.to_vec()).validate()?;
+        let refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let results = self.embed_batch(&refs)?;
+        Ok(results.into_iter().map(InferenceOutput::Embedding).collect())
+    }
+
     async fn unload(&mut self) -> Result<(), InferenceError> {
         self.memory_bytes.store(0, Ordering::SeqCst);
         #[cfg(feature = "onnx")]
         {
-            self._model = None;
+            self.encoder = None;
         }
         Ok(())
     }
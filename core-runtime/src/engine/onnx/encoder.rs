@@ -0,0 +1,141 @@
+//! Candle ONNX session for BERT-style encoders.
+//!
+//! Tokenizes a batch of texts or text pairs with WordPiece, feeds
+//! `input_ids`, `attention_mask` and (if the graph declares it)
+//! `token_type_ids`, and returns the first graph output. Pooling and heads
+//! are applied by the embedder, classifier and reranker.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use candle_core::{DType, Device, Tensor};
+use candle_onnx::onnx::ModelProto;
+
+use super::pooling::EncoderOutput;
+use super::wordpiece::{Encoding, WordPieceTokenizer};
+use super::OnnxConfig;
+use crate::engine::InferenceError;
+
+const INPUT_IDS: &str = "input_ids";
+const ATTENTION_MASK: &str = "attention_mask";
+const TOKEN_TYPE_IDS: &str = "token_type_ids";
+
+/// Loaded ONNX encoder graph plus its tokenizer.
+pub struct OnnxEncoder {
+    model: ModelProto,
+    tokenizer: WordPieceTokenizer,
+    inputs: Vec<String>,
+    output: String,
+    max_sequence_length: usize,
+}
+
+impl OnnxEncoder {
+    /// Load the graph at `path` and the vocabulary next to it (or at
+    /// `config.vocab_path`).
+    ///
+    /// # Errors
+    /// Returns `InvalidFormat` if the graph does not look like a BERT encoder.
+    pub fn load(path: &Path, config: &OnnxConfig) -> Result<Self, InferenceError> {
+        let vocab_path = config
+            .vocab_path
+            .clone()
+            .unwrap_or_else(|| path.with_file_name("vocab.txt"));
+        let tokenizer = WordPieceTokenizer::from_file(&vocab_path, config.lowercase)?;
+
+        let model = candle_onnx::read_file(path).map_err(|e| {
+            InferenceError::ModelError(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        let graph = model
+            .graph
+            .as_ref()
+            .ok_or_else(|| InferenceError::InvalidFormat("ONNX file has no graph".into()))?;
+
+        // Pre-IR4 exports list weights as graph inputs; skip those.
+        let mut inputs = Vec::new();
+        for input in &graph.input {
+            if graph.initializer.iter().any(|t| t.name == input.name) {
+                continue;
+            }
+            match input.name.as_str() {
+                INPUT_IDS | ATTENTION_MASK | TOKEN_TYPE_IDS => inputs.push(input.name.clone()),
+                other => {
+                    return Err(InferenceError::InvalidFormat(format!(
+                        "unsupported encoder input '{}'",
+                        other
+                    )))
+                }
+            }
+        }
+        if !inputs.iter().any(|name| name == INPUT_IDS) {
+            return Err(InferenceError::InvalidFormat(
+                "encoder graph has no input_ids input".into(),
+            ));
+        }
+        let output = graph
+            .output
+            .first()
+            .map(|o| o.name.clone())
+            .ok_or_else(|| InferenceError::InvalidFormat("encoder graph has no outputs".into()))?;
+
+        Ok(Self {
+            model,
+            tokenizer,
+            inputs,
+            output,
+            max_sequence_length: config.max_sequence_length,
+        })
+    }
+
+    /// Run one padded batch through the graph.
+    pub fn run(&self, texts: &[&str]) -> Result<EncoderOutput, InferenceError> {
+        self.run_encodings(self.tokenizer.encode_batch(texts, self.max_sequence_length))
+    }
+
+    /// Run one padded batch of text pairs (e.g. query and document).
+    pub fn run_pairs(&self, pairs: &[(&str, &str)]) -> Result<EncoderOutput, InferenceError> {
+        self.run_encodings(self.tokenizer.encode_pair_batch(pairs, self.max_sequence_length))
+    }
+
+    fn run_encodings(&self, encodings: Vec<Encoding>) -> Result<EncoderOutput, InferenceError> {
+        let shape = (encodings.len(), encodings.first().map_or(0, Encoding::len));
+
+        let mut feeds = HashMap::new();
+        for name in &self.inputs {
+            let values: Vec<i64> = match name.as_str() {
+                INPUT_IDS => flatten(&encodings, |e| &e.input_ids),
+                ATTENTION_MASK => flatten(&encodings, |e| &e.attention_mask),
+                _ => flatten(&encodings, |e| &e.token_type_ids),
+            };
+            let tensor = Tensor::from_vec(values, shape, &Device::Cpu).map_err(candle_error)?;
+            feeds.insert(name.clone(), tensor);
+        }
+
+        let mut outputs = candle_onnx::simple_eval(&self.model, feeds).map_err(candle_error)?;
+        let tensor = outputs.remove(&self.output).ok_or_else(|| {
+            InferenceError::ModelError(format!("encoder produced no '{}' output", self.output))
+        })?;
+        let dims = tensor.dims().to_vec();
+        let data = tensor
+            .to_dtype(DType::F32)
+            .and_then(|t| t.flatten_all())
+            .and_then(|t| t.to_vec1::<f32>())
+            .map_err(candle_error)?;
+
+        Ok(EncoderOutput {
+            shape: dims,
+            data,
+            attention_mask: encodings.into_iter().map(|e| e.attention_mask).collect(),
+        })
+    }
+}
+
+fn flatten(encodings: &[Encoding], field: impl Fn(&Encoding) -> &Vec<u32>) -> Vec<i64> {
+    encodings
+        .iter()
+        .flat_map(|e| field(e).iter().map(|&v| v as i64))
+        .collect()
+}
+
+fn candle_error(e: candle_core::Error) -> InferenceError {
+    InferenceError::ModelError(format!("ONNX execution failed: {}", e))
+}
@@ -1,14 +1,24 @@
 //! ONNX inference backend using Candle.
 //!
-//! Provides classification and embedding models via pure Rust ONNX runtime.
+//! Provides classification, embedding and reranking models via pure Rust ONNX
+//! runtime.
+//! Targets BERT-style encoders exported with a WordPiece `vocab.txt`.
 
 mod classifier;
 mod embedder;
+#[cfg(feature = "onnx")]
+mod encoder;
+mod pooling;
+mod reranker;
+mod wordpiece;
 
 pub use classifier::OnnxClassifier;
 pub use embedder::OnnxEmbedder;
+pub use pooling::{l2_normalize, softmax, EncoderOutput, OnnxPooling};
+pub use reranker::OnnxReranker;
+pub use wordpiece::{Encoding, WordPieceTokenizer};
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crate::engine::{InferenceCapability, InferenceConfig, InferenceError};
@@ -21,6 +31,21 @@ pub struct OnnxConfig {
     pub max_batch_size: usize,
     /// Device to run inference on (cpu only for sandboxed runtime).
     pub device: OnnxDevice,
+    /// Which head the model file implements.
+    pub task: OnnxTask,
+    /// Sentence pooling for embedding models.
+    pub pooling: OnnxPooling,
+    /// L2-normalize embeddings (cosine similarity becomes a dot product).
+    pub normalize: bool,
+    /// Maximum tokens per input, including `[CLS]` and `[SEP]`.
+    pub max_sequence_length: usize,
+    /// Lowercase and strip accents before WordPiece (uncased models).
+    pub lowercase: bool,
+    /// WordPiece vocabulary. None = `vocab.txt` next to the model file.
+    pub vocab_path: Option<PathBuf>,
+    /// Classifier labels by output index. Empty = `id2label` from the
+    /// `config.json` next to the model, else `LABEL_<n>`.
+    pub labels: Vec<String>,
 }
 
 impl Default for OnnxConfig {
@@ -28,10 +53,28 @@ impl Default for OnnxConfig {
         Self {
             max_batch_size: 32,
             device: OnnxDevice::Cpu,
+            task: OnnxTask::Embedding,
+            pooling: OnnxPooling::Mean,
+            normalize: true,
+            max_sequence_length: 512,
+            lowercase: true,
+            vocab_path: None,
+            labels: Vec::new(),
         }
     }
 }
 
+/// Model head served by an ONNX encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnnxTask {
+    /// Hidden states pooled into sentence embeddings.
+    Embedding,
+    /// Sequence classification logits.
+    Classification,
+    /// Cross-encoder relevance logits for query-document pairs.
+    Rerank,
+}
+
 /// Device for ONNX inference.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OnnxDevice {
@@ -51,6 +94,36 @@ pub trait OnnxModel: Send + Sync {
         config: &InferenceConfig,
     ) -> Result<InferenceOutput, InferenceError>;
 
+    /// Run each text as its own input, returning outputs in order.
+    ///
+    /// The default runs texts one at a time; encoders override this to run
+    /// padded batches.
+    async fn infer_batch(
+        &self,
+        texts: &[String],
+        config: &InferenceConfig,
+    ) -> Result<Vec<InferenceOutput>, InferenceError> {
+        let mut outputs = Vec::with_capacity(texts.len());
+        for text in texts {
+            outputs.push(self.infer(&InferenceInput::Text(text.clone()), config).await?);
+        }
+        Ok(outputs)
+    }
+
+    /// Relevance score of each document for `query`, in input order, for
+    /// at most `MAX_BATCH_SIZE` documents. Each query-document pair is
+    /// truncated to fit the model.
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<f32>, InferenceError> {
+        let _ = (query, documents);
+        Err(InferenceError::CapabilityNotSupported(
+            "model does not support reranking".into(),
+        ))
+    }
+
     async fn unload(&mut self) -> Result<(), InferenceError>;
 }
 
@@ -65,14 +138,23 @@ pub trait OnnxModel: Send + Sync {
 /// Returns error if model cannot be loaded or is invalid format.
 #[cfg(feature = "onnx")]
 pub fn load_onnx_model(
-    _path: &Path,
-    _model_id: &str,
-    _config: &OnnxConfig,
+    path: &Path,
+    model_id: &str,
+    config: &OnnxConfig,
 ) -> Result<Arc<dyn OnnxModel>, InferenceError> {
-    // Actual candle-onnx loading would go here
-    Err(InferenceError::ModelError(
-        "ONNX model loading requires candle implementation".into(),
-    ))
+    if !path.exists() {
+        return Err(InferenceError::ModelError(
+            format!("model file not found: {}", path.display()),
+        ));
+    }
+    let model: Arc<dyn OnnxModel> = match config.task {
+        OnnxTask::Embedding => Arc::new(OnnxEmbedder::load(model_id.to_string(), path, config)?),
+        OnnxTask::Classification => {
+            Arc::new(OnnxClassifier::load(model_id.to_string(), path, config)?)
+        }
+        OnnxTask::Rerank => Arc::new(OnnxReranker::load(model_id.to_string(), path, config)?),
+    };
+    Ok(model)
 }
 
 /// Stub for non-onnx builds.
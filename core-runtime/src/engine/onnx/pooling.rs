@@ -0,0 +1,215 @@
+//! Post-processing for encoder outputs: pooling, normalization, softmax.
+//!
+//! Operates on plain `f32` slices so the math is shared by every backend
+//! and testable without the `onnx` feature.
+
+use crate::engine::InferenceError;
+
+/// Raw output tensor of an encoder run, flattened row-major.
+#[derive(Debug, Clone)]
+pub struct EncoderOutput {
+    /// Tensor shape: `[batch, seq, hidden]` or already pooled `[batch, n]`.
+    pub shape: Vec<usize>,
+    /// Tensor values.
+    pub data: Vec<f32>,
+    /// Attention mask of each input sequence.
+    pub attention_mask: Vec<Vec<u32>>,
+}
+
+impl EncoderOutput {
+    /// One sentence vector per input, pooled from token states if needed.
+    ///
+    /// # Errors
+    /// Returns `InvalidFormat` if the tensor is neither rank 2 nor rank 3.
+    pub fn sentence_embeddings(
+        &self,
+        pooling: OnnxPooling,
+        normalize: bool,
+    ) -> Result<Vec<Vec<f32>>, InferenceError> {
+        let mut vectors: Vec<Vec<f32>> = match self.shape.as_slice() {
+            [_, seq, dim] if seq * dim > 0 => self
+                .data
+                .chunks_exact(seq * dim)
+                .zip(&self.attention_mask)
+                .map(|(hidden, mask)| pooling.pool(hidden, mask, *dim))
+                .collect(),
+            [_, _] => self.rows()?.into_iter().map(<[f32]>::to_vec).collect(),
+            other => {
+                return Err(InferenceError::InvalidFormat(format!(
+                    "unexpected encoder output shape {:?}",
+                    other
+                )))
+            }
+        };
+        if normalize {
+            vectors.iter_mut().for_each(|v| l2_normalize(v));
+        }
+        Ok(vectors)
+    }
+
+    /// Rows of a rank-2 output (e.g. classifier logits).
+    ///
+    /// # Errors
+    /// Returns `InvalidFormat` for any other rank.
+    pub fn rows(&self) -> Result<Vec<&[f32]>, InferenceError> {
+        match self.shape.as_slice() {
+            [_, n] if *n > 0 => Ok(self.data.chunks_exact(*n).collect()),
+            other => Err(InferenceError::InvalidFormat(format!(
+                "expected [batch, n] output, got shape {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// One relevance score per input pair from a cross-encoder head: the
+    /// logit of a single-output head, or the probability of the second
+    /// ("relevant") class of a two-class head.
+    ///
+    /// # Errors
+    /// Returns `InvalidFormat` for any other output shape.
+    pub fn relevance_scores(&self) -> Result<Vec<f32>, InferenceError> {
+        match self.shape.as_slice() {
+            [_, 1] => Ok(self.data.clone()),
+            [_, 2] => Ok(self.rows()?.into_iter().map(|logits| softmax(logits)[1]).collect()),
+            other => Err(InferenceError::InvalidFormat(format!(
+                "expected [batch, 1] or [batch, 2] relevance logits, got shape {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// How token-level hidden states are reduced to one sentence vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnnxPooling {
+    /// Average over non-padding tokens (sentence-transformers default).
+    #[default]
+    Mean,
+    /// Hidden state of the leading `[CLS]` token.
+    Cls,
+}
+
+impl OnnxPooling {
+    /// Reduce one sequence of hidden states (`mask.len()` rows of `dim`).
+    pub fn pool(&self, hidden: &[f32], mask: &[u32], dim: usize) -> Vec<f32> {
+        match self {
+            Self::Cls => hidden[..dim].to_vec(),
+            Self::Mean => {
+                let mut sum = vec![0.0f32; dim];
+                let mut count = 0usize;
+                for (row, &m) in hidden.chunks_exact(dim).zip(mask) {
+                    if m == 0 {
+                        continue;
+                    }
+                    count += 1;
+                    for (acc, v) in sum.iter_mut().zip(row) {
+                        *acc += v;
+                    }
+                }
+                let denom = count.max(1) as f32;
+                sum.iter_mut().for_each(|v| *v /= denom);
+                sum
+            }
+        }
+    }
+}
+
+/// Scale a vector to unit L2 norm in place. Zero vectors are left as-is.
+pub fn l2_normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > f32::EPSILON {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// Numerically stable softmax over classifier logits.
+pub fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = logits.iter().map(|l| (l - max).exp()).collect();
+    let total: f32 = exps.iter().sum();
+    exps.into_iter().map(|e| e / total).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Three tokens of dim 2; the last one is padding.
+    const HIDDEN: [f32; 6] = [1.0, 2.0, 3.0, 4.0, 100.0, 100.0];
+    const MASK: [u32; 3] = [1, 1, 0];
+
+    #[test]
+    fn mean_pooling_ignores_padding() {
+        assert_eq!(OnnxPooling::Mean.pool(&HIDDEN, &MASK, 2), vec![2.0, 3.0]);
+    }
+
+    #[test]
+    fn cls_pooling_takes_first_token() {
+        assert_eq!(OnnxPooling::Cls.pool(&HIDDEN, &MASK, 2), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn sentence_embeddings_pool_each_sequence() {
+        let output = EncoderOutput {
+            shape: vec![2, 3, 2],
+            data: [HIDDEN, [5.0, 5.0, 0.0, 0.0, 0.0, 0.0]].concat(),
+            attention_mask: vec![MASK.to_vec(), vec![1, 0, 0]],
+        };
+        let vectors = output.sentence_embeddings(OnnxPooling::Mean, false).unwrap();
+        assert_eq!(vectors, vec![vec![2.0, 3.0], vec![5.0, 5.0]]);
+
+        let normalized = output.sentence_embeddings(OnnxPooling::Mean, true).unwrap();
+        let norm: f32 = normalized[1].iter().map(|v| v * v).sum();
+        assert!((norm - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pooled_output_used_directly() {
+        let output = EncoderOutput {
+            shape: vec![1, 2],
+            data: vec![0.5, 0.25],
+            attention_mask: vec![vec![1, 1, 1]],
+        };
+        let vectors = output.sentence_embeddings(OnnxPooling::Cls, false).unwrap();
+        assert_eq!(vectors, vec![vec![0.5, 0.25]]);
+
+        let bad = EncoderOutput { shape: vec![4], ..output };
+        assert!(matches!(
+            bad.sentence_embeddings(OnnxPooling::Mean, false),
+            Err(InferenceError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn relevance_scores_read_one_or_two_class_heads() {
+        let single = EncoderOutput {
+            shape: vec![2, 1],
+            data: vec![1.5, -0.5],
+            attention_mask: vec![vec![1], vec![1]],
+        };
+        assert_eq!(single.relevance_scores().unwrap(), vec![1.5, -0.5]);
+
+        let pair = EncoderOutput { shape: vec![1, 2], data: vec![0.0, 0.0], ..single.clone() };
+        assert_eq!(pair.relevance_scores().unwrap(), vec![0.5]);
+        let wide = EncoderOutput { shape: vec![1, 3], data: vec![0.0; 3], ..single };
+        assert!(matches!(wide.relevance_scores(), Err(InferenceError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn l2_normalize_produces_unit_vector() {
+        let mut v = vec![3.0, 4.0];
+        l2_normalize(&mut v);
+        assert_eq!(v, vec![0.6, 0.8]);
+
+        let mut zero = vec![0.0, 0.0];
+        l2_normalize(&mut zero);
+        assert_eq!(zero, vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn softmax_sums_to_one_and_preserves_order() {
+        let probs = softmax(&[1000.0, 1001.0, 999.0]);
+        assert!((probs.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(probs[1] > probs[0] && probs[0] > probs[2]);
+    }
+}
@@ -0,0 +1,117 @@
+//! ONNX-based reranking model.
+//!
+//! Wraps Candle ONNX runtime for cross-encoders (ms-marco MiniLM, bge-reranker)
+//! that score a query and document read together as one sequence.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::engine::{
+    InferenceCapability, InferenceConfig, InferenceError, InferenceInput, InferenceOutput,
+};
+
+/// ONNX cross-encoder reranking model using Candle.
+pub struct OnnxReranker {
+    model_id: String,
+    memory_bytes: AtomicUsize,
+    max_batch_size: usize,
+    #[cfg(feature = "onnx")]
+    encoder: Option<super::encoder::OnnxEncoder>,
+}
+
+impl OnnxReranker {
+    /// Create a new reranker with the given model ID.
+    pub fn new(model_id: String) -> Self {
+        Self {
+            model_id,
+            memory_bytes: AtomicUsize::new(0),
+            max_batch_size: crate::engine::MAX_BATCH_SIZE,
+            #[cfg(feature = "onnx")]
+            encoder: None,
+        }
+    }
+
+    /// Load a cross-encoder from an ONNX file. A probe run checks that the
+    /// head produces one relevance score per pair.
+    #[cfg(feature = "onnx")]
+    pub fn load(
+        model_id: String,
+        path: &std::path::Path,
+        config: &super::OnnxConfig,
+    ) -> Result<Self, InferenceError> {
+        let encoder = super::encoder::OnnxEncoder::load(path, config)?;
+        let mem = std::fs::metadata(path).map(|m| m.len() as usize).unwrap_or(0);
+        encoder.run_pairs(&[("probe", "probe")])?.relevance_scores()?;
+
+        Ok(Self {
+            model_id,
+            memory_bytes: AtomicUsize::new(mem),
+            max_batch_size: config.max_batch_size.max(1),
+            encoder: Some(encoder),
+        })
+    }
+
+    /// Score documents against `query` in padded batches of at most
+    /// `max_batch_size` pairs.
+    fn score(&self, query: &str, documents: &[&str]) -> Result<Vec<f32>, InferenceError> {
+        #[cfg(feature = "onnx")]
+        if let Some(encoder) = &self.encoder {
+            let mut scores = Vec::with_capacity(documents.len());
+            for chunk in documents.chunks(self.max_batch_size) {
+                let pairs: Vec<(&str, &str)> = chunk.iter().map(|doc| (query, *doc)).collect();
+                scores.extend(encoder.run_pairs(&pairs)?.relevance_scores()?);
+            }
+            return Ok(scores);
+        }
+        let _ = (query, documents, self.max_batch_size);
+        // ONNX model not loaded - fail rather than return mock data
+        Err(InferenceError::ModelError(format!(
+            "ONNX model '{}' not loaded - enable 'onnx' feature and load model",
+            self.model_id
+        )))
+    }
+}
+
+#[async_trait::async_trait]
+impl super::OnnxModel for OnnxReranker {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn capabilities(&self) -> &[InferenceCapability] {
+        &[InferenceCapability::Rerank]
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.memory_bytes.load(Ordering::SeqCst)
+    }
+
+    async fn infer(
+        &self,
+        _input: &InferenceInput,
+        _config: &InferenceConfig,
+    ) -> Result<InferenceOutput, InferenceError> {
+        Err(InferenceError::CapabilityNotSupported(
+            "reranking models only score query-document pairs".into(),
+        ))
+    }
+
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<f32>, InferenceError> {
+        InferenceInput::Text(query.to_string()).validate()?;
+        InferenceInput::TextBatch(documents.to_vec()).validate()?;
+        let refs: Vec<&str> = documents.iter().map(String::as_str).collect();
+        self.score(query, &refs)
+    }
+
+    async fn unload(&mut self) -> Result<(), InferenceError> {
+        self.memory_bytes.store(0, Ordering::SeqCst);
+        #[cfg(feature = "onnx")]
+        {
+            self.encoder = None;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,344 @@
+//! BERT WordPiece tokenizer for ONNX encoder models.
+//!
+//! Reads the `vocab.txt` shipped with BERT-style exports and reproduces the
+//! reference pipeline: text cleanup, optional lowercasing with accent
+//! stripping, punctuation/CJK splitting, then greedy longest-match-first
+//! subword lookup. Pure Rust so it works without the `onnx` feature.
+//!
+//! Cross-encoders take a query and document as one `[CLS] a [SEP] b [SEP]`
+//! sequence, with segment IDs telling the two apart.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::engine::context::truncate_pair;
+use crate::engine::InferenceError;
+
+const CLS_TOKEN: &str = "[CLS]";
+const SEP_TOKEN: &str = "[SEP]";
+const PAD_TOKEN: &str = "[PAD]";
+const UNK_TOKEN: &str = "[UNK]";
+
+/// Words longer than this map to `[UNK]` (matches the reference tokenizer).
+const MAX_WORD_CHARS: usize = 100;
+
+/// One tokenized sequence, ready to be fed to an encoder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encoding {
+    /// Token IDs including `[CLS]` and `[SEP]`.
+    pub input_ids: Vec<u32>,
+    /// 1 for real tokens, 0 for padding.
+    pub attention_mask: Vec<u32>,
+    /// Segment of each position: 0 for the first text, 1 for the second.
+    pub token_type_ids: Vec<u32>,
+}
+
+impl Encoding {
+    /// Number of positions (including padding).
+    pub fn len(&self) -> usize {
+        self.input_ids.len()
+    }
+
+    /// Returns true if the encoding has no positions.
+    pub fn is_empty(&self) -> bool {
+        self.input_ids.is_empty()
+    }
+}
+
+/// WordPiece tokenizer backed by a BERT vocabulary.
+#[derive(Debug, Clone)]
+pub struct WordPieceTokenizer {
+    vocab: HashMap<String, u32>,
+    lowercase: bool,
+    cls_id: u32,
+    sep_id: u32,
+    pad_id: u32,
+    unk_id: u32,
+}
+
+impl WordPieceTokenizer {
+    /// Build a tokenizer from vocabulary entries, one token per ID in order.
+    ///
+    /// # Errors
+    /// Returns `InvalidFormat` if a required special token is missing.
+    pub fn from_tokens<I, S>(tokens: I, lowercase: bool) -> Result<Self, InferenceError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let vocab: HashMap<String, u32> = tokens
+            .into_iter()
+            .enumerate()
+            .map(|(id, token)| (token.into(), id as u32))
+            .collect();
+        let special = |token: &str| {
+            vocab.get(token).copied().ok_or_else(|| {
+                InferenceError::InvalidFormat(format!("vocabulary is missing {}", token))
+            })
+        };
+        Ok(Self {
+            cls_id: special(CLS_TOKEN)?,
+            sep_id: special(SEP_TOKEN)?,
+            pad_id: special(PAD_TOKEN)?,
+            unk_id: special(UNK_TOKEN)?,
+            vocab,
+            lowercase,
+        })
+    }
+
+    /// Load a `vocab.txt` file (one token per line, line number = ID).
+    pub fn from_file(path: &Path, lowercase: bool) -> Result<Self, InferenceError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            InferenceError::ModelError(format!("cannot read vocabulary {}: {}", path.display(), e))
+        })?;
+        Self::from_tokens(contents.lines().map(|l| l.trim_end_matches('\r')), lowercase)
+    }
+
+    /// Number of entries in the vocabulary.
+    pub fn vocab_size(&self) -> usize {
+        self.vocab.len()
+    }
+
+    /// ID used for padding positions.
+    pub fn pad_id(&self) -> u32 {
+        self.pad_id
+    }
+
+    /// Split text into WordPiece tokens (no special tokens).
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        let mut pieces = Vec::new();
+        for word in self.basic_tokenize(text) {
+            self.wordpiece(&word, &mut pieces);
+        }
+        pieces
+    }
+
+    /// Encode text as `[CLS] tokens [SEP]`, truncated to `max_len` positions.
+    pub fn encode(&self, text: &str, max_len: usize) -> Encoding {
+        let budget = max_len.saturating_sub(2);
+        let mut input_ids = Vec::with_capacity(budget.min(512) + 2);
+        input_ids.push(self.cls_id);
+        input_ids.extend(self.ids(text).into_iter().take(budget));
+        input_ids.push(self.sep_id);
+        let attention_mask = vec![1; input_ids.len()];
+        let token_type_ids = vec![0; input_ids.len()];
+        Encoding { input_ids, attention_mask, token_type_ids }
+    }
+
+    /// Encode a text pair as `[CLS] first [SEP] second [SEP]`, cutting the
+    /// longer text first until it fits `max_len` positions.
+    pub fn encode_pair(&self, first: &str, second: &str, max_len: usize) -> Encoding {
+        let (mut first, mut second) = (self.ids(first), self.ids(second));
+        truncate_pair(&mut first, &mut second, max_len.saturating_sub(3));
+        let mut input_ids = Vec::with_capacity(first.len() + second.len() + 3);
+        input_ids.push(self.cls_id);
+        input_ids.extend(first);
+        input_ids.push(self.sep_id);
+        let segment = input_ids.len();
+        input_ids.extend(second);
+        input_ids.push(self.sep_id);
+        let attention_mask = vec![1; input_ids.len()];
+        let mut token_type_ids = vec![0; input_ids.len()];
+        token_type_ids[segment..].fill(1);
+        Encoding { input_ids, attention_mask, token_type_ids }
+    }
+
+    /// Encode a batch, right-padding every sequence to the longest one.
+    pub fn encode_batch(&self, texts: &[&str], max_len: usize) -> Vec<Encoding> {
+        self.pad(texts.iter().map(|text| self.encode(text, max_len)).collect())
+    }
+
+    /// Encode a batch of text pairs, padded like `encode_batch`.
+    pub fn encode_pair_batch(&self, pairs: &[(&str, &str)], max_len: usize) -> Vec<Encoding> {
+        self.pad(pairs.iter().map(|(a, b)| self.encode_pair(a, b, max_len)).collect())
+    }
+
+    /// Right-pad every encoding to the longest one.
+    fn pad(&self, mut encodings: Vec<Encoding>) -> Vec<Encoding> {
+        let width = encodings.iter().map(Encoding::len).max().unwrap_or(0);
+        for enc in &mut encodings {
+            enc.input_ids.resize(width, self.pad_id);
+            enc.attention_mask.resize(width, 0);
+            enc.token_type_ids.resize(width, 0);
+        }
+        encodings
+    }
+
+    /// Vocabulary IDs of the text's WordPiece tokens.
+    fn ids(&self, text: &str) -> Vec<u32> {
+        self.tokenize(text)
+            .iter()
+            .map(|piece| self.vocab.get(piece).copied().unwrap_or(self.unk_id))
+            .collect()
+    }
+
+    /// Whitespace, punctuation and CJK splitting with optional lowercasing.
+    fn basic_tokenize(&self, text: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut current = String::new();
+        let flush = |current: &mut String, words: &mut Vec<String>| {
+            if !current.is_empty() {
+                words.push(std::mem::take(current));
+            }
+        };
+
+        let normalized: String = if self.lowercase {
+            text.nfd()
+                .filter(|c| !is_combining_mark(*c))
+                .flat_map(char::to_lowercase)
+                .collect()
+        } else {
+            text.to_string()
+        };
+
+        for c in normalized.chars() {
+            if c == '\0' || c == '\u{fffd}' || (c.is_control() && !c.is_whitespace()) {
+                continue;
+            }
+            if c.is_whitespace() {
+                flush(&mut current, &mut words);
+            } else if is_punctuation(c) || is_cjk(c) {
+                flush(&mut current, &mut words);
+                words.push(c.to_string());
+            } else {
+                current.push(c);
+            }
+        }
+        flush(&mut current, &mut words);
+        words
+    }
+
+    /// Greedy longest-match-first subword split of one word.
+    fn wordpiece(&self, word: &str, out: &mut Vec<String>) {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() > MAX_WORD_CHARS {
+            out.push(UNK_TOKEN.to_string());
+            return;
+        }
+
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = chars.len();
+            let mut found = None;
+            while start < end {
+                let mut candidate: String = chars[start..end].iter().collect();
+                if start > 0 {
+                    candidate.insert_str(0, "##");
+                }
+                if self.vocab.contains_key(&candidate) {
+                    found = Some(candidate);
+                    break;
+                }
+                end -= 1;
+            }
+            match found {
+                Some(piece) => {
+                    pieces.push(piece);
+                    start = end;
+                }
+                None => {
+                    out.push(UNK_TOKEN.to_string());
+                    return;
+                }
+            }
+        }
+        out.extend(pieces);
+    }
+}
+
+/// BERT treats every ASCII symbol as punctuation, plus the Unicode
+/// general, CJK and fullwidth punctuation blocks.
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || matches!(
+            c,
+            '\u{2000}'..='\u{206f}' | '\u{3000}'..='\u{303f}' | '\u{ff01}'..='\u{ff0f}'
+        )
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{4e00}'..='\u{9fff}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{20000}'..='\u{2a6df}'
+            | '\u{2a700}'..='\u{2b73f}'
+            | '\u{2b740}'..='\u{2b81f}'
+            | '\u{2b820}'..='\u{2ceaf}'
+            | '\u{f900}'..='\u{faff}'
+            | '\u{2f800}'..='\u{2fa1f}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokenizer() -> WordPieceTokenizer {
+        let vocab = [
+            "[PAD]", "[UNK]", "[CLS]", "[SEP]", "the", "cat", "sat", "un", "##aff", "##able",
+            ",", "!", "cafe", "中",
+        ];
+        WordPieceTokenizer::from_tokens(vocab, true).unwrap()
+    }
+
+    #[test]
+    fn splits_subwords_greedily() {
+        assert_eq!(tokenizer().tokenize("unaffable"), vec!["un", "##aff", "##able"]);
+    }
+
+    #[test]
+    fn lowercases_strips_accents_and_splits_punctuation() {
+        let tokens = tokenizer().tokenize("The CAT, Café!");
+        assert_eq!(tokens, vec!["the", "cat", ",", "cafe", "!"]);
+    }
+
+    #[test]
+    fn unknown_words_map_to_unk() {
+        let tokens = tokenizer().tokenize("the dog 中");
+        assert_eq!(tokens, vec!["the", "[UNK]", "中"]);
+    }
+
+    #[test]
+    fn encode_adds_special_tokens_and_truncates() {
+        let t = tokenizer();
+        let enc = t.encode("the cat sat", 16);
+        assert_eq!(enc.input_ids, vec![2, 4, 5, 6, 3]);
+        assert_eq!(enc.attention_mask, vec![1; 5]);
+
+        let enc = t.encode("the cat sat", 4);
+        assert_eq!(enc.input_ids, vec![2, 4, 5, 3]);
+    }
+
+    #[test]
+    fn encode_batch_pads_to_longest() {
+        let encodings = tokenizer().encode_batch(&["the cat sat", "cat"], 16);
+        assert_eq!(encodings[1].input_ids, vec![2, 5, 3, 0, 0]);
+        assert_eq!(encodings[1].attention_mask, vec![1, 1, 1, 0, 0]);
+        assert_eq!(encodings[0].len(), encodings[1].len());
+    }
+
+    #[test]
+    fn encode_pair_marks_segments_and_truncates_the_longer_text() {
+        let t = tokenizer();
+        let enc = t.encode_pair("cat", "the cat sat", 16);
+        assert_eq!(enc.input_ids, vec![2, 5, 3, 4, 5, 6, 3]);
+        assert_eq!(enc.token_type_ids, vec![0, 0, 0, 1, 1, 1, 1]);
+
+        let enc = t.encode_pair("cat", "the cat sat", 6);
+        assert_eq!(enc.input_ids, vec![2, 5, 3, 4, 5, 3]);
+        let encodings = t.encode_pair_batch(&[("cat", "sat"), ("the cat", "sat")], 16);
+        assert_eq!(encodings[0].token_type_ids, vec![0, 0, 0, 1, 1, 0]);
+        assert_eq!(encodings[0].attention_mask, vec![1, 1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn missing_special_tokens_rejected() {
+        let result = WordPieceTokenizer::from_tokens(["hello", "world"], true);
+        assert!(matches!(result, Err(InferenceError::InvalidFormat(_))));
+    }
+}
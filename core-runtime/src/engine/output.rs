@@ -2,6 +2,12 @@
 //!
 //! Each output variant maps to a specific inference capability.
 
+use std::time::Duration;
+
+use super::logprobs::TokenLogprob;
+use super::stop::StopSequence;
+use super::streaming::StreamingOutput;
+
 /// Output variants for inference operations.
 #[derive(Debug, Clone)]
 pub enum InferenceOutput {
@@ -31,6 +37,80 @@ pub struct GenerationResult {
     pub tokens_generated: u32,
     /// Reason generation stopped.
     pub finish_reason: FinishReason,
+    /// Number of prompt tokens evaluated.
+    pub prompt_tokens: u32,
+    /// Backend timings.
+    pub timings: GenerationTimings,
+    /// Stop sequence that ended generation, if any.
+    pub stop_sequence: Option<StopSequence>,
+    /// Per-token log-probabilities, one per generated token, when requested.
+    pub logprobs: Vec<TokenLogprob>,
+    /// Every returned completion, best first, when `n > 1`. The fields
+    /// above then describe the first one, except `tokens_generated`, which
+    /// counts the tokens of every sequence generated.
+    pub choices: Vec<Completion>,
+}
+
+impl GenerationResult {
+    /// Assemble a result from a generation's stream outputs. None if the
+    /// stream ended before its final output.
+    pub fn from_outputs(outputs: impl IntoIterator<Item = StreamingOutput>) -> Option<Self> {
+        let mut text = String::new();
+        let mut logprobs = Vec::new();
+        for output in outputs {
+            text.push_str(&output.text);
+            logprobs.extend(output.logprob);
+            if let Some(finish) = output.finish {
+                return Some(Self {
+                    text,
+                    tokens_generated: finish.completion_tokens,
+                    finish_reason: finish.finish_reason,
+                    prompt_tokens: finish.prompt_tokens,
+                    timings: finish.timings,
+                    stop_sequence: finish.stop_sequence,
+                    logprobs,
+                    choices: Vec::new(),
+                });
+            }
+        }
+        None
+    }
+}
+
+/// One of several completions generated for a request.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    pub tokens_generated: u32,
+    pub finish_reason: FinishReason,
+    pub stop_sequence: Option<StopSequence>,
+    /// Per-token log-probabilities, when requested.
+    pub logprobs: Vec<TokenLogprob>,
+    /// Sum of the generated tokens' log-probabilities.
+    pub cumulative_logprob: f32,
+}
+
+/// Backend timings for one generation, measured from the start of the call.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationTimings {
+    /// Tokenizing and evaluating the prompt.
+    pub prefill: Duration,
+    /// Until the first completion token was sampled (includes prefill).
+    pub first_token: Duration,
+    /// Sampling and decoding completion tokens (after prefill).
+    pub decode: Duration,
+}
+
+impl GenerationTimings {
+    /// Completion tokens per second over the decode phase.
+    pub fn decode_tokens_per_sec(&self, completion_tokens: u32) -> f64 {
+        let secs = self.decode.as_secs_f64();
+        if secs > 0.0 {
+            completion_tokens as f64 / secs
+        } else {
+            0.0
+        }
+    }
 }
 
 /// Result of embedding generation.
@@ -42,6 +122,25 @@ pub struct EmbeddingResult {
     pub dimensions: usize,
 }
 
+/// Result of reranking documents against a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RerankResult {
+    /// Relevance score of each document, in input order; higher is more
+    /// relevant. Scores come from the model's head and are not normalized.
+    pub scores: Vec<f32>,
+    /// Document indices, most relevant first (input order on ties).
+    pub order: Vec<usize>,
+}
+
+impl RerankResult {
+    /// Rank documents by their scores.
+    pub fn from_scores(scores: Vec<f32>) -> Self {
+        let mut order: Vec<usize> = (0..scores.len()).collect();
+        order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        Self { scores, order }
+    }
+}
+
 /// Result of named entity recognition.
 #[derive(Debug, Clone)]
 pub struct EntityResult {
@@ -58,7 +157,8 @@ pub struct EntityResult {
 }
 
 /// Reason why text generation finished.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FinishReason {
     /// Model emitted stop token naturally.
     Stop,
@@ -68,6 +168,8 @@ pub enum FinishReason {
     Timeout,
     /// Content filter triggered.
     ContentFiltered,
+    /// Aborted by the caller (cancel request or disconnect).
+    Cancelled,
 }
 
 impl InferenceOutput {
@@ -1,7 +1,9 @@
 //! Prefill executor optimized for parallel prompt processing.
 //!
-//! Processes prompt tokens in chunks with batch-parallel execution.
+//! Processes prompt tokens in chunks with batch-parallel execution. The
+//! native backend evaluates prompts through [`PrefillExecutor::forward`].
 
+use crate::engine::native::NativeSession;
 use crate::engine::InferenceError;
 use crate::memory::paged::{PageTable, PAGE_TOKENS};
 
@@ -70,6 +72,31 @@ impl PrefillExecutor {
         })
     }
 
+    /// Evaluate `tokens` in a native `session`, `chunk_size` at a time,
+    /// and return the logits after the last one. Returns None, leaving the
+    /// session part way through the prompt, when `proceed` returns false
+    /// before a chunk.
+    pub fn forward(
+        &self,
+        session: &mut NativeSession<'_>,
+        tokens: &[u32],
+        mut proceed: impl FnMut() -> bool,
+    ) -> Result<Option<Vec<f32>>, InferenceError> {
+        if tokens.is_empty() {
+            return Err(InferenceError::InputValidation(
+                "prefill requires non-empty prompt".into(),
+            ));
+        }
+        let mut logits = Vec::new();
+        for chunk in tokens.chunks(self.config.chunk_size.max(1)) {
+            if !proceed() {
+                return Ok(None);
+            }
+            logits = session.forward(chunk)?;
+        }
+        Ok(Some(logits))
+    }
+
     /// Process a single chunk of tokens.
     fn process_chunk(
         &self,
@@ -0,0 +1,82 @@
+//! Prompt lookup drafting for speculative decoding.
+//!
+//! Extraction and rewriting outputs largely copy spans of their input. The
+//! last few tokens of the text so far are looked up earlier in the prompt
+//! and output, and the tokens that followed the latest match are proposed
+//! as the continuation. No draft model is needed; the target still checks
+//! every proposed token.
+
+/// N-gram matching settings for prompt lookup drafting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PromptLookup {
+    /// Longest suffix to look up; longer matches are tried first.
+    pub max_ngram: usize,
+    /// Shortest suffix to look up.
+    pub min_ngram: usize,
+    /// Most tokens proposed at once.
+    pub max_draft: usize,
+}
+
+impl Default for PromptLookup {
+    fn default() -> Self {
+        Self { max_ngram: 3, min_ngram: 2, max_draft: 8 }
+    }
+}
+
+impl PromptLookup {
+    /// Up to `limit` tokens that followed the latest earlier occurrence of
+    /// the longest matching suffix of `tokens`. Empty without a match.
+    pub fn propose(&self, tokens: &[u32], limit: usize) -> Vec<u32> {
+        let limit = limit.min(self.max_draft);
+        if limit == 0 {
+            return Vec::new();
+        }
+        for n in (self.min_ngram.max(1)..=self.max_ngram).rev() {
+            if tokens.len() <= n {
+                continue;
+            }
+            let suffix = &tokens[tokens.len() - n..];
+            // Leaving out the last token excludes the suffix itself
+            let earlier = &tokens[..tokens.len() - 1];
+            if let Some(start) = earlier.windows(n).rposition(|w| w == suffix) {
+                let from = start + n;
+                return tokens[from..(from + limit).min(tokens.len())].to_vec();
+            }
+        }
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proposes_continuation_of_latest_match() {
+        let lookup = PromptLookup::default();
+        // The suffix "7 1 2" occurred once before; its continuation runs to the end
+        let tokens = [1, 2, 3, 4, 5, 7, 1, 2, 3, 9, 9, 7, 1, 2];
+        assert_eq!(lookup.propose(&tokens, 8), vec![3, 9, 9, 7, 1, 2]);
+        assert_eq!(lookup.propose(&tokens, 2), vec![3, 9]);
+    }
+
+    #[test]
+    fn prefers_longer_ngrams() {
+        let lookup = PromptLookup::default();
+        // The 3-gram "5 1 2" only matches the first occurrence
+        let tokens = [5, 1, 2, 8, 6, 1, 2, 4, 5, 1, 2];
+        assert_eq!(lookup.propose(&tokens, 1), vec![8]);
+        let bigrams = PromptLookup { max_ngram: 2, ..lookup };
+        assert_eq!(bigrams.propose(&tokens, 1), vec![4]);
+    }
+
+    #[test]
+    fn proposes_nothing_without_a_match() {
+        let lookup = PromptLookup::default();
+        assert!(lookup.propose(&[1, 2, 3, 4], 8).is_empty());
+        assert!(lookup.propose(&[1, 2, 1, 2], 0).is_empty());
+        assert!(lookup.propose(&[], 8).is_empty());
+        // A single repeated token is below the minimum n-gram
+        assert!(lookup.propose(&[7, 3, 7], 8).is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+//! Stop sequences for text generation.
+//!
+//! A stop sequence is either a string matched against the decoded output or
+//! a token ID matched against the sampled token. Text that could be the
+//! start of a stop string is held back until it is ruled out, so streamed
+//! output never contains part of a stop sequence.
+
+use serde::{Deserialize, Serialize};
+
+/// Most stop sequences accepted per request.
+pub const MAX_STOP_SEQUENCES: usize = 16;
+
+/// Longest stop string accepted, in bytes.
+pub const MAX_STOP_SEQUENCE_BYTES: usize = 256;
+
+/// A string or token ID that ends generation.
+///
+/// Serialized untagged: a JSON number is a token ID, a string is text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StopSequence {
+    Token(u32),
+    Text(String),
+}
+
+/// Incremental stop sequence matcher over decoded generation text.
+#[derive(Debug, Default)]
+pub struct StopMatcher {
+    texts: Vec<String>,
+    tokens: Vec<u32>,
+    held: String,
+}
+
+impl StopMatcher {
+    pub fn new(stops: &[StopSequence]) -> Self {
+        let mut matcher = Self::default();
+        for stop in stops {
+            match stop {
+                StopSequence::Token(token) => matcher.tokens.push(*token),
+                StopSequence::Text(text) if !text.is_empty() => {
+                    matcher.texts.push(text.clone())
+                }
+                StopSequence::Text(_) => {}
+            }
+        }
+        matcher
+    }
+
+    /// The stop sequence for `token`, if it is a stop token. The token's
+    /// own text is not part of the output.
+    pub fn stop_token(&self, token: u32) -> Option<StopSequence> {
+        self.tokens
+            .contains(&token)
+            .then_some(StopSequence::Token(token))
+    }
+
+    /// Append decoded text and return the text that is safe to emit.
+    ///
+    /// On a match, returns the text before the earliest stop string and the
+    /// matched sequence; everything after it is discarded.
+    pub fn push(&mut self, text: &str) -> (String, Option<StopSequence>) {
+        if self.texts.is_empty() {
+            return (text.to_string(), None);
+        }
+        self.held.push_str(text);
+
+        let earliest = self
+            .texts
+            .iter()
+            .filter_map(|stop| self.held.find(stop.as_str()).map(|pos| (pos, stop)))
+            .min_by_key(|(pos, _)| *pos);
+        if let Some((pos, stop)) = earliest {
+            let stop = StopSequence::Text(stop.clone());
+            let mut out = std::mem::take(&mut self.held);
+            out.truncate(pos);
+            return (out, Some(stop));
+        }
+
+        let keep = self
+            .texts
+            .iter()
+            .map(|stop| partial_suffix_len(&self.held, stop))
+            .max()
+            .unwrap_or(0);
+        let out = self.held[..self.held.len() - keep].to_string();
+        self.held.drain(..self.held.len() - keep);
+        (out, None)
+    }
+
+    /// Release held-back text at the end of generation.
+    pub fn flush(&mut self) -> String {
+        std::mem::take(&mut self.held)
+    }
+}
+
+/// Length of the longest suffix of `text` that is a proper prefix of `stop`.
+fn partial_suffix_len(text: &str, stop: &str) -> usize {
+    (1..stop.len().min(text.len() + 1))
+        .rev()
+        .find(|&len| stop.is_char_boundary(len) && text.ends_with(&stop[..len]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> StopSequence {
+        StopSequence::Text(s.to_string())
+    }
+
+    #[test]
+    fn holds_back_possible_stop_prefix() {
+        let mut matcher = StopMatcher::new(&[text("\n\nQ:")]);
+        assert_eq!(matcher.push("Paris."), ("Paris.".into(), None));
+        assert_eq!(matcher.push("\n"), (String::new(), None));
+        assert_eq!(matcher.push("\nQ"), (String::new(), None));
+        assert_eq!(matcher.push(": next"), (String::new(), Some(text("\n\nQ:"))));
+    }
+
+    #[test]
+    fn releases_text_once_ruled_out() {
+        let mut matcher = StopMatcher::new(&[text("END")]);
+        assert_eq!(matcher.push("the E"), ("the ".into(), None));
+        assert_eq!(matcher.push("N"), (String::new(), None));
+        assert_eq!(matcher.push("D"), (String::new(), Some(text("END"))));
+
+        let mut matcher = StopMatcher::new(&[text("END")]);
+        assert_eq!(matcher.push("EN"), (String::new(), None));
+        assert_eq!(matcher.push("ter"), ("ENter".into(), None));
+        assert_eq!(matcher.push("E"), (String::new(), None));
+        assert_eq!(matcher.flush(), "E");
+    }
+
+    #[test]
+    fn earliest_match_wins_and_trims_output() {
+        let mut matcher = StopMatcher::new(&[text("world"), text("lo")]);
+        assert_eq!(matcher.push("hello world"), ("hel".into(), Some(text("lo"))));
+    }
+
+    #[test]
+    fn stop_tokens_and_multibyte_prefixes() {
+        let matcher = StopMatcher::new(&[StopSequence::Token(7)]);
+        assert_eq!(matcher.stop_token(7), Some(StopSequence::Token(7)));
+        assert_eq!(matcher.stop_token(8), None);
+
+        let mut matcher = StopMatcher::new(&[text("é!")]);
+        assert_eq!(matcher.push("café"), ("caf".into(), None));
+        assert_eq!(matcher.push("?"), ("é?".into(), None));
+    }
+
+    #[test]
+    fn stop_sequence_serializes_untagged() {
+        let stops: Vec<StopSequence> = serde_json::from_str(r#"["Q:", 2]"#).unwrap();
+        assert_eq!(stops, vec![text("Q:"), StopSequence::Token(2)]);
+        assert_eq!(serde_json::to_string(&stops).unwrap(), r#"["Q:",2]"#);
+    }
+}
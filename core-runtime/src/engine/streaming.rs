@@ -2,11 +2,32 @@
 
 use tokio::sync::mpsc;
 
+use super::{FinishReason, GenerationTimings, StopSequence, TokenLogprob};
+
 /// A single streamed token output.
 #[derive(Debug, Clone)]
 pub struct StreamingOutput {
     pub token: u32,
+    /// Text completed by this token. Empty while a multi-byte character is
+    /// still split across tokens.
+    pub text: String,
     pub is_final: bool,
+    /// Log-probability of this token, when requested and the token is part
+    /// of the completion.
+    pub logprob: Option<TokenLogprob>,
+    /// Usage summary, set on the final output of a completed generation.
+    pub finish: Option<StreamFinish>,
+}
+
+/// Token counts, finish reason and timings of a completed stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamFinish {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub finish_reason: FinishReason,
+    pub timings: GenerationTimings,
+    /// Stop sequence that ended generation, if any.
+    pub stop_sequence: Option<StopSequence>,
 }
 
 /// Async stream of generated tokens.
@@ -47,18 +68,105 @@ pub struct TokenStreamSender {
 impl TokenStreamSender {
     /// Send a token to the stream.
     pub async fn send(&self, token: u32, is_final: bool) -> Result<(), StreamSendError> {
+        self.send_text(token, String::new(), None, is_final).await
+    }
+
+    /// Send a token together with its decoded text and log-probability.
+    pub async fn send_text(
+        &self,
+        token: u32,
+        text: String,
+        logprob: Option<TokenLogprob>,
+        is_final: bool,
+    ) -> Result<(), StreamSendError> {
         self.sender
-            .send(StreamingOutput { token, is_final })
+            .send(StreamingOutput { token, text, is_final, logprob, finish: None })
             .await
             .map_err(|_| StreamSendError)
     }
 
+    /// Send the final token with the usage summary of the generation.
+    pub async fn finish(
+        &self,
+        token: u32,
+        text: String,
+        logprob: Option<TokenLogprob>,
+        finish: StreamFinish,
+    ) -> Result<(), StreamSendError> {
+        let output =
+            StreamingOutput { token, text, is_final: true, logprob, finish: Some(finish) };
+        self.sender.send(output).await.map_err(|_| StreamSendError)
+    }
+
+    /// Send an output produced elsewhere, such as a model's batch loop.
+    pub async fn send_output(&self, output: StreamingOutput) -> Result<(), StreamSendError> {
+        self.sender.send(output).await.map_err(|_| StreamSendError)
+    }
+
     /// Close the stream by dropping the sender.
     pub fn close(self) {
         drop(self.sender);
     }
 }
 
+/// Incremental UTF-8 decoder for token byte pieces.
+///
+/// Tokens may end in the middle of a multi-byte character. Bytes of an
+/// incomplete trailing sequence are held back until the next push, so
+/// emitted text never contains half a character. Invalid bytes decode to
+/// U+FFFD.
+#[derive(Debug, Default)]
+pub struct Utf8StreamDecoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8StreamDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append bytes and return all text that is now complete.
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        let mut rest = self.pending.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        // Incomplete sequence at the end: wait for more bytes.
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending = rest.to_vec();
+        text
+    }
+
+    /// Flush held-back bytes at end of stream, replacing any incomplete
+    /// sequence with U+FFFD.
+    pub fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+}
+
 #[derive(Debug)]
 pub struct StreamSendError;
 
@@ -69,3 +177,38 @@ impl std::fmt::Display for StreamSendError {
 }
 
 impl std::error::Error for StreamSendError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_decoder_holds_split_characters() {
+        let mut decoder = Utf8StreamDecoder::new();
+        let bytes = "é€😀".as_bytes();
+        let mut out = String::new();
+        for byte in bytes {
+            let text = decoder.push(&[*byte]);
+            assert!(out.len() + text.len() <= bytes.len());
+            out.push_str(&text);
+        }
+        assert_eq!(out, "é€😀");
+        assert!(decoder.finish().is_empty());
+    }
+
+    #[test]
+    fn utf8_decoder_emits_only_complete_prefix() {
+        let mut decoder = Utf8StreamDecoder::new();
+        // "a" followed by the first two bytes of "€"
+        assert_eq!(decoder.push(&[b'a', 0xE2, 0x82]), "a");
+        assert_eq!(decoder.push(&[0xAC, b'b']), "€b");
+    }
+
+    #[test]
+    fn utf8_decoder_replaces_invalid_bytes() {
+        let mut decoder = Utf8StreamDecoder::new();
+        assert_eq!(decoder.push(&[b'a', 0xFF, b'b']), "a\u{FFFD}b");
+        assert_eq!(decoder.push(&[0xF0, 0x9F]), "");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+    }
+}
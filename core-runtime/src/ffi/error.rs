@@ -94,6 +94,7 @@ impl From<crate::engine::InferenceError> for CoreErrorCode {
             InferenceError::ModelNotLoaded(_) => CoreErrorCode::ModelNotFound,
             InferenceError::InputValidation(_) => CoreErrorCode::InvalidParams,
             InferenceError::Timeout(_) => CoreErrorCode::Timeout,
+            InferenceError::ContextExceeded { .. } => CoreErrorCode::ContextExceeded,
             InferenceError::MemoryExceeded { .. } => CoreErrorCode::ContextExceeded,
             InferenceError::OutputFiltered { .. } => CoreErrorCode::InferenceFailed,
             InferenceError::ModelError(_) => CoreErrorCode::InferenceFailed,
@@ -129,6 +130,8 @@ impl From<crate::engine::inference::InferenceError> for CoreErrorCode {
             InferenceError::InvalidParams(_) => CoreErrorCode::InvalidParams,
             InferenceError::ExecutionFailed(_) => CoreErrorCode::InferenceFailed,
             InferenceError::ContextExceeded { .. } => CoreErrorCode::ContextExceeded,
+            InferenceError::CapabilityNotSupported(_) => CoreErrorCode::InvalidParams,
+            InferenceError::UnsupportedConstraint(_) => CoreErrorCode::InvalidParams,
         }
     }
 }
@@ -147,6 +147,7 @@ pub(super) fn params_from_c(c: &CoreInferenceParams) -> InferenceParams {
         } else {
             Some(c.timeout_ms)
         },
+        ..Default::default()
     }
 }
 
@@ -1,5 +1,6 @@
 //! Request/response handling for IPC connections.
 
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio_util::sync::CancellationToken;
@@ -7,17 +8,26 @@ use tokio_util::sync::CancellationToken;
 use super::auth::{AuthError, SessionAuth, SessionToken};
 use super::health_handler::HealthHandler;
 use super::protocol::{
-    decode_message, encode_message, InferenceRequest, InferenceResponse, IpcMessage, ModelInfo,
-    ModelsListResponse, ProtocolError, ProtocolVersion, StreamChunk, WarmupResponse,
+    decode_message, encode_message, AdapterInfo, ChatRequest, Choice, ClassifyRequest,
+    ClassifyResponse, EmbedRequest, EmbedResponse, InferenceRequest, InferenceResponse,
+    IpcMessage, LoadAdapterRequest, LoadModelRequest, ModelInfo, ModelLifecycleResponse,
+    ModelsListResponse, ProtocolError, ProtocolVersion, RequestId, RerankRequest, RerankResponse,
+    RollbackModelRequest, StreamChunk, SwapModelRequest, UnloadAdapterRequest,
+    UnloadModelRequest, UsageReport, WarmupResponse,
+};
+use crate::engine::{
+    FinishReason, InferenceEngine, InferenceInput, InferenceParams, TokenLogprob,
 };
-use crate::engine::InferenceEngine;
-#[cfg(feature = "gguf")]
 use crate::engine::TokenStream;
 use crate::health::HealthChecker;
-use crate::models::ModelRegistry;
+use crate::models::{
+    FlightGuard, LifecycleError, LifecycleReport, LifecycleStage, ModelLifecycle, ModelRegistry,
+    DEFAULT_DRAIN_TIMEOUT,
+};
 use crate::scheduler::Priority;
 use crate::scheduler::RequestQueue;
-use crate::shutdown::ShutdownCoordinator;
+use crate::security::{SecurityConfig, SecurityPipeline, SecurityReport, SecurityStreamState};
+use crate::shutdown::{ShutdownCoordinator, ShutdownGuard};
 use crate::telemetry::{self, MetricsStore};
 
 #[derive(Error, Debug)]
@@ -45,14 +55,24 @@ pub enum HandlerError {
 #[derive(Debug, Clone)]
 pub struct IpcHandlerConfig {
     pub require_auth: bool,
+    /// Prompt scanning and output sanitization applied to inference requests.
+    pub security: SecurityConfig,
 }
 
 impl Default for IpcHandlerConfig {
     fn default() -> Self {
-        Self { require_auth: true }
+        Self {
+            require_auth: true,
+            security: SecurityConfig::default(),
+        }
     }
 }
 
+/// Drain timeout from an admin request, defaulting to 30 seconds.
+fn drain_timeout(millis: Option<u64>) -> std::time::Duration {
+    millis.map_or(DEFAULT_DRAIN_TIMEOUT, std::time::Duration::from_millis)
+}
+
 /// Trait for sending streaming responses over IPC.
 #[async_trait::async_trait]
 pub trait StreamSender: Send + Sync {
@@ -84,6 +104,8 @@ pub struct IpcHandler {
     metrics_store: Arc<MetricsStore>,
     model_registry: Arc<ModelRegistry>,
     inference_engine: Arc<InferenceEngine>,
+    security: SecurityPipeline,
+    lifecycle: Option<Arc<ModelLifecycle>>,
 }
 
 impl IpcHandler {
@@ -103,6 +125,7 @@ impl IpcHandler {
             Arc::clone(&model_registry),
             Arc::clone(&queue),
         );
+        let security = SecurityPipeline::new(config.security.clone());
         Self {
             auth,
             queue,
@@ -112,9 +135,17 @@ impl IpcHandler {
             metrics_store,
             model_registry,
             inference_engine,
+            security,
+            lifecycle: None,
         }
     }
 
+    /// Enable the load/unload/swap/rollback admin messages.
+    pub fn with_model_lifecycle(mut self, lifecycle: Arc<ModelLifecycle>) -> Self {
+        self.lifecycle = Some(lifecycle);
+        self
+    }
+
     /// Process incoming message bytes and return response bytes.
     pub async fn process(
         &self,
@@ -149,10 +180,72 @@ impl IpcHandler {
 
             IpcMessage::InferenceRequest(request) => {
                 self.require_auth(session).await?;
-                let response = self.handle_inference(request).await;
+                let response =
+                    self.handle_inference(request, session, CancellationToken::new()).await;
                 Ok((IpcMessage::InferenceResponse(response), None))
             }
 
+            IpcMessage::ChatRequest(request) => {
+                self.require_auth(session).await?;
+                let response = self.handle_chat(request, session, CancellationToken::new()).await;
+                Ok((IpcMessage::InferenceResponse(response), None))
+            }
+
+            IpcMessage::EmbedRequest(request) => {
+                self.require_auth(session).await?;
+                let response = self.handle_embed(request).await;
+                Ok((IpcMessage::EmbedResponse(response), None))
+            }
+
+            IpcMessage::ClassifyRequest(request) => {
+                self.require_auth(session).await?;
+                let response = self.handle_classify(request).await;
+                Ok((IpcMessage::ClassifyResponse(response), None))
+            }
+
+            IpcMessage::RerankRequest(request) => {
+                self.require_auth(session).await?;
+                let response = self.handle_rerank(request).await;
+                Ok((IpcMessage::RerankResponse(response), None))
+            }
+
+            IpcMessage::LoadModel(request) => {
+                // AUTH REQUIRED: lifecycle changes affect every client
+                self.require_auth(session).await?;
+                let response = self.handle_load_model(request).await;
+                Ok((IpcMessage::ModelLifecycleResponse(response), None))
+            }
+
+            IpcMessage::UnloadModel(request) => {
+                self.require_auth(session).await?;
+                let response = self.handle_unload_model(request).await;
+                Ok((IpcMessage::ModelLifecycleResponse(response), None))
+            }
+
+            IpcMessage::SwapModel(request) => {
+                self.require_auth(session).await?;
+                let response = self.handle_swap_model(request).await;
+                Ok((IpcMessage::ModelLifecycleResponse(response), None))
+            }
+
+            IpcMessage::RollbackModel(request) => {
+                self.require_auth(session).await?;
+                let response = self.handle_rollback_model(request).await;
+                Ok((IpcMessage::ModelLifecycleResponse(response), None))
+            }
+
+            IpcMessage::LoadAdapter(request) => {
+                self.require_auth(session).await?;
+                let response = self.handle_load_adapter(request).await;
+                Ok((IpcMessage::ModelLifecycleResponse(response), None))
+            }
+
+            IpcMessage::UnloadAdapter(request) => {
+                self.require_auth(session).await?;
+                let response = self.handle_unload_adapter(request).await;
+                Ok((IpcMessage::ModelLifecycleResponse(response), None))
+            }
+
             IpcMessage::HealthCheck { check_type } => {
                 // NO AUTH REQUIRED for health checks (orchestrator pattern)
                 let response = self.health_handler.handle(check_type).await;
@@ -210,43 +303,109 @@ impl IpcHandler {
         Ok(())
     }
 
-    async fn handle_inference(&self, request: InferenceRequest) -> InferenceResponse {
+    /// Replace a request's `conversation_id` with a digest of it and the
+    /// caller's session, so one client can never resume another client's
+    /// conversation state.
+    fn scope_conversation(parameters: &mut InferenceParams, session: Option<&SessionToken>) {
+        if let (Some(id), Some(session)) = (&mut parameters.conversation_id, session) {
+            let mut hasher = Sha256::new();
+            hasher.update(session.as_str().as_bytes());
+            hasher.update([0]);
+            hasher.update(id.as_bytes());
+            *id = hex::encode(hasher.finalize());
+        }
+    }
+
+    async fn handle_inference(
+        &self,
+        mut request: InferenceRequest,
+        session: Option<&SessionToken>,
+        cancel: CancellationToken,
+    ) -> InferenceResponse {
+        if let Err(e) = request.validate() {
+            return InferenceResponse::error(request.request_id, e.to_string());
+        }
+        Self::scope_conversation(&mut request.parameters, session);
+        self.execute_inference(
+            request.request_id,
+            request.model_id,
+            InferenceInput::Text(request.prompt),
+            request.parameters,
+            cancel,
+        )
+        .await
+    }
+
+    async fn handle_chat(
+        &self,
+        mut request: ChatRequest,
+        session: Option<&SessionToken>,
+        cancel: CancellationToken,
+    ) -> InferenceResponse {
+        if let Err(e) = request.validate() {
+            return InferenceResponse::error(request.request_id, e.to_string());
+        }
+        Self::scope_conversation(&mut request.parameters, session);
+        self.execute_inference(
+            request.request_id,
+            request.model_id,
+            InferenceInput::ChatMessages(request.messages),
+            request.parameters,
+            cancel,
+        )
+        .await
+    }
+
+    /// Shared non-streaming path for prompt and chat requests.
+    async fn execute_inference(
+        &self,
+        request_id: RequestId,
+        model_id: String,
+        input: InferenceInput,
+        parameters: InferenceParams,
+        cancel: CancellationToken,
+    ) -> InferenceResponse {
         // Check shutdown state before accepting new request
         let _guard = match self.shutdown.track() {
             Some(g) => g,
             None => {
-                return InferenceResponse::error(
-                    request.request_id,
-                    "Server is shutting down".into(),
-                );
+                return InferenceResponse::error(request_id, "Server is shutting down".into());
             }
         };
+        let accepted = std::time::Instant::now();
+        let _flight = self.track_flight(&model_id).await;
 
-        if let Err(e) = request.validate() {
-            return InferenceResponse::error(request.request_id, e.to_string());
+        let prompt_text = input.joined_text();
+        let mut report = self.security.scan_prompt(&prompt_text);
+        if report.prompt_blocked {
+            let message = Self::blocked_message(&report);
+            telemetry::record_request_failure(&model_id, &message);
+            self.security.audit(&report, request_id.0, &model_id).await;
+            return InferenceResponse::error(request_id, message).with_security(report);
         }
 
         // Track request in queue for metrics
         let enqueue_result = self
             .queue
             .enqueue(
-                request.model_id.clone(),
-                request.prompt.clone(),
-                request.parameters.clone(),
+                model_id.clone(),
+                prompt_text,
+                parameters.clone(),
                 Priority::Normal,
             )
             .await;
 
         if let Err(e) = enqueue_result {
-            return InferenceResponse::error(request.request_id, e.to_string());
+            return InferenceResponse::error(request_id, e.to_string());
         }
 
         // Run inference using model_id to look up the model
         let start = std::time::Instant::now();
+        let queue_wait = accepted.elapsed();
 
         match self
             .inference_engine
-            .run(&request.model_id, &request.prompt, &request.parameters)
+            .run_input_cancellable(&model_id, input, &parameters, cancel)
             .await
         {
             Ok(result) => {
@@ -254,34 +413,353 @@ impl IpcHandler {
 
                 // Record metrics via telemetry facade (Prometheus-compatible)
                 telemetry::record_request_success(
-                    &request.model_id,
+                    &model_id,
                     latency_ms,
                     result.tokens_generated as u64,
                 );
 
                 // Also record in model registry with correct handle for per-model stats
-                if let Some(handle) = self.inference_engine.get_handle(&request.model_id).await {
+                if let Some(handle) = self.inference_engine.get_handle(&model_id).await {
                     self.model_registry
                         .record_request(handle, latency_ms as f64)
                         .await;
                 }
 
-                InferenceResponse::success(
-                    request.request_id,
-                    result.output,
+                // With several choices the top-level output repeats the first
+                let choices: Vec<_> = result
+                    .choices
+                    .into_iter()
+                    .map(|c| (self.security.sanitize_output(&c.text, &mut report), c))
+                    .collect();
+                let output = match choices.first() {
+                    Some((output, _)) => output.clone(),
+                    None => self.security.sanitize_output(&result.output, &mut report),
+                };
+                if parameters.grammar.is_some() || parameters.json_schema.is_some() {
+                    let json = parameters.json_schema.is_some();
+                    if choices.is_empty() {
+                        self.security.check_format(&output, json, &mut report);
+                    }
+                    for (output, _) in &choices {
+                        self.security.check_format(output, json, &mut report);
+                    }
+                }
+                self.security.audit(&report, request_id.0, &model_id).await;
+                let usage = UsageReport::new(
+                    result.prompt_tokens,
+                    result.tokens_generated,
+                    Self::finish_reason(result.finish_reason, &report),
+                    queue_wait,
+                    &result.timings,
+                )
+                .with_stop_sequence(result.stop_sequence);
+
+                let logprobs = parameters
+                    .logprobs
+                    .map(|_| Self::visible_logprobs(result.logprobs, &report));
+                let choices: Vec<_> = choices
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (output, c))| Choice {
+                        index,
+                        output,
+                        tokens_generated: c.tokens_generated as usize,
+                        finish_reason: Self::finish_reason(c.finish_reason, &report),
+                        stop_sequence: c.stop_sequence,
+                        cumulative_logprob: c.cumulative_logprob,
+                        logprobs: parameters
+                            .logprobs
+                            .map(|_| Self::visible_logprobs(c.logprobs, &report)),
+                    })
+                    .collect();
+                let mut response = InferenceResponse::success(
+                    request_id,
+                    output,
                     result.tokens_generated,
                     result.finished,
                 )
+                .with_security(report)
+                .with_usage(usage);
+                if let Some(logprobs) = logprobs {
+                    response = response.with_logprobs(logprobs);
+                }
+                if !choices.is_empty() {
+                    response = response.with_choices(choices);
+                }
+                response
             }
             Err(e) => {
                 // Record failure metrics
-                telemetry::record_request_failure(&request.model_id, &e.to_string());
-                InferenceResponse::error(request.request_id, e.to_string())
+                telemetry::record_request_failure(&model_id, &e.to_string());
+                self.security.audit(&report, request_id.0, &model_id).await;
+                InferenceResponse::error(request_id, e.to_string()).with_security(report)
             }
         }
         // guard dropped here, decrementing in-flight count
     }
 
+    /// Embed texts with an ONNX (or other embedding-capable) model.
+    ///
+    /// Inputs are data, not instructions, and no text is returned, so the
+    /// injection scan and output sanitization do not apply here.
+    async fn handle_embed(&self, request: EmbedRequest) -> EmbedResponse {
+        if let Err(e) = request.validate() {
+            return EmbedResponse::error(request.request_id, e.to_string());
+        }
+        let Some(_guard) = self.shutdown.track() else {
+            return EmbedResponse::error(request.request_id, "Server is shutting down".into());
+        };
+        let _flight = self.track_flight(&request.model_id).await;
+
+        let start = std::time::Instant::now();
+        let result = self
+            .inference_engine
+            .embed(&request.model_id, &request.inputs)
+            .await;
+        match result {
+            Ok(results) => {
+                self.record_success(&request.model_id, start).await;
+                EmbedResponse::success(request.request_id, results)
+            }
+            Err(e) => {
+                telemetry::record_request_failure(&request.model_id, &e.to_string());
+                EmbedResponse::error(request.request_id, e.to_string())
+            }
+        }
+    }
+
+    /// Classify texts with a classification-capable model.
+    async fn handle_classify(&self, request: ClassifyRequest) -> ClassifyResponse {
+        if let Err(e) = request.validate() {
+            return ClassifyResponse::error(request.request_id, e.to_string());
+        }
+        let Some(_guard) = self.shutdown.track() else {
+            return ClassifyResponse::error(request.request_id, "Server is shutting down".into());
+        };
+        let _flight = self.track_flight(&request.model_id).await;
+
+        let start = std::time::Instant::now();
+        let result = self
+            .inference_engine
+            .classify(&request.model_id, &request.inputs)
+            .await;
+        match result {
+            Ok(results) => {
+                self.record_success(&request.model_id, start).await;
+                ClassifyResponse::success(request.request_id, results)
+            }
+            Err(e) => {
+                telemetry::record_request_failure(&request.model_id, &e.to_string());
+                ClassifyResponse::error(request.request_id, e.to_string())
+            }
+        }
+    }
+
+    /// Score documents against a query with a reranking model.
+    async fn handle_rerank(&self, request: RerankRequest) -> RerankResponse {
+        if let Err(e) = request.validate() {
+            return RerankResponse::error(request.request_id, e.to_string());
+        }
+        let Some(_guard) = self.shutdown.track() else {
+            return RerankResponse::error(request.request_id, "Server is shutting down".into());
+        };
+        let _flight = self.track_flight(&request.model_id).await;
+
+        let start = std::time::Instant::now();
+        let result = self
+            .inference_engine
+            .rerank(&request.model_id, &request.query, &request.documents)
+            .await;
+        match result {
+            Ok(result) => {
+                self.record_success(&request.model_id, start).await;
+                RerankResponse::success(request.request_id, result)
+            }
+            Err(e) => {
+                telemetry::record_request_failure(&request.model_id, &e.to_string());
+                RerankResponse::error(request.request_id, e.to_string())
+            }
+        }
+    }
+
+    /// Record latency for a request that produced no generated tokens.
+    async fn record_success(&self, model_id: &str, start: std::time::Instant) {
+        let latency_ms = start.elapsed().as_millis() as u64;
+        telemetry::record_request_success(model_id, latency_ms, 0);
+        if let Some(handle) = self.inference_engine.get_handle(model_id).await {
+            self.model_registry
+                .record_request(handle, latency_ms as f64)
+                .await;
+        }
+    }
+
+    /// Count a request against the routed model so swaps and unloads drain it.
+    async fn track_flight(&self, model_id: &str) -> Option<FlightGuard> {
+        match &self.lifecycle {
+            Some(lifecycle) => lifecycle.track(model_id).await,
+            None => None,
+        }
+    }
+
+    async fn handle_load_model(&self, request: LoadModelRequest) -> ModelLifecycleResponse {
+        if let Err(e) = request.validate() {
+            return Self::lifecycle_error(request.request_id, request.model_id, e.to_string());
+        }
+        let (lifecycle, _guard) = match self.lifecycle_for_admin() {
+            Ok(pair) => pair,
+            Err(message) => {
+                return Self::lifecycle_error(request.request_id, request.model_id, message)
+            }
+        };
+        let result = lifecycle
+            .load(&request.model_id, &request.path, request.manifest.as_deref())
+            .await;
+        Self::lifecycle_response(request.request_id, request.model_id, result)
+    }
+
+    async fn handle_unload_model(&self, request: UnloadModelRequest) -> ModelLifecycleResponse {
+        let (lifecycle, _guard) = match self.lifecycle_for_admin() {
+            Ok(pair) => pair,
+            Err(message) => {
+                return Self::lifecycle_error(request.request_id, request.model_id, message)
+            }
+        };
+        let result = lifecycle
+            .unload(&request.model_id, drain_timeout(request.drain_timeout_ms))
+            .await;
+        Self::lifecycle_response(request.request_id, request.model_id, result)
+    }
+
+    async fn handle_swap_model(&self, request: SwapModelRequest) -> ModelLifecycleResponse {
+        if let Err(e) = request.validate() {
+            return Self::lifecycle_error(request.request_id, request.model_id, e.to_string());
+        }
+        let (lifecycle, _guard) = match self.lifecycle_for_admin() {
+            Ok(pair) => pair,
+            Err(message) => {
+                return Self::lifecycle_error(request.request_id, request.model_id, message)
+            }
+        };
+        let result = lifecycle
+            .swap(
+                &request.model_id,
+                &request.path,
+                request.manifest.as_deref(),
+                drain_timeout(request.drain_timeout_ms),
+            )
+            .await;
+        Self::lifecycle_response(request.request_id, request.model_id, result)
+    }
+
+    async fn handle_rollback_model(&self, request: RollbackModelRequest) -> ModelLifecycleResponse {
+        let (lifecycle, _guard) = match self.lifecycle_for_admin() {
+            Ok(pair) => pair,
+            Err(message) => {
+                return Self::lifecycle_error(request.request_id, request.model_id, message)
+            }
+        };
+        let result = lifecycle
+            .rollback(&request.model_id, drain_timeout(request.drain_timeout_ms))
+            .await;
+        Self::lifecycle_response(request.request_id, request.model_id, result)
+    }
+
+    async fn handle_load_adapter(&self, request: LoadAdapterRequest) -> ModelLifecycleResponse {
+        if let Err(e) = request.validate() {
+            return Self::lifecycle_error(request.request_id, request.model_id, e.to_string());
+        }
+        let (lifecycle, _guard) = match self.lifecycle_for_admin() {
+            Ok(pair) => pair,
+            Err(message) => {
+                return Self::lifecycle_error(request.request_id, request.model_id, message)
+            }
+        };
+        let result = lifecycle
+            .load_adapter(
+                &request.model_id,
+                &request.adapter_id,
+                &request.path,
+                request.manifest.as_deref(),
+            )
+            .await;
+        Self::lifecycle_response(request.request_id, request.model_id, result)
+    }
+
+    async fn handle_unload_adapter(&self, request: UnloadAdapterRequest) -> ModelLifecycleResponse {
+        let (lifecycle, _guard) = match self.lifecycle_for_admin() {
+            Ok(pair) => pair,
+            Err(message) => {
+                return Self::lifecycle_error(request.request_id, request.model_id, message)
+            }
+        };
+        let result = lifecycle
+            .unload_adapter(&request.model_id, &request.adapter_id)
+            .await;
+        Self::lifecycle_response(request.request_id, request.model_id, result)
+    }
+
+    /// Lifecycle manager plus a shutdown guard, or the reason there is none.
+    fn lifecycle_for_admin(&self) -> Result<(&ModelLifecycle, ShutdownGuard), String> {
+        let lifecycle = self
+            .lifecycle
+            .as_deref()
+            .ok_or_else(|| "Model lifecycle management is not enabled".to_string())?;
+        let guard = self
+            .shutdown
+            .track()
+            .ok_or_else(|| "Server is shutting down".to_string())?;
+        Ok((lifecycle, guard))
+    }
+
+    fn lifecycle_response(
+        request_id: RequestId,
+        model_id: String,
+        result: Result<LifecycleReport, LifecycleError>,
+    ) -> ModelLifecycleResponse {
+        match result {
+            Ok(report) => ModelLifecycleResponse::success(request_id, report),
+            Err(e) => ModelLifecycleResponse::error(request_id, model_id, e.stage(), e.to_string()),
+        }
+    }
+
+    fn lifecycle_error(
+        request_id: RequestId,
+        model_id: String,
+        message: String,
+    ) -> ModelLifecycleResponse {
+        ModelLifecycleResponse::error(request_id, model_id, LifecycleStage::Validate, message)
+    }
+
+    /// Token text would reveal what the sanitizer redacted, so it is dropped
+    /// from log-probabilities once the output has been modified.
+    fn visible_logprobs(logprobs: Vec<TokenLogprob>, report: &SecurityReport) -> Vec<TokenLogprob> {
+        logprobs.into_iter().map(|l| Self::visible_logprob(l, report)).collect()
+    }
+
+    fn visible_logprob(logprob: TokenLogprob, report: &SecurityReport) -> TokenLogprob {
+        if report.output_modified {
+            logprob.without_text()
+        } else {
+            logprob
+        }
+    }
+
+    fn blocked_message(report: &SecurityReport) -> String {
+        format!(
+            "Prompt rejected by injection filter (risk score {})",
+            report.injection_risk_score
+        )
+    }
+
+    /// Backend finish reason, unless the output filter removed content.
+    fn finish_reason(reason: FinishReason, report: &SecurityReport) -> FinishReason {
+        if report.content_filtered > 0 {
+            FinishReason::ContentFiltered
+        } else {
+            reason
+        }
+    }
+
     async fn handle_warmup(&self, model_id: String, _tokens: usize) -> WarmupResponse {
         let start = std::time::Instant::now();
         let result = self
@@ -289,7 +767,7 @@ impl IpcHandler {
             .enqueue(
                 model_id.clone(),
                 "warmup".to_string(), // Minimal warmup prompt
-                crate::engine::InferenceParams::default(),
+                InferenceParams::default(),
                 Priority::Low,
             )
             .await;
@@ -302,7 +780,10 @@ impl IpcHandler {
 
     async fn handle_models_request(&self) -> ModelsListResponse {
         let models = self.model_registry.list_models().await;
-        let total_memory_bytes = models.iter().map(|m| m.memory_bytes).sum();
+        let total_memory_bytes = models
+            .iter()
+            .map(|m| m.memory_bytes + m.adapters.iter().map(|a| a.memory_bytes).sum::<u64>())
+            .sum();
 
         let model_infos: Vec<ModelInfo> = models
             .into_iter()
@@ -322,6 +803,17 @@ impl IpcHandler {
                     request_count: m.request_count,
                     avg_latency_ms,
                     loaded_at: format_system_time(m.loaded_at),
+                    adapters: m
+                        .adapters
+                        .into_iter()
+                        .map(|a| AdapterInfo {
+                            name: a.name,
+                            sha256: a.sha256,
+                            size_bytes: a.size_bytes,
+                            memory_bytes: a.memory_bytes,
+                            loaded_at: format_system_time(a.loaded_at),
+                        })
+                        .collect(),
                 }
             })
             .collect();
@@ -332,67 +824,170 @@ impl IpcHandler {
         }
     }
 
+    /// Process a non-streaming inference request that can be cancelled.
+    ///
+    /// Once `cancel` fires, generation stops at the next decode step and the
+    /// partial output is returned with finish reason `cancelled`.
+    pub async fn process_inference(
+        &self,
+        request: InferenceRequest,
+        session: &SessionToken,
+        cancel: CancellationToken,
+    ) -> Result<InferenceResponse, HandlerError> {
+        self.auth.validate(session).await?;
+        Ok(self.handle_inference(request, Some(session), cancel).await)
+    }
+
+    /// Process a non-streaming chat request. Same contract as
+    /// `process_inference`.
+    pub async fn process_chat(
+        &self,
+        request: ChatRequest,
+        session: &SessionToken,
+        cancel: CancellationToken,
+    ) -> Result<InferenceResponse, HandlerError> {
+        self.auth.validate(session).await?;
+        Ok(self.handle_chat(request, Some(session), cancel).await)
+    }
+
     /// Process streaming inference request. Sends token chunks via sender.
     ///
     /// Creates a token stream channel, spawns inference on a blocking task,
     /// and relays tokens to the client until completion or cancellation.
-    #[allow(unused_variables)]
     pub async fn process_streaming(
         &self,
-        request: InferenceRequest,
+        mut request: InferenceRequest,
         session: &SessionToken,
         sender: &dyn StreamSender,
         cancel: CancellationToken,
     ) -> Result<(), HandlerError> {
         self.auth.validate(session).await?;
-        let _guard = self.shutdown.track().ok_or(HandlerError::ShuttingDown)?;
+        if let Err(e) = request.validate() {
+            let chunk = StreamChunk::error(request.request_id, e.to_string());
+            sender.send(IpcMessage::StreamChunk(chunk)).await?;
+            return Ok(());
+        }
+        Self::scope_conversation(&mut request.parameters, Some(session));
+        self.stream_inference(
+            request.request_id,
+            request.model_id,
+            InferenceInput::Text(request.prompt),
+            request.parameters,
+            sender,
+            cancel,
+        )
+        .await
+    }
 
+    /// Process streaming chat request. Same contract as `process_streaming`.
+    pub async fn process_chat_streaming(
+        &self,
+        mut request: ChatRequest,
+        session: &SessionToken,
+        sender: &dyn StreamSender,
+        cancel: CancellationToken,
+    ) -> Result<(), HandlerError> {
+        self.auth.validate(session).await?;
         if let Err(e) = request.validate() {
             let chunk = StreamChunk::error(request.request_id, e.to_string());
             sender.send(IpcMessage::StreamChunk(chunk)).await?;
             return Ok(());
         }
+        Self::scope_conversation(&mut request.parameters, Some(session));
+        self.stream_inference(
+            request.request_id,
+            request.model_id,
+            InferenceInput::ChatMessages(request.messages),
+            request.parameters,
+            sender,
+            cancel,
+        )
+        .await
+    }
 
-        // Streaming requires gguf feature
-        #[cfg(not(feature = "gguf"))]
-        {
-            let chunk = StreamChunk::error(
-                request.request_id,
-                "Streaming requires GGUF feature. Rebuild with --features gguf.".into(),
-            );
+    /// Shared streaming path for prompt and chat requests.
+    async fn stream_inference(
+        &self,
+        request_id: RequestId,
+        model_id: String,
+        input: InferenceInput,
+        parameters: InferenceParams,
+        sender: &dyn StreamSender,
+        cancel: CancellationToken,
+    ) -> Result<(), HandlerError> {
+        let _guard = self.shutdown.track().ok_or(HandlerError::ShuttingDown)?;
+        let accepted = std::time::Instant::now();
+        let _flight = self.track_flight(&model_id).await;
+
+        // Rejected here because `to_config` drops a constraint it cannot build
+        if let Err(e) = parameters.validate() {
+            let chunk = StreamChunk::error(request_id, e.to_string());
             sender.send(IpcMessage::StreamChunk(chunk)).await?;
             return Ok(());
         }
 
-        #[cfg(feature = "gguf")]
-        {
-            self.run_streaming_inference(request, sender, cancel).await
+        let report = self.security.scan_prompt(&input.joined_text());
+        if report.prompt_blocked {
+            let message = Self::blocked_message(&report);
+            telemetry::record_request_failure(&model_id, &message);
+            self.security.audit(&report, request_id.0, &model_id).await;
+            let chunk = StreamChunk::error(request_id, message).with_security(report);
+            sender.send(IpcMessage::StreamChunk(chunk)).await?;
+            return Ok(());
         }
+
+        self.run_streaming_inference(
+            request_id, model_id, input, parameters, report, accepted, sender, cancel,
+        )
+        .await
     }
 
-    /// Internal streaming implementation (gguf feature only).
-    #[cfg(feature = "gguf")]
+    /// Internal streaming implementation.
+    #[allow(clippy::too_many_arguments)]
     async fn run_streaming_inference(
         &self,
-        request: InferenceRequest,
+        request_id: RequestId,
+        model_id: String,
+        input: InferenceInput,
+        parameters: InferenceParams,
+        mut report: SecurityReport,
+        accepted: std::time::Instant,
         sender: &dyn StreamSender,
         cancel: CancellationToken,
     ) -> Result<(), HandlerError> {
-        let request_id = request.request_id;
-        let model_id = request.model_id.clone();
-        let prompt = request.prompt.clone();
-        let config = request.parameters.to_config();
+        let mut config = parameters.to_config();
+        config.cancel = cancel.clone();
+        // Constrained output is collected so its final form can be checked
+        let constrained = config.constraint.as_ref().map(|c| c.is_json());
+        let mut emitted = String::new();
         let engine = Arc::clone(&self.inference_engine);
+        let stream_model_id = model_id.clone();
 
         // Create channel for token streaming
         let (token_sender, mut stream) = TokenStream::new(32);
+        let mut sanitizer_state = SecurityStreamState::default();
+        // A token's text may be part of a match that is redacted only after
+        // its chunk is sent, so streamed logprobs never carry text when the
+        // pipeline can change output
+        let strip_logprob_text = self.security.may_modify_output();
+        let stream_logprob = |l: TokenLogprob| {
+            if strip_logprob_text {
+                l.without_text()
+            } else {
+                l
+            }
+        };
 
         // Spawn blocking inference task
         let inf_handle = tokio::task::spawn_blocking(move || {
-            engine.run_stream_sync(&model_id, &prompt, &config, token_sender)
+            let queue_wait = accepted.elapsed();
+            let outcome = engine.run_stream_sync(&stream_model_id, &input, &config, token_sender);
+            (queue_wait, outcome)
         });
 
-        // Relay tokens to IPC, handling cancellation
+        // Relay tokens to IPC, handling cancellation. The final token is held
+        // back until the task reports its queue wait.
+        let mut last = None;
         loop {
             tokio::select! {
                 biased;
@@ -404,15 +999,26 @@ impl IpcHandler {
                 token_opt = stream.next() => {
                     match token_opt {
                         Some(output) => {
-                            let chunk = if output.is_final {
-                                StreamChunk::final_token(request_id, output.token)
-                            } else {
-                                StreamChunk::token(request_id, output.token)
-                            };
-                            sender.send(IpcMessage::StreamChunk(chunk)).await?;
+                            let mut text = self.security.sanitize_chunk(
+                                &output.text,
+                                &mut sanitizer_state,
+                                &mut report,
+                            );
+                            if output.is_final {
+                                let held = &mut sanitizer_state;
+                                text.push_str(&self.security.flush_stream(held, &mut report));
+                            }
+                            if constrained.is_some() {
+                                emitted.push_str(&text);
+                            }
                             if output.is_final {
+                                last = Some((output, text));
                                 break;
                             }
+                            let logprob = output.logprob.map(stream_logprob);
+                            let chunk = StreamChunk::token_with_text(request_id, output.token, text)
+                                .with_logprob(logprob);
+                            sender.send(IpcMessage::StreamChunk(chunk)).await?;
                         }
                         None => break, // Channel closed
                     }
@@ -420,8 +1026,47 @@ impl IpcHandler {
             }
         }
 
-        // Wait for inference task (ignore result - tokens already sent)
-        let _ = inf_handle.await;
+        // Drop the receiver so a cancelled generation unblocks and stops,
+        // then wait for inference task
+        drop(stream);
+        let (queue_wait, outcome) = inf_handle.await.unwrap_or_else(|e| {
+            let failed = crate::engine::inference::InferenceError::ExecutionFailed(e.to_string());
+            (Default::default(), Err(failed))
+        });
+
+        // A generation that failed before its first token (e.g. the prompt
+        // exceeds the context window) still gets a terminal chunk
+        if let (None, Err(e)) = (&last, outcome) {
+            if !cancel.is_cancelled() {
+                telemetry::record_request_failure(&model_id, &e.to_string());
+                let chunk = StreamChunk::error(request_id, e.to_string()).with_security(report);
+                sender.send(IpcMessage::StreamChunk(chunk)).await?;
+            }
+            return Ok(());
+        }
+
+        if let Some((output, text)) = last {
+            if let Some(json) = constrained {
+                self.security.check_format(&emitted, json, &mut report);
+            }
+            self.security.audit(&report, request_id.0, &model_id).await;
+            let usage = output.finish.map(|finish| {
+                UsageReport::new(
+                    finish.prompt_tokens as usize,
+                    finish.completion_tokens as usize,
+                    Self::finish_reason(finish.finish_reason, &report),
+                    queue_wait,
+                    &finish.timings,
+                )
+                .with_stop_sequence(finish.stop_sequence)
+            });
+            let logprob = output.logprob.map(stream_logprob);
+            let mut chunk = StreamChunk::final_token_with_text(request_id, output.token, text)
+                .with_logprob(logprob)
+                .with_security(report);
+            chunk.usage = usage;
+            sender.send(IpcMessage::StreamChunk(chunk)).await?;
+        }
         Ok(())
     }
 }
@@ -18,10 +18,14 @@ pub use encoding::{get_encoder, TokenEncoder, V1Encoder, V2Encoder};
 pub use handler::{HandlerError, IpcHandler, IpcHandlerConfig, StreamSender};
 pub use stream_bridge::IpcStreamBridge;
 pub use protocol::{
-    decode_message, decode_message_binary, encode_message, encode_message_binary,
-    HealthCheckResponse, HealthCheckType, InferenceRequest, InferenceResponse, IpcMessage,
-    ModelInfo, ModelsListResponse, ProtocolError, ProtocolVersion, RequestId, StreamChunk,
-    WarmupRequest, WarmupResponse,
+    decode_message, decode_message_binary, encode_message, encode_message_binary, AdapterInfo,
+    ChatRequest, Choice, Classification, ClassifyRequest, ClassifyResponse, EmbedRequest,
+    EmbedResponse, HealthCheckResponse, HealthCheckType, InferenceRequest, InferenceResponse,
+    IpcMessage, LabelScore, LifecycleStageReport, LoadAdapterRequest, LoadModelRequest,
+    ModelInfo, ModelLifecycleResponse, ModelsListResponse, ProtocolError, ProtocolVersion,
+    RequestId, RerankRequest, RerankResponse, RollbackModelRequest, StreamChunk,
+    SwapModelRequest, UnloadAdapterRequest, UnloadModelRequest, UsageReport, WarmupRequest,
+    WarmupResponse,
 };
 // Re-export MetricsSnapshot for IPC consumers
 pub use crate::telemetry::MetricsSnapshot;
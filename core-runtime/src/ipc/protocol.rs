@@ -8,8 +8,14 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::engine::InferenceParams;
+use std::time::Duration;
+
+use crate::engine::{ChatMessage, ClassificationResult, EmbeddingResult, InferenceParams};
+use crate::engine::RerankResult;
+use crate::engine::{FinishReason, GenerationTimings, StopSequence, TokenLogprob};
 use crate::health::HealthReport;
+use crate::models::{LifecycleReport, LifecycleStage};
+use crate::security::SecurityReport;
 use crate::telemetry::{ExportableSpan, MetricsSnapshot};
 
 /// Model information for diagnostics.
@@ -33,6 +39,24 @@ pub struct ModelInfo {
     pub avg_latency_ms: f64,
     /// Timestamp when loaded (ISO 8601)
     pub loaded_at: String,
+    /// LoRA adapters loaded against the model
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub adapters: Vec<AdapterInfo>,
+}
+
+/// A LoRA adapter loaded against a model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdapterInfo {
+    /// Name requests select the adapter by
+    pub name: String,
+    /// SHA-256 of the adapter file
+    pub sha256: String,
+    /// Adapter size in bytes
+    pub size_bytes: u64,
+    /// Memory usage in bytes
+    pub memory_bytes: u64,
+    /// Timestamp when loaded (ISO 8601)
+    pub loaded_at: String,
 }
 
 /// Models list response for diagnostics.
@@ -123,6 +147,32 @@ impl InferenceRequest {
     }
 }
 
+/// Chat inference request: role-tagged messages rendered with the model's
+/// chat template. Answered with an `InferenceResponse` (or stream chunks).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatRequest {
+    pub request_id: RequestId,
+    pub model_id: String,
+    /// Conversation so far; the model replies as the assistant.
+    pub messages: Vec<ChatMessage>,
+    pub parameters: InferenceParams,
+}
+
+impl ChatRequest {
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        if self.model_id.is_empty() {
+            return Err(ProtocolError::MissingField("model_id".into()));
+        }
+        if self.messages.is_empty() {
+            return Err(ProtocolError::MissingField("messages".into()));
+        }
+        if self.messages.iter().any(|m| m.content.is_empty()) {
+            return Err(ProtocolError::MissingField("messages[].content".into()));
+        }
+        Ok(())
+    }
+}
+
 /// Inference response to caller.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InferenceResponse {
@@ -133,6 +183,33 @@ pub struct InferenceResponse {
     pub tokens_generated: usize,
     pub finished: bool,
     pub error: Option<String>,
+    /// Security pipeline findings (prompt scan and output sanitization).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<SecurityReport>,
+    /// Token counts, finish reason and timings (successful requests only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<UsageReport>,
+    /// Log-probability of each generated token, when requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Vec<TokenLogprob>>,
+    /// Every completion, best first, when `n > 1`. `output` repeats the first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<Choice>>,
+}
+
+/// One of several completions returned for a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Choice {
+    pub index: usize,
+    pub output: String,
+    pub tokens_generated: usize,
+    pub finish_reason: FinishReason,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_sequence: Option<StopSequence>,
+    /// Sum of the completion's token log-probabilities, used for `best_of`.
+    pub cumulative_logprob: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Vec<TokenLogprob>>,
 }
 
 impl InferenceResponse {
@@ -143,6 +220,10 @@ impl InferenceResponse {
             tokens_generated,
             finished,
             error: None,
+            security: None,
+            usage: None,
+            logprobs: None,
+            choices: None,
         }
     }
 
@@ -153,8 +234,87 @@ impl InferenceResponse {
             tokens_generated: 0,
             finished: true,
             error: Some(error),
+            security: None,
+            usage: None,
+            logprobs: None,
+            choices: None,
         }
     }
+
+    /// Attach the security report for this request.
+    pub fn with_security(mut self, report: SecurityReport) -> Self {
+        self.security = Some(report);
+        self
+    }
+
+    /// Attach usage and timing data for this request.
+    pub fn with_usage(mut self, usage: UsageReport) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Attach per-token log-probabilities.
+    pub fn with_logprobs(mut self, logprobs: Vec<TokenLogprob>) -> Self {
+        self.logprobs = Some(logprobs);
+        self
+    }
+
+    /// Attach the completions of an `n > 1` request.
+    pub fn with_choices(mut self, choices: Vec<Choice>) -> Self {
+        self.choices = Some(choices);
+        self
+    }
+}
+
+/// Per-request token counts, finish reason and latency breakdown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub finish_reason: FinishReason,
+    /// From request receipt until the model started work.
+    pub queue_wait_ms: f64,
+    /// Prompt tokenization and evaluation.
+    pub prefill_ms: f64,
+    /// From request receipt until the first completion token was sampled.
+    pub time_to_first_token_ms: f64,
+    /// Completion tokens per second after prefill.
+    pub decode_tokens_per_sec: f64,
+    /// Stop sequence that ended generation, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_sequence: Option<StopSequence>,
+}
+
+impl UsageReport {
+    pub fn new(
+        prompt_tokens: usize,
+        completion_tokens: usize,
+        finish_reason: FinishReason,
+        queue_wait: Duration,
+        timings: &GenerationTimings,
+    ) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            finish_reason,
+            queue_wait_ms: millis(queue_wait),
+            prefill_ms: millis(timings.prefill),
+            time_to_first_token_ms: millis(queue_wait + timings.first_token),
+            decode_tokens_per_sec: timings
+                .decode_tokens_per_sec(u32::try_from(completion_tokens).unwrap_or(u32::MAX)),
+            stop_sequence: None,
+        }
+    }
+
+    /// Record the stop sequence that ended generation.
+    pub fn with_stop_sequence(mut self, stop_sequence: Option<StopSequence>) -> Self {
+        self.stop_sequence = stop_sequence;
+        self
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
 }
 
 /// Single token chunk for streaming responses.
@@ -167,6 +327,15 @@ pub struct StreamChunk {
     pub text: Option<String>,
     pub is_final: bool,
     pub error: Option<String>,
+    /// Security pipeline findings (final or error chunk only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<SecurityReport>,
+    /// Token counts, finish reason and timings (final chunk only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<UsageReport>,
+    /// Log-probability of this chunk's token, when requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprob: Option<TokenLogprob>,
 }
 
 impl StreamChunk {
@@ -178,6 +347,9 @@ impl StreamChunk {
             text: None,
             is_final: false,
             error: None,
+            security: None,
+            usage: None,
+            logprob: None,
         }
     }
 
@@ -189,6 +361,9 @@ impl StreamChunk {
             text: Some(text),
             is_final: false,
             error: None,
+            security: None,
+            usage: None,
+            logprob: None,
         }
     }
 
@@ -200,6 +375,9 @@ impl StreamChunk {
             text: None,
             is_final: true,
             error: None,
+            security: None,
+            usage: None,
+            logprob: None,
         }
     }
 
@@ -211,6 +389,9 @@ impl StreamChunk {
             text: Some(text),
             is_final: true,
             error: None,
+            security: None,
+            usage: None,
+            logprob: None,
         }
     }
 
@@ -222,8 +403,29 @@ impl StreamChunk {
             text: None,
             is_final: true,
             error: Some(error),
+            security: None,
+            usage: None,
+            logprob: None,
         }
     }
+
+    /// Attach the security report for this request.
+    pub fn with_security(mut self, report: SecurityReport) -> Self {
+        self.security = Some(report);
+        self
+    }
+
+    /// Attach usage and timing data for the finished stream.
+    pub fn with_usage(mut self, usage: UsageReport) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Attach the log-probability of this chunk's token.
+    pub fn with_logprob(mut self, logprob: Option<TokenLogprob>) -> Self {
+        self.logprob = logprob;
+        self
+    }
 }
 
 /// Warmup request to prime a model.
@@ -268,6 +470,356 @@ impl WarmupResponse {
     }
 }
 
+/// Embedding request: one vector per input text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedRequest {
+    pub request_id: RequestId,
+    pub model_id: String,
+    /// Texts to embed, each encoded independently.
+    pub inputs: Vec<String>,
+}
+
+impl EmbedRequest {
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        validate_text_inputs(&self.model_id, &self.inputs)
+    }
+}
+
+/// Embedding response, vectors in input order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedResponse {
+    pub request_id: RequestId,
+    pub embeddings: Vec<Vec<f32>>,
+    /// Vector dimensionality (0 on error).
+    pub dimensions: usize,
+    pub error: Option<String>,
+}
+
+impl EmbedResponse {
+    pub fn success(request_id: RequestId, results: Vec<EmbeddingResult>) -> Self {
+        Self {
+            request_id,
+            dimensions: results.first().map_or(0, |r| r.dimensions),
+            embeddings: results.into_iter().map(|r| r.vector).collect(),
+            error: None,
+        }
+    }
+
+    pub fn error(request_id: RequestId, error: String) -> Self {
+        Self {
+            request_id,
+            embeddings: Vec::new(),
+            dimensions: 0,
+            error: Some(error),
+        }
+    }
+}
+
+/// Classification request: one label distribution per input text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassifyRequest {
+    pub request_id: RequestId,
+    pub model_id: String,
+    /// Texts to classify, each scored independently.
+    pub inputs: Vec<String>,
+}
+
+impl ClassifyRequest {
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        validate_text_inputs(&self.model_id, &self.inputs)
+    }
+}
+
+/// Label with its probability.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelScore {
+    pub label: String,
+    pub score: f32,
+}
+
+/// Classification of one input text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Classification {
+    /// Highest-scoring label.
+    pub label: String,
+    pub confidence: f32,
+    /// All labels, sorted by score descending.
+    pub scores: Vec<LabelScore>,
+}
+
+impl From<ClassificationResult> for Classification {
+    fn from(result: ClassificationResult) -> Self {
+        Self {
+            label: result.label,
+            confidence: result.confidence,
+            scores: result
+                .all_labels
+                .into_iter()
+                .map(|(label, score)| LabelScore { label, score })
+                .collect(),
+        }
+    }
+}
+
+/// Classification response, results in input order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassifyResponse {
+    pub request_id: RequestId,
+    pub results: Vec<Classification>,
+    pub error: Option<String>,
+}
+
+impl ClassifyResponse {
+    pub fn success(request_id: RequestId, results: Vec<ClassificationResult>) -> Self {
+        Self {
+            request_id,
+            results: results.into_iter().map(Classification::from).collect(),
+            error: None,
+        }
+    }
+
+    pub fn error(request_id: RequestId, error: String) -> Self {
+        Self {
+            request_id,
+            results: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+/// Rerank request: one relevance score per document for the query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerankRequest {
+    pub request_id: RequestId,
+    pub model_id: String,
+    pub query: String,
+    /// Documents to score against the query, each paired with it.
+    pub documents: Vec<String>,
+}
+
+impl RerankRequest {
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        if self.model_id.is_empty() {
+            return Err(ProtocolError::MissingField("model_id".into()));
+        }
+        if self.query.is_empty() {
+            return Err(ProtocolError::MissingField("query".into()));
+        }
+        if self.documents.is_empty() {
+            return Err(ProtocolError::MissingField("documents".into()));
+        }
+        if self.documents.iter().any(|text| text.is_empty()) {
+            return Err(ProtocolError::MissingField("documents[]".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Rerank response: scores in input order plus the document indices
+/// sorted by score descending.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerankResponse {
+    pub request_id: RequestId,
+    pub scores: Vec<f32>,
+    pub order: Vec<usize>,
+    pub error: Option<String>,
+}
+
+impl RerankResponse {
+    pub fn success(request_id: RequestId, result: RerankResult) -> Self {
+        Self {
+            request_id,
+            scores: result.scores,
+            order: result.order,
+            error: None,
+        }
+    }
+
+    pub fn error(request_id: RequestId, error: String) -> Self {
+        Self {
+            request_id,
+            scores: Vec::new(),
+            order: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+/// Admin request: load a model file and route `model_id` to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadModelRequest {
+    pub request_id: RequestId,
+    pub model_id: String,
+    /// Model file, relative to the runtime base path (under `models/`).
+    pub path: String,
+    /// Manifest JSON, relative to the base path. Without one the format is
+    /// taken from the file extension and the version is 0.0.0.
+    #[serde(default)]
+    pub manifest: Option<String>,
+}
+
+impl LoadModelRequest {
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        validate_model_path(&self.model_id, &self.path)
+    }
+}
+
+/// Admin request: drain and release a model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnloadModelRequest {
+    pub request_id: RequestId,
+    pub model_id: String,
+    /// How long to wait for in-flight requests (default 30 s).
+    #[serde(default)]
+    pub drain_timeout_ms: Option<u64>,
+}
+
+/// Admin request: hot-swap a loaded model to a new file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapModelRequest {
+    pub request_id: RequestId,
+    pub model_id: String,
+    pub path: String,
+    #[serde(default)]
+    pub manifest: Option<String>,
+    #[serde(default)]
+    pub drain_timeout_ms: Option<u64>,
+}
+
+impl SwapModelRequest {
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        validate_model_path(&self.model_id, &self.path)
+    }
+}
+
+/// Admin request: swap back to the previously active version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackModelRequest {
+    pub request_id: RequestId,
+    pub model_id: String,
+    #[serde(default)]
+    pub drain_timeout_ms: Option<u64>,
+}
+
+/// Admin request: load a LoRA adapter against a loaded model so requests
+/// can select it by `adapter_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadAdapterRequest {
+    pub request_id: RequestId,
+    /// Base model the adapter is applied to.
+    pub model_id: String,
+    pub adapter_id: String,
+    /// Adapter file, relative to the runtime base path (under `models/`).
+    pub path: String,
+    /// Adapter manifest JSON, relative to the base path.
+    #[serde(default)]
+    pub manifest: Option<String>,
+}
+
+impl LoadAdapterRequest {
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        validate_model_path(&self.model_id, &self.path)?;
+        if self.adapter_id.is_empty() {
+            return Err(ProtocolError::MissingField("adapter_id".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Admin request: release a LoRA adapter of a loaded model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnloadAdapterRequest {
+    pub request_id: RequestId,
+    pub model_id: String,
+    pub adapter_id: String,
+}
+
+/// One completed stage of a lifecycle operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifecycleStageReport {
+    pub stage: LifecycleStage,
+    pub elapsed_ms: u64,
+}
+
+/// Result of a load, unload, swap or rollback request, or of loading or
+/// unloading an adapter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelLifecycleResponse {
+    pub request_id: RequestId,
+    pub model_id: String,
+    /// Stages completed, in order.
+    pub stages: Vec<LifecycleStageReport>,
+    /// Registry handle now serving `model_id` (absent after unload).
+    pub handle_id: Option<u64>,
+    /// Version now serving `model_id` (absent after unload).
+    pub version: Option<String>,
+    /// Stage that failed, if any. On failure the model that was serving
+    /// before the request keeps serving.
+    pub failed_stage: Option<LifecycleStage>,
+    pub error: Option<String>,
+}
+
+impl ModelLifecycleResponse {
+    pub fn success(request_id: RequestId, report: LifecycleReport) -> Self {
+        Self {
+            request_id,
+            model_id: report.model_id,
+            stages: report
+                .stages
+                .into_iter()
+                .map(|s| LifecycleStageReport {
+                    stage: s.stage,
+                    elapsed_ms: s.elapsed.as_millis() as u64,
+                })
+                .collect(),
+            handle_id: report.handle.map(|h| h.id()),
+            version: report.version.map(|v| v.to_string()),
+            failed_stage: None,
+            error: None,
+        }
+    }
+
+    pub fn error(
+        request_id: RequestId,
+        model_id: String,
+        failed_stage: LifecycleStage,
+        error: String,
+    ) -> Self {
+        Self {
+            request_id,
+            model_id,
+            stages: Vec::new(),
+            handle_id: None,
+            version: None,
+            failed_stage: Some(failed_stage),
+            error: Some(error),
+        }
+    }
+}
+
+fn validate_model_path(model_id: &str, path: &str) -> Result<(), ProtocolError> {
+    if model_id.is_empty() {
+        return Err(ProtocolError::MissingField("model_id".into()));
+    }
+    if path.is_empty() {
+        return Err(ProtocolError::MissingField("path".into()));
+    }
+    Ok(())
+}
+
+fn validate_text_inputs(model_id: &str, inputs: &[String]) -> Result<(), ProtocolError> {
+    if model_id.is_empty() {
+        return Err(ProtocolError::MissingField("model_id".into()));
+    }
+    if inputs.is_empty() {
+        return Err(ProtocolError::MissingField("inputs".into()));
+    }
+    if inputs.iter().any(|text| text.is_empty()) {
+        return Err(ProtocolError::MissingField("inputs[]".into()));
+    }
+    Ok(())
+}
+
 /// Health check request types.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HealthCheckType {
@@ -310,9 +862,51 @@ pub enum IpcMessage {
     #[serde(rename = "inference_response")]
     InferenceResponse(InferenceResponse),
 
+    #[serde(rename = "chat_request")]
+    ChatRequest(ChatRequest),
+
     #[serde(rename = "stream_chunk")]
     StreamChunk(StreamChunk),
 
+    #[serde(rename = "embed_request")]
+    EmbedRequest(EmbedRequest),
+
+    #[serde(rename = "embed_response")]
+    EmbedResponse(EmbedResponse),
+
+    #[serde(rename = "classify_request")]
+    ClassifyRequest(ClassifyRequest),
+
+    #[serde(rename = "classify_response")]
+    ClassifyResponse(ClassifyResponse),
+
+    #[serde(rename = "rerank_request")]
+    RerankRequest(RerankRequest),
+
+    #[serde(rename = "rerank_response")]
+    RerankResponse(RerankResponse),
+
+    #[serde(rename = "load_model")]
+    LoadModel(LoadModelRequest),
+
+    #[serde(rename = "unload_model")]
+    UnloadModel(UnloadModelRequest),
+
+    #[serde(rename = "swap_model")]
+    SwapModel(SwapModelRequest),
+
+    #[serde(rename = "rollback_model")]
+    RollbackModel(RollbackModelRequest),
+
+    #[serde(rename = "load_adapter")]
+    LoadAdapter(LoadAdapterRequest),
+
+    #[serde(rename = "unload_adapter")]
+    UnloadAdapter(UnloadAdapterRequest),
+
+    #[serde(rename = "model_lifecycle_response")]
+    ModelLifecycleResponse(ModelLifecycleResponse),
+
     #[serde(rename = "health_check")]
     HealthCheck { check_type: HealthCheckType },
 
@@ -563,6 +1157,31 @@ mod tests {
         assert!(response.error.is_none());
     }
 
+    #[test]
+    fn test_inference_response_choices_serialization() {
+        let response = InferenceResponse::success(RequestId(1), "a".to_string(), 2, true);
+        let json = serde_json::to_value(&response).unwrap();
+        assert!(json.get("choices").is_none());
+
+        let choices = ["a", "b"]
+            .iter()
+            .enumerate()
+            .map(|(index, output)| Choice {
+                index,
+                output: output.to_string(),
+                tokens_generated: 1,
+                finish_reason: FinishReason::Stop,
+                stop_sequence: None,
+                cumulative_logprob: -0.5,
+                logprobs: None,
+            })
+            .collect();
+        let json = serde_json::to_value(response.with_choices(choices)).unwrap();
+        assert_eq!(json["choices"][1]["output"], "b");
+        assert_eq!(json["choices"][1]["finish_reason"], "stop");
+        assert!(json["choices"][0].get("logprobs").is_none());
+    }
+
     #[test]
     fn test_inference_response_error() {
         let response = InferenceResponse::error(RequestId(1), "test error".to_string());
@@ -646,4 +1265,128 @@ mod tests {
         let err = ProtocolError::MissingField("test".to_string());
         assert!(err.to_string().contains("test"));
     }
+
+    #[test]
+    fn test_chat_request_decode() {
+        let json = br#"{"type":"chat_request","request_id":3,"model_id":"qwen",
+            "messages":[{"role":"system","content":"Be brief."},{"role":"user","content":"Hi"}],
+            "parameters":{"max_tokens":8,"temperature":0.7,"top_p":0.9,"top_k":40}}"#;
+        match decode_message(json).unwrap() {
+            IpcMessage::ChatRequest(req) => {
+                assert_eq!(req.request_id, RequestId(3));
+                assert_eq!(req.messages.len(), 2);
+                assert_eq!(req.messages[0].role, crate::engine::ChatRole::System);
+                assert!(req.validate().is_ok());
+            }
+            other => panic!("expected ChatRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_chat_request_rejects_unknown_role() {
+        let json = br#"{"type":"chat_request","request_id":3,"model_id":"qwen",
+            "messages":[{"role":"tool","content":"x"}],
+            "parameters":{"max_tokens":8,"temperature":0.7,"top_p":0.9,"top_k":40}}"#;
+        assert!(decode_message(json).is_err());
+    }
+
+    #[test]
+    fn test_chat_request_validate() {
+        let mut req = ChatRequest {
+            request_id: RequestId(1),
+            model_id: "m".into(),
+            messages: vec![],
+            parameters: InferenceParams::default(),
+        };
+        assert!(matches!(req.validate(), Err(ProtocolError::MissingField(f)) if f == "messages"));
+
+        req.messages.push(ChatMessage {
+            role: crate::engine::ChatRole::User,
+            content: String::new(),
+        });
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_embed_request_decode_and_validate() {
+        let json = br#"{"type":"embed_request","request_id":4,"model_id":"minilm",
+            "inputs":["first passage","second passage"]}"#;
+        match decode_message(json).unwrap() {
+            IpcMessage::EmbedRequest(req) => {
+                assert_eq!(req.inputs.len(), 2);
+                assert!(req.validate().is_ok());
+            }
+            other => panic!("expected EmbedRequest, got {:?}", other),
+        }
+
+        let empty = EmbedRequest {
+            request_id: RequestId(1),
+            model_id: "minilm".into(),
+            inputs: vec!["ok".into(), String::new()],
+        };
+        assert!(matches!(empty.validate(), Err(ProtocolError::MissingField(f)) if f == "inputs[]"));
+    }
+
+    #[test]
+    fn test_classify_response_from_results() {
+        let result = ClassificationResult {
+            label: "positive".into(),
+            confidence: 0.9,
+            all_labels: vec![("positive".into(), 0.9), ("negative".into(), 0.1)],
+        };
+        let response = ClassifyResponse::success(RequestId(5), vec![result]);
+        assert_eq!(response.results[0].label, "positive");
+        assert_eq!(response.results[0].scores[1].label, "negative");
+
+        let json = serde_json::to_value(IpcMessage::ClassifyResponse(response)).unwrap();
+        assert_eq!(json["type"], "classify_response");
+        assert_eq!(json["results"][0]["scores"][0]["score"], 0.9f32 as f64);
+    }
+
+    #[test]
+    fn test_rerank_messages_roundtrip() {
+        let json = br#"{"type":"rerank_request","request_id":6,"model_id":"bge-reranker",
+            "query":"cats","documents":["a cat","a dog"]}"#;
+        let request = match decode_message(json).unwrap() {
+            IpcMessage::RerankRequest(req) => req,
+            other => panic!("expected RerankRequest, got {:?}", other),
+        };
+        assert!(request.validate().is_ok());
+        let blank = RerankRequest { documents: vec!["ok".into(), String::new()], ..request };
+        let err = blank.validate().unwrap_err();
+        assert!(matches!(err, ProtocolError::MissingField(ref f) if f == "documents[]"));
+        let no_query = RerankRequest { query: String::new(), ..blank };
+        let err = no_query.validate().unwrap_err();
+        assert!(matches!(err, ProtocolError::MissingField(ref f) if f == "query"));
+
+        let result = RerankResult::from_scores(vec![0.2, 0.9]);
+        let response = RerankResponse::success(RequestId(6), result);
+        let json = serde_json::to_value(IpcMessage::RerankResponse(response)).unwrap();
+        assert_eq!(json["type"], "rerank_response");
+        assert_eq!(json["order"], serde_json::json!([1, 0]));
+    }
+
+    #[test]
+    fn test_lifecycle_messages_roundtrip() {
+        let json = br#"{"type":"swap_model","request_id":3,"model_id":"phi-3",
+            "path":"models/phi-3-q8.gguf"}"#;
+        match decode_message(json).unwrap() {
+            IpcMessage::SwapModel(req) => {
+                assert!(req.manifest.is_none());
+                assert!(req.drain_timeout_ms.is_none());
+                assert!(req.validate().is_ok());
+            }
+            other => panic!("expected SwapModel, got {:?}", other),
+        }
+
+        let response = ModelLifecycleResponse::error(
+            RequestId(3),
+            "phi-3".into(),
+            LifecycleStage::Drain,
+            "Drain timed out".into(),
+        );
+        let json = serde_json::to_value(IpcMessage::ModelLifecycleResponse(response)).unwrap();
+        assert_eq!(json["type"], "model_lifecycle_response");
+        assert_eq!(json["failed_stage"], "drain");
+    }
 }
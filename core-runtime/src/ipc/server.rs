@@ -6,6 +6,10 @@
 //!
 //! All connections use length-prefixed framing (4-byte LE + payload)
 //! matching the CLI client protocol in `cli::ipc_client`.
+//!
+//! Generation requests (streaming or not) are multiplexed: each runs on its
+//! own task and frames from concurrent requests interleave, tagged by
+//! `request_id`. `CancelRequest` and client disconnects abort them.
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -15,14 +19,24 @@ use tokio::sync::{watch, Mutex};
 use tokio_util::sync::CancellationToken;
 use thiserror::Error;
 
+use super::auth::SessionToken;
 use super::connections::{ConnectionPool, OwnedConnectionGuard};
-use super::handler::IpcHandler;
-use super::protocol::{decode_message, encode_message, IpcMessage};
+use super::handler::{HandlerError, IpcHandler};
+use super::protocol::{
+    decode_message, encode_message, ChatRequest, InferenceRequest, InferenceResponse,
+    IpcMessage, RequestId, StreamChunk,
+};
 use super::stream_bridge::IpcStreamBridge;
 
 /// Maximum allowed message frame size (16 MB).
 const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
 
+/// Maximum concurrent generation requests multiplexed on a single connection.
+const MAX_REQUESTS_PER_CONNECTION: usize = 32;
+
+/// In-flight generation requests on one connection, keyed by request ID.
+type ActiveRequests = Arc<Mutex<HashMap<RequestId, CancellationToken>>>;
+
 #[derive(Error, Debug)]
 pub enum ServerError {
     #[error("IO error: {0}")]
@@ -74,7 +88,11 @@ async fn write_frame_locked<W: AsyncWriteExt + Unpin>(
 }
 
 /// Handle one IPC connection: read requests, dispatch, write responses.
-/// Supports both synchronous request/response and streaming inference.
+///
+/// Generation requests run as independent tasks keyed by `RequestId`, so the
+/// read loop keeps accepting frames while they are in flight. Responses and
+/// chunks interleave on the shared writer, and `CancelRequest` can reach a
+/// request mid-generation.
 async fn handle_connection<S: AsyncReadExt + AsyncWriteExt + Unpin + Send + 'static>(
     stream: S,
     handler: Arc<IpcHandler>,
@@ -82,8 +100,8 @@ async fn handle_connection<S: AsyncReadExt + AsyncWriteExt + Unpin + Send + 'sta
 ) {
     let (mut read_half, write_half) = tokio::io::split(stream);
     let write_half = Arc::new(Mutex::new(write_half));
-    let mut session = None;
-    let mut active_streams: HashMap<u64, CancellationToken> = HashMap::new();
+    let mut session: Option<SessionToken> = None;
+    let active_requests: ActiveRequests = Arc::new(Mutex::new(HashMap::new()));
 
     loop {
         let request_bytes = match read_frame(&mut read_half).await {
@@ -110,38 +128,29 @@ async fn handle_connection<S: AsyncReadExt + AsyncWriteExt + Unpin + Send + 'sta
         };
 
         match message {
-            // Streaming inference request
-            IpcMessage::InferenceRequest(ref req) if req.parameters.stream => {
-                if let Some(ref sess) = session {
-                    let cancel = CancellationToken::new();
-                    active_streams.insert(req.request_id.0, cancel.clone());
-                    let bridge = IpcStreamBridge::new(
-                        Arc::clone(&write_half),
-                        req.request_id,
-                        cancel.clone(),
-                    );
-                    let _ = handler
-                        .process_streaming(req.clone(), sess, &bridge, cancel)
-                        .await;
-                    active_streams.remove(&req.request_id.0);
-                } else {
-                    let err = r#"{"type":"error","code":401,"message":"Not authenticated"}"#;
-                    let _ = write_frame_locked(&write_half, err.as_bytes()).await;
-                }
+            // Generation request: run as its own task. Unauthenticated
+            // non-streaming requests fall through to the standard path.
+            IpcMessage::InferenceRequest(req) if req.parameters.stream || session.is_some() => {
+                let req = GenerationRequest::Prompt(req);
+                start_generation(req, &session, &handler, &write_half, &active_requests).await;
+            }
+            IpcMessage::ChatRequest(req) if req.parameters.stream || session.is_some() => {
+                let req = GenerationRequest::Chat(req);
+                start_generation(req, &session, &handler, &write_half, &active_requests).await;
             }
 
-            // Cancel request - trigger cancellation for active streams
+            // Cancel request - trigger cancellation for active generations
             IpcMessage::CancelRequest { request_id } => {
-                let cancelled = if let Some(cancel) = active_streams.get(&request_id.0) {
-                    cancel.cancel();
-                    true
-                } else {
-                    false
+                let cancel = active_requests.lock().await.get(&request_id).cloned();
+                let cancelled = match cancel {
+                    Some(cancel) => {
+                        cancel.cancel();
+                        true
+                    }
+                    None => false,
                 };
                 let response = IpcMessage::CancelResponse { request_id, cancelled };
-                if let Ok(bytes) = encode_message(&response) {
-                    let _ = write_frame_locked(&write_half, &bytes).await;
-                }
+                let _ = send_message(&write_half, &response).await;
             }
 
             // Non-streaming: use standard request/response processing
@@ -165,6 +174,157 @@ async fn handle_connection<S: AsyncReadExt + AsyncWriteExt + Unpin + Send + 'sta
             }
         }
     }
+
+    // Client is gone: stop any generation still running for this connection
+    for cancel in active_requests.lock().await.values() {
+        cancel.cancel();
+    }
+}
+
+/// Generation request payloads served by `spawn_generation`.
+enum GenerationRequest {
+    Prompt(InferenceRequest),
+    Chat(ChatRequest),
+}
+
+impl GenerationRequest {
+    fn request_id(&self) -> RequestId {
+        match self {
+            Self::Prompt(req) => req.request_id,
+            Self::Chat(req) => req.request_id,
+        }
+    }
+
+    fn is_streaming(&self) -> bool {
+        match self {
+            Self::Prompt(req) => req.parameters.stream,
+            Self::Chat(req) => req.parameters.stream,
+        }
+    }
+}
+
+/// Error reply in the shape the client is waiting for.
+fn error_reply(request_id: RequestId, streaming: bool, message: String) -> IpcMessage {
+    if streaming {
+        IpcMessage::StreamChunk(StreamChunk::error(request_id, message))
+    } else {
+        IpcMessage::InferenceResponse(InferenceResponse::error(request_id, message))
+    }
+}
+
+/// Start a generation for an authenticated connection, or reject with 401.
+async fn start_generation<W: AsyncWriteExt + Unpin + Send + 'static>(
+    request: GenerationRequest,
+    session: &Option<SessionToken>,
+    handler: &Arc<IpcHandler>,
+    writer: &Arc<Mutex<W>>,
+    active: &ActiveRequests,
+) {
+    if let Some(sess) = session {
+        spawn_generation(request, sess.clone(), handler, writer, active).await;
+    } else {
+        let err = r#"{"type":"error","code":401,"message":"Not authenticated"}"#;
+        let _ = write_frame_locked(writer, err.as_bytes()).await;
+    }
+}
+
+/// Register a generation request and run it on a dedicated task.
+///
+/// Rejects duplicate request IDs and enforces the per-connection request
+/// limit. The entry is removed from `active` when the request finishes.
+async fn spawn_generation<W: AsyncWriteExt + Unpin + Send + 'static>(
+    request: GenerationRequest,
+    session: SessionToken,
+    handler: &Arc<IpcHandler>,
+    writer: &Arc<Mutex<W>>,
+    active: &ActiveRequests,
+) {
+    let request_id = request.request_id();
+    let streaming = request.is_streaming();
+    let cancel = CancellationToken::new();
+
+    let rejection = {
+        let mut streams = active.lock().await;
+        if streams.contains_key(&request_id) {
+            Some(format!("request_id {} already in flight", request_id.0))
+        } else if streams.len() >= MAX_REQUESTS_PER_CONNECTION {
+            Some(format!(
+                "too many concurrent requests (max {})",
+                MAX_REQUESTS_PER_CONNECTION
+            ))
+        } else {
+            streams.insert(request_id, cancel.clone());
+            None
+        }
+    };
+    if let Some(reason) = rejection {
+        let _ = send_message(writer, &error_reply(request_id, streaming, reason)).await;
+        return;
+    }
+
+    let handler = Arc::clone(handler);
+    let writer = Arc::clone(writer);
+    let active = Arc::clone(active);
+    tokio::spawn(async move {
+        let result = run_generation(request, session, &handler, &writer, &cancel).await;
+        active.lock().await.remove(&request_id);
+
+        // Surface failures that happened before any reply could be sent
+        if let Err(e) = result {
+            if !cancel.is_cancelled() {
+                let reply = error_reply(request_id, streaming, e.to_string());
+                let _ = send_message(&writer, &reply).await;
+            }
+        }
+    });
+}
+
+/// Serve one generation request. Streams relay chunks through a bridge;
+/// non-streaming requests send a single response, which after a cancel
+/// holds the partial output.
+async fn run_generation<W: AsyncWriteExt + Unpin + Send + 'static>(
+    request: GenerationRequest,
+    session: SessionToken,
+    handler: &IpcHandler,
+    writer: &Arc<Mutex<W>>,
+    cancel: &CancellationToken,
+) -> Result<(), HandlerError> {
+    let request_id = request.request_id();
+    let bridge = IpcStreamBridge::new(Arc::clone(writer), request_id, cancel.clone());
+    let response = match request {
+        GenerationRequest::Prompt(req) if req.parameters.stream => {
+            return handler
+                .process_streaming(req, &session, &bridge, cancel.clone())
+                .await;
+        }
+        GenerationRequest::Chat(req) if req.parameters.stream => {
+            return handler
+                .process_chat_streaming(req, &session, &bridge, cancel.clone())
+                .await;
+        }
+        GenerationRequest::Prompt(req) => {
+            handler.process_inference(req, &session, cancel.clone()).await?
+        }
+        GenerationRequest::Chat(req) => {
+            handler.process_chat(req, &session, cancel.clone()).await?
+        }
+    };
+    let _ = send_message(writer, &IpcMessage::InferenceResponse(response)).await;
+    Ok(())
+}
+
+/// Encode and write a message using a locked writer.
+async fn send_message<W: AsyncWriteExt + Unpin>(
+    writer: &Arc<Mutex<W>>,
+    message: &IpcMessage,
+) -> Result<(), ServerError> {
+    match encode_message(message) {
+        Ok(bytes) => write_frame_locked(writer, &bytes).await,
+        Err(e) => {
+            eprintln!("Message encode error: {}", e);
+            Ok(())
+        }
+    }
 }
 
 /// Accept one connection, acquire a guard, and spawn a handler task.
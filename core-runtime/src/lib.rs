@@ -57,6 +57,7 @@ pub mod ffi;
 #[cfg(feature = "python")]
 pub mod python;
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
@@ -67,10 +68,11 @@ use ipc::{ConnectionConfig, ConnectionPool, IpcHandler, IpcHandlerConfig, Sessio
 use memory::{
     ContextCache, ContextCacheConfig, GpuMemory, GpuMemoryConfig, MemoryPool, MemoryPoolConfig,
 };
-use models::{ModelLoader, ModelRegistry};
+use models::{ModelLifecycle, ModelLoader, ModelRegistry};
 use scheduler::{
     BatchConfig, BatchProcessor, OutputCache, OutputCacheConfig, RequestQueue, RequestQueueConfig,
 };
+use security::SecurityConfig;
 use shutdown::ShutdownCoordinator;
 use telemetry::MetricsStore;
 use tokio::sync::Mutex;
@@ -90,6 +92,9 @@ pub struct RuntimeConfig {
     pub shutdown_timeout: Duration,
     pub output_cache: OutputCacheConfig,
     pub connections: ConnectionConfig,
+    pub security: SecurityConfig,
+    /// Draft model_id for each target model_id decoded speculatively.
+    pub draft_models: HashMap<String, String>,
 }
 
 impl Default for RuntimeConfig {
@@ -107,6 +112,8 @@ impl Default for RuntimeConfig {
             shutdown_timeout: Duration::from_secs(30),
             output_cache: OutputCacheConfig::default(),
             connections: ConnectionConfig::default(),
+            security: SecurityConfig::default(),
+            draft_models: HashMap::new(),
         }
     }
 }
@@ -120,6 +127,7 @@ pub struct Runtime {
     pub model_loader: ModelLoader,
     pub model_registry: Arc<ModelRegistry>,
     pub inference_engine: Arc<InferenceEngine>,
+    pub model_lifecycle: Arc<ModelLifecycle>,
     pub request_queue: Arc<RequestQueue>,
     pub batch_processor: BatchProcessor,
     pub ipc_handler: IpcHandler,
@@ -138,7 +146,8 @@ impl Runtime {
         let context_cache = ContextCache::new(config.context_cache.clone());
         let model_loader = ModelLoader::new(config.base_path.clone());
         let model_registry = Arc::new(ModelRegistry::new());
-        let inference_engine = InferenceEngine::new(config.max_context_length);
+        let inference_engine = InferenceEngine::new(config.max_context_length)
+            .with_draft_models(config.draft_models.clone());
         let request_queue = Arc::new(RequestQueue::new(config.request_queue.clone()));
         let batch_processor = BatchProcessor::new(config.batch.clone());
         let shutdown = Arc::new(ShutdownCoordinator::new());
@@ -149,16 +158,25 @@ impl Runtime {
 
         let session_auth = Arc::new(SessionAuth::new(&config.auth_token, config.session_timeout));
         let inference_engine = Arc::new(inference_engine);
+        let model_lifecycle = Arc::new(ModelLifecycle::new(
+            config.base_path.clone(),
+            model_registry.clone(),
+            Arc::clone(&inference_engine),
+        ));
         let ipc_handler = IpcHandler::new(
             session_auth,
             request_queue.clone(),
-            IpcHandlerConfig::default(),
+            IpcHandlerConfig {
+                security: config.security.clone(),
+                ..Default::default()
+            },
             shutdown.clone(),
             health.clone(),
             model_registry.clone(),
             metrics_store.clone(),
             Arc::clone(&inference_engine),
-        );
+        )
+        .with_model_lifecycle(Arc::clone(&model_lifecycle));
 
         Self {
             config,
@@ -168,6 +186,7 @@ impl Runtime {
             model_loader,
             model_registry,
             inference_engine,
+            model_lifecycle,
             request_queue,
             batch_processor,
             ipc_handler,
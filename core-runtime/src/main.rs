@@ -12,12 +12,16 @@
 //! - `GG-CORE health` - Full health check (exit 0/1)
 //! - `GG-CORE live` - Liveness probe (exit 0/1)
 //! - `GG-CORE ready` - Readiness probe (exit 0/1)
+//! - `GG-CORE models inspect <file>` - Read a GGUF header offline (exit 0/1)
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process::ExitCode;
 use std::time::Duration;
 
-use gg_core::cli::{get_socket_path, run_health, run_liveness, run_readiness, run_status, CliIpcClient};
+use gg_core::cli::{
+    get_socket_path, run_health, run_inspect, run_liveness, run_readiness, run_status, CliIpcClient,
+};
 use gg_core::engine::InferenceParams;
 use gg_core::ipc::server;
 use gg_core::security::fips_tests;
@@ -101,6 +105,22 @@ async fn main() -> ExitCode {
                     eprintln!("Models list not yet implemented.");
                     ExitCode::from(2u8)
                 }
+                "load" | "unload" | "swap" | "rollback" => {
+                    let code = run_models_admin(subcommand, &args[3..]).await;
+                    ExitCode::from(code as u8)
+                }
+                "inspect" => {
+                    let json_output = args[3..].iter().any(|a| a == "--json");
+                    let files: Vec<&String> =
+                        args[3..].iter().filter(|a| !a.starts_with("--")).collect();
+                    match files.as_slice() {
+                        [file] => ExitCode::from(run_inspect(file, json_output) as u8),
+                        _ => {
+                            eprintln!("Usage: GG-CORE models inspect <FILE> [--json]");
+                            ExitCode::FAILURE
+                        }
+                    }
+                }
                 _ => {
                     eprintln!("Unknown models subcommand: {}", subcommand);
                     print_command_help("models");
@@ -146,7 +166,7 @@ COMMANDS:
     ready        Readiness probe for Kubernetes (exit 0 if ready)
     status       Show system status and statistics
     verify       Verify deployment health and configuration
-    models       Manage loaded models (list, load, unload)
+    models       Manage models (list, load, unload, swap, rollback, inspect)
     config       Manage configuration (validate, show)
     version      Show version information
     help         Show this help message
@@ -167,12 +187,15 @@ EXAMPLES:
     GG-CORE ready                    # Readiness probe
     GG-CORE status                   # Show system status
     GG-CORE models list              # List loaded models
+    GG-CORE models load phi-3 models/phi-3.gguf  # Load a model
+    GG-CORE models inspect models/phi-3.gguf     # Check a model file offline
     GG-CORE config validate          # Validate configuration
     GG-CORE --socket /custom/path    # Use custom socket path
 
 ENVIRONMENT:
     VERITAS_SOCKET_PATH  IPC socket path (default: /var/run/veritas/GG-CORE.sock on Unix)
-    CORE_AUTH_TOKEN      Authentication token for server mode
+    CORE_AUTH_TOKEN      Authentication token (server mode and model admin commands)
+    CORE_DRAFT_MODELS    Speculative decoding pairs as target=draft, comma-separated
     RUST_LOG             Log level (debug, info, warn, error)
     VERITAS_ENV          Environment (development, staging, production)
 
@@ -402,20 +425,36 @@ USAGE:
     GG-CORE models <SUBCOMMAND> [OPTIONS]
 
 SUBCOMMANDS:
-    list           List loaded models
-    load <NAME>    Load a model
-    unload <NAME>  Unload a model
-    info <NAME>    Show model information
+    list                  List loaded models
+    load <ID> <PATH>      Load a model file and serve it as <ID>
+    unload <ID>           Drain and unload a model
+    swap <ID> <PATH>      Hot-swap a loaded model to a new file
+    rollback <ID>         Swap a model back to its previous version
+    info <ID>             Show model information
+    inspect <FILE>        Show a GGUF file's metadata and tensors offline
 
 OPTIONS:
-    --socket PATH  Override IPC socket path
-    --json         Output in JSON format
+    --manifest PATH       Manifest JSON for load/swap (relative to base path)
+    --drain-timeout MS    Wait for in-flight requests (default 30000)
+    --socket PATH         Override IPC socket path
+    --json                Output in JSON format
+
+Model and manifest paths are relative to the runtime base path and must
+be under its models/ directory. Admin commands authenticate with
+CORE_AUTH_TOKEN.
+
+inspect reads the file directly and needs no running server: it checks the
+GGUF header and that no tensor extends past the end of the file, then
+prints architecture, context length, quantization, tokenizer and tensor
+table. It exits 1 for files that are not valid GGUF.
 
 EXAMPLES:
     GG-CORE models list
-    GG-CORE models load llama-2-7b-chat
-    GG-CORE models info llama-2-7b-chat
-    GG-CORE models unload llama-2-7b-chat
+    GG-CORE models load llama-2-7b-chat models/llama-2-7b-chat.Q4_K_M.gguf
+    GG-CORE models swap llama-2-7b-chat models/llama-2-7b-chat.Q8_0.gguf --manifest models/llama-2-7b-chat.json
+    GG-CORE models rollback llama-2-7b-chat
+    GG-CORE models unload llama-2-7b-chat --drain-timeout 5000
+    GG-CORE models inspect models/llama-2-7b-chat.Q4_K_M.gguf --json
 "
             );
         }
@@ -459,10 +498,23 @@ fn load_config() -> RuntimeConfig {
         auth_token: std::env::var("CORE_AUTH_TOKEN").unwrap_or_default(),
         session_timeout: Duration::from_secs(3600),
         max_context_length: 4096,
+        draft_models: std::env::var("CORE_DRAFT_MODELS")
+            .map(|pairs| parse_draft_models(&pairs))
+            .unwrap_or_default(),
         ..Default::default()
     }
 }
 
+/// Parse `target=draft` pairs separated by commas, skipping malformed ones.
+fn parse_draft_models(pairs: &str) -> HashMap<String, String> {
+    pairs
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(target, draft)| (target.trim().to_string(), draft.trim().to_string()))
+        .filter(|(target, draft)| !target.is_empty() && !draft.is_empty() && target != draft)
+        .collect()
+}
+
 /// Run the inference CLI command.
 async fn run_inference(args: &[String]) -> i32 {
     let mut model_id = String::new();
@@ -544,6 +596,102 @@ async fn run_inference(args: &[String]) -> i32 {
     }
 }
 
+/// Run `models load|unload|swap|rollback` against the running server.
+async fn run_models_admin(subcommand: &str, args: &[String]) -> i32 {
+    let mut positional = Vec::new();
+    let mut manifest: Option<String> = None;
+    let mut drain_timeout: Option<Duration> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--manifest" => {
+                if i + 1 < args.len() {
+                    manifest = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("Missing value for --manifest");
+                    return 1;
+                }
+            }
+            "--drain-timeout" => match args.get(i + 1).and_then(|v| v.parse::<u64>().ok()) {
+                Some(ms) => {
+                    drain_timeout = Some(Duration::from_millis(ms));
+                    i += 2;
+                }
+                None => {
+                    eprintln!("--drain-timeout expects milliseconds");
+                    return 1;
+                }
+            },
+            arg if arg.starts_with("--") => {
+                eprintln!("Unknown argument: {}", arg);
+                return 1;
+            }
+            arg => {
+                positional.push(arg.to_string());
+                i += 1;
+            }
+        }
+    }
+
+    let needs_path = matches!(subcommand, "load" | "swap");
+    let expected = if needs_path { 2 } else { 1 };
+    if positional.len() != expected {
+        let usage = if needs_path { "<ID> <PATH> [--manifest PATH]" } else { "<ID>" };
+        eprintln!("Usage: GG-CORE models {} {} [--drain-timeout MS]", subcommand, usage);
+        return 1;
+    }
+    let model_id = positional[0].as_str();
+    let manifest = manifest.as_deref();
+
+    let mut client = CliIpcClient::new(get_socket_path())
+        // Loading and hashing large files takes a while
+        .with_timeout(Duration::from_secs(600));
+    if let Ok(token) = std::env::var("CORE_AUTH_TOKEN") {
+        client = client.with_auth_token(token);
+    }
+
+    let result = match subcommand {
+        "load" => client.load_model(model_id, &positional[1], manifest).await,
+        "swap" => {
+            client
+                .swap_model(model_id, &positional[1], manifest, drain_timeout)
+                .await
+        }
+        "rollback" => client.rollback_model(model_id, drain_timeout).await,
+        _ => client.unload_model(model_id, drain_timeout).await,
+    };
+
+    match result {
+        Ok(response) => {
+            for stage in &response.stages {
+                println!("  {:<10} {} ms", stage.stage.to_string(), stage.elapsed_ms);
+            }
+            if let Some(error) = response.error {
+                let stage = response.failed_stage.map(|s| s.to_string()).unwrap_or_default();
+                eprintln!("Error ({}): {}", stage, error);
+                return 1;
+            }
+            let done = match subcommand {
+                "load" => "loaded",
+                "swap" => "swapped",
+                "rollback" => "rolled back",
+                _ => "unloaded",
+            };
+            match response.version {
+                Some(version) => println!("{} {} (version {})", model_id, done, version),
+                None => println!("{} {}", model_id, done),
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
 async fn run_ipc_server(runtime: Runtime) -> Result<(), Box<dyn std::error::Error>> {
     let socket_path = get_socket_path();
     let handler = std::sync::Arc::new(runtime.ipc_handler);
@@ -1,7 +1,8 @@
 //! Memory management module for CORE Runtime.
 //!
 //! Provides pooled memory allocation, GPU memory tracking, context caching,
-//! arena allocation, paged KV-cache, and resource limit enforcement.
+//! arena allocation, paged KV-cache, conversation session snapshots, and
+//! resource limit enforcement.
 
 mod arena;
 mod cache;
@@ -12,6 +13,7 @@ mod limits;
 pub mod paged;
 mod pool;
 pub mod prompt_cache;
+pub mod session_kv;
 
 pub use arena::{Arena, ArenaPool, ArenaSlice};
 pub use cache::{ContextCache, ContextCacheConfig, KvCache, KvCacheEntry};
@@ -23,4 +25,5 @@ pub use kv_quant::{compute_scale, dequantize, quantize_to, Q8KvStore};
 pub use limits::{ResourceLimits, ResourceLimitsConfig};
 pub use paged::{Page, PageId, PageTable, PAGE_TOKENS};
 pub use pool::{MemoryPool, MemoryPoolConfig, PooledBuffer};
-pub use prompt_cache::{CachedKv, PromptCache};
+pub use prompt_cache::{CachedKv, PromptCache, PromptCacheConfig, PromptCacheStats};
+pub use session_kv::{reusable_prefix, SessionKv, SessionKvConfig, SessionKvStore};
@@ -1,15 +1,64 @@
 //! LRU prompt cache for repeated prefix reuse.
 //!
-//! Caches serialized KV data keyed by token sequence hash.
+//! Caches serialized KV data keyed by token sequence hash. A lookup returns
+//! the entry sharing the longest prefix with the new prompt, so a snapshot
+//! of one full prompt also serves later prompts that only share its system
+//! prompt or few-shot block.
 
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Budget and matching threshold for a prompt cache.
+#[derive(Debug, Clone)]
+pub struct PromptCacheConfig {
+    /// Maximum cached prefixes (0 disables the cache).
+    pub max_entries: usize,
+    /// Maximum total KV bytes across entries.
+    pub max_bytes: usize,
+    /// Shortest shared prefix worth restoring, in tokens.
+    pub min_prefix_tokens: usize,
+}
+
+impl Default for PromptCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 8,
+            max_bytes: 512 * 1024 * 1024,
+            min_prefix_tokens: 64,
+        }
+    }
+}
+
+/// Lookup and memory counters for a prompt cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PromptCacheStats {
+    pub entries: usize,
+    pub memory_bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Prompt tokens served from cache instead of being prefilled.
+    pub reused_tokens: u64,
+}
+
+impl PromptCacheStats {
+    /// Fraction of lookups that found a usable prefix (0.0 with no lookups).
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
 
 /// Cached KV entry with LRU tracking.
 #[derive(Debug, Clone)]
 pub struct CachedKv {
     _token_hash: [u8; 32],
-    kv_data: Vec<u8>,
+    tokens: Arc<Vec<u32>>,
+    kv_data: Arc<Vec<u8>>,
     seq_len: usize,
     last_used: u64,
 }
@@ -21,23 +70,38 @@ impl CachedKv {
     pub fn seq_len(&self) -> usize {
         self.seq_len
     }
+    /// Tokens the KV data was computed for.
+    pub fn tokens(&self) -> &[u32] {
+        &self.tokens
+    }
 }
 
 /// LRU prompt cache with hash-based lookup.
 #[derive(Debug)]
 pub struct PromptCache {
     entries: HashMap<[u8; 32], CachedKv>,
-    max_entries: usize,
+    config: PromptCacheConfig,
     access_counter: u64,
+    stats: PromptCacheStats,
 }
 
 impl PromptCache {
     /// Create a new prompt cache with given capacity.
     pub fn new(max_entries: usize) -> Self {
-        Self {
-            entries: HashMap::with_capacity(max_entries),
+        Self::with_config(PromptCacheConfig {
             max_entries,
+            max_bytes: usize::MAX,
+            min_prefix_tokens: 1,
+        })
+    }
+
+    /// Create a prompt cache with a byte budget and matching threshold.
+    pub fn with_config(config: PromptCacheConfig) -> Self {
+        Self {
+            entries: HashMap::with_capacity(config.max_entries),
+            config,
             access_counter: 0,
+            stats: PromptCacheStats::default(),
         }
     }
 
@@ -63,50 +127,81 @@ impl PromptCache {
     }
 
     /// Store computed KV for token sequence.
+    ///
+    /// Entries are evicted least recently used first to stay within the
+    /// entry and byte budget. KV data larger than the byte budget on its own
+    /// is not stored, nor are sequences shorter than `min_prefix_tokens`,
+    /// which could never be a hit.
     pub fn insert(&mut self, tokens: &[u32], kv_data: Vec<u8>, seq_len: usize) {
-        if self.entries.len() >= self.max_entries {
-            self.evict_lru();
+        if !self.accepts(tokens.len()) || kv_data.len() > self.config.max_bytes {
+            return;
         }
-
         let hash = Self::hash_tokens(tokens);
+        self.entries.remove(&hash);
+        while self.entries.len() >= self.config.max_entries
+            || self.memory_bytes() + kv_data.len() > self.config.max_bytes
+        {
+            if !self.evict_lru() {
+                break;
+            }
+        }
+
         self.access_counter += 1;
         self.entries.insert(
             hash,
             CachedKv {
                 _token_hash: hash,
-                kv_data,
+                tokens: Arc::new(tokens.to_vec()),
+                kv_data: Arc::new(kv_data),
                 seq_len,
                 last_used: self.access_counter,
             },
         );
     }
 
-    /// Find longest cached prefix of tokens. Returns (prefix_len, cloned entry).
+    /// Whether a sequence of `len` tokens would be cached, so callers can
+    /// skip serializing KV data that `insert` would drop.
+    pub fn accepts(&self, len: usize) -> bool {
+        self.config.max_entries > 0 && len >= self.config.min_prefix_tokens
+    }
+
+    /// Find the entry sharing the longest prefix with `tokens`. Returns
+    /// (shared prefix length, cloned entry).
+    ///
+    /// The entry may extend past the shared prefix; its KV data beyond that
+    /// point belongs to another prompt and must be discarded by the caller.
+    /// Prefixes shorter than `min_prefix_tokens` count as a miss.
     pub fn find_prefix(&mut self, tokens: &[u32]) -> Option<(usize, CachedKv)> {
-        for len in (1..=tokens.len()).rev() {
-            let hash = Self::hash_tokens(&tokens[..len]);
-            if self.entries.contains_key(&hash) {
-                self.access_counter += 1;
-                let counter = self.access_counter;
-                if let Some(entry) = self.entries.get_mut(&hash) {
-                    entry.last_used = counter;
-                    return Some((len, entry.clone()));
-                }
-            }
-        }
-        None
+        let best = self
+            .entries
+            .iter()
+            .map(|(hash, e)| (common_prefix(&e.tokens, tokens).min(e.seq_len), *hash))
+            .max_by_key(|&(len, _)| len)
+            .filter(|&(len, _)| len > 0 && len >= self.config.min_prefix_tokens);
+        let Some((len, hash)) = best else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.access_counter += 1;
+        let counter = self.access_counter;
+        let entry = self.entries.get_mut(&hash)?;
+        entry.last_used = counter;
+        self.stats.hits += 1;
+        self.stats.reused_tokens += len as u64;
+        Some((len, entry.clone()))
     }
 
-    /// Evict least recently used entry.
-    fn evict_lru(&mut self) {
+    /// Evict least recently used entry. Returns false if the cache is empty.
+    fn evict_lru(&mut self) -> bool {
         let oldest = self
             .entries
             .iter()
             .min_by_key(|(_, e)| e.last_used)
             .map(|(k, _)| *k);
 
-        if let Some(hash) = oldest {
-            self.entries.remove(&hash);
+        match oldest {
+            Some(hash) => self.entries.remove(&hash).is_some(),
+            None => false,
         }
     }
 
@@ -124,4 +219,18 @@ impl PromptCache {
     pub fn memory_bytes(&self) -> usize {
         self.entries.values().map(|e| e.kv_data.len()).sum()
     }
+
+    /// Lookup counters and current memory use.
+    pub fn stats(&self) -> PromptCacheStats {
+        PromptCacheStats {
+            entries: self.entries.len(),
+            memory_bytes: self.memory_bytes(),
+            ..self.stats
+        }
+    }
+}
+
+/// Number of leading tokens `a` and `b` have in common.
+fn common_prefix(a: &[u32], b: &[u32]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
 }
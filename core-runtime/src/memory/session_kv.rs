@@ -0,0 +1,123 @@
+//! Saved KV state for named conversation sessions.
+//!
+//! After each turn the backend snapshots its context state together with
+//! the tokens it holds. The next turn restores the snapshot and decodes
+//! only the tokens past the shared prefix. Snapshots expire after `ttl`
+//! and are evicted least recently used beyond the session and byte budget.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Budget for saved session state.
+#[derive(Debug, Clone)]
+pub struct SessionKvConfig {
+    /// Maximum sessions kept (0 disables session reuse).
+    pub max_sessions: usize,
+    /// Maximum total snapshot bytes across sessions.
+    pub max_bytes: usize,
+    /// Idle time after which a session is dropped.
+    pub ttl: Duration,
+}
+
+impl Default for SessionKvConfig {
+    fn default() -> Self {
+        Self {
+            max_sessions: 16,
+            max_bytes: 512 * 1024 * 1024,
+            ttl: Duration::from_secs(600), // 10 minutes
+        }
+    }
+}
+
+/// KV snapshot of one conversation.
+#[derive(Debug, Clone, Default)]
+pub struct SessionKv {
+    /// Tokens evaluated into the snapshot, in position order.
+    pub tokens: Vec<u32>,
+    /// Serialized backend context state.
+    pub state: Vec<u8>,
+}
+
+struct SessionEntry {
+    kv: SessionKv,
+    stored_at: Instant,
+}
+
+/// LRU/TTL store of conversation snapshots keyed by session name.
+///
+/// `take` removes the snapshot, so a session is only ever restored by one
+/// generation at a time; a concurrent turn on the same session prefills
+/// from scratch and the last one to finish is kept.
+pub struct SessionKvStore {
+    entries: Mutex<HashMap<String, SessionEntry>>,
+    config: SessionKvConfig,
+}
+
+impl SessionKvStore {
+    pub fn new(config: SessionKvConfig) -> Self {
+        Self { entries: Mutex::new(HashMap::new()), config }
+    }
+
+    /// Remove and return a session's snapshot if it has not expired.
+    pub fn take(&self, session: &str) -> Option<SessionKv> {
+        let entry = self.entries.lock().remove(session)?;
+        (entry.stored_at.elapsed() <= self.config.ttl).then_some(entry.kv)
+    }
+
+    /// Store a session's snapshot, evicting expired then least recently
+    /// stored sessions to stay within budget.
+    ///
+    /// A snapshot larger than `max_bytes` on its own is not kept.
+    pub fn put(&self, session: String, kv: SessionKv) {
+        if self.config.max_sessions == 0 || kv.state.len() > self.config.max_bytes {
+            return;
+        }
+        let mut entries = self.entries.lock();
+        entries.remove(&session);
+        entries.retain(|_, e| e.stored_at.elapsed() <= self.config.ttl);
+        let mut bytes: usize = entries.values().map(|e| e.kv.state.len()).sum();
+        while entries.len() >= self.config.max_sessions
+            || bytes + kv.state.len() > self.config.max_bytes
+        {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, e)| e.stored_at)
+                .map(|(k, _)| k.clone());
+            let Some(oldest) = oldest else {
+                break;
+            };
+            if let Some(evicted) = entries.remove(&oldest) {
+                bytes -= evicted.kv.state.len();
+            }
+        }
+        entries.insert(session, SessionEntry { kv, stored_at: Instant::now() });
+    }
+
+    /// Drop a session's snapshot.
+    pub fn remove(&self, session: &str) {
+        self.entries.lock().remove(session);
+    }
+
+    /// Number of stored sessions, including expired ones not yet evicted.
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+
+    /// Total snapshot bytes held.
+    pub fn memory_bytes(&self) -> usize {
+        self.entries.lock().values().map(|e| e.kv.state.len()).sum()
+    }
+}
+
+/// Length of the prefix `tokens` shares with a snapshot, capped so at least
+/// one token is left to decode (sampling needs fresh logits).
+pub fn reusable_prefix(saved: &[u32], tokens: &[u32]) -> usize {
+    let common = saved.iter().zip(tokens).take_while(|(a, b)| a == b).count();
+    common.min(tokens.len().saturating_sub(1))
+}
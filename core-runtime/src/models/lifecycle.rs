@@ -0,0 +1,788 @@
+//! Runtime model lifecycle: load, unload, hot-swap and rollback.
+//!
+//! Ties the registry, router, flight tracker and swap manager to the
+//! inference engine so models can be changed without a restart. Operations
+//! are serialized; a second one fails fast with `LifecycleError::Busy`.
+//!
+//! LoRA adapters are loaded against a routed model and follow it through
+//! swaps and rollbacks: they are loaded onto the new model before its route
+//! goes live.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::sync::{Mutex, RwLock};
+
+use super::drain::{DrainError, FlightGuard, FlightTracker};
+use super::history::{VersionHistory, VersionSource};
+use super::loader::{LoadError, ModelLoader, ModelMetadata, ModelPath};
+use super::manifest::{AdapterManifest, ModelArchitecture, ModelCapability, ModelManifest};
+use super::preload::PreloadError;
+use super::registry::{LoadedAdapterInfo, ModelHandle, ModelRegistry};
+use super::router::ModelRouter;
+use super::swap::{SwapError, SwapManager};
+use super::version::ModelVersion;
+use crate::engine::gguf::{load_gguf_model, GgufConfig, GgufMetadata, GgufPooling};
+use crate::engine::onnx::{load_onnx_model, OnnxConfig, OnnxTask};
+use crate::engine::{EngineModel, InferenceEngine, InferenceError};
+
+/// Drain timeout used when a request does not specify one.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Version recorded for models loaded without a manifest.
+const UNVERSIONED: &str = "0.0.0";
+
+/// Step of a lifecycle operation, in execution order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleStage {
+    /// Path, manifest and route checks.
+    Validate,
+    /// Hash verification and backend load of the new model.
+    Preload,
+    /// Waiting for in-flight requests on the old model.
+    Drain,
+    /// Routing `model_id` to the new model.
+    Route,
+    /// Releasing the old model.
+    Unload,
+}
+
+impl fmt::Display for LifecycleStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Validate => "validate",
+            Self::Preload => "preload",
+            Self::Drain => "drain",
+            Self::Route => "route",
+            Self::Unload => "unload",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LifecycleError {
+    #[error("Another model lifecycle operation is in progress")]
+    Busy,
+
+    #[error("Model path rejected: {0}")]
+    Path(#[from] LoadError),
+
+    #[error("Model already loaded: {0} (use swap_model to replace it)")]
+    AlreadyLoaded(String),
+
+    #[error("Model not loaded: {0}")]
+    NotLoaded(String),
+
+    #[error("No previous version to roll back to: {0}")]
+    NoPreviousVersion(String),
+
+    #[error("Preload failed: {0}")]
+    Preload(#[from] PreloadError),
+
+    #[error("Swap failed: {0}")]
+    Swap(#[from] SwapError),
+
+    #[error("Drain timed out waiting for in-flight requests")]
+    DrainTimeout,
+
+    #[error("Adapter already loaded: {0} (unload it first)")]
+    AdapterAlreadyLoaded(String),
+
+    #[error("Adapter not loaded: {0}")]
+    AdapterNotLoaded(String),
+}
+
+impl LifecycleError {
+    /// Stage the operation was in when it failed.
+    pub fn stage(&self) -> LifecycleStage {
+        match self {
+            Self::Preload(PreloadError::ManifestInvalid(_)) => LifecycleStage::Validate,
+            Self::Preload(_) | Self::Swap(SwapError::PreloadFailed(_)) => LifecycleStage::Preload,
+            Self::DrainTimeout | Self::Swap(SwapError::DrainTimeout) => LifecycleStage::Drain,
+            _ => LifecycleStage::Validate,
+        }
+    }
+}
+
+/// Time spent in one completed stage.
+#[derive(Debug, Clone, Copy)]
+pub struct StageTiming {
+    pub stage: LifecycleStage,
+    pub elapsed: Duration,
+}
+
+/// Outcome of a successful lifecycle operation.
+#[derive(Debug, Clone)]
+pub struct LifecycleReport {
+    pub model_id: String,
+    /// Handle now serving `model_id` (None after unload).
+    pub handle: Option<ModelHandle>,
+    /// Version now serving `model_id` (None after unload).
+    pub version: Option<ModelVersion>,
+    /// Completed stages in order.
+    pub stages: Vec<StageTiming>,
+}
+
+impl LifecycleReport {
+    fn new(model_id: &str) -> Self {
+        Self {
+            model_id: model_id.to_string(),
+            handle: None,
+            version: None,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Record a stage that started at `since` and return a fresh start time.
+    fn finish(&mut self, stage: LifecycleStage, since: Instant) -> Instant {
+        self.stages.push(StageTiming { stage, elapsed: since.elapsed() });
+        Instant::now()
+    }
+}
+
+/// Builds an engine model from a manifest and a validated file path.
+///
+/// Called on a blocking thread; implementations may do heavy I/O.
+pub trait ModelFactory: Send + Sync {
+    fn load(&self, manifest: &ModelManifest, path: &Path) -> Result<EngineModel, InferenceError>;
+}
+
+/// Loads GGUF and ONNX files with the compiled-in backends. GGUF models
+/// whose manifest declares `Rerank` or `Embedding` open in reranking or
+/// embedding mode, and a manifest `backend` overrides `gguf.backend`.
+#[derive(Debug, Clone, Default)]
+pub struct BackendModelFactory {
+    pub gguf: GgufConfig,
+    pub onnx: OnnxConfig,
+}
+
+impl ModelFactory for BackendModelFactory {
+    fn load(&self, manifest: &ModelManifest, path: &Path) -> Result<EngineModel, InferenceError> {
+        match manifest.architecture {
+            ModelArchitecture::Gguf => {
+                let config = GgufConfig {
+                    backend: manifest.backend.unwrap_or(self.gguf.backend),
+                    chat_template: manifest.chat_template.clone(),
+                    embedding: manifest.has_capability(ModelCapability::Embedding),
+                    rerank: manifest.has_capability(ModelCapability::Rerank),
+                    ..self.gguf.clone()
+                };
+                load_gguf_model(path, &manifest.model_id, &config).map(EngineModel::Gguf)
+            }
+            ModelArchitecture::Onnx => {
+                let task = if manifest.has_capability(ModelCapability::Rerank) {
+                    OnnxTask::Rerank
+                } else if manifest.has_capability(ModelCapability::TextClassification) {
+                    OnnxTask::Classification
+                } else {
+                    OnnxTask::Embedding
+                };
+                let config = OnnxConfig { task, ..self.onnx.clone() };
+                load_onnx_model(path, &manifest.model_id, &config).map(EngineModel::Onnx)
+            }
+            ModelArchitecture::SafeTensors => Err(InferenceError::CapabilityNotSupported(
+                "SafeTensors models cannot be served".into(),
+            )),
+        }
+    }
+}
+
+/// A model file together with the manifest it was loaded under.
+#[derive(Debug, Clone)]
+struct Deployment {
+    manifest: ModelManifest,
+    path: PathBuf,
+    version: ModelVersion,
+}
+
+/// A LoRA adapter file together with the manifest it was loaded under.
+#[derive(Debug, Clone)]
+struct AdapterDeployment {
+    manifest: AdapterManifest,
+    path: PathBuf,
+}
+
+impl AdapterDeployment {
+    fn info(&self, memory_bytes: usize) -> LoadedAdapterInfo {
+        LoadedAdapterInfo {
+            name: self.manifest.adapter_id.clone(),
+            sha256: self.manifest.sha256.clone(),
+            size_bytes: self.manifest.size_bytes,
+            memory_bytes: memory_bytes as u64,
+            loaded_at: SystemTime::now(),
+        }
+    }
+}
+
+/// Current and previous deployment of one model_id.
+struct ModelSlot {
+    current: Deployment,
+    previous: Option<Deployment>,
+    history: VersionHistory,
+    /// Adapters loaded against whichever deployment is current.
+    adapters: BTreeMap<String, AdapterDeployment>,
+}
+
+/// Loads, unloads, hot-swaps and rolls back models at runtime.
+pub struct ModelLifecycle {
+    loader: ModelLoader,
+    registry: Arc<ModelRegistry>,
+    engine: Arc<InferenceEngine>,
+    router: Arc<ModelRouter>,
+    flight_tracker: Arc<FlightTracker>,
+    swap: SwapManager,
+    factory: Arc<dyn ModelFactory>,
+    slots: RwLock<HashMap<String, ModelSlot>>,
+    operation: Mutex<()>,
+}
+
+impl ModelLifecycle {
+    /// Model and manifest paths are resolved against `base_path` and must
+    /// lie in its `models/` or `tokenizers/` directory.
+    pub fn new(
+        base_path: PathBuf,
+        registry: Arc<ModelRegistry>,
+        engine: Arc<InferenceEngine>,
+    ) -> Self {
+        let router = Arc::new(ModelRouter::new());
+        let flight_tracker = Arc::new(FlightTracker::new());
+        let swap = SwapManager::new(registry.clone(), router.clone(), flight_tracker.clone());
+        Self {
+            loader: ModelLoader::new(base_path),
+            registry,
+            engine,
+            router,
+            flight_tracker,
+            swap,
+            factory: Arc::new(BackendModelFactory::default()),
+            slots: RwLock::new(HashMap::new()),
+            operation: Mutex::new(()),
+        }
+    }
+
+    /// Replace the backend loader (custom configs or test doubles).
+    pub fn with_factory(mut self, factory: Arc<dyn ModelFactory>) -> Self {
+        self.factory = factory;
+        self
+    }
+
+    /// Count a request against the model currently routed for `model_id`
+    /// so swaps and unloads wait for it. None if the model is not managed.
+    pub async fn track(&self, model_id: &str) -> Option<FlightGuard> {
+        let handle = self.router.resolve(model_id).await?;
+        Some(self.flight_tracker.track(handle).await)
+    }
+
+    /// Version history of a managed model.
+    pub async fn history(&self, model_id: &str) -> Option<VersionHistory> {
+        self.slots.read().await.get(model_id).map(|s| s.history.clone())
+    }
+
+    /// Load a model file and route `model_id` to it.
+    ///
+    /// `manifest` is a manifest JSON path; without one the architecture is
+    /// taken from the file extension and the version is recorded as 0.0.0.
+    pub async fn load(
+        &self,
+        model_id: &str,
+        path: &str,
+        manifest: Option<&str>,
+    ) -> Result<LifecycleReport, LifecycleError> {
+        let _operation = self.operation.try_lock().map_err(|_| LifecycleError::Busy)?;
+        let mut report = LifecycleReport::new(model_id);
+        let start = Instant::now();
+
+        if self.router.has_route(model_id).await || self.engine.has_model(model_id).await {
+            return Err(LifecycleError::AlreadyLoaded(model_id.to_string()));
+        }
+        let deployment = self.resolve(model_id, path, manifest)?;
+        let start = report.finish(LifecycleStage::Validate, start);
+
+        let (deployment, model) = self.preload(deployment).await?;
+        let start = report.finish(LifecycleStage::Preload, start);
+
+        let metadata = ModelMetadata {
+            name: deployment.manifest.name.clone(),
+            size_bytes: deployment.manifest.size_bytes,
+        };
+        let format = deployment.manifest.architecture.as_str().to_string();
+        let handle = self
+            .registry
+            .register_with_format(metadata, model.memory_usage(), format)
+            .await;
+        self.engine.register(model_id.to_string(), handle, model).await;
+        self.pair_draft(model_id, &deployment.manifest).await;
+        self.router.swap_route(model_id, handle).await;
+        report.finish(LifecycleStage::Route, start);
+
+        let mut history = VersionHistory::new();
+        history.record(deployment.version.clone(), VersionSource::Manual);
+        report.handle = Some(handle);
+        report.version = Some(deployment.version.clone());
+        self.slots.write().await.insert(
+            model_id.to_string(),
+            ModelSlot { current: deployment, previous: None, history, adapters: BTreeMap::new() },
+        );
+        Ok(report)
+    }
+
+    /// Drain in-flight requests and release a model.
+    ///
+    /// On drain timeout the model stays loaded and routed.
+    pub async fn unload(
+        &self,
+        model_id: &str,
+        drain_timeout: Duration,
+    ) -> Result<LifecycleReport, LifecycleError> {
+        let _operation = self.operation.try_lock().map_err(|_| LifecycleError::Busy)?;
+        let mut report = LifecycleReport::new(model_id);
+        let start = Instant::now();
+
+        let handle = self
+            .router
+            .resolve(model_id)
+            .await
+            .ok_or_else(|| LifecycleError::NotLoaded(model_id.to_string()))?;
+        let start = report.finish(LifecycleStage::Validate, start);
+
+        if let Err(DrainError::Timeout) = self.flight_tracker.drain(handle, drain_timeout).await {
+            return Err(LifecycleError::DrainTimeout);
+        }
+        let start = report.finish(LifecycleStage::Drain, start);
+
+        self.router.remove_route(model_id).await;
+        self.engine.unregister_model(model_id).await;
+        self.registry.unregister(handle).await;
+        self.flight_tracker.remove(handle).await;
+        self.slots.write().await.remove(model_id);
+        report.finish(LifecycleStage::Unload, start);
+        Ok(report)
+    }
+
+    /// Replace a loaded model with a new file: preload, drain, swap route.
+    pub async fn swap(
+        &self,
+        model_id: &str,
+        path: &str,
+        manifest: Option<&str>,
+        drain_timeout: Duration,
+    ) -> Result<LifecycleReport, LifecycleError> {
+        let _operation = self.operation.try_lock().map_err(|_| LifecycleError::Busy)?;
+        let mut report = LifecycleReport::new(model_id);
+        let start = Instant::now();
+
+        if !self.router.has_route(model_id).await {
+            return Err(LifecycleError::NotLoaded(model_id.to_string()));
+        }
+        let deployment = self.resolve(model_id, path, manifest)?;
+        let start = report.finish(LifecycleStage::Validate, start);
+
+        self.replace(model_id, deployment, VersionSource::HotSwap, drain_timeout, &mut report, start)
+            .await?;
+        Ok(report)
+    }
+
+    /// Swap back to the deployment that was active before the last load
+    /// change. Rolling back twice returns to the newer version.
+    pub async fn rollback(
+        &self,
+        model_id: &str,
+        drain_timeout: Duration,
+    ) -> Result<LifecycleReport, LifecycleError> {
+        let _operation = self.operation.try_lock().map_err(|_| LifecycleError::Busy)?;
+        let mut report = LifecycleReport::new(model_id);
+        let start = Instant::now();
+
+        let previous = {
+            let slots = self.slots.read().await;
+            let slot = slots
+                .get(model_id)
+                .ok_or_else(|| LifecycleError::NotLoaded(model_id.to_string()))?;
+            slot.previous
+                .clone()
+                .ok_or_else(|| LifecycleError::NoPreviousVersion(model_id.to_string()))?
+        };
+        let start = report.finish(LifecycleStage::Validate, start);
+
+        self.replace(model_id, previous, VersionSource::Rollback, drain_timeout, &mut report, start)
+            .await?;
+        Ok(report)
+    }
+
+    /// Load a LoRA adapter file against the model routed for `model_id` so
+    /// requests can select it as `adapter_id` without reloading the base
+    /// weights.
+    ///
+    /// `manifest` is an adapter manifest JSON path; without one the hash is
+    /// computed and recorded.
+    pub async fn load_adapter(
+        &self,
+        model_id: &str,
+        adapter_id: &str,
+        path: &str,
+        manifest: Option<&str>,
+    ) -> Result<LifecycleReport, LifecycleError> {
+        let _operation = self.operation.try_lock().map_err(|_| LifecycleError::Busy)?;
+        let mut report = LifecycleReport::new(model_id);
+        let start = Instant::now();
+
+        let (handle, base) = self.routed(model_id).await?;
+        let adapter = self.resolve_adapter(model_id, adapter_id, path, manifest)?;
+        let slots = self.slots.read().await;
+        if slots.get(model_id).is_some_and(|s| s.adapters.contains_key(adapter_id)) {
+            return Err(LifecycleError::AdapterAlreadyLoaded(adapter_id.to_string()));
+        }
+        drop(slots);
+        let start = report.finish(LifecycleStage::Validate, start);
+
+        let model = self
+            .engine
+            .model(model_id)
+            .await
+            .ok_or_else(|| LifecycleError::NotLoaded(model_id.to_string()))?;
+        let (adapter, memory) = preload_adapter(model, &base, adapter).await?;
+        self.registry.register_adapter(handle, adapter.info(memory)).await;
+        if let Some(slot) = self.slots.write().await.get_mut(model_id) {
+            slot.adapters.insert(adapter_id.to_string(), adapter);
+        }
+        report.finish(LifecycleStage::Preload, start);
+
+        report.handle = Some(handle);
+        report.version = Some(base.version);
+        Ok(report)
+    }
+
+    /// Release a LoRA adapter of the model routed for `model_id`. Requests
+    /// already using it finish with it.
+    pub async fn unload_adapter(
+        &self,
+        model_id: &str,
+        adapter_id: &str,
+    ) -> Result<LifecycleReport, LifecycleError> {
+        let _operation = self.operation.try_lock().map_err(|_| LifecycleError::Busy)?;
+        let mut report = LifecycleReport::new(model_id);
+        let start = Instant::now();
+
+        let (handle, base) = self.routed(model_id).await?;
+        let removed = self
+            .slots
+            .write()
+            .await
+            .get_mut(model_id)
+            .and_then(|slot| slot.adapters.remove(adapter_id));
+        if removed.is_none() {
+            return Err(LifecycleError::AdapterNotLoaded(adapter_id.to_string()));
+        }
+        let start = report.finish(LifecycleStage::Validate, start);
+
+        if let Some(model) = self.engine.model(model_id).await {
+            model.unload_adapter(adapter_id);
+        }
+        self.registry.unregister_adapter(handle, adapter_id).await;
+        report.finish(LifecycleStage::Unload, start);
+
+        report.handle = Some(handle);
+        report.version = Some(base.version);
+        Ok(report)
+    }
+
+    /// Handle and current deployment of a routed, managed model.
+    async fn routed(&self, model_id: &str) -> Result<(ModelHandle, Deployment), LifecycleError> {
+        let not_loaded = || LifecycleError::NotLoaded(model_id.to_string());
+        let handle = self.router.resolve(model_id).await.ok_or_else(not_loaded)?;
+        let slots = self.slots.read().await;
+        let slot = slots.get(model_id).ok_or_else(not_loaded)?;
+        Ok((handle, slot.current.clone()))
+    }
+
+    /// Pair `model_id` with the draft model its manifest declares. Without
+    /// one, any configured pairing stays in place.
+    async fn pair_draft(&self, model_id: &str, manifest: &ModelManifest) {
+        if let Some(draft) = &manifest.draft_model {
+            self.engine.set_draft_model(model_id, Some(draft.clone())).await;
+        }
+    }
+
+    /// Shared preload → drain → route swap path for swap and rollback.
+    async fn replace(
+        &self,
+        model_id: &str,
+        deployment: Deployment,
+        source: VersionSource,
+        drain_timeout: Duration,
+        report: &mut LifecycleReport,
+        start: Instant,
+    ) -> Result<(), LifecycleError> {
+        let (deployment, model) = self.preload(deployment).await?;
+        // Requests may select any loaded adapter as soon as the route moves
+        let adapters = {
+            let slots = self.slots.read().await;
+            slots.get(model_id).map(|s| s.adapters.clone()).unwrap_or_default()
+        };
+        let mut reloaded = Vec::new();
+        for adapter in adapters.into_values() {
+            reloaded.push(preload_adapter(model.clone(), &deployment, adapter).await?);
+        }
+        report.finish(LifecycleStage::Preload, start);
+
+        let result = self
+            .swap
+            .execute_swap(model_id, deployment.manifest.clone(), drain_timeout)
+            .await?;
+        report.stages.push(StageTiming {
+            stage: LifecycleStage::Drain,
+            elapsed: result.drain_duration,
+        });
+
+        // The route now points at the new handle; move the engine over so
+        // lookups by model_id reach the new weights. In-flight requests hold
+        // their own reference, so the old weights are freed when they finish.
+        let start = Instant::now();
+        self.engine.register(model_id.to_string(), result.new_handle, model).await;
+        self.pair_draft(model_id, &deployment.manifest).await;
+        for (adapter, memory) in &reloaded {
+            self.registry.register_adapter(result.new_handle, adapter.info(*memory)).await;
+        }
+        report.finish(LifecycleStage::Route, start);
+
+        report.handle = Some(result.new_handle);
+        report.version = Some(deployment.version.clone());
+        let mut slots = self.slots.write().await;
+        if let Some(slot) = slots.get_mut(model_id) {
+            slot.history.record(deployment.version.clone(), source);
+            slot.previous = Some(std::mem::replace(&mut slot.current, deployment));
+        }
+        Ok(())
+    }
+
+    /// Validate paths and read (or derive) the manifest.
+    fn resolve(
+        &self,
+        model_id: &str,
+        path: &str,
+        manifest: Option<&str>,
+    ) -> Result<Deployment, LifecycleError> {
+        let model_path = self.loader.validate_path(path)?;
+        let manifest = match manifest {
+            Some(manifest_path) => {
+                let manifest_path = self.loader.validate_path(manifest_path)?;
+                ModelManifest::from_file(manifest_path.as_path())
+                    .and_then(|m| m.validate().map(|_| m))
+                    .map_err(|e| PreloadError::ManifestInvalid(e.to_string()))?
+            }
+            None => self.derive_manifest(model_id, &model_path)?,
+        };
+        if manifest.model_id != model_id {
+            return Err(PreloadError::ManifestInvalid(format!(
+                "manifest is for '{}', not '{}'",
+                manifest.model_id, model_id
+            ))
+            .into());
+        }
+        let version = ModelVersion::parse(&manifest.version)
+            .map_err(|e| PreloadError::ManifestInvalid(e.to_string()))?;
+
+        Ok(Deployment {
+            manifest,
+            path: model_path.as_path().to_path_buf(),
+            version,
+        })
+    }
+
+    /// Validate the adapter path and read (or derive) its manifest.
+    fn resolve_adapter(
+        &self,
+        model_id: &str,
+        adapter_id: &str,
+        path: &str,
+        manifest: Option<&str>,
+    ) -> Result<AdapterDeployment, LifecycleError> {
+        let adapter_path = self.loader.validate_path(path)?;
+        let manifest = match manifest {
+            Some(manifest_path) => {
+                let manifest_path = self.loader.validate_path(manifest_path)?;
+                AdapterManifest::from_file(manifest_path.as_path())
+                    .map_err(|e| PreloadError::ManifestInvalid(e.to_string()))?
+            }
+            None => AdapterManifest {
+                adapter_id: adapter_id.to_string(),
+                base_model: model_id.to_string(),
+                sha256: String::new(),
+                size_bytes: 0,
+            },
+        };
+        manifest
+            .validate()
+            .map_err(|e| PreloadError::ManifestInvalid(e.to_string()))?;
+        if manifest.adapter_id != adapter_id {
+            return Err(PreloadError::ManifestInvalid(format!(
+                "manifest is for adapter '{}', not '{}'",
+                manifest.adapter_id, adapter_id
+            ))
+            .into());
+        }
+        if manifest.base_model != model_id {
+            return Err(PreloadError::ManifestInvalid(format!(
+                "adapter '{}' is for '{}', not '{}'",
+                adapter_id, manifest.base_model, model_id
+            ))
+            .into());
+        }
+        Ok(AdapterDeployment {
+            manifest,
+            path: adapter_path.as_path().to_path_buf(),
+        })
+    }
+
+    /// Minimal manifest for a bare model file. The hash is filled in during
+    /// preload.
+    fn derive_manifest(
+        &self,
+        model_id: &str,
+        model_path: &ModelPath,
+    ) -> Result<ModelManifest, LifecycleError> {
+        let path = model_path.as_path();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let (architecture, capability) = match extension.to_ascii_lowercase().as_str() {
+            "gguf" => (ModelArchitecture::Gguf, gguf_capability(path)),
+            "onnx" => (ModelArchitecture::Onnx, ModelCapability::Embedding),
+            _ => {
+                return Err(PreloadError::ManifestInvalid(format!(
+                    "cannot infer model format of {}; provide a manifest",
+                    path.display()
+                ))
+                .into())
+            }
+        };
+        let metadata = self.loader.load_metadata(model_path)?;
+        Ok(ModelManifest {
+            model_id: model_id.to_string(),
+            name: metadata.name,
+            version: UNVERSIONED.to_string(),
+            capabilities: vec![capability],
+            sha256: String::new(),
+            size_bytes: metadata.size_bytes,
+            architecture,
+            license: String::new(),
+            chat_template: None,
+            draft_model: None,
+            backend: None,
+        })
+    }
+
+    /// Verify the file hash and load the model on a blocking thread.
+    async fn preload(
+        &self,
+        mut deployment: Deployment,
+    ) -> Result<(Deployment, EngineModel), LifecycleError> {
+        let factory = Arc::clone(&self.factory);
+        let task = tokio::task::spawn_blocking(move || {
+            // Reject malformed or truncated files before hashing them
+            deployment
+                .manifest
+                .validate_file(&deployment.path)
+                .map_err(|e| PreloadError::ValidationFailed(e.to_string()))?;
+            verify_hash(&deployment.path, &mut deployment.manifest.sha256)?;
+            let model = factory
+                .load(&deployment.manifest, &deployment.path)
+                .map_err(|e| PreloadError::LoadFailed(e.to_string()))?;
+            Ok::<_, PreloadError>((deployment, model))
+        });
+        let result = task
+            .await
+            .map_err(|e| PreloadError::LoadFailed(e.to_string()))?;
+        Ok(result?)
+    }
+}
+
+/// Check an adapter file against its manifest and the base model's file,
+/// verify its hash and load it into `model` on a blocking thread. Returns
+/// the adapter with its hash and size filled in, and the memory it uses.
+async fn preload_adapter(
+    model: EngineModel,
+    base: &Deployment,
+    mut adapter: AdapterDeployment,
+) -> Result<(AdapterDeployment, usize), LifecycleError> {
+    if base.manifest.architecture != ModelArchitecture::Gguf {
+        return Err(PreloadError::ValidationFailed(
+            "LoRA adapters apply to GGUF models only".into(),
+        )
+        .into());
+    }
+    let base_path = base.path.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        let header = adapter
+            .manifest
+            .validate_file(&adapter.path)
+            .map_err(|e| PreloadError::ValidationFailed(e.to_string()))?;
+        let base_header = GgufMetadata::read(&base_path)
+            .map_err(|e| PreloadError::ValidationFailed(e.to_string()))?;
+        if header.architecture() != base_header.architecture() {
+            return Err(PreloadError::ValidationFailed(format!(
+                "adapter is for {} models, not {}",
+                header.architecture().unwrap_or("unknown"),
+                base_header.architecture().unwrap_or("unknown")
+            )));
+        }
+        verify_hash(&adapter.path, &mut adapter.manifest.sha256)?;
+        adapter.manifest.size_bytes = header.file_size;
+        let memory = model
+            .load_adapter(&adapter.manifest.adapter_id, &adapter.path)
+            .map_err(|e| PreloadError::LoadFailed(e.to_string()))?;
+        Ok((adapter, memory))
+    });
+    let result = task
+        .await
+        .map_err(|e| PreloadError::LoadFailed(e.to_string()))?;
+    Ok(result?)
+}
+
+/// Capability of a GGUF file loaded without a manifest: embedding models
+/// declare how they pool token states, rerankers declare rank pooling and
+/// generators declare neither.
+fn gguf_capability(path: &Path) -> ModelCapability {
+    let pooling = GgufMetadata::read(path).ok().and_then(|header| header.pooling_type());
+    match pooling {
+        Some(GgufPooling::Mean | GgufPooling::Cls | GgufPooling::Last) => {
+            ModelCapability::Embedding
+        }
+        Some(GgufPooling::Rank) => ModelCapability::Rerank,
+        _ => ModelCapability::TextGeneration,
+    }
+}
+
+/// Check a file against the hash its manifest declares, or record the hash
+/// when none is declared.
+fn verify_hash(path: &Path, expected: &mut String) -> Result<(), PreloadError> {
+    let actual = sha256_file(path).map_err(|e| PreloadError::LoadFailed(e.to_string()))?;
+    if expected.is_empty() {
+        *expected = actual;
+    } else if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(PreloadError::HashMismatch { expected: expected.clone(), actual });
+    }
+    Ok(())
+}
+
+/// Hex SHA-256 of a file, read in chunks.
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 use crate::engine::error::InferenceError;
+use crate::engine::gguf::{GgufBackend, GgufMetadata};
+use crate::engine::MAX_ADAPTER_NAME_BYTES;
 
 /// Model metadata from manifest.json file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +28,35 @@ pub struct ModelManifest {
     pub architecture: ModelArchitecture,
     /// License identifier (SPDX).
     pub license: String,
+    /// Chat template override for generation models: a built-in name
+    /// ("llama3", "chatml", "phi3") or a Jinja template. When absent, the
+    /// GGUF `tokenizer.chat_template` metadata is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_template: Option<String>,
+    /// Model ID of a draft model for speculative decoding of this
+    /// generation model. It must share this model's tokenizer; requests are
+    /// decoded normally while it is not loaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub draft_model: Option<String>,
+    /// Backend for a GGUF generation model: "llama_cpp" or "native". When
+    /// absent, the runtime's configured backend is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<GgufBackend>,
+}
+
+/// LoRA adapter metadata from an adapter manifest JSON file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdapterManifest {
+    /// Name requests select the adapter by (e.g., "support-tone").
+    pub adapter_id: String,
+    /// Model ID of the base model the adapter is loaded against.
+    pub base_model: String,
+    /// SHA-256 hash of the adapter file; computed at load when empty.
+    #[serde(default)]
+    pub sha256: String,
+    /// Size in bytes on disk; not checked when 0.
+    #[serde(default)]
+    pub size_bytes: u64,
 }
 
 /// What a model can do.
@@ -36,6 +67,7 @@ pub enum ModelCapability {
     TextGeneration,
     Embedding,
     NamedEntityRecognition,
+    Rerank,
 }
 
 /// Model file format.
@@ -50,6 +82,17 @@ pub enum ModelArchitecture {
     SafeTensors,
 }
 
+impl ModelArchitecture {
+    /// Lowercase format name, as shown in model listings.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModelArchitecture::Gguf => "gguf",
+            ModelArchitecture::Onnx => "onnx",
+            ModelArchitecture::SafeTensors => "safetensors",
+        }
+    }
+}
+
 impl ModelManifest {
     /// Load manifest from a JSON file.
     pub fn from_file(path: &Path) -> Result<Self, InferenceError> {
@@ -81,6 +124,42 @@ impl ModelManifest {
                 "capabilities cannot be empty".into(),
             ));
         }
+        if matches!(&self.chat_template, Some(t) if t.trim().is_empty()) {
+            return Err(InferenceError::ModelError(
+                "chat_template cannot be empty when set".into(),
+            ));
+        }
+        if matches!(&self.draft_model, Some(d) if d.is_empty() || *d == self.model_id) {
+            return Err(InferenceError::ModelError(
+                "draft_model must name another model".into(),
+            ));
+        }
+        if self.backend.is_some() && self.architecture != ModelArchitecture::Gguf {
+            return Err(InferenceError::ModelError(
+                "backend can only be set for GGUF models".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validate the model file at `path` against the manifest without
+    /// loading it. A GGUF file must have a readable header whose tensors fit
+    /// in the file, and must match `size_bytes` when that is set.
+    pub fn validate_file(&self, path: &Path) -> Result<(), InferenceError> {
+        if self.architecture != ModelArchitecture::Gguf {
+            return Ok(());
+        }
+        let header = GgufMetadata::read(path).map_err(|e| {
+            InferenceError::ModelError(format!("invalid GGUF file {}: {}", path.display(), e))
+        })?;
+        if self.size_bytes != 0 && self.size_bytes != header.file_size {
+            return Err(InferenceError::ModelError(format!(
+                "size_bytes is {} but {} has {} bytes",
+                self.size_bytes,
+                path.display(),
+                header.file_size
+            )));
+        }
         Ok(())
     }
 
@@ -89,3 +168,62 @@ impl ModelManifest {
         self.capabilities.contains(&cap)
     }
 }
+
+impl AdapterManifest {
+    /// Load an adapter manifest from a JSON file.
+    pub fn from_file(path: &Path) -> Result<Self, InferenceError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            InferenceError::ModelError(format!("failed to read adapter manifest: {}", e))
+        })?;
+        Self::from_json(&content)
+    }
+
+    /// Parse an adapter manifest from a JSON string.
+    pub fn from_json(json: &str) -> Result<Self, InferenceError> {
+        serde_json::from_str(json).map_err(|e| {
+            InferenceError::ModelError(format!("invalid adapter manifest JSON: {}", e))
+        })
+    }
+
+    /// Validate manifest fields for correctness.
+    pub fn validate(&self) -> Result<(), InferenceError> {
+        if self.adapter_id.is_empty() || self.adapter_id.len() > MAX_ADAPTER_NAME_BYTES {
+            return Err(InferenceError::ModelError(format!(
+                "adapter_id must be 1 to {} bytes",
+                MAX_ADAPTER_NAME_BYTES
+            )));
+        }
+        if self.base_model.is_empty() {
+            return Err(InferenceError::ModelError("base_model cannot be empty".into()));
+        }
+        if !self.sha256.is_empty() && self.sha256.len() != 64 {
+            return Err(InferenceError::ModelError(
+                "sha256 must be 64 hex characters when set".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validate the adapter file at `path` without loading it: a GGUF LoRA
+    /// adapter matching `size_bytes` when that is set. Returns its header.
+    pub fn validate_file(&self, path: &Path) -> Result<GgufMetadata, InferenceError> {
+        let header = GgufMetadata::read(path).map_err(|e| {
+            InferenceError::ModelError(format!("invalid GGUF file {}: {}", path.display(), e))
+        })?;
+        if header.adapter_type() != Some("lora") {
+            return Err(InferenceError::ModelError(format!(
+                "{} is not a LoRA adapter",
+                path.display()
+            )));
+        }
+        if self.size_bytes != 0 && self.size_bytes != header.file_size {
+            return Err(InferenceError::ModelError(format!(
+                "size_bytes is {} but {} has {} bytes",
+                self.size_bytes,
+                path.display(),
+                header.file_size
+            )));
+        }
+        Ok(header)
+    }
+}
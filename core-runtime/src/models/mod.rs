@@ -8,6 +8,7 @@ pub mod smart_loader;
 pub mod tier_synergy;
 
 mod drain;
+mod lifecycle;
 mod loader;
 mod preload;
 pub mod registry;
@@ -22,13 +23,19 @@ pub mod version;
 
 pub use drain::{DrainError, FlightGuard, FlightTracker};
 pub use history::{VersionHistory, VersionHistoryEntry, VersionSource};
+pub use lifecycle::{
+    BackendModelFactory, LifecycleError, LifecycleReport, LifecycleStage, ModelFactory,
+    ModelLifecycle, StageTiming, DEFAULT_DRAIN_TIMEOUT,
+};
 pub use loader::{LoadError, MappedModel, ModelLoader, ModelMetadata, ModelPath};
-pub use manifest::{ModelArchitecture, ModelCapability, ModelManifest};
+pub use manifest::{AdapterManifest, ModelArchitecture, ModelCapability, ModelManifest};
 pub use persistence::{PersistenceError, PersistedModel, RegistryPersistence, RegistryState};
 pub use pool::{ModelPool, PoolConfig, PoolError, PoolMetrics, PoolStatus, SwitchResult};
 pub use pool::ModelTier as PoolModelTier;
 pub use preload::{ModelPreloader, PreloadError, PreloadedModel};
-pub use registry::{LoadedModelInfo, LoadedModelState, ModelHandle, ModelRegistry};
+pub use registry::{
+    LoadedAdapterInfo, LoadedModelInfo, LoadedModelState, ModelHandle, ModelRegistry,
+};
 pub use router::{ModelRouter, RouterError};
 pub use search::{ModelQuery, ModelQueryBuilder, ModelSearchResult};
 pub use smart_loader::{LoadHint, SmartLoader, SmartLoaderConfig, SmartLoaderError, SmartLoaderMetrics, SmartLoaderStatus};
@@ -431,13 +431,14 @@ mod tests {
             ModelCapability::TextGeneration,
             ModelCapability::Embedding,
             ModelCapability::NamedEntityRecognition,
+            ModelCapability::Rerank,
         ];
 
         let model = PersistedModel {
             model_id: "test".to_string(),
             path: PathBuf::from("/test.bin"),
             version: ModelVersion::new(1, 0, 0),
-            capabilities,
+            capabilities: capabilities.clone(),
             architecture: ModelArchitecture::Gguf,
             auto_load: true,
             history: VersionHistory::new(),
@@ -445,6 +446,6 @@ mod tests {
 
         let json = serde_json::to_string(&model).unwrap();
         let deserialized: PersistedModel = serde_json::from_str(&json).unwrap();
-        assert_eq!(deserialized.capabilities.len(), 4);
+        assert_eq!(deserialized.capabilities, capabilities);
     }
 }
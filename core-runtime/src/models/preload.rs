@@ -48,7 +48,11 @@ impl ModelPreloader {
             size_bytes: manifest.size_bytes,
         };
 
-        let handle = self.registry.register(metadata, manifest.size_bytes as usize).await;
+        let format = manifest.architecture.as_str().to_string();
+        let handle = self
+            .registry
+            .register_with_format(metadata, manifest.size_bytes as usize, format)
+            .await;
 
         Ok(PreloadedModel { handle, manifest })
     }
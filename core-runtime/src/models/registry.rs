@@ -55,6 +55,19 @@ pub struct LoadedModelInfo {
     pub request_count: u64,
     pub total_latency_ms: f64,
     pub loaded_at: SystemTime,
+    /// LoRA adapters loaded against the model, by name.
+    pub adapters: Vec<LoadedAdapterInfo>,
+}
+
+/// A LoRA adapter loaded against a registered model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedAdapterInfo {
+    pub name: String,
+    /// SHA-256 of the adapter file, hex encoded.
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub memory_bytes: u64,
+    pub loaded_at: SystemTime,
 }
 
 struct LoadedModel {
@@ -65,6 +78,15 @@ struct LoadedModel {
     request_count: AtomicU64,
     total_latency_ms: std::sync::atomic::AtomicU64,
     loaded_at: SystemTime,
+    adapters: Vec<LoadedAdapterInfo>,
+}
+
+impl LoadedModel {
+    /// Memory of the model and its adapters.
+    fn total_memory(&self) -> usize {
+        let adapters: u64 = self.adapters.iter().map(|a| a.memory_bytes).sum();
+        self.memory_bytes + adapters as usize
+    }
 }
 
 /// Thread-safe registry of loaded models.
@@ -104,6 +126,7 @@ impl ModelRegistry {
             request_count: AtomicU64::new(0),
             total_latency_ms: AtomicU64::new(0),
             loaded_at: SystemTime::now(),
+            adapters: Vec::new(),
         };
         self.models.write().await.insert(handle, model);
 
@@ -120,14 +143,42 @@ impl ModelRegistry {
         self.models.read().await.get(&handle).map(|m| m.metadata.clone())
     }
 
-    /// Remove a model from the registry.
+    /// Remove a model from the registry. Returns the memory it and its
+    /// adapters used.
     pub async fn unregister(&self, handle: ModelHandle) -> Option<usize> {
-        self.models.write().await.remove(&handle).map(|m| m.memory_bytes)
+        self.models.write().await.remove(&handle).map(|m| m.total_memory())
+    }
+
+    /// Record an adapter loaded against a model, replacing any of the same
+    /// name. False if the handle is not registered.
+    pub async fn register_adapter(&self, handle: ModelHandle, adapter: LoadedAdapterInfo) -> bool {
+        let mut models = self.models.write().await;
+        let Some(model) = models.get_mut(&handle) else {
+            return false;
+        };
+        model.adapters.retain(|a| a.name != adapter.name);
+        model.adapters.push(adapter);
+        model.adapters.sort_by(|a, b| a.name.cmp(&b.name));
+        true
+    }
+
+    /// Forget an adapter of a model. Returns the memory it used.
+    pub async fn unregister_adapter(&self, handle: ModelHandle, name: &str) -> Option<usize> {
+        let mut models = self.models.write().await;
+        let adapters = &mut models.get_mut(&handle)?.adapters;
+        let index = adapters.iter().position(|a| a.name == name)?;
+        Some(adapters.remove(index).memory_bytes as usize)
+    }
+
+    /// Adapters loaded against a model, by name.
+    pub async fn adapters(&self, handle: ModelHandle) -> Vec<LoadedAdapterInfo> {
+        let models = self.models.read().await;
+        models.get(&handle).map(|m| m.adapters.clone()).unwrap_or_default()
     }
 
-    /// Total memory used by all registered models.
+    /// Total memory used by all registered models and their adapters.
     pub async fn total_memory(&self) -> usize {
-        self.models.read().await.values().map(|m| m.memory_bytes).sum()
+        self.models.read().await.values().map(LoadedModel::total_memory).sum()
     }
 
     /// Number of loaded models.
@@ -150,6 +201,7 @@ impl ModelRegistry {
                 request_count: model.request_count.load(Ordering::Relaxed),
                 total_latency_ms: f64::from_bits(model.total_latency_ms.load(Ordering::Relaxed)),
                 loaded_at: model.loaded_at,
+                adapters: model.adapters.clone(),
             })
             .collect()
     }
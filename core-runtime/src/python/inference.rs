@@ -101,6 +101,7 @@ impl From<&InferenceParams> for RustParams {
             top_k: py.top_k as usize,
             stream: py.stream,
             timeout_ms: py.timeout_ms,
+            ..Default::default()
         }
     }
 }
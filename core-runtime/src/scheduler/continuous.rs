@@ -120,6 +120,59 @@ impl ContinuousBatcher {
         admitted
     }
 
+    /// Admit pending requests in arrival order while their prompts fit in
+    /// `token_budget`, each into the free slot `score` rates highest for it
+    /// (the lowest index on ties). Admission stops at the first request that
+    /// does not fit, so later ones cannot overtake it.
+    pub fn admit_pending_by<F>(
+        &mut self,
+        mut token_budget: usize,
+        score: F,
+    ) -> Vec<(usize, PendingRequest)>
+    where
+        F: Fn(usize, &PendingRequest) -> usize,
+    {
+        let mut admitted = Vec::new();
+        while let Some(req) = self.pending.front() {
+            if req.prompt_tokens.len() > token_budget {
+                break;
+            }
+            let free = self.slots.iter().enumerate().filter(|(_, s)| s.is_none());
+            let Some(idx) = free
+                .map(|(idx, _)| (idx, score(idx, req)))
+                .reduce(|best, next| if next.1 > best.1 { next } else { best })
+                .map(|(idx, _)| idx)
+            else {
+                break;
+            };
+            let Some(req) = self.pending.pop_front() else {
+                break;
+            };
+            token_budget -= req.prompt_tokens.len();
+            self.slots[idx] =
+                Some(BatchSlot::new(req.request_id, req.prompt_tokens.len(), req.max_tokens));
+            admitted.push((idx, req));
+        }
+        admitted
+    }
+
+    /// Remove the pending requests `expired` selects, keeping the rest in
+    /// arrival order. Returns the removed requests.
+    pub fn remove_pending<F>(&mut self, mut expired: F) -> Vec<PendingRequest>
+    where
+        F: FnMut(&PendingRequest) -> bool,
+    {
+        let mut removed = Vec::new();
+        self.pending.retain(|req| {
+            let drop = expired(req);
+            if drop {
+                removed.push(req.clone());
+            }
+            !drop
+        });
+        removed
+    }
+
     /// Evict completed requests, freeing slots.
     pub fn evict_completed(&mut self) -> Vec<RequestId> {
         let mut evicted = Vec::new();
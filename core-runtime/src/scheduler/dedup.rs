@@ -7,7 +7,7 @@ use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
-use crate::engine::InferenceParams;
+use crate::engine::{InferenceParams, DEFAULT_SEED};
 
 /// Cached output for a completed request.
 #[derive(Debug, Clone)]
@@ -58,6 +58,33 @@ impl OutputCache {
         hasher.update(params.temperature.to_le_bytes());
         hasher.update(params.top_p.to_le_bytes());
         hasher.update(params.top_k.to_le_bytes());
+        hasher.update(params.seed.unwrap_or(DEFAULT_SEED).to_le_bytes());
+        for value in [
+            params.repetition_penalty,
+            params.presence_penalty,
+            params.frequency_penalty,
+            params.min_p,
+            params.typical_p,
+            params.mirostat_tau,
+            params.mirostat_eta,
+        ] {
+            hasher.update(value.to_le_bytes());
+        }
+        hasher.update(params.penalty_last_n.to_le_bytes());
+        hasher.update([params.mirostat, params.context_strategy as u8]);
+        for (token, bias) in &params.logit_bias {
+            hasher.update(token.to_le_bytes());
+            hasher.update(bias.to_le_bytes());
+        }
+        // Tagged so a grammar and a schema with the same text differ
+        if let Some(grammar) = &params.grammar {
+            hasher.update([1]);
+            hasher.update(grammar.as_bytes());
+        }
+        if let Some(schema) = &params.json_schema {
+            hasher.update([2]);
+            hasher.update(schema.to_string().as_bytes());
+        }
         hasher.finalize().into()
     }
 
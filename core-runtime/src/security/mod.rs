@@ -14,6 +14,7 @@ pub mod fips_tests;
 pub mod key_rotation;
 pub mod output_sanitizer;
 pub mod pii_detector;
+pub mod pipeline;
 pub mod prompt_injection;
 
 pub use audit::{AuditCategory, AuditEvent, AuditLogger, AuditSeverity};
@@ -22,6 +23,7 @@ pub use fips_tests::{run_power_on_self_tests, SelfTestError, SelfTestResults};
 pub use key_rotation::{KeyRotationError, KeyRotationManager};
 pub use output_sanitizer::OutputSanitizer;
 pub use pii_detector::{PIIDetector, PIIMatch};
+pub use pipeline::{SecurityPipeline, SecurityReport, SecurityStreamState};
 pub use prompt_injection::{InjectionMatch, PromptInjectionFilter};
 
 /// Security configuration
@@ -35,6 +37,8 @@ pub struct SecurityConfig {
     pub enable_pii_detection: bool,
     /// Redact PII in outputs
     pub redact_pii: bool,
+    /// Apply content filtering and length limits to outputs
+    pub sanitize_output: bool,
     /// Enable model encryption
     pub enable_model_encryption: bool,
     /// Encryption key (if None, generates from machine ID)
@@ -48,6 +52,7 @@ impl Default for SecurityConfig {
             block_prompt_injection: true,
             enable_pii_detection: true,
             redact_pii: true,
+            sanitize_output: true,
             enable_model_encryption: false,
             encryption_key: None,
         }
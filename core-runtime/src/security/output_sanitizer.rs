@@ -3,8 +3,32 @@
 //! Sanitizes model outputs for security and safety.
 //! Combines PII detection, content filtering, and format validation.
 
-use crate::security::{PIIDetector, pii_detector::PIIType};
+use crate::security::{PIIDetector, pii_detector::{PIIMatch, PIIType}};
 use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
+
+/// Maximum length of any PII we might detect (conservative estimate)
+pub(crate) const MAX_PII_LENGTH: usize = 100;
+
+/// Content patterns to filter (basic harmful content markers)
+const CONTENT_PATTERNS: [(&str, &str); 4] = [
+    // Self-harm indicators (replace with resources)
+    ("I want to kill myself", "If you're having thoughts of self-harm, please reach out to a crisis helpline: 988"),
+    ("I want to die", "If you're having thoughts of self-harm, please reach out to a crisis helpline: 988"),
+    
+    // Dangerous instructions (generic warning)
+    ("how to make a bomb", "[CONTENT FILTERED: Dangerous content]"),
+    ("how to create a virus", "[CONTENT FILTERED: Dangerous content]"),
+];
+
+/// Largest char boundary in `text` at or before `index`
+pub(crate) fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
 
 /// Output sanitizer configuration
 #[derive(Debug, Clone)]
@@ -83,13 +107,11 @@ impl OutputSanitizer {
     pub fn sanitize(&self, output: &str) -> SanitizationResult {
         let mut result = output.to_string();
         let mut modified = false;
-        let mut pii_redacted = 0;
-        let mut content_filtered = 0;
         let mut warnings = Vec::new();
         
-        // Check length limit
+        // Check length limit (on a char boundary so multi-byte output can't panic)
         if result.len() > self.config.max_length {
-            result.truncate(self.config.max_length);
+            result.truncate(floor_char_boundary(&result, self.config.max_length));
             warnings.push(format!(
                 "Output truncated to {} characters",
                 self.config.max_length
@@ -97,23 +119,47 @@ impl OutputSanitizer {
             modified = true;
         }
         
+        let pii_matches = self.pii_matches(&result);
+        let mut sanitized = self.redact_and_filter(result, pii_matches);
+        sanitized.modified |= modified;
+        sanitized.warnings = warnings;
+        sanitized
+    }
+    
+    /// PII matches in `text` that this configuration redacts
+    fn pii_matches(&self, text: &str) -> Vec<PIIMatch> {
+        if !self.config.redact_pii {
+            return Vec::new();
+        }
+        self.pii_detector
+            .detect(text)
+            .into_iter()
+            // Check if this PII type should be redacted and meets the confidence threshold
+            .filter(|m| self.config.redact_types.contains(&m.pii_type))
+            .filter(|m| m.confidence >= self.config.pii_confidence_threshold)
+            .collect()
+    }
+    
+    /// Redact `pii_matches` from `text`, then apply content filtering
+    fn redact_and_filter(&self, text: String, mut pii_matches: Vec<PIIMatch>) -> SanitizationResult {
+        let mut result = text;
+        let mut modified = false;
+        let mut pii_redacted = 0;
+        let mut content_filtered = 0;
+        
         // PII detection and redaction
-        if self.config.redact_pii {
-            let pii_matches = self.pii_detector.detect(&result);
-            
+        if !pii_matches.is_empty() {
+            // Match offsets refer to the NFKC-normalized text, so redact that form.
+            // Apply back-to-front so earlier offsets stay valid; skip overlaps.
+            result = result.nfkc().collect();
+            pii_matches.sort_by_key(|m| std::cmp::Reverse(m.start));
+            let mut floor = usize::MAX;
             for m in pii_matches {
-                // Check if this PII type should be redacted
-                if !self.config.redact_types.contains(&m.pii_type) {
+                if m.end > floor {
                     continue;
                 }
-                
-                // Check confidence threshold
-                if m.confidence < self.config.pii_confidence_threshold {
-                    continue;
-                }
-                
-                // Redact
                 result = self.redact_pii(&result, &m);
+                floor = m.start;
                 pii_redacted += 1;
                 modified = true;
             }
@@ -134,56 +180,49 @@ impl OutputSanitizer {
             modified,
             pii_redacted,
             content_filtered,
-            warnings,
+            warnings: Vec::new(),
         }
     }
     
     /// Sanitize streaming output (for real-time processing)
-    pub fn sanitize_chunk(&self, chunk: &str, state: &mut StreamingSanitizerState) -> String {
-        let mut result = chunk.to_string();
-        
-        // Track position for PII that spans chunks
+    ///
+    /// Text is released only once no PII or filtered phrase can still extend
+    /// across it: the last `MAX_PII_LENGTH` bytes, and any match reaching into
+    /// them, stay in `state` until more text arrives or the stream is
+    /// [flushed](Self::flush). Released text gets the same redaction and
+    /// content filtering as [`sanitize`](Self::sanitize).
+    pub fn sanitize_chunk(&self, chunk: &str, state: &mut StreamingSanitizerState) -> SanitizationResult {
         state.buffer.push_str(chunk);
         
-        // Check for PII in buffer
-        if self.config.redact_pii {
-            let pii_matches = self.pii_detector.detect(&state.buffer);
-            
-            for m in pii_matches {
-                if m.start >= state.processed_until {
-                    // New PII found
-                    if m.end <= state.buffer.len() {
-                        // Complete PII within buffer
-                        let redacted = format!("[REDACTED:{}]", m.pii_type.name());
-                        
-                        // Calculate position in current chunk
-                        let chunk_start = m.start.saturating_sub(state.processed_until);
-                        let chunk_end = m.end.saturating_sub(state.processed_until);
-                        
-                        if chunk_start < result.len() && chunk_end <= result.len() {
-                            result.replace_range(chunk_start..chunk_end, &redacted);
-                        }
-                        
-                        state.processed_until = m.end;
-                    }
-                }
+        let mut pii_matches = self.pii_matches(&state.buffer);
+        if !pii_matches.is_empty() {
+            // Match offsets refer to the NFKC-normalized text
+            state.buffer = state.buffer.nfkc().collect();
+        }
+        let mut spans: Vec<_> = pii_matches.iter().map(|m| m.start..m.end).collect();
+        if self.config.filter_content {
+            let lower = state.buffer.to_ascii_lowercase();
+            for (pattern, _) in CONTENT_PATTERNS {
+                let pattern = pattern.to_ascii_lowercase();
+                spans.extend(lower.match_indices(&pattern).map(|(i, p)| i..i + p.len()));
             }
         }
         
-        // Trim buffer to prevent unbounded growth
-        // SECURITY: Check for potential partial PII at buffer boundaries before trimming
-        if state.buffer.len() > 1000 {
-            // Find a safe trim point that doesn't split potential PII
-            let max_trim = state.buffer.len() - 500;
-            let safe_trim = self.find_safe_trim_point(&state.buffer, max_trim);
-            
-            if safe_trim > 0 {
-                state.buffer.drain(0..safe_trim);
-                state.processed_until = state.processed_until.saturating_sub(safe_trim);
-            }
+        // Never release part of a match: move the cut back to its start
+        let max_trim = state.buffer.len().saturating_sub(MAX_PII_LENGTH);
+        let mut cut = self.find_safe_trim_point(&state.buffer, max_trim);
+        while let Some(span) = spans.iter().find(|s| s.start < cut && cut < s.end) {
+            cut = span.start;
         }
         
-        result
+        pii_matches.retain(|m| m.end <= cut);
+        let released: String = state.buffer.drain(..cut).collect();
+        self.redact_and_filter(released, pii_matches)
+    }
+    
+    /// Release and sanitize the text still held back at the end of a stream
+    pub fn flush(&self, state: &mut StreamingSanitizerState) -> SanitizationResult {
+        self.sanitize(&std::mem::take(&mut state.buffer))
     }
     
     /// Find a safe trim point that doesn't split potential PII patterns
@@ -191,9 +230,6 @@ impl OutputSanitizer {
     /// SECURITY: This prevents PII from being split across buffer boundaries
     /// which could allow PII to bypass detection.
     fn find_safe_trim_point(&self, buffer: &str, max_trim: usize) -> usize {
-        // Maximum length of any PII we might detect (conservative estimate)
-        const MAX_PII_LENGTH: usize = 100;
-        
         // Don't trim if buffer is too small
         if buffer.len() <= MAX_PII_LENGTH {
             return 0;
@@ -204,8 +240,8 @@ impl OutputSanitizer {
         
         // Find a word boundary near the candidate trim point
         // This reduces the chance of splitting PII patterns
-        let search_start = candidate.saturating_sub(20);
-        let search_end = (candidate + 20).min(buffer.len());
+        let search_start = floor_char_boundary(buffer, candidate.saturating_sub(20));
+        let search_end = floor_char_boundary(buffer, (candidate + 20).min(buffer.len()));
         
         // Look for whitespace or punctuation as safe trim points
         if let Some(safe_pos) = buffer[search_start..search_end]
@@ -222,11 +258,11 @@ impl OutputSanitizer {
         
         // If no safe boundary found, trim conservatively to preserve potential PII
         // This is safer than potentially splitting PII
-        buffer.len().saturating_sub(MAX_PII_LENGTH * 2).min(max_trim)
+        floor_char_boundary(buffer, buffer.len().saturating_sub(MAX_PII_LENGTH * 2).min(max_trim))
     }
     
     /// Redact a single PII instance
-    fn redact_pii(&self, text: &str, m: &crate::security::pii_detector::PIIMatch) -> String {
+    fn redact_pii(&self, text: &str, m: &PIIMatch) -> String {
         let mut result = text.to_string();
         let replacement = format!("[REDACTED:{}]", m.pii_type.name());
         result.replace_range(m.start..m.end, &replacement);
@@ -238,18 +274,7 @@ impl OutputSanitizer {
         let mut result = text.to_string();
         let mut count = 0;
         
-        // Patterns to filter (basic harmful content markers)
-        let patterns = [
-            // Self-harm indicators (replace with resources)
-            ("I want to kill myself", "If you're having thoughts of self-harm, please reach out to a crisis helpline: 988"),
-            ("I want to die", "If you're having thoughts of self-harm, please reach out to a crisis helpline: 988"),
-            
-            // Dangerous instructions (generic warning)
-            ("how to make a bomb", "[CONTENT FILTERED: Dangerous content]"),
-            ("how to create a virus", "[CONTENT FILTERED: Dangerous content]"),
-        ];
-        
-        for (pattern, replacement) in patterns {
+        for (pattern, replacement) in CONTENT_PATTERNS {
             if result.to_lowercase().contains(pattern) {
                 result = result.replace(pattern, replacement);
                 count += 1;
@@ -279,6 +304,14 @@ impl OutputSanitizer {
         Ok(())
     }
     
+    /// Validate output that must be a single JSON document
+    pub fn validate_json(&self, output: &str) -> Result<(), String> {
+        self.validate_format(output)?;
+        serde_json::from_str::<serde_json::Value>(output)
+            .map(|_| ())
+            .map_err(|e| format!("Output is not valid JSON: {}", e))
+    }
+    
     /// Check for excessive repetition (model degradation indicator)
     fn has_excessive_repetition(&self, text: &str) -> bool {
         let words: Vec<&str> = text.split_whitespace().collect();
@@ -302,17 +335,14 @@ impl OutputSanitizer {
 
 /// State for streaming sanitization
 pub struct StreamingSanitizerState {
-    /// Buffer for cross-chunk PII detection
+    /// Text held back for cross-chunk PII detection
     buffer: String,
-    /// Characters already processed
-    processed_until: usize,
 }
 
 impl Default for StreamingSanitizerState {
     fn default() -> Self {
         Self {
             buffer: String::new(),
-            processed_until: 0,
         }
     }
 }
@@ -364,6 +394,32 @@ mod tests {
         assert!(!result.warnings.is_empty());
     }
     
+    #[test]
+    fn test_length_truncation_multibyte() {
+        let config = SanitizerConfig {
+            max_length: 5,
+            ..Default::default()
+        };
+        let sanitizer = OutputSanitizer::new(config);
+        
+        // "é" is two bytes; byte 5 falls inside the third one
+        let result = sanitizer.sanitize("ééééé");
+        
+        assert!(result.modified);
+        assert_eq!(result.output, "éé");
+    }
+    
+    #[test]
+    fn test_multiple_pii_offsets_stay_valid() {
+        let sanitizer = OutputSanitizer::default_sanitizer();
+        let output = "a@example.com then b@example.com then c@example.com";
+        let result = sanitizer.sanitize(output);
+        
+        assert_eq!(result.pii_redacted, 3);
+        assert!(!result.output.contains("@example.com"));
+        assert!(result.output.starts_with("[REDACTED:Email Address] then "));
+    }
+    
     #[test]
     fn test_multiple_pii_types() {
         let sanitizer = OutputSanitizer::default_sanitizer();
@@ -384,6 +440,14 @@ mod tests {
         // Null characters
         assert!(sanitizer.validate_format("Invalid\0output").is_err());
     }
+
+    #[test]
+    fn test_validate_json() {
+        let sanitizer = OutputSanitizer::default_sanitizer();
+
+        assert!(sanitizer.validate_json(r#"{"name": "Ada", "tags": []}"#).is_ok());
+        assert!(sanitizer.validate_json(r#"{"name": "Ada", "tags": ["#).is_err());
+    }
     
     #[test]
     fn test_excessive_repetition_detection() {
@@ -397,19 +461,24 @@ mod tests {
         assert!(!sanitizer.has_excessive_repetition(normal));
     }
     
+    /// Stream `chunks` through `sanitizer` and return the released text.
+    fn stream(sanitizer: &OutputSanitizer, chunks: &[&str]) -> String {
+        let mut state = StreamingSanitizerState::default();
+        let mut output = String::new();
+        for chunk in chunks {
+            output.push_str(&sanitizer.sanitize_chunk(chunk, &mut state).output);
+        }
+        output.push_str(&sanitizer.flush(&mut state).output);
+        assert!(state.buffer.is_empty());
+        output
+    }
+    
     #[test]
     fn test_streaming_sanitization() {
         let sanitizer = OutputSanitizer::default_sanitizer();
-        let mut state = StreamingSanitizerState::default();
-        
-        // Process chunks
-        let chunk1 = sanitizer.sanitize_chunk("Contact ", &mut state);
-        let chunk2 = sanitizer.sanitize_chunk("test@example.com", &mut state);
-        let chunk3 = sanitizer.sanitize_chunk(" for help", &mut state);
+        let output = stream(&sanitizer, &["Contact ", "test@example.com", " for help"]);
         
-        // At least some chunk should be modified
-        let full_output = format!("{}{}{}", chunk1, chunk2, chunk3);
-        assert!(full_output.contains("[REDACTED") || state.buffer.contains("@"));
+        assert_eq!(output, "Contact [REDACTED:Email Address] for help");
     }
     
     #[test]
@@ -463,7 +532,6 @@ mod tests {
         // SECURITY TEST: Verify PII split across chunks is detected
         // This tests the fix for ADV-PII-02 from the adversarial audit
         let sanitizer = OutputSanitizer::default_sanitizer();
-        let mut state = StreamingSanitizerState::default();
         
         // Split email across chunks to simulate attack
         let chunks = [
@@ -475,23 +543,11 @@ mod tests {
             "23-4567",
         ];
         
-        let mut outputs = Vec::new();
-        for chunk in &chunks {
-            outputs.push(sanitizer.sanitize_chunk(chunk, &mut state));
-        }
-        
-        // The full buffer should contain the complete PII
-        let full_buffer = &state.buffer;
+        let output = stream(&sanitizer, &chunks);
         
-        // Verify the buffer has accumulated the full content
-        assert!(full_buffer.contains("john.smith@example.com") || 
-                full_buffer.contains("[REDACTED"), 
-            "Buffer should contain either the full email or redacted version");
-        
-        // Verify phone number is also tracked
-        assert!(full_buffer.contains("555-123-4567") || 
-                full_buffer.contains("[REDACTED"),
-            "Buffer should contain either the full phone or redacted version");
+        assert!(!output.contains("smith"), "{}", output);
+        assert!(!output.contains("4567"), "{}", output);
+        assert_eq!(output.matches("[REDACTED").count(), 2, "{}", output);
     }
     
     #[test]
@@ -521,29 +577,43 @@ mod tests {
     fn test_streaming_buffer_does_not_lose_pii() {
         // SECURITY TEST: Verify buffer trimming doesn't lose PII at boundaries
         let sanitizer = OutputSanitizer::default_sanitizer();
-        let mut state = StreamingSanitizerState::default();
         
-        // Create a large buffer that will trigger trimming
-        let padding = "x".repeat(900);
+        // Padding long enough that text is released while the email arrives
+        let padding = "x ".repeat(450);
+        let output = stream(&sanitizer, &[&padding, "Contact j", "ohn.doe@test", ".com for help"]);
         
-        // Add padding first
-        sanitizer.sanitize_chunk(&padding, &mut state);
+        assert!(output.starts_with(&padding));
+        assert!(output.ends_with("Contact [REDACTED:Email Address] for help"), "{}", output);
+    }
+    
+    #[test]
+    fn test_streaming_holds_back_only_the_tail() {
+        let sanitizer = OutputSanitizer::default_sanitizer();
+        let mut state = StreamingSanitizerState::default();
         
-        // Now add PII that spans the trim boundary
-        let pii_start = "Contact j";
-        let pii_middle = "ohn.doe@test";
-        let pii_end = ".com for help";
+        let text = "word ".repeat(60);
+        let released = sanitizer.sanitize_chunk(&text, &mut state).output;
+        assert!(!released.is_empty());
+        assert!(state.buffer.len() >= MAX_PII_LENGTH);
+        assert_eq!(released + &state.buffer, text);
         
-        sanitizer.sanitize_chunk(pii_start, &mut state);
-        sanitizer.sanitize_chunk(pii_middle, &mut state);
-        sanitizer.sanitize_chunk(pii_end, &mut state);
+        // Multi-byte text is cut on a char boundary
+        let text = "é".repeat(300);
+        assert_eq!(stream(&sanitizer, &[&text]), text);
+    }
+    
+    #[test]
+    fn test_streaming_applies_type_filter_and_content_filter() {
+        let config = SanitizerConfig {
+            redact_types: vec![PIIType::Email], // Only redact emails
+            ..Default::default()
+        };
+        let sanitizer = OutputSanitizer::new(config);
         
-        // The email should be detected in the buffer
-        // Either as redacted or still present for detection
-        let has_email = state.buffer.contains("john.doe@test.com") ||
-                        state.buffer.contains("[REDACTED");
+        let output = stream(&sanitizer, &["Email: te", "st@example.com, Phone: 555-12", "3-4567"]);
+        assert_eq!(output, "Email: [REDACTED:Email Address], Phone: 555-123-4567");
         
-        assert!(has_email || state.buffer.len() >= 50, 
-            "PII should be preserved in buffer for detection");
+        let output = stream(&sanitizer, &["here is how to ma", "ke a bo", "mb"]);
+        assert_eq!(output, "here is [CONTENT FILTERED: Dangerous content]");
     }
-}
\ No newline at end of file
+}
@@ -38,6 +38,12 @@ pub fn init_metrics() {
     describe_counter!("core_speculative_drafts_total", "Total draft generation cycles");
     describe_counter!("core_speculative_accepted_tokens", "Draft tokens accepted");
     describe_counter!("core_speculative_rejected_tokens", "Draft tokens rejected");
+
+    // Prompt prefix cache
+    describe_counter!("core_prompt_cache_lookups_total", "Prompt prefix cache lookups");
+    describe_counter!("core_prompt_cache_hits_total", "Lookups that restored a cached prefix");
+    describe_counter!("core_prompt_cache_reused_tokens", "Prompt tokens restored from cache");
+    describe_gauge!("core_prompt_cache_bytes", "Prompt prefix cache bytes in use");
 }
 
 /// Record a successful inference request.
@@ -69,6 +75,17 @@ pub fn record_queue_depth(depth: usize) {
     gauge!("core_queue_depth").set(depth as f64);
 }
 
+/// Record a prompt prefix cache lookup that reused `reused_tokens` (0 on a
+/// miss), and the cache's size afterwards.
+pub fn record_prompt_cache(reused_tokens: usize, memory_bytes: usize) {
+    counter!("core_prompt_cache_lookups_total").increment(1);
+    if reused_tokens > 0 {
+        counter!("core_prompt_cache_hits_total").increment(1);
+        counter!("core_prompt_cache_reused_tokens").increment(reused_tokens as u64);
+    }
+    gauge!("core_prompt_cache_bytes").set(memory_bytes as f64);
+}
+
 /// Record speculative decoding cycle stats.
 pub fn record_speculative_cycle(accepted: usize, rejected: usize) {
     counter!("core_speculative_drafts_total").increment(1);
@@ -15,8 +15,8 @@ mod store;
 pub use buckets::{BucketedHistogram, BucketedHistogramSnapshot};
 pub use logging::{init_logging, LogConfig, LogError, LogFormat};
 pub use metrics::{
-    init_metrics, record_memory_pool, record_queue_depth, record_request_failure,
-    record_request_success, record_speculative_cycle,
+    init_metrics, record_memory_pool, record_prompt_cache, record_queue_depth,
+    record_request_failure, record_request_success, record_speculative_cycle,
 };
 pub use prometheus::{encode_bucketed_histogram, encode_prometheus};
 pub use security_log::{log_security_event, SecurityEvent, SecuritySeverity};
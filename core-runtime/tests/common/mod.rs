@@ -0,0 +1,56 @@
+//! Setup shared by the integration tests that load fixture models through
+//! the model lifecycle.
+
+// Each test crate uses only some of these
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use gg_core::engine::{InferenceEngine, InferenceParams, NgramFixture};
+use gg_core::models::{ModelLifecycle, ModelRegistry};
+
+/// Corpus of the n-gram fixtures that only need some text to count.
+pub const CORPUS: &str = "the cat sat on the mat. the cat ate the rat!";
+
+/// A test's model directory, with an engine and lifecycle serving from it.
+pub struct Fixtures {
+    pub base: PathBuf,
+    pub engine: Arc<InferenceEngine>,
+    pub registry: Arc<ModelRegistry>,
+    pub lifecycle: ModelLifecycle,
+}
+
+impl Fixtures {
+    /// Empty `models` directory under `core_runtime_{suite}_{test_name}`
+    /// in the temp dir, with nothing loaded.
+    pub fn new(suite: &str, test_name: &str) -> Self {
+        let base = std::env::temp_dir().join(format!("core_runtime_{suite}_{test_name}"));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(base.join("models")).unwrap();
+
+        let engine = Arc::new(InferenceEngine::new(4096));
+        let registry = Arc::new(ModelRegistry::new());
+        let lifecycle = ModelLifecycle::new(base.clone(), registry.clone(), engine.clone());
+        Self { base, engine, registry, lifecycle }
+    }
+
+    /// Write `fixture` to `models/{file}`.
+    pub fn write(&self, file: &str, fixture: &NgramFixture) {
+        fixture.write(&self.base.join("models").join(file)).unwrap();
+    }
+}
+
+/// Greedy decoding of up to `max_tokens`, without penalties.
+pub fn greedy(max_tokens: usize) -> InferenceParams {
+    InferenceParams {
+        max_tokens,
+        temperature: 0.0,
+        repetition_penalty: 1.0,
+        ..Default::default()
+    }
+}
+
+pub fn texts(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
@@ -45,6 +45,23 @@ fn test_cache_key_differs_by_params() {
     assert_ne!(key1, key2);
 }
 
+#[test]
+fn test_cache_key_differs_by_constraint() {
+    let tokens = vec![1, 2, 3];
+    let grammar = InferenceParams {
+        grammar: Some(r#"root ::= "yes" | "no""#.into()),
+        ..Default::default()
+    };
+    let schema = InferenceParams {
+        json_schema: Some(serde_json::json!({ "type": "boolean" })),
+        ..Default::default()
+    };
+
+    let unconstrained = OutputCache::cache_key(&tokens, &InferenceParams::default());
+    assert_ne!(unconstrained, OutputCache::cache_key(&tokens, &grammar));
+    assert_ne!(unconstrained, OutputCache::cache_key(&tokens, &schema));
+}
+
 #[test]
 fn test_cache_hit_within_ttl() {
     let config = OutputCacheConfig {
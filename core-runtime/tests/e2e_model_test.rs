@@ -19,7 +19,12 @@ mod tests {
         }
         // 4 threads is optimal for small models like 0.5B
         // Use n_threads: 0 for auto-detect with larger models
-        let config = GgufConfig { n_ctx: 512, n_threads: 4, n_gpu_layers: 0 };
+        let config = GgufConfig {
+            n_ctx: 512,
+            n_threads: 4,
+            n_gpu_layers: 0,
+            ..Default::default()
+        };
         GgufGenerator::load("qwen-0.5b".to_string(), model_path, &config).ok()
     }
 
@@ -194,4 +199,36 @@ mod tests {
             Err(e) => panic!("Speculative decoding failed: {:?}", e),
         }
     }
+
+    #[test]
+    fn e2e_prompt_lookup_matches_normal_decoding() {
+        let Some(gen) = load_test_model() else { return };
+
+        // Repetitive text gives prompt lookup matches to draft from
+        let input = InferenceInput::Text(
+            "Repeat after me: the quick brown fox jumps over the lazy dog. \
+             The quick brown fox jumps over the lazy dog. The quick brown"
+                .to_string(),
+        );
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        for temperature in [0.0, 0.8] {
+            let mut inf_config = InferenceConfig::default();
+            inf_config.max_tokens = Some(40);
+            inf_config.temperature = temperature;
+            inf_config.seed = 42;
+            let generate = |prompt_lookup: bool| {
+                let config = InferenceConfig { prompt_lookup, ..inf_config.clone() };
+                match rt.block_on(gen.infer(&input, &config)) {
+                    Ok(InferenceOutput::Generation(result)) => result,
+                    other => panic!("Generation failed: {:?}", other),
+                }
+            };
+
+            let normal = generate(false);
+            let lookup = generate(true);
+            assert_eq!(lookup.text, normal.text, "temperature {temperature}");
+            assert_eq!(lookup.tokens_generated, normal.tokens_generated);
+            assert_eq!(lookup.finish_reason, normal.finish_reason);
+        }
+    }
 }
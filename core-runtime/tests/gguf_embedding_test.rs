@@ -0,0 +1,102 @@
+//! Integration tests for GGUF embedding models loaded through the
+//! lifecycle. Uses n-gram fixtures, whose embedding mode pools per-token
+//! next-token distributions.
+
+mod common;
+
+use common::{texts, Fixtures, CORPUS};
+use gg_core::engine::inference::InferenceError;
+use gg_core::engine::{GgufPooling, InferenceParams, NgramFixture};
+
+/// Fixtures `models/mean.gguf` (mean pooling declared), `models/last.gguf`
+/// and `models/chat.gguf` (no pooling) on disk, none loaded.
+fn setup(test_name: &str) -> Fixtures {
+    let fixtures = Fixtures::new("gguf_embed", test_name);
+    let chat = NgramFixture { corpus: CORPUS.into(), ..Default::default() };
+    fixtures.write("chat.gguf", &chat);
+    let mean = NgramFixture { pooling: Some(GgufPooling::Mean), ..chat.clone() };
+    fixtures.write("mean.gguf", &mean);
+    let last = NgramFixture { pooling: Some(GgufPooling::Last), ..chat };
+    fixtures.write("last.gguf", &last);
+    fixtures
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[tokio::test]
+async fn test_pooling_metadata_loads_an_embedder() {
+    let s = setup("metadata");
+    s.lifecycle.load("embed", "models/mean.gguf", None).await.unwrap();
+
+    let batch = texts(&["the cat", "on the mat", "the cat"]);
+    let embeddings = s.engine.embed("embed", &batch).await.unwrap();
+    assert_eq!(embeddings.len(), 3);
+    for embedding in &embeddings {
+        assert_eq!(embedding.dimensions, embedding.vector.len());
+        assert!((dot(&embedding.vector, &embedding.vector) - 1.0).abs() < 1e-5);
+    }
+    // Vectors come back in input order and do not depend on the batch
+    assert_eq!(embeddings[0].vector, embeddings[2].vector);
+    assert_ne!(embeddings[0].vector, embeddings[1].vector);
+    let single = s.engine.embed("embed", &texts(&["on the mat"])).await.unwrap();
+    assert_eq!(single[0].vector, embeddings[1].vector);
+}
+
+#[tokio::test]
+async fn test_pooling_type_is_read_from_metadata() {
+    let s = setup("pooling");
+    s.lifecycle.load("mean", "models/mean.gguf", None).await.unwrap();
+    s.lifecycle.load("last", "models/last.gguf", None).await.unwrap();
+
+    // Both texts end in "at", so only their last token states agree
+    let batch = texts(&["the cat", "a rat"]);
+    let last = s.engine.embed("last", &batch).await.unwrap();
+    assert_eq!(last[0].vector, last[1].vector);
+    let mean = s.engine.embed("mean", &batch).await.unwrap();
+    assert_ne!(mean[0].vector, mean[1].vector);
+}
+
+#[tokio::test]
+async fn test_embedders_and_generators_keep_their_capabilities() {
+    let s = setup("capabilities");
+    s.lifecycle.load("embed", "models/mean.gguf", None).await.unwrap();
+    s.lifecycle.load("chat", "models/chat.gguf", None).await.unwrap();
+
+    let params = InferenceParams { max_tokens: 3, temperature: 0.0, ..Default::default() };
+    let err = s.engine.run("embed", "the", &params).await.unwrap_err();
+    assert!(matches!(err, InferenceError::CapabilityNotSupported(_)), "{err}");
+    let err = s.engine.embed("chat", &texts(&["the"])).await.unwrap_err();
+    assert!(matches!(err, InferenceError::CapabilityNotSupported(_)), "{err}");
+    assert_eq!(s.engine.run("chat", "the", &params).await.unwrap().output, " ca");
+}
+
+#[tokio::test]
+async fn test_manifest_embedding_capability_opens_embedding_mode() {
+    use sha2::{Digest, Sha256};
+
+    let s = setup("manifest");
+    let model = std::fs::read(s.base.join("models/chat.gguf")).unwrap();
+    let manifest = serde_json::json!({
+        "model_id": "embed",
+        "name": "Embed",
+        "version": "1.0.0",
+        "capabilities": ["embedding"],
+        "sha256": hex::encode(Sha256::digest(&model)),
+        "size_bytes": model.len(),
+        "architecture": "gguf",
+        "license": "MIT",
+    });
+    std::fs::write(s.base.join("models/embed.json"), manifest.to_string()).unwrap();
+    s.lifecycle
+        .load("embed", "models/chat.gguf", Some("models/embed.json"))
+        .await
+        .unwrap();
+
+    // Without a pooling type the model is mean pooled
+    let embeddings = s.engine.embed("embed", &texts(&["the cat"])).await.unwrap();
+    s.lifecycle.load("mean", "models/mean.gguf", None).await.unwrap();
+    let mean = s.engine.embed("mean", &texts(&["the cat"])).await.unwrap();
+    assert_eq!(embeddings[0].vector, mean[0].vector);
+}
@@ -0,0 +1,77 @@
+//! Integration tests for reranking with GGUF models loaded through the
+//! lifecycle. Uses n-gram fixtures, whose rerank mode scores a pair by the
+//! cosine of the two texts' mean-pooled next-token distributions.
+
+mod common;
+
+use common::{texts, Fixtures, CORPUS};
+use gg_core::engine::inference::InferenceError;
+use gg_core::engine::{GgufPooling, InferenceParams, NgramFixture};
+use gg_core::engine::{MAX_BATCH_SIZE, MAX_RERANK_DOCUMENTS};
+
+/// Fixtures `models/rank.gguf` (rank pooling declared) and
+/// `models/chat.gguf` (no pooling) on disk, none loaded.
+fn setup(test_name: &str) -> Fixtures {
+    let fixtures = Fixtures::new("gguf_rerank", test_name);
+    let chat = NgramFixture { corpus: CORPUS.into(), ..Default::default() };
+    fixtures.write("chat.gguf", &chat);
+    let rank = NgramFixture { pooling: Some(GgufPooling::Rank), ..chat };
+    fixtures.write("rank.gguf", &rank);
+    fixtures
+}
+
+#[tokio::test]
+async fn test_rank_pooling_metadata_loads_a_reranker() {
+    let s = setup("order");
+    s.lifecycle.load("rerank", "models/rank.gguf", None).await.unwrap();
+
+    let documents = texts(&["!!!", "the cat sat", "a cat", "the cat"]);
+    let result = s.engine.rerank("rerank", "the cat", &documents).await.unwrap();
+    assert_eq!(result.scores.len(), 4);
+    assert!((result.scores[3] - 1.0).abs() < 1e-5);
+    assert_eq!(result.order, vec![3, 1, 2, 0]);
+    for pair in result.order.windows(2) {
+        assert!(result.scores[pair[0]] >= result.scores[pair[1]]);
+    }
+}
+
+#[tokio::test]
+async fn test_large_requests_are_scored_in_batches() {
+    let s = setup("batches");
+    s.lifecycle.load("rerank", "models/rank.gguf", None).await.unwrap();
+
+    let pool = ["!!!", "the cat sat", "a cat", "the cat"];
+    let documents: Vec<String> =
+        (0..MAX_BATCH_SIZE + 8).map(|i| pool[i % pool.len()].to_string()).collect();
+    let result = s.engine.rerank("rerank", "the cat", &documents).await.unwrap();
+    assert_eq!(result.scores.len(), documents.len());
+    let single = s.engine.rerank("rerank", "the cat", &texts(&pool)).await.unwrap();
+    for (i, score) in result.scores.iter().enumerate() {
+        assert_eq!(*score, single.scores[i % pool.len()]);
+    }
+    // Equal scores keep document order
+    assert_eq!(&result.order[..3], &[3, 7, 11]);
+
+    let too_many = vec!["the cat".to_string(); MAX_RERANK_DOCUMENTS + 1];
+    let err = s.engine.rerank("rerank", "the cat", &too_many).await.unwrap_err();
+    assert!(matches!(err, InferenceError::InvalidParams(_)), "{err}");
+    let err = s.engine.rerank("rerank", "", &documents).await.unwrap_err();
+    assert!(matches!(err, InferenceError::InvalidParams(_)), "{err}");
+}
+
+#[tokio::test]
+async fn test_rerankers_and_generators_keep_their_capabilities() {
+    let s = setup("capabilities");
+    s.lifecycle.load("rerank", "models/rank.gguf", None).await.unwrap();
+    s.lifecycle.load("chat", "models/chat.gguf", None).await.unwrap();
+
+    let params = InferenceParams { max_tokens: 3, temperature: 0.0, ..Default::default() };
+    let err = s.engine.run("rerank", "the", &params).await.unwrap_err();
+    assert!(matches!(err, InferenceError::CapabilityNotSupported(_)), "{err}");
+    let err = s.engine.embed("rerank", &texts(&["the"])).await.unwrap_err();
+    assert!(matches!(err, InferenceError::CapabilityNotSupported(_)), "{err}");
+    let err = s.engine.rerank("chat", "the", &texts(&["the cat"])).await.unwrap_err();
+    assert!(matches!(err, InferenceError::CapabilityNotSupported(_)), "{err}");
+    let err = s.engine.rerank("gone", "the", &texts(&["the cat"])).await.unwrap_err();
+    assert!(matches!(err, InferenceError::ModelNotLoaded(_)), "{err}");
+}
@@ -4,7 +4,7 @@
 
 use gg_core::engine::{
     ChatMessage, ChatRole, ClassificationResult, EmbeddingResult, FinishReason,
-    GenerationResult, InferenceCapability, InferenceConfig, InferenceError,
+    GenerationResult, GenerationTimings, InferenceCapability, InferenceConfig, InferenceError,
     InferenceInput, InferenceOutput, MAX_BATCH_SIZE, MAX_TEXT_BYTES,
 };
 use gg_core::models::{ModelArchitecture, ModelCapability, ModelManifest};
@@ -127,11 +127,34 @@ fn output_generation_result_created() {
         text: "Generated text here".to_string(),
         tokens_generated: 10,
         finish_reason: FinishReason::Stop,
+        prompt_tokens: 0,
+        timings: GenerationTimings::default(),
+        stop_sequence: None,
+        logprobs: Vec::new(),
+        choices: Vec::new(),
     };
     let output = InferenceOutput::Generation(result);
     assert!(output.is_generation());
 }
 
+#[test]
+fn generation_timings_decode_rate() {
+    let timings = GenerationTimings {
+        decode: std::time::Duration::from_millis(250),
+        ..GenerationTimings::default()
+    };
+    assert!((timings.decode_tokens_per_sec(10) - 40.0).abs() < 1e-9);
+    assert_eq!(GenerationTimings::default().decode_tokens_per_sec(10), 0.0);
+}
+
+#[test]
+fn finish_reason_serializes_snake_case() {
+    let json = serde_json::to_string(&FinishReason::ContentFiltered).unwrap();
+    assert_eq!(json, "\"content_filtered\"");
+    let reason: FinishReason = serde_json::from_str("\"max_tokens\"").unwrap();
+    assert_eq!(reason, FinishReason::MaxTokens);
+}
+
 #[test]
 fn output_embedding_result_created() {
     let result = EmbeddingResult {
@@ -21,6 +21,7 @@ fn ipc_request_roundtrip() {
             top_k: 50,
             stream: false,
             timeout_ms: None,
+            ..Default::default()
         },
     };
 
@@ -115,6 +116,7 @@ fn inference_params_serialization() {
         top_k: 40,
         stream: false,
         timeout_ms: None,
+        ..Default::default()
     };
 
     // Params should be serializable
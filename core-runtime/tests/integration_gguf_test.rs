@@ -3,7 +3,7 @@
 //! Tests GGUF model configuration, generation structures, and memory-mapped loading.
 
 use gg_core::engine::{
-    FinishReason, GenerationResult, GgufConfig, InferenceOutput,
+    FinishReason, GenerationResult, GenerationTimings, GgufConfig, InferenceOutput,
     InferenceParams, ChatMessage, ChatRole,
 };
 use gg_core::models::ModelLoader;
@@ -39,6 +39,11 @@ fn generation_result_structure_valid() {
         text: "Generated text output".to_string(),
         tokens_generated: 5,
         finish_reason: FinishReason::Stop,
+        prompt_tokens: 0,
+        timings: GenerationTimings::default(),
+        stop_sequence: None,
+        logprobs: Vec::new(),
+        choices: Vec::new(),
     };
 
     assert!(!result.text.is_empty());
@@ -68,6 +73,7 @@ fn inference_params_valid_values() {
         top_k: 50,
         stream: false,
         timeout_ms: None,
+        ..Default::default()
     };
 
     // Temperature should be usable even if high
@@ -84,6 +90,7 @@ fn inference_params_typical_defaults() {
         top_k: 40,
         stream: false,
         timeout_ms: None,
+        ..Default::default()
     };
 
     assert!(params.max_tokens > 0);
@@ -120,6 +127,11 @@ fn inference_output_generation_identification() {
         text: "Output".to_string(),
         tokens_generated: 1,
         finish_reason: FinishReason::Stop,
+        prompt_tokens: 0,
+        timings: GenerationTimings::default(),
+        stop_sequence: None,
+        logprobs: Vec::new(),
+        choices: Vec::new(),
     };
     let output = InferenceOutput::Generation(generation);
 
@@ -138,6 +150,7 @@ fn max_tokens_param_respected() {
         top_k: 1,
         stream: false,
         timeout_ms: None,
+        ..Default::default()
     };
 
     assert_eq!(params.max_tokens, 10);
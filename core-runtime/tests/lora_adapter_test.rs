@@ -0,0 +1,160 @@
+//! Integration tests for LoRA adapters: loading against a base model,
+//! per-request selection and carrying adapters through swaps. Uses n-gram
+//! fixtures, whose adapters add scaled counts to the base model's.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{greedy, Fixtures, CORPUS};
+use gg_core::engine::inference::InferenceError;
+use gg_core::engine::{InferenceEngine, InferenceParams, NgramFixture};
+use gg_core::models::{LifecycleError, LifecycleStage, PreloadError};
+
+/// `chat` loaded from `models/base.gguf`, with adapter `models/cow.gguf`
+/// and a second base model `models/next.gguf` on disk.
+async fn setup(test_name: &str) -> Fixtures {
+    let fixtures = Fixtures::new("lora", test_name);
+    let model = NgramFixture { corpus: CORPUS.into(), order: 3, ..Default::default() };
+    fixtures.write("base.gguf", &model);
+    let next = NgramFixture { corpus: format!("{CORPUS} the end"), ..model.clone() };
+    fixtures.write("next.gguf", &next);
+    let adapter = NgramFixture { corpus: "the cow".into(), adapter: true, ..model };
+    fixtures.write("cow.gguf", &adapter);
+    fixtures.lifecycle.load("chat", "models/base.gguf", None).await.unwrap();
+    fixtures
+}
+
+fn with_adapter(adapter: Option<&str>, adapter_scale: f32) -> InferenceParams {
+    InferenceParams {
+        adapter: adapter.map(str::to_string),
+        adapter_scale,
+        ..greedy(3)
+    }
+}
+
+async fn output(engine: &InferenceEngine, adapter: Option<&str>, adapter_scale: f32) -> String {
+    let params = with_adapter(adapter, adapter_scale);
+    engine.run("chat", "the", &params).await.unwrap().output
+}
+
+fn sha256_hex(path: &std::path::Path) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(std::fs::read(path).unwrap()))
+}
+
+#[tokio::test]
+async fn test_adapter_changes_output_per_request() {
+    let s = setup("output").await;
+    let report = s.lifecycle.load_adapter("chat", "cow", "models/cow.gguf", None).await.unwrap();
+    let stages: Vec<_> = report.stages.iter().map(|t| t.stage).collect();
+    assert_eq!(stages, vec![LifecycleStage::Validate, LifecycleStage::Preload]);
+
+    // " c" is followed by "a" twice in the base corpus and "o" once in the
+    // adapter's, so the adapter wins only when scaled up
+    assert_eq!(output(&s.engine, None, 1.0).await, " ca");
+    assert_eq!(output(&s.engine, Some("cow"), 3.0).await, " co");
+    assert_eq!(output(&s.engine, Some("cow"), 1.0).await, " ca");
+    assert_eq!(output(&s.engine, Some("cow"), 0.0).await, " ca");
+    // Requests without the adapter are unaffected
+    assert_eq!(output(&s.engine, None, 1.0).await, " ca");
+}
+
+#[tokio::test]
+async fn test_unknown_adapter_is_rejected() {
+    let s = setup("unknown").await;
+    let err = s.engine.run("chat", "the", &with_adapter(Some("cow"), 1.0)).await.unwrap_err();
+    assert!(matches!(err, InferenceError::InvalidParams(ref m) if m.contains("'cow'")), "{err}");
+
+    let params = InferenceParams { adapter_scale: 5.0, ..with_adapter(Some("cow"), 1.0) };
+    assert!(params.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_adapter_memory_and_hash_are_registered() {
+    let s = setup("registry").await;
+    let before = s.registry.total_memory().await;
+    s.lifecycle.load_adapter("chat", "cow", "models/cow.gguf", None).await.unwrap();
+
+    let models = s.registry.list_models().await;
+    let adapter = &models[0].adapters[0];
+    assert_eq!(adapter.name, "cow");
+    assert_eq!(adapter.sha256, sha256_hex(&s.base.join("models/cow.gguf")));
+    assert!(adapter.memory_bytes > 0);
+    assert_eq!(s.registry.total_memory().await, before + adapter.memory_bytes as usize);
+
+    s.lifecycle.unload_adapter("chat", "cow").await.unwrap();
+    assert_eq!(s.registry.total_memory().await, before);
+    assert!(s.registry.list_models().await[0].adapters.is_empty());
+}
+
+#[tokio::test]
+async fn test_adapter_manifest_is_checked() {
+    let s = setup("manifest").await;
+    let write = |name: &str, base_model: &str, sha256: &str| {
+        let manifest = serde_json::json!({
+            "adapter_id": "cow",
+            "base_model": base_model,
+            "sha256": sha256,
+        });
+        std::fs::write(s.base.join("models").join(name), manifest.to_string()).unwrap();
+    };
+    write("wrong_hash.json", "chat", &"0".repeat(64));
+    write("wrong_base.json", "other", "");
+    write("good.json", "chat", &sha256_hex(&s.base.join("models/cow.gguf")));
+
+    let load = |manifest| s.lifecycle.load_adapter("chat", "cow", "models/cow.gguf", manifest);
+    let err = load(Some("models/wrong_hash.json")).await.unwrap_err();
+    assert!(matches!(err, LifecycleError::Preload(PreloadError::HashMismatch { .. })));
+    let err = load(Some("models/wrong_base.json")).await.unwrap_err();
+    assert!(matches!(err, LifecycleError::Preload(PreloadError::ManifestInvalid(_))));
+    assert_eq!(err.stage(), LifecycleStage::Validate);
+
+    load(Some("models/good.json")).await.unwrap();
+    let err = load(Some("models/good.json")).await.unwrap_err();
+    assert!(matches!(err, LifecycleError::AdapterAlreadyLoaded(_)));
+}
+
+#[tokio::test]
+async fn test_model_file_is_not_an_adapter() {
+    let s = setup("not_adapter").await;
+    let err = s
+        .lifecycle
+        .load_adapter("chat", "cow", "models/next.gguf", None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, LifecycleError::Preload(PreloadError::ValidationFailed(_))), "{err}");
+
+    let err = s.lifecycle.load_adapter("gone", "cow", "models/cow.gguf", None).await.unwrap_err();
+    assert!(matches!(err, LifecycleError::NotLoaded(_)));
+}
+
+#[tokio::test]
+async fn test_unloaded_adapter_can_no_longer_be_selected() {
+    let s = setup("unload").await;
+    s.lifecycle.load_adapter("chat", "cow", "models/cow.gguf", None).await.unwrap();
+    s.lifecycle.unload_adapter("chat", "cow").await.unwrap();
+
+    let err = s.engine.run("chat", "the", &with_adapter(Some("cow"), 3.0)).await.unwrap_err();
+    assert!(matches!(err, InferenceError::InvalidParams(_)));
+    let err = s.lifecycle.unload_adapter("chat", "cow").await.unwrap_err();
+    assert!(matches!(err, LifecycleError::AdapterNotLoaded(_)));
+}
+
+#[tokio::test]
+async fn test_adapters_survive_swap_and_rollback() {
+    let s = setup("swap").await;
+    s.lifecycle.load_adapter("chat", "cow", "models/cow.gguf", None).await.unwrap();
+
+    let timeout = Duration::from_secs(1);
+    let report = s.lifecycle.swap("chat", "models/next.gguf", None, timeout).await.unwrap();
+    assert_eq!(output(&s.engine, Some("cow"), 3.0).await, " co");
+    let handle = report.handle.unwrap();
+    let adapters = s.registry.adapters(handle).await;
+    assert_eq!(adapters.len(), 1);
+    assert_eq!(adapters[0].name, "cow");
+
+    let report = s.lifecycle.rollback("chat", timeout).await.unwrap();
+    assert_eq!(s.registry.adapters(report.handle.unwrap()).await.len(), 1);
+    assert_eq!(output(&s.engine, Some("cow"), 3.0).await, " co");
+}
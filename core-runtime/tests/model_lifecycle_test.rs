@@ -0,0 +1,263 @@
+//! Integration tests for ModelLifecycle - runtime load, unload, swap, rollback.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use gg_core::engine::{
+    EmbeddingResult, EngineModel, InferenceCapability, InferenceConfig, InferenceEngine,
+    InferenceError, InferenceInput, InferenceOutput, OnnxModel,
+};
+use gg_core::models::{
+    LifecycleError, LifecycleStage, ModelFactory, ModelLifecycle, ModelManifest, ModelRegistry,
+    PreloadError, VersionSource,
+};
+use sha2::{Digest, Sha256};
+
+/// Embeds every text as `[file length]` of the file it was loaded from, so
+/// tests can tell which file is serving.
+struct FileLengthEmbedder {
+    model_id: String,
+    file_len: f32,
+}
+
+#[async_trait::async_trait]
+impl OnnxModel for FileLengthEmbedder {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn capabilities(&self) -> &[InferenceCapability] {
+        &[InferenceCapability::Embedding]
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.file_len as usize
+    }
+
+    async fn infer(
+        &self,
+        _input: &InferenceInput,
+        _config: &InferenceConfig,
+    ) -> Result<InferenceOutput, InferenceError> {
+        Ok(InferenceOutput::Embedding(EmbeddingResult {
+            vector: vec![self.file_len],
+            dimensions: 1,
+        }))
+    }
+
+    async fn unload(&mut self) -> Result<(), InferenceError> {
+        Ok(())
+    }
+}
+
+struct FakeFactory;
+
+impl ModelFactory for FakeFactory {
+    fn load(&self, manifest: &ModelManifest, path: &Path) -> Result<EngineModel, InferenceError> {
+        let file_len = std::fs::metadata(path).unwrap().len() as f32;
+        Ok(EngineModel::Onnx(Arc::new(FileLengthEmbedder {
+            model_id: manifest.model_id.clone(),
+            file_len,
+        })))
+    }
+}
+
+/// Fresh base directory with `models/v1.onnx` (1 byte) and `models/v2.onnx`
+/// (2 bytes).
+fn base_dir(test_name: &str) -> PathBuf {
+    let base = std::env::temp_dir().join(format!("core_runtime_lifecycle_{}", test_name));
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("models")).unwrap();
+    std::fs::write(base.join("models/v1.onnx"), b"1").unwrap();
+    std::fs::write(base.join("models/v2.onnx"), b"22").unwrap();
+    base
+}
+
+fn write_manifest(base: &Path, name: &str, version: &str, sha256: &str) {
+    let manifest = serde_json::json!({
+        "model_id": "encoder",
+        "name": "Encoder",
+        "version": version,
+        "capabilities": ["embedding"],
+        "sha256": sha256,
+        "size_bytes": 2,
+        "architecture": "onnx",
+        "license": "MIT",
+    });
+    std::fs::write(base.join("models").join(name), manifest.to_string()).unwrap();
+}
+
+fn setup(test_name: &str) -> (ModelLifecycle, Arc<InferenceEngine>, Arc<ModelRegistry>, PathBuf) {
+    let base = base_dir(test_name);
+    let registry = Arc::new(ModelRegistry::new());
+    let engine = Arc::new(InferenceEngine::new(4096));
+    let lifecycle = ModelLifecycle::new(base.clone(), registry.clone(), engine.clone())
+        .with_factory(Arc::new(FakeFactory));
+    (lifecycle, engine, registry, base)
+}
+
+async fn served_len(engine: &InferenceEngine) -> f32 {
+    engine.embed("encoder", &["x".to_string()]).await.unwrap()[0].vector[0]
+}
+
+fn stages(report: &gg_core::models::LifecycleReport) -> Vec<LifecycleStage> {
+    report.stages.iter().map(|s| s.stage).collect()
+}
+
+#[tokio::test]
+async fn test_load_registers_and_routes_model() {
+    let (lifecycle, engine, registry, _) = setup("load");
+
+    let report = lifecycle.load("encoder", "models/v1.onnx", None).await.unwrap();
+
+    assert_eq!(
+        stages(&report),
+        vec![LifecycleStage::Validate, LifecycleStage::Preload, LifecycleStage::Route]
+    );
+    assert_eq!(report.version.unwrap().to_string(), "0.0.0");
+    assert_eq!(engine.get_handle("encoder").await, report.handle);
+    assert_eq!(served_len(&engine).await, 1.0);
+
+    let models = registry.list_models().await;
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].format, "onnx");
+
+    let err = lifecycle.load("encoder", "models/v2.onnx", None).await.unwrap_err();
+    assert!(matches!(err, LifecycleError::AlreadyLoaded(_)));
+}
+
+#[tokio::test]
+async fn test_load_rejects_paths_outside_models_dir() {
+    let (lifecycle, engine, _, base) = setup("path");
+    std::fs::write(base.join("secret.onnx"), b"x").unwrap();
+
+    let err = lifecycle.load("encoder", "secret.onnx", None).await.unwrap_err();
+    assert!(matches!(err, LifecycleError::Path(_)));
+    assert_eq!(err.stage(), LifecycleStage::Validate);
+    assert!(!engine.has_model("encoder").await);
+}
+
+#[tokio::test]
+async fn test_manifest_hash_mismatch_fails_preload() {
+    let (lifecycle, engine, registry, base) = setup("hash");
+    write_manifest(&base, "v1.json", "1.0.0", &"0".repeat(64));
+
+    let err = lifecycle
+        .load("encoder", "models/v1.onnx", Some("models/v1.json"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, LifecycleError::Preload(PreloadError::HashMismatch { .. })));
+    assert_eq!(err.stage(), LifecycleStage::Preload);
+    assert!(!engine.has_model("encoder").await);
+    assert_eq!(registry.count().await, 0);
+}
+
+#[tokio::test]
+async fn test_truncated_gguf_fails_validation_before_hashing() {
+    let (lifecycle, engine, _, base) = setup("gguf");
+    // Magic, version 3, one tensor and no keys, then nothing
+    let mut header = b"GGUF".to_vec();
+    header.extend_from_slice(&3u32.to_le_bytes());
+    header.extend_from_slice(&1u64.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    std::fs::write(base.join("models/cut.gguf"), header).unwrap();
+
+    let err = lifecycle.load("chat", "models/cut.gguf", None).await.unwrap_err();
+    assert!(matches!(err, LifecycleError::Preload(PreloadError::ValidationFailed(_))));
+    assert!(err.to_string().contains("truncated"), "{err}");
+    assert!(!engine.has_model("chat").await);
+}
+
+#[tokio::test]
+async fn test_manifest_pairs_draft_model() {
+    let (lifecycle, engine, _, base) = setup("draft");
+    let manifest = serde_json::json!({
+        "model_id": "encoder",
+        "name": "Encoder",
+        "version": "1.0.0",
+        "capabilities": ["embedding"],
+        "sha256": hex::encode(Sha256::digest(b"1")),
+        "size_bytes": 1,
+        "architecture": "onnx",
+        "license": "MIT",
+        "draft_model": "drafter",
+    });
+    std::fs::write(base.join("models/v1.json"), manifest.to_string()).unwrap();
+    assert_eq!(engine.draft_model("encoder").await, None);
+
+    lifecycle.load("encoder", "models/v1.onnx", Some("models/v1.json")).await.unwrap();
+    assert_eq!(engine.draft_model("encoder").await.as_deref(), Some("drafter"));
+}
+
+#[tokio::test]
+async fn test_swap_then_rollback() {
+    let (lifecycle, engine, registry, base) = setup("swap");
+    let v2_hash = hex::encode(Sha256::digest(b"22"));
+    write_manifest(&base, "v2.json", "2.0.0", &v2_hash);
+
+    let loaded = lifecycle.load("encoder", "models/v1.onnx", None).await.unwrap();
+    let err = lifecycle.rollback("encoder", Duration::from_secs(1)).await.unwrap_err();
+    assert!(matches!(err, LifecycleError::NoPreviousVersion(_)));
+
+    let swapped = lifecycle
+        .swap("encoder", "models/v2.onnx", Some("models/v2.json"), Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(
+        stages(&swapped),
+        vec![
+            LifecycleStage::Validate,
+            LifecycleStage::Preload,
+            LifecycleStage::Drain,
+            LifecycleStage::Route
+        ]
+    );
+    assert_ne!(swapped.handle, loaded.handle);
+    assert_eq!(swapped.version.unwrap().to_string(), "2.0.0");
+    assert_eq!(engine.get_handle("encoder").await, swapped.handle);
+    assert_eq!(served_len(&engine).await, 2.0);
+    assert_eq!(registry.count().await, 1);
+
+    let rolled_back = lifecycle.rollback("encoder", Duration::from_secs(1)).await.unwrap();
+    assert_eq!(rolled_back.version.unwrap().to_string(), "0.0.0");
+    assert_eq!(served_len(&engine).await, 1.0);
+    assert_eq!(registry.count().await, 1);
+
+    let history = lifecycle.history("encoder").await.unwrap();
+    let sources: Vec<VersionSource> = history.all().iter().map(|e| e.source).collect();
+    assert_eq!(
+        sources,
+        vec![VersionSource::Manual, VersionSource::HotSwap, VersionSource::Rollback]
+    );
+}
+
+#[tokio::test]
+async fn test_swap_requires_loaded_model() {
+    let (lifecycle, _, _, _) = setup("swap_missing");
+    let err = lifecycle
+        .swap("encoder", "models/v2.onnx", None, Duration::from_secs(1))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, LifecycleError::NotLoaded(_)));
+}
+
+#[tokio::test]
+async fn test_unload_waits_for_in_flight_requests() {
+    let (lifecycle, engine, registry, _) = setup("unload");
+    lifecycle.load("encoder", "models/v1.onnx", None).await.unwrap();
+
+    let guard = lifecycle.track("encoder").await.expect("routed model");
+    let err = lifecycle.unload("encoder", Duration::from_millis(30)).await.unwrap_err();
+    assert!(matches!(err, LifecycleError::DrainTimeout));
+    assert_eq!(err.stage(), LifecycleStage::Drain);
+    assert!(engine.has_model("encoder").await);
+
+    drop(guard);
+    let report = lifecycle.unload("encoder", Duration::from_secs(1)).await.unwrap();
+    assert_eq!(report.stages.last().unwrap().stage, LifecycleStage::Unload);
+    assert!(report.handle.is_none());
+    assert!(!engine.has_model("encoder").await);
+    assert_eq!(registry.count().await, 0);
+    assert!(lifecycle.track("encoder").await.is_none());
+}
@@ -0,0 +1,155 @@
+//! Integration tests for the native backend, selected by a model manifest
+//! and served through the engine like llama.cpp models.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use common::{greedy, Fixtures};
+use gg_core::engine::gguf::GgufMetadata;
+use gg_core::engine::{
+    ChatMessage, ChatRole, ChatTemplate, FinishReason, InferenceEngine, InferenceInput,
+    InferenceParams, NativeConfig, NativeFixture, NativeModel, NativeTokenizer, TokenStream,
+    Utf8StreamDecoder,
+};
+use sha2::{Digest, Sha256};
+
+/// Engine with `native` loaded from a native fixture under a manifest that
+/// selects the native backend; also returns the model file's path.
+async fn setup(test_name: &str) -> (Arc<InferenceEngine>, PathBuf) {
+    let Fixtures { base, engine, lifecycle, .. } = Fixtures::new("native", test_name);
+    let bytes = NativeFixture::default().to_bytes();
+    std::fs::write(base.join("models/tiny.gguf"), &bytes).unwrap();
+    let manifest = serde_json::json!({
+        "model_id": "native",
+        "name": "Native",
+        "version": "1.0.0",
+        "capabilities": ["text_generation"],
+        "sha256": hex::encode(Sha256::digest(&bytes)),
+        "size_bytes": bytes.len(),
+        "architecture": "gguf",
+        "license": "MIT",
+        "backend": "native",
+    });
+    std::fs::write(base.join("models/tiny.json"), manifest.to_string()).unwrap();
+    lifecycle
+        .load("native", "models/tiny.gguf", Some("models/tiny.json"))
+        .await
+        .unwrap();
+    (engine, base.join("models/tiny.gguf"))
+}
+
+/// What a plain greedy loop over `NativeModel::logits` generates after
+/// `prompt`: the tokens, their text, and whether an end-of-generation
+/// token stopped it before `max_tokens`.
+fn reference(path: &Path, prompt: &str, max_tokens: usize) -> (Vec<u32>, String, bool) {
+    let header = GgufMetadata::read(path).unwrap();
+    let tokenizer = NativeTokenizer::from_header(&header).unwrap();
+    let model = NativeModel::load(path, &NativeConfig::default()).unwrap();
+    let mut tokens = tokenizer.encode(prompt);
+    let mut generated = Vec::new();
+    let mut utf8 = Utf8StreamDecoder::new();
+    let mut text = String::new();
+    while generated.len() < max_tokens {
+        let logits = model.logits(&tokens).unwrap();
+        let next = (0..logits.len()).fold(0, |best, i| {
+            if logits[i] > logits[best] {
+                i
+            } else {
+                best
+            }
+        }) as u32;
+        if tokenizer.is_end_of_generation(next) {
+            text.push_str(&utf8.finish());
+            return (generated, text, true);
+        }
+        generated.push(next);
+        tokens.push(next);
+        text.push_str(&utf8.push(tokenizer.token_bytes(next)));
+    }
+    text.push_str(&utf8.finish());
+    (generated, text, false)
+}
+
+#[tokio::test]
+async fn test_native_generation_matches_greedy_reference() {
+    let (engine, path) = setup("generate").await;
+    let prompt = "the quick brown fox";
+
+    let result = engine.run("native", prompt, &greedy(12)).await.unwrap();
+    let (tokens, text, stopped) = reference(&path, prompt, 12);
+    assert!(!tokens.is_empty());
+    assert_eq!(result.output, text);
+    assert_eq!(result.tokens_generated, tokens.len());
+    // BOS, then "▁the", "▁quick", "▁brown" and "▁fox"
+    assert_eq!(result.prompt_tokens, 5);
+    let expected = if stopped { FinishReason::Stop } else { FinishReason::MaxTokens };
+    assert_eq!(result.finish_reason, expected);
+}
+
+#[tokio::test]
+async fn test_native_chat_renders_the_template() {
+    let (engine, path) = setup("chat").await;
+    let messages = vec![ChatMessage { role: ChatRole::User, content: "the lazy dog".into() }];
+
+    let result = engine.run_chat("native", &messages, &greedy(8)).await.unwrap();
+    let prompt = ChatTemplate::ChatMl.render(&messages).unwrap();
+    let (tokens, text, _) = reference(&path, &prompt, 8);
+    assert_eq!(result.output, text);
+    assert_eq!(result.tokens_generated, tokens.len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_native_stream_matches_generation() {
+    let (engine, path) = setup("stream").await;
+    let expected = engine.run("native", "jumps over", &greedy(10)).await.unwrap();
+    let (tokens, ..) = reference(&path, "jumps over", 10);
+
+    let (sender, mut stream) = TokenStream::new(8);
+    let config = greedy(10).to_config();
+    let streaming = engine.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        let input = InferenceInput::Text("jumps over".into());
+        streaming.run_stream_sync("native", &input, &config, sender)
+    });
+
+    let mut text = String::new();
+    let mut streamed = Vec::new();
+    let mut finish = None;
+    while let Some(output) = stream.next().await {
+        text.push_str(&output.text);
+        streamed.push(output.token);
+        finish = output.finish.or(finish);
+    }
+    task.await.unwrap().unwrap();
+    assert_eq!(text, expected.output);
+    assert_eq!(streamed[..tokens.len()], tokens[..]);
+    let finish = finish.unwrap();
+    assert_eq!(finish.completion_tokens as usize, expected.tokens_generated);
+    assert_eq!(finish.finish_reason, expected.finish_reason);
+}
+
+#[tokio::test]
+async fn test_native_sampling_is_reproducible() {
+    let (engine, _) = setup("seed").await;
+    let params = InferenceParams {
+        temperature: 1.0,
+        seed: Some(7),
+        ..greedy(16)
+    };
+
+    let first = engine.run("native", "the dog", &params).await.unwrap();
+    let second = engine.run("native", "the dog", &params).await.unwrap();
+    assert_eq!(first.output, second.output);
+    assert_eq!(first.tokens_generated, second.tokens_generated);
+
+    // Choice `i` samples with `seed + i`, as a request of its own would
+    let params = InferenceParams { n: 2, ..params };
+    let result = engine.run("native", "the dog", &params).await.unwrap();
+    assert_eq!(result.choices.len(), 2);
+    assert_eq!(result.choices[0].text, first.output);
+    let alone = InferenceParams { seed: Some(8), n: 1, ..params };
+    let alone = engine.run("native", "the dog", &alone).await.unwrap();
+    assert_eq!(result.choices[1].text, alone.output);
+}
@@ -0,0 +1,157 @@
+//! Integration tests for the n-gram test backend through the normal GGUF
+//! loading path.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{greedy, Fixtures};
+use gg_core::engine::{
+    ChatMessage, ChatRole, FinishReason, InferenceEngine, InferenceInput, InferenceParams,
+    NgramFixture, TokenStream,
+};
+
+/// Corpus with longer words than the shared one, so outputs are checkable.
+const CORPUS: &str = "the quick brown fox jumps over the lazy dog. the lazy cat sleeps!";
+
+/// Engine with `chat` loaded from an n-gram fixture by the default factory.
+async fn setup(test_name: &str, token_latency_ms: u32) -> Arc<InferenceEngine> {
+    let fixtures = Fixtures::new("ngram", test_name);
+    let fixture = NgramFixture {
+        corpus: CORPUS.into(),
+        order: 4,
+        token_latency_ms,
+        ..Default::default()
+    };
+    fixtures.write("tiny.gguf", &fixture);
+    fixtures.lifecycle.load("chat", "models/tiny.gguf", None).await.unwrap();
+    fixtures.engine
+}
+
+#[tokio::test]
+async fn test_ngram_fixture_loads_and_generates() {
+    let engine = setup("generate", 0).await;
+
+    let result = engine.run("chat", "the quick", &greedy(16)).await.unwrap();
+    assert_eq!(result.output, " brown fox jumps");
+    assert_eq!(result.prompt_tokens, 9);
+    assert_eq!(result.finish_reason, FinishReason::MaxTokens);
+
+    let result = engine.run("chat", "the lazy c", &greedy(64)).await.unwrap();
+    assert_eq!(result.output, "at sleeps!");
+    assert_eq!(result.finish_reason, FinishReason::Stop);
+}
+
+#[tokio::test]
+async fn test_ngram_sampling_is_reproducible() {
+    let engine = setup("seed", 0).await;
+    let params = InferenceParams {
+        max_tokens: 48,
+        temperature: 1.0,
+        seed: Some(7),
+        ..Default::default()
+    };
+    let messages = vec![ChatMessage { role: ChatRole::User, content: "the".into() }];
+
+    let first = engine.run_chat("chat", &messages, &params).await.unwrap();
+    let second = engine.run_chat("chat", &messages, &params).await.unwrap();
+    assert_eq!(first.output, second.output);
+    assert_eq!(first.tokens_generated, second.tokens_generated);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ngram_stream_matches_generation() {
+    let engine = setup("stream", 0).await;
+    let expected = engine.run("chat", "the quick", &greedy(16)).await.unwrap();
+
+    let (sender, mut stream) = TokenStream::new(8);
+    let config = greedy(16).to_config();
+    let streaming = engine.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        let input = InferenceInput::Text("the quick".into());
+        streaming.run_stream_sync("chat", &input, &config, sender)
+    });
+
+    let mut text = String::new();
+    let mut finish = None;
+    while let Some(output) = stream.next().await {
+        text.push_str(&output.text);
+        finish = output.finish.or(finish);
+    }
+    task.await.unwrap().unwrap();
+    assert_eq!(text, expected.output);
+    let finish = finish.unwrap();
+    assert_eq!(finish.completion_tokens as usize, expected.tokens_generated);
+    assert_eq!(finish.finish_reason, FinishReason::MaxTokens);
+}
+
+#[tokio::test]
+async fn test_ngram_token_latency_hits_timeout() {
+    let engine = setup("latency", 25).await;
+    let params = InferenceParams { timeout_ms: Some(100), ..greedy(64) };
+
+    let result = engine.run("chat", "the quick", &params).await.unwrap();
+    assert_eq!(result.finish_reason, FinishReason::Timeout);
+    assert!(!result.finished);
+    assert!(result.tokens_generated < 8, "{}", result.tokens_generated);
+}
+
+#[tokio::test]
+async fn test_ngram_n_completions_match_single_requests() {
+    let engine = setup("n", 0).await;
+    let single = |seed| InferenceParams { seed: Some(seed), temperature: 1.0, ..greedy(32) };
+    let before = engine.run("chat", "the", &single(7)).await.unwrap();
+
+    // Sequence `i` samples with `seed + i`, as a request of its own would
+    let params = InferenceParams { n: 3, ..single(7) };
+    let result = engine.run("chat", "the", &params).await.unwrap();
+    assert_eq!(result.choices.len(), 3);
+    assert_eq!(result.output, result.choices[0].text);
+    let mut tokens_generated = 0;
+    for (i, choice) in result.choices.iter().enumerate() {
+        let alone = engine.run("chat", "the", &single(7 + i as u32)).await.unwrap();
+        assert_eq!(choice.text, alone.output);
+        assert_eq!(choice.tokens_generated as usize, alone.tokens_generated);
+        tokens_generated += alone.tokens_generated;
+    }
+    assert_eq!(result.tokens_generated, tokens_generated);
+    assert!(result.choices.iter().any(|c| c.text != result.choices[0].text));
+
+    // A multi-sequence request leaves later single requests unchanged
+    let after = engine.run("chat", "the", &single(7)).await.unwrap();
+    assert_eq!(after.output, before.output);
+
+    // Greedy sequences do not diverge
+    let params = InferenceParams { n: 2, ..greedy(16) };
+    let result = engine.run("chat", "the quick", &params).await.unwrap();
+    assert_eq!(result.choices.len(), 2);
+    assert_eq!(result.choices[0].text, " brown fox jumps");
+    assert_eq!(result.choices[1].text, result.choices[0].text);
+}
+
+#[tokio::test]
+async fn test_ngram_best_of_keeps_the_most_likely_completions() {
+    let engine = setup("best_of", 0).await;
+    let params = InferenceParams { temperature: 1.0, seed: Some(3), ..greedy(32) };
+
+    let all = InferenceParams { n: 4, best_of: Some(4), ..params.clone() };
+    let all = engine.run("chat", "the", &all).await.unwrap();
+    let mut ranked = all.choices.clone();
+    ranked.sort_by(|a, b| b.cumulative_logprob.total_cmp(&a.cumulative_logprob));
+
+    let best = InferenceParams { n: 2, best_of: Some(4), ..params.clone() };
+    let best = engine.run("chat", "the", &best).await.unwrap();
+    assert_eq!(best.choices.len(), 2);
+    for (kept, expected) in best.choices.iter().zip(&ranked) {
+        assert_eq!(kept.text, expected.text);
+        assert_eq!(kept.cumulative_logprob, expected.cumulative_logprob);
+    }
+    // Every generated sequence is counted, returned or not
+    assert_eq!(best.tokens_generated, all.tokens_generated);
+
+    // With n = 1 only the best completion is returned, without choices
+    let one = InferenceParams { best_of: Some(4), ..params };
+    let one = engine.run("chat", "the", &one).await.unwrap();
+    assert_eq!(one.output, ranked[0].text);
+    assert!(one.choices.is_empty());
+}
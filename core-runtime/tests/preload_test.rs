@@ -15,6 +15,9 @@ fn test_manifest() -> ModelManifest {
         size_bytes: 1024,
         architecture: ModelArchitecture::Gguf,
         license: "MIT".to_string(),
+        chat_template: None,
+        draft_model: None,
+        backend: None,
     }
 }
 
@@ -1,6 +1,6 @@
 //! Tests for LRU prompt cache.
 
-use gg_core::memory::prompt_cache::PromptCache;
+use gg_core::memory::prompt_cache::{PromptCache, PromptCacheConfig};
 
 #[test]
 fn cache_insert_and_get() {
@@ -131,3 +131,68 @@ fn cache_memory_bytes_tracking() {
 
     assert_eq!(cache.memory_bytes(), 300);
 }
+
+#[test]
+fn cache_find_prefix_shares_part_of_longer_entry() {
+    let mut cache = PromptCache::new(10);
+    // System prompt [1, 2, 3] followed by one user turn
+    cache.insert(&[1, 2, 3, 10, 11], vec![0; 64], 5);
+
+    let (len, entry) = cache.find_prefix(&[1, 2, 3, 20]).expect("shared system prompt");
+    assert_eq!(len, 3);
+    assert_eq!(entry.seq_len(), 5);
+    assert_eq!(entry.tokens(), [1, 2, 3, 10, 11]);
+}
+
+#[test]
+fn cache_respects_min_prefix_tokens() {
+    let mut cache = PromptCache::with_config(PromptCacheConfig {
+        max_entries: 10,
+        max_bytes: 1024,
+        min_prefix_tokens: 3,
+    });
+    assert!(!cache.accepts(2));
+    cache.insert(&[1, 2], vec![0; 8], 2);
+    assert!(cache.is_empty(), "too short to ever be a hit");
+
+    cache.insert(&[1, 2, 3, 4], vec![0; 8], 4);
+    assert!(cache.find_prefix(&[1, 2, 9]).is_none());
+    assert_eq!(cache.find_prefix(&[1, 2, 3, 9]).map(|(len, _)| len), Some(3));
+}
+
+#[test]
+fn cache_evicts_lru_to_stay_within_byte_budget() {
+    let mut cache = PromptCache::with_config(PromptCacheConfig {
+        max_entries: 10,
+        max_bytes: 250,
+        min_prefix_tokens: 1,
+    });
+    cache.insert(&[1], vec![0; 100], 1);
+    cache.insert(&[2], vec![0; 100], 1);
+    cache.get(&[1]);
+    cache.insert(&[3], vec![0; 100], 1);
+
+    assert_eq!(cache.memory_bytes(), 200);
+    assert!(cache.get(&[2]).is_none(), "least recently used entry evicted");
+
+    cache.insert(&[4], vec![0; 251], 1);
+    assert!(cache.get(&[4]).is_none(), "larger than the whole budget");
+}
+
+#[test]
+fn cache_stats_track_hit_rate() {
+    let mut cache = PromptCache::new(10);
+    assert_eq!(cache.stats().hit_rate(), 0.0);
+    cache.insert(&[1, 2, 3], vec![0; 64], 3);
+
+    cache.find_prefix(&[1, 2, 3, 4]);
+    cache.find_prefix(&[1, 2, 5]);
+    cache.find_prefix(&[9]);
+    cache.find_prefix(&[1, 9]);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses), (3, 1));
+    assert_eq!(stats.reused_tokens, 3 + 2 + 1);
+    assert_eq!(stats.hit_rate(), 0.75);
+    assert_eq!((stats.entries, stats.memory_bytes), (1, 64));
+}
@@ -0,0 +1,83 @@
+//! Tests for conversation session snapshots.
+
+use std::time::Duration;
+
+use gg_core::memory::{reusable_prefix, SessionKv, SessionKvConfig, SessionKvStore};
+
+fn snapshot(tokens: &[u32], bytes: usize) -> SessionKv {
+    SessionKv { tokens: tokens.to_vec(), state: vec![0u8; bytes] }
+}
+
+fn config(max_sessions: usize, max_bytes: usize) -> SessionKvConfig {
+    SessionKvConfig { max_sessions, max_bytes, ttl: Duration::from_secs(60) }
+}
+
+#[test]
+fn take_removes_the_snapshot() {
+    let store = SessionKvStore::new(config(4, 1024));
+    store.put("chat".into(), snapshot(&[1, 2, 3], 16));
+    assert_eq!(store.len(), 1);
+
+    let kv = store.take("chat").expect("snapshot stored");
+    assert_eq!(kv.tokens, [1, 2, 3]);
+    assert!(store.take("chat").is_none());
+    assert!(store.is_empty());
+}
+
+#[test]
+fn put_evicts_oldest_beyond_session_budget() {
+    let store = SessionKvStore::new(config(2, 1024));
+    store.put("a".into(), snapshot(&[1], 8));
+    std::thread::sleep(Duration::from_millis(2));
+    store.put("b".into(), snapshot(&[2], 8));
+    std::thread::sleep(Duration::from_millis(2));
+    store.put("c".into(), snapshot(&[3], 8));
+
+    assert_eq!(store.len(), 2);
+    assert!(store.take("a").is_none());
+    assert!(store.take("c").is_some());
+}
+
+#[test]
+fn put_evicts_to_stay_within_byte_budget() {
+    let store = SessionKvStore::new(config(8, 100));
+    store.put("a".into(), snapshot(&[1], 60));
+    store.put("b".into(), snapshot(&[2], 60));
+    assert_eq!(store.len(), 1);
+    assert_eq!(store.memory_bytes(), 60);
+
+    // Too large to keep at all
+    store.put("c".into(), snapshot(&[3], 101));
+    assert!(store.take("c").is_none());
+    assert!(store.take("b").is_some());
+}
+
+#[test]
+fn expired_snapshots_are_not_returned() {
+    let store = SessionKvStore::new(SessionKvConfig {
+        ttl: Duration::from_millis(1),
+        ..config(4, 1024)
+    });
+    store.put("chat".into(), snapshot(&[1, 2], 8));
+    std::thread::sleep(Duration::from_millis(5));
+    assert!(store.take("chat").is_none());
+}
+
+#[test]
+fn zero_sessions_disables_reuse() {
+    let store = SessionKvStore::new(config(0, 1024));
+    store.put("chat".into(), snapshot(&[1], 8));
+    assert!(store.is_empty());
+}
+
+#[test]
+fn reusable_prefix_leaves_a_token_to_decode() {
+    // New turn extends the saved conversation
+    assert_eq!(reusable_prefix(&[1, 2, 3, 4], &[1, 2, 3, 4, 5, 6]), 4);
+    // Diverges after two tokens
+    assert_eq!(reusable_prefix(&[1, 2, 3, 4], &[1, 2, 9, 4]), 2);
+    // Identical prompt still decodes its last token for fresh logits
+    assert_eq!(reusable_prefix(&[1, 2, 3], &[1, 2, 3]), 2);
+    assert_eq!(reusable_prefix(&[7], &[1, 2]), 0);
+    assert_eq!(reusable_prefix(&[1], &[]), 0);
+}
@@ -1,8 +1,11 @@
 //! Tests for streaming response functionality.
 
-use gg_core::engine::InferenceParams;
+use std::time::Duration;
+
+use gg_core::engine::{FinishReason, GenerationTimings, InferenceParams, StopSequence};
 use gg_core::ipc::{
     decode_message, encode_message, InferenceRequest, IpcMessage, RequestId, StreamChunk,
+    UsageReport,
 };
 
 // =============================================================================
@@ -103,6 +106,79 @@ fn test_stream_chunk_final_roundtrip() {
     }
 }
 
+#[test]
+fn test_stream_chunk_final_usage_roundtrip() {
+    let timings = GenerationTimings {
+        prefill: Duration::from_millis(40),
+        first_token: Duration::from_millis(50),
+        decode: Duration::from_millis(500),
+    };
+    let queue_wait = Duration::from_millis(5);
+    let usage = UsageReport::new(12, 20, FinishReason::MaxTokens, queue_wait, &timings);
+    assert!((usage.queue_wait_ms - 5.0).abs() < 1e-6);
+    assert!((usage.prefill_ms - 40.0).abs() < 1e-6);
+    assert!((usage.time_to_first_token_ms - 55.0).abs() < 1e-6);
+    assert!((usage.decode_tokens_per_sec - 40.0).abs() < 1e-6);
+
+    let chunk = StreamChunk::final_token_with_text(RequestId(7), 2, "!".into())
+        .with_usage(usage.clone());
+    let encoded = encode_message(&IpcMessage::StreamChunk(chunk)).expect("encode");
+    let json: serde_json::Value = serde_json::from_slice(&encoded).expect("json");
+    assert_eq!(json["usage"]["finish_reason"], "max_tokens");
+    assert_eq!(json["usage"]["prompt_tokens"], 12);
+
+    match decode_message(&encoded).expect("decode") {
+        IpcMessage::StreamChunk(decoded) => assert_eq!(decoded.usage, Some(usage)),
+        _ => panic!("Expected StreamChunk message"),
+    }
+
+    // Non-final chunks carry no usage field on the wire
+    let chunk = StreamChunk::token_with_text(RequestId(7), 1, "hi".into());
+    let encoded = encode_message(&IpcMessage::StreamChunk(chunk)).expect("encode");
+    let json: serde_json::Value = serde_json::from_slice(&encoded).expect("json");
+    assert!(json.get("usage").is_none());
+}
+
+#[test]
+fn test_stream_chunk_usage_reports_stop_sequence() {
+    let usage = UsageReport::new(
+        4,
+        3,
+        FinishReason::Stop,
+        Duration::ZERO,
+        &GenerationTimings::default(),
+    )
+    .with_stop_sequence(Some(StopSequence::Text("\n\nQ:".into())));
+
+    let chunk = StreamChunk::final_token_with_text(RequestId(8), 5, String::new())
+        .with_usage(usage.clone());
+    let encoded = encode_message(&IpcMessage::StreamChunk(chunk)).expect("encode");
+    let json: serde_json::Value = serde_json::from_slice(&encoded).expect("json");
+    assert_eq!(json["usage"]["finish_reason"], "stop");
+    assert_eq!(json["usage"]["stop_sequence"], "\n\nQ:");
+
+    match decode_message(&encoded).expect("decode") {
+        IpcMessage::StreamChunk(decoded) => assert_eq!(decoded.usage, Some(usage)),
+        _ => panic!("Expected StreamChunk message"),
+    }
+}
+
+#[test]
+fn test_inference_params_stop_accepts_strings_and_tokens() {
+    let params: InferenceParams = serde_json::from_str(
+        r#"{"max_tokens":8,"temperature":0.0,"top_p":1.0,"top_k":1,"stop":["\n\nQ:",2]}"#,
+    )
+    .expect("parse");
+    assert_eq!(
+        params.stop,
+        vec![StopSequence::Text("\n\nQ:".into()), StopSequence::Token(2)]
+    );
+    assert!(params.validate().is_ok());
+
+    let empty = InferenceParams { stop: vec![StopSequence::Text(String::new())], ..params };
+    assert!(empty.validate().is_err());
+}
+
 #[test]
 fn test_stream_chunk_error_roundtrip() {
     let request_id = RequestId(789);
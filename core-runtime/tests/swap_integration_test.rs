@@ -17,6 +17,9 @@ fn test_manifest(model_id: &str) -> ModelManifest {
         size_bytes: 1024,
         architecture: ModelArchitecture::Gguf,
         license: "MIT".to_string(),
+        chat_template: None,
+        draft_model: None,
+        backend: None,
     }
 }
 
@@ -135,6 +135,51 @@ fn continuous_respects_max_slots() {
     assert_eq!(batcher.pending_count(), 3);
 }
 
+#[test]
+fn continuous_admits_by_score_within_budget() {
+    let mut batcher = ContinuousBatcher::new(3);
+    for (i, len) in [(1, 4), (2, 4), (3, 1)] {
+        batcher.enqueue(PendingRequest {
+            request_id: RequestId(i),
+            prompt_tokens: vec![7; len],
+            max_tokens: 10,
+        });
+    }
+
+    // Slot 2 scores highest; request 2 no longer fits and blocks request 3
+    let admitted = batcher.admit_pending_by(6, |slot, _| slot);
+    assert_eq!(admitted.len(), 1);
+    assert_eq!((admitted[0].0, admitted[0].1.request_id), (2, RequestId(1)));
+    assert_eq!(batcher.pending_count(), 2);
+
+    let admitted = batcher.admit_pending_by(8, |_, _| 0);
+    let slots: Vec<usize> = admitted.iter().map(|(slot, _)| *slot).collect();
+    assert_eq!(slots, [0, 1]);
+    assert_eq!(batcher.active_count(), 3);
+}
+
+#[test]
+fn continuous_removes_expired_pending_requests() {
+    let mut batcher = ContinuousBatcher::new(1);
+    for i in 0..4 {
+        batcher.enqueue(PendingRequest {
+            request_id: RequestId(i),
+            prompt_tokens: vec![1],
+            max_tokens: 10,
+        });
+    }
+
+    let removed = batcher.remove_pending(|req| req.request_id.0 % 2 == 0);
+    let removed: Vec<u64> = removed.iter().map(|req| req.request_id.0).collect();
+    assert_eq!(removed, [0, 2]);
+    assert_eq!(batcher.pending_count(), 2);
+
+    // The rest keep their order
+    let admitted = batcher.admit_pending();
+    assert_eq!(admitted[0].1.request_id, RequestId(1));
+    assert_eq!(batcher.pending_count(), 1);
+}
+
 #[test]
 fn batch_slot_phase_transitions() {
     let mut slot = BatchSlot::new(RequestId(1), 100, 50);